@@ -0,0 +1,294 @@
+/*
+ * Filename: asynch.rs
+ * Description: embedded-hal-async support, only built with the `async`
+ * feature. `AsyncSensor` drives the same sans-IO machines as the blocking
+ * driver over an async `I2c`, and waits with an async `DelayNs`, so a task
+ * measuring doesn't hold up the executor for the conversion time.
+ *
+ * It's the protocol and nothing more: init, measurements, status reads and
+ * soft resets with the driver's timings, command parameters and bus quirks,
+ * in the same bus traffic and errors as the blocking driver. The journal,
+ * fault tracking and the rest of `Sensor`'s bookkeeping aren't here.
+ */
+
+use embedded_hal_async::delay::DelayNs;
+use embedded_hal_async::i2c::I2c;
+
+use crate::core::data::SensorData;
+use crate::core::measurement::{Measurement, SensorId};
+use crate::core::params::CommandParams;
+use crate::core::quirks::BusQuirks;
+use crate::core::retry::Deadline;
+use crate::core::sansio::{
+    Action, InitCycle, Input, Machine, MeasureCycle, ResetCycle, FRAME_LEN,
+};
+use crate::core::sensor_status::SensorStatus;
+use crate::core::timings::Timings;
+use crate::core::commands;
+use crate::{Error, BUILD_VARIANT, EARLY_NACK_DELAY_MS, EARLY_NACK_RETRIES};
+
+///The sensor on an embedded-hal-async `I2c`.
+///
+///`let m = AsyncSensor::new(i2c, SENSOR_ADDR).init(&mut delay).await?;`
+///then `sensor.measure(&mut delay).await` as often as needed.
+pub struct AsyncSensor<I2C> {
+    i2c: I2C,
+    address: u8,
+    id: SensorId,
+    timings: Timings,
+    params: CommandParams,
+    quirks: BusQuirks,
+    started_up: bool,
+}
+
+impl<I2C: I2c> AsyncSensor<I2C> {
+    ///Same defaults as `Sensor::new()`: the `BUILD_VARIANT` timings, the
+    ///data sheet command parameters and no bus quirks.
+    pub fn new(i2c: I2C, address: u8) -> Self {
+        AsyncSensor {
+            i2c,
+            address,
+            id: SensorId(0),
+            timings: BUILD_VARIANT.timings(),
+            params: CommandParams::DATASHEET,
+            quirks: BusQuirks::NONE,
+            started_up: false,
+        }
+    }
+
+    pub fn set_timings(&mut self, timings: Timings) {
+        self.timings = timings;
+    }
+
+    ///See `Sensor::set_bus_quirks()`.
+    pub fn set_bus_quirks(&mut self, quirks: BusQuirks) {
+        self.quirks = quirks;
+    }
+
+    ///The id copied into every `Measurement`.
+    pub fn set_id(&mut self, id: SensorId) {
+        self.id = id;
+    }
+
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    ///Startup delay, init command and a calibration if needed, as
+    ///`Sensor::init()`. The startup delay and the early retries of a
+    ///sensor still powering up only happen on the first call.
+    pub async fn init(&mut self, delay: &mut impl DelayNs) -> Result<(), Error<I2C::Error>> {
+        let first = !self.started_up;
+        let mut machine = InitCycle::new(&self.timings, &self.params);
+        if self.started_up {
+            machine = machine.skip_startup();
+        }
+        let mut early_nacks = 0;
+        loop {
+            let r = self.run(&mut machine, delay).await;
+            self.started_up = true;
+            match r {
+                Err(Error::I2C(_)) if first && machine.is_init_pending() && early_nacks < EARLY_NACK_RETRIES => {
+                    early_nacks += 1;
+                    delay.delay_ms(EARLY_NACK_DELAY_MS.into()).await;
+                    machine = InitCycle::new(&self.timings, &self.params).skip_startup();
+                },
+                r => return r,
+            }
+        }
+    }
+
+    pub async fn read_status(&mut self) -> Result<SensorStatus, Error<I2C::Error>> {
+        self.i2c.write(self.address, &commands::read_status()).await.map_err(Error::I2C)?;
+        let mut buf = [0u8; 1];
+        self.i2c.read(self.address, &mut buf).await.map_err(Error::I2C)?;
+        Ok(SensorStatus::new(buf[0]))
+    }
+
+    ///Triggers a conversion, waits it out and reads the frame, as
+    ///`InitializedSensor::measure()` without degradation: a CRC miss is
+    ///`Error::InvalidChecksum`, busy past the maximum conversion time
+    ///`Error::DeviceTimeOut`.
+    pub async fn measure(&mut self, delay: &mut impl DelayNs) -> Result<Measurement, Error<I2C::Error>> {
+        let t = self.timings;
+        let retry = Deadline {
+            interval_ms: t.busy_poll_ms,
+            deadline_ms: t.measure_max_ms.saturating_sub(t.measure_typ_ms),
+        };
+        let mut machine = MeasureCycle::new(&t, &self.params, retry).with_quirks(self.quirks);
+        self.run(&mut machine, delay).await?;
+
+        let mut sd = SensorData::new();
+        sd.bytes = machine.frame().ok_or(Error::Internal)?;
+        if !sd.is_crc_good() {
+            return Err(Error::InvalidChecksum);
+        }
+        Ok(Measurement::from_data(self.id, &sd))
+    }
+
+    ///Soft reset, refused with `Error::UnexpectedBusy` while the sensor
+    ///reports busy, as `InitializedSensor::soft_reset()`. Returns the
+    ///status read back after `reset_ms`.
+    pub async fn soft_reset(&mut self, delay: &mut impl DelayNs) -> Result<SensorStatus, Error<I2C::Error>> {
+        if self.read_status().await?.is_busy() {
+            return Err(Error::UnexpectedBusy);
+        }
+        let mut machine = ResetCycle::new(&self.timings);
+        self.run(&mut machine, delay).await?;
+        machine.status().ok_or(Error::Internal)
+    }
+
+    //`Sensor::run()` with awaits, failed transfers are retried as the bus
+    //quirks ask.
+    async fn run<M: Machine>(
+        &mut self,
+        machine: &mut M,
+        delay: &mut impl DelayNs,
+        ) -> Result<(), Error<I2C::Error>>
+    {
+        let mut buf = [0u8; FRAME_LEN];
+        let mut read = None;
+        loop {
+            let input = match read.take() {
+                Some(n) => Input::Data(&buf[..n]),
+                None => Input::Ready,
+            };
+            match machine.poll(input) {
+                Action::Write(wbuf) => {
+                    let mut left = self.quirks.transfer_retries;
+                    while let Err(e) = self.i2c.write(self.address, wbuf.as_slice()).await {
+                        if left == 0 {
+                            return Err(Error::I2C(e));
+                        }
+                        left -= 1;
+                        delay.delay_ms(self.quirks.settle_ms.into()).await;
+                    }
+                },
+                Action::Read(n) => {
+                    let n = n.min(FRAME_LEN);
+                    let mut left = self.quirks.transfer_retries;
+                    while let Err(e) = self.i2c.read(self.address, &mut buf[..n]).await {
+                        if left == 0 {
+                            return Err(Error::I2C(e));
+                        }
+                        left -= 1;
+                        delay.delay_ms(self.quirks.settle_ms.into()).await;
+                    }
+                    read = Some(n);
+                },
+                Action::DelayMs(ms) => delay.delay_ms(ms.into()).await,
+                Action::Done => return Ok(()),
+                Action::Fail(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod asynch_tests {
+    use core::future::Future;
+    use core::pin::pin;
+    use core::task::{Context, Poll, Waker};
+
+    use embedded_hal_async::i2c::{ErrorKind, ErrorType, NoAcknowledgeSource, Operation};
+
+    use super::*;
+    use crate::{Aht2xTransport, Celsius, RelativeHumidity, SimulatedSensor, SENSOR_ADDR};
+
+    ///Runs a future that never waits on anything, which all of these are.
+    pub(crate) fn block_on<F: Future>(f: F) -> F::Output {
+        let mut f = pin!(f);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(v) = f.as_mut().poll(&mut cx) {
+                return v;
+            }
+        }
+    }
+
+    ///Records what it was asked to wait, in ms.
+    #[derive(Default)]
+    pub(crate) struct DelayLog(pub Vec<u32>);
+
+    impl DelayNs for DelayLog {
+        async fn delay_ns(&mut self, ns: u32) {
+            self.0.push(ns / 1_000_000);
+        }
+
+        async fn delay_ms(&mut self, ms: u32) {
+            self.0.push(ms);
+        }
+    }
+
+    ///The simulator behind an async `I2c`.
+    struct AsyncSim(SimulatedSensor);
+
+    impl ErrorType for AsyncSim {
+        type Error = ErrorKind;
+    }
+
+    impl I2c for AsyncSim {
+        async fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), ErrorKind> {
+            let nack = |_| ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown);
+            for op in operations {
+                match op {
+                    Operation::Write(bytes) => self.0.write_frame(address, bytes).map_err(nack)?,
+                    Operation::Read(buf) => self.0.read_frame(address, buf).map_err(nack)?,
+                }
+            }
+            Ok(())
+        }
+    }
+
+    fn sensor(busy_reads: u8) -> AsyncSensor<AsyncSim> {
+        let mut sim = SimulatedSensor::new(Celsius(22.0), RelativeHumidity(45.0));
+        sim.busy_reads = busy_reads;
+        AsyncSensor::new(AsyncSim(sim), SENSOR_ADDR)
+    }
+
+    #[test]
+    fn init_and_measure()
+    {
+        let mut s = sensor(1);
+        let mut delay = DelayLog::default();
+        block_on(s.init(&mut delay)).unwrap();
+        assert_eq!(delay.0, [40]);
+
+        s.set_id(SensorId(3));
+        let mut delay = DelayLog::default();
+        let m = block_on(s.measure(&mut delay)).unwrap();
+        assert_eq!(delay.0, [80, 20]);
+        assert_eq!(m.id, SensorId(3));
+        assert!(m.temperature.abs_delta(Celsius(22.0)) < Celsius(0.01));
+        assert!(m.humidity.abs_delta(RelativeHumidity(45.0)) < RelativeHumidity(0.01));
+
+        //Initialized already, no startup delay again.
+        let mut delay = DelayLog::default();
+        block_on(s.init(&mut delay)).unwrap();
+        assert!(delay.0.is_empty());
+    }
+
+    #[test]
+    fn times_out()
+    {
+        let mut s = sensor(10);
+        let mut delay = DelayLog::default();
+        block_on(s.init(&mut delay)).unwrap();
+        assert_eq!(block_on(s.measure(&mut delay)), Err(Error::DeviceTimeOut));
+    }
+
+    #[test]
+    fn soft_reset()
+    {
+        let mut s = sensor(0);
+        let mut delay = DelayLog::default();
+        let status = block_on(s.soft_reset(&mut delay)).unwrap();
+        assert!(!status.is_busy());
+        assert_eq!(delay.0, [20]);
+        //The reset drops the calibration, init brings it back.
+        assert!(!block_on(s.read_status()).unwrap().is_calibration_enabled());
+        block_on(s.init(&mut delay)).unwrap();
+        assert!(block_on(s.read_status()).unwrap().is_calibration_enabled());
+        assert_eq!(s.release().0.busy_reads, 0);
+    }
+}
@@ -0,0 +1,317 @@
+/*
+ * Filename: bridge.rs
+ * Description: Transports for the USB-HID to I2C bridges found on bench
+ * adapters, the Silicon Labs CP2112 (AN495) and the FTDI FT260 (AN_394).
+ * With these a sensor can be verified from a laptop with the same driver
+ * logic that ships in the firmware.
+ *
+ * The HID device itself is left to the user through `HidDevice`, its
+ * methods match the `hidapi` crate's `HidDevice` so the impl is a few
+ * lines. For SMBus/i2c-dev on Linux no bridge is needed,
+ * `linux-embedded-hal`'s `I2cdev` implements the embedded-hal traits and
+ * works as is.
+ */
+
+extern crate std;
+
+use std::io;
+
+use crate::Aht2xTransport;
+
+///A HID device, as opened by `hidapi`.
+pub trait HidDevice {
+    ///Writes an output report, the first byte is the report id.
+    fn write(&mut self, report: &[u8]) -> io::Result<usize>;
+
+    ///Reads an input report, 0 bytes read means the timeout expired.
+    fn read_timeout(&mut self, buf: &mut [u8], timeout_ms: i32) -> io::Result<usize>;
+
+    ///Reads a feature report, `buf[0]` holds the report id.
+    fn get_feature_report(&mut self, buf: &mut [u8]) -> io::Result<usize>;
+}
+
+#[derive(Debug)]
+pub enum BridgeError {
+    Io(io::Error),
+    ///The sensor didn't acknowledge its address or the data.
+    Nack,
+    ///The bridge reported a bus error or answered with something unexpected.
+    Bus,
+    ///The bridge didn't finish the transfer in time.
+    Timeout,
+}
+
+impl From<io::Error> for BridgeError {
+    fn from(e: io::Error) -> Self {
+        BridgeError::Io(e)
+    }
+}
+
+///Status polls before a transfer is given up on.
+const MAX_POLLS: usize = 20;
+const READ_TIMEOUT_MS: i32 = 100;
+
+///Silicon Labs CP2112 HID to SMBus/I2C bridge.
+pub struct Cp2112<D> {
+    dev: D,
+}
+
+impl<D: HidDevice> Cp2112<D> {
+    const DATA_READ_REQUEST: u8 = 0x10;
+    const DATA_READ_FORCE_SEND: u8 = 0x12;
+    const DATA_READ_RESPONSE: u8 = 0x13;
+    const DATA_WRITE: u8 = 0x14;
+    const TRANSFER_STATUS_REQUEST: u8 = 0x15;
+    const TRANSFER_STATUS_RESPONSE: u8 = 0x16;
+
+    ///Takes an opened device, the SMBus configuration (clock speed etc.)
+    ///is left as it is.
+    pub fn new(dev: D) -> Self {
+        Cp2112 { dev }
+    }
+
+    pub fn release(self) -> D {
+        self.dev
+    }
+
+    //Polls the transfer status until the bridge is done with the transfer.
+    fn wait_complete(&mut self) -> Result<(), BridgeError> {
+        for _ in 0..MAX_POLLS {
+            self.dev.write(&[Self::TRANSFER_STATUS_REQUEST, 0x01])?;
+            let mut buf = [0u8; 64];
+            let n = self.dev.read_timeout(&mut buf, READ_TIMEOUT_MS)?;
+            if n < 3 || buf[0] != Self::TRANSFER_STATUS_RESPONSE {
+                return Err(BridgeError::Bus);
+            }
+            //Status 0: 0 idle, 1 busy, 2 complete, 3 error. Status 1 on an
+            //error: 0 address NACK timeout, 1 bus not free, 2 arbitration
+            //lost, 3/4 read/write incomplete, 5 succeeded after retries.
+            match (buf[1], buf[2]) {
+                (0x01, _) => continue,
+                (0x00, _) | (0x02, _) | (0x03, 0x05) => return Ok(()),
+                (0x03, 0x00) => return Err(BridgeError::Nack),
+                _ => return Err(BridgeError::Bus),
+            }
+        }
+        Err(BridgeError::Timeout)
+    }
+}
+
+impl<D: HidDevice> Aht2xTransport for Cp2112<D> {
+    type Error = BridgeError;
+
+    fn write_frame(&mut self, address: u8, bytes: &[u8]) -> Result<(), BridgeError> {
+        let mut report = [0u8; 64];
+        let len = bytes.len().min(61);
+        report[0] = Self::DATA_WRITE;
+        report[1] = address << 1;
+        report[2] = len as u8;
+        report[3..3 + len].copy_from_slice(&bytes[..len]);
+        self.dev.write(&report[..3 + len])?;
+        self.wait_complete()
+    }
+
+    fn read_frame(&mut self, address: u8, buf: &mut [u8]) -> Result<(), BridgeError> {
+        let len = buf.len() as u16;
+        let [hi, lo] = len.to_be_bytes();
+        self.dev.write(&[Self::DATA_READ_REQUEST, address << 1, hi, lo])?;
+        self.wait_complete()?;
+        self.dev.write(&[Self::DATA_READ_FORCE_SEND, hi, lo])?;
+
+        let mut got = 0;
+        for _ in 0..MAX_POLLS {
+            if got == buf.len() {
+                return Ok(());
+            }
+            let mut report = [0u8; 64];
+            let n = self.dev.read_timeout(&mut report, READ_TIMEOUT_MS)?;
+            if n < 3 || report[0] != Self::DATA_READ_RESPONSE || report[1] == 0x03 {
+                return Err(BridgeError::Bus);
+            }
+            let chunk = (report[2] as usize).min(buf.len() - got).min(n - 3);
+            buf[got..got + chunk].copy_from_slice(&report[3..3 + chunk]);
+            got += chunk;
+        }
+        Err(BridgeError::Timeout)
+    }
+}
+
+///FTDI FT260 HID to I2C bridge.
+pub struct Ft260<D> {
+    dev: D,
+}
+
+impl<D: HidDevice> Ft260<D> {
+    const I2C_STATUS: u8 = 0xC0;
+    const I2C_READ_REQUEST: u8 = 0xC2;
+    //0xD0 to 0xDE, one report id per 4 bytes of payload.
+    const I2C_REPORT_MIN: u8 = 0xD0;
+    const START_AND_STOP: u8 = 0x06;
+
+    const STATUS_BUSY: u8 = 1 << 0;
+    const STATUS_ERROR: u8 = 1 << 1;
+    const STATUS_NACK: u8 = (1 << 2) | (1 << 3);
+
+    ///Takes an opened device already set to I2C mode.
+    pub fn new(dev: D) -> Self {
+        Ft260 { dev }
+    }
+
+    pub fn release(self) -> D {
+        self.dev
+    }
+
+    fn report_id(len: usize) -> u8 {
+        Self::I2C_REPORT_MIN + (len.saturating_sub(1) / 4) as u8
+    }
+
+    fn wait_idle(&mut self) -> Result<(), BridgeError> {
+        for _ in 0..MAX_POLLS {
+            let mut buf = [0u8; 5];
+            buf[0] = Self::I2C_STATUS;
+            self.dev.get_feature_report(&mut buf)?;
+            let status = buf[1];
+            if status & Self::STATUS_BUSY != 0 {
+                continue;
+            }
+            if status & Self::STATUS_NACK != 0 {
+                return Err(BridgeError::Nack);
+            }
+            if status & Self::STATUS_ERROR != 0 {
+                return Err(BridgeError::Bus);
+            }
+            return Ok(());
+        }
+        Err(BridgeError::Timeout)
+    }
+}
+
+impl<D: HidDevice> Aht2xTransport for Ft260<D> {
+    type Error = BridgeError;
+
+    fn write_frame(&mut self, address: u8, bytes: &[u8]) -> Result<(), BridgeError> {
+        let len = bytes.len().min(60);
+        let mut report = [0u8; 64];
+        report[0] = Self::report_id(len);
+        report[1] = address;
+        report[2] = Self::START_AND_STOP;
+        report[3] = len as u8;
+        report[4..4 + len].copy_from_slice(&bytes[..len]);
+        self.dev.write(&report[..4 + len])?;
+        self.wait_idle()
+    }
+
+    fn read_frame(&mut self, address: u8, buf: &mut [u8]) -> Result<(), BridgeError> {
+        let [lo, hi] = (buf.len() as u16).to_le_bytes();
+        self.dev.write(&[Self::I2C_READ_REQUEST, address, Self::START_AND_STOP, lo, hi])?;
+
+        let mut got = 0;
+        for _ in 0..MAX_POLLS {
+            if got == buf.len() {
+                return self.wait_idle();
+            }
+            let mut report = [0u8; 64];
+            let n = self.dev.read_timeout(&mut report, READ_TIMEOUT_MS)?;
+            if n < 2 || !(Self::I2C_REPORT_MIN..=0xDE).contains(&report[0]) {
+                return Err(BridgeError::Bus);
+            }
+            let chunk = (report[1] as usize).min(buf.len() - got).min(n - 2);
+            buf[got..got + chunk].copy_from_slice(&report[2..2 + chunk]);
+            got += chunk;
+        }
+        Err(BridgeError::Timeout)
+    }
+}
+
+#[cfg(test)]
+mod bridge_tests {
+    use std::collections::VecDeque;
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::{Sensor, SENSOR_ADDR};
+
+    ///Records the output reports and plays back scripted input reports.
+    #[derive(Default)]
+    struct FakeHid {
+        written: Vec<Vec<u8>>,
+        input: VecDeque<Vec<u8>>,
+        features: VecDeque<Vec<u8>>,
+    }
+
+    impl HidDevice for FakeHid {
+        fn write(&mut self, report: &[u8]) -> io::Result<usize> {
+            self.written.push(report.to_vec());
+            Ok(report.len())
+        }
+
+        fn read_timeout(&mut self, buf: &mut [u8], _timeout_ms: i32) -> io::Result<usize> {
+            let r = self.input.pop_front().unwrap_or_default();
+            buf[..r.len()].copy_from_slice(&r);
+            Ok(r.len())
+        }
+
+        fn get_feature_report(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let r = self.features.pop_front().unwrap_or_default();
+            buf[..r.len()].copy_from_slice(&r);
+            Ok(r.len())
+        }
+    }
+
+    #[test]
+    fn cp2112_read_status()
+    {
+        let mut hid = FakeHid::default();
+        hid.input.extend([
+            vec![0x16, 0x02, 0x05, 0, 0, 0, 0],
+            vec![0x16, 0x01, 0x02, 0, 0, 0, 0],
+            vec![0x16, 0x02, 0x05, 0, 0, 0, 0],
+            vec![0x13, 0x02, 0x01, 0x18],
+        ]);
+
+        let mut sensor = Sensor::new(Cp2112::new(hid), SENSOR_ADDR);
+        assert!(sensor.read_status().unwrap().is_calibration_enabled());
+
+        let hid = sensor.i2c.release();
+        assert_eq!(hid.written[0], [0x14, 0x70, 0x01, 0x71]);
+        assert_eq!(hid.written[2], [0x10, 0x70, 0x00, 0x01]);
+        assert_eq!(hid.written[5], [0x12, 0x00, 0x01]);
+    }
+
+    #[test]
+    fn cp2112_nack()
+    {
+        let mut hid = FakeHid::default();
+        hid.input.push_back(vec![0x16, 0x03, 0x00, 0, 0, 0, 0]);
+
+        let mut bridge = Cp2112::new(hid);
+        let r = bridge.write_frame(SENSOR_ADDR, &[0x71]);
+        assert!(matches!(r, Err(BridgeError::Nack)));
+    }
+
+    #[test]
+    fn ft260_read_status()
+    {
+        let mut hid = FakeHid::default();
+        hid.features.extend([vec![0xC0, 0x20], vec![0xC0, 0x21], vec![0xC0, 0x20]]);
+        hid.input.push_back(vec![0xD0, 0x01, 0x18]);
+
+        let mut sensor = Sensor::new(Ft260::new(hid), SENSOR_ADDR);
+        assert!(sensor.read_status().unwrap().is_calibration_enabled());
+
+        let hid = sensor.i2c.release();
+        assert_eq!(hid.written[0], [0xD0, 0x38, 0x06, 0x01, 0x71]);
+        assert_eq!(hid.written[1], [0xC2, 0x38, 0x06, 0x01, 0x00]);
+    }
+
+    #[test]
+    fn ft260_timeout()
+    {
+        let mut hid = FakeHid::default();
+        hid.features.extend(std::iter::repeat_n(vec![0xC0, 0x01], MAX_POLLS));
+
+        let mut bridge = Ft260::new(hid);
+        let r = bridge.write_frame(SENSOR_ADDR, &[0x71]);
+        assert!(matches!(r, Err(BridgeError::Timeout)));
+    }
+}
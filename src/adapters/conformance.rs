@@ -0,0 +1,485 @@
+/*
+ * Filename: conformance.rs
+ * Description: Scenarios every adapter has to pass, so the frontends can't
+ * drift apart. A scenario is the driver calls, the bus traffic the sensor
+ * side expects (and answers with) and the outcome of each call. Each adapter
+ * gets a runner that turns the script into its own kind of bus.
+ *
+ * The simulator models the sensor rather than replaying a script, its
+ * runner only lets the script steer what a model can't know (busy reads,
+ * NACKs, a lost calibration) and checks the rest. `adapters_agree` runs it
+ * against the other adapters without a script.
+ */
+
+use crate::commands::{CALIBRATE, INIT_SENSOR, READ_STATUS, SOFT_RESET, TRIG_MESSURE};
+use crate::{
+    Aht2xTransport, Delay, ErrorKind, InitializedSensor, Sensor, CAL_PARAM0, CAL_PARAM1,
+    EARLY_NACK_RETRIES, SENSOR_ADDR, TRIG_MEASURE_PARAM0, TRIG_MEASURE_PARAM1,
+};
+
+///One transfer, a write of or a read answered with `bytes`. A `nack`
+///transfer fails on the bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Step {
+    pub write: bool,
+    pub bytes: &'static [u8],
+    pub nack: bool,
+}
+
+const fn w(bytes: &'static [u8]) -> Step {
+    Step { write: true, bytes, nack: false }
+}
+
+const fn r(bytes: &'static [u8]) -> Step {
+    Step { write: false, bytes, nack: false }
+}
+
+const fn nack(step: Step) -> Step {
+    Step { nack: true, ..step }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Op {
+    Init,
+    ReadStatus,
+    Measure,
+    SoftReset,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Outcome {
+    Ok,
+    Status(u8),
+    ///Temperature and humidity in hundredths.
+    Reading(i32, i32),
+    Err(ErrorKind),
+}
+
+pub(crate) struct Scenario {
+    pub name: &'static str,
+    pub ops: &'static [(Op, Outcome)],
+    pub bus: &'static [Step],
+}
+
+const TRIGGER: Step = w(&[TRIG_MESSURE, TRIG_MEASURE_PARAM0, TRIG_MEASURE_PARAM1]);
+const FRAME: Step = r(&[0x18, 0x7E, 0x51, 0x65, 0xD4, 0xA0, 0xDA]);
+const BUSY_FRAME: Step = r(&[0x98, 0x7E, 0x51, 0x65, 0xD4, 0xA0, 0xDA]);
+const READING: Outcome = Outcome::Reading(2288, 4934);
+
+pub(crate) const SCENARIOS: &[Scenario] = &[
+    Scenario {
+        name: "init calibrated",
+        ops: &[(Op::Init, Outcome::Ok)],
+        bus: &[w(&[INIT_SENSOR]), w(&[READ_STATUS]), r(&[0x18])],
+    },
+    Scenario {
+        name: "init needs calibrating",
+        ops: &[(Op::Init, Outcome::Ok)],
+        bus: &[
+            w(&[INIT_SENSOR]), w(&[READ_STATUS]), r(&[0x10]),
+            w(&[CALIBRATE, CAL_PARAM0, CAL_PARAM1]), w(&[READ_STATUS]), r(&[0x18]),
+        ],
+    },
+    Scenario {
+        name: "init nack",
+        ops: &[(Op::Init, Outcome::Err(ErrorKind::I2C))],
+        bus: &[nack(w(&[INIT_SENSOR])); 1 + EARLY_NACK_RETRIES as usize],
+    },
+    Scenario {
+        name: "init early nack",
+        ops: &[(Op::Init, Outcome::Ok)],
+        bus: &[nack(w(&[INIT_SENSOR])), nack(w(&[INIT_SENSOR])), w(&[INIT_SENSOR]), w(&[READ_STATUS]), r(&[0x18])],
+    },
+    Scenario {
+        name: "read status",
+        ops: &[(Op::ReadStatus, Outcome::Status(0x18)), (Op::ReadStatus, Outcome::Err(ErrorKind::I2C))],
+        bus: &[w(&[READ_STATUS]), r(&[0x18]), w(&[READ_STATUS]), nack(r(&[0x00]))],
+    },
+    Scenario {
+        name: "measure",
+        ops: &[(Op::Measure, READING), (Op::Measure, READING)],
+        bus: &[TRIGGER, FRAME, TRIGGER, BUSY_FRAME, FRAME],
+    },
+    Scenario {
+        name: "measure bad crc",
+        ops: &[(Op::Measure, Outcome::Err(ErrorKind::InvalidChecksum))],
+        bus: &[TRIGGER, r(&[0x18, 0x7E, 0x51, 0x65, 0xD4, 0xA0, 0x00])],
+    },
+    Scenario {
+        name: "measure time out",
+        ops: &[(Op::Measure, Outcome::Err(ErrorKind::DeviceTimeOut))],
+        bus: &[TRIGGER, BUSY_FRAME, BUSY_FRAME, BUSY_FRAME, BUSY_FRAME],
+    },
+    Scenario {
+        name: "soft reset",
+        ops: &[(Op::SoftReset, Outcome::Status(0x18))],
+        bus: &[w(&[READ_STATUS]), r(&[0x18]), w(&[SOFT_RESET]), w(&[READ_STATUS]), r(&[0x18])],
+    },
+];
+
+//Rounds to hundredths.
+fn centi(v: f32) -> i32 {
+    (v * 100.0 + 0.5) as i32
+}
+
+///Runs the scenario's ops over `bus` and checks their outcomes, gives the
+///bus back so the runner can check the script was used up.
+pub(crate) fn run<T: Aht2xTransport>(scenario: &Scenario, bus: T) -> T {
+    run_with_delay(scenario, bus, &mut embedded_hal_mock::delay::MockNoop)
+}
+
+///`run()` waiting on `delay`, for runners of adapters that bring their own.
+pub(crate) fn run_with_delay<T: Aht2xTransport>(
+    scenario: &Scenario,
+    bus: T,
+    delay: &mut impl Delay,
+    ) -> T
+{
+    //Started as initialized so the ops can run in any order.
+    let mut inited = Some(InitializedSensor { sensor: Sensor::new(bus, SENSOR_ADDR) });
+
+    for (i, &(op, expected)) in scenario.ops.iter().enumerate() {
+        let sensor = inited.as_mut().unwrap();
+        let outcome = match op {
+            Op::Init => {
+                let (r, again) = match inited.take().unwrap().downgrade().init(delay) {
+                    Ok(again) => (Ok(Outcome::Ok), again),
+                    Err(e) => (Err(e.error.kind()), InitializedSensor { sensor: e.sensor }),
+                };
+                inited = Some(again);
+                r
+            },
+            Op::ReadStatus => sensor.sensor.read_status()
+                .map(|s| Outcome::Status(s.status))
+                .map_err(|e| e.kind()),
+            Op::Measure => sensor.measure(delay)
+                .map(|m| Outcome::Reading(centi(m.temperature.0), centi(m.humidity.0)))
+                .map_err(|e| e.kind()),
+            Op::SoftReset => sensor.soft_reset(delay)
+                .map(|s| Outcome::Status(s.status))
+                .map_err(|e| e.kind()),
+        };
+        let outcome = outcome.unwrap_or_else(Outcome::Err);
+        assert_eq!(outcome, expected, "{}: op {} ({:?})", scenario.name, i, op);
+    }
+    inited.unwrap().downgrade().i2c
+}
+
+///A bare `Aht2xTransport` playing the script back, the reference the HAL
+///adapters are held to.
+pub(crate) struct ScriptedBus {
+    script: &'static [Step],
+    pos: usize,
+}
+
+impl ScriptedBus {
+    pub fn new(script: &'static [Step]) -> Self {
+        ScriptedBus { script, pos: 0 }
+    }
+
+    pub fn done(&self) {
+        assert_eq!(self.pos, self.script.len(), "transfers left over");
+    }
+
+    fn next(&mut self, write: bool) -> Step {
+        let step = *self.script.get(self.pos).expect("transfer past the end of the script");
+        assert_eq!(step.write, write, "transfer {} direction", self.pos);
+        self.pos += 1;
+        step
+    }
+}
+
+impl Aht2xTransport for ScriptedBus {
+    type Error = ();
+
+    fn write_frame(&mut self, address: u8, bytes: &[u8]) -> Result<(), ()> {
+        assert_eq!(address, SENSOR_ADDR);
+        let step = self.next(true);
+        assert_eq!(bytes, step.bytes, "transfer {}", self.pos - 1);
+        if step.nack {
+            return Err(());
+        }
+        Ok(())
+    }
+
+    fn read_frame(&mut self, address: u8, buf: &mut [u8]) -> Result<(), ()> {
+        assert_eq!(address, SENSOR_ADDR);
+        let step = self.next(false);
+        assert_eq!(buf.len(), step.bytes.len(), "transfer {} length", self.pos - 1);
+        if step.nack {
+            return Err(());
+        }
+        buf.copy_from_slice(step.bytes);
+        Ok(())
+    }
+}
+
+mod conformance_tests {
+    use embedded_hal_mock::i2c::{Mock as I2cMock, Transaction as I2cTransaction};
+    use embedded_hal_mock::MockError;
+
+    use super::*;
+    use crate::adapters::shared::{BusLock, SharedBus};
+    use crate::core::sensor_status::{BUSY_BM, CALENABLED_BM};
+    use crate::{Celsius, RelativeHumidity, SimulatedSensor};
+
+    #[test]
+    fn scripted_bus()
+    {
+        for scenario in SCENARIOS {
+            run(scenario, ScriptedBus::new(scenario.bus)).done();
+        }
+    }
+
+    #[test]
+    fn eh0()
+    {
+        for scenario in SCENARIOS {
+            let expected: Vec<I2cTransaction> = scenario.bus.iter()
+                .map(|s| {
+                    let t = match s.write {
+                        true => I2cTransaction::write(SENSOR_ADDR, s.bytes.to_vec()),
+                        false => I2cTransaction::read(SENSOR_ADDR, s.bytes.to_vec()),
+                    };
+                    match s.nack {
+                        true => t.with_error(MockError::Io(std::io::ErrorKind::Other)),
+                        false => t,
+                    }
+                })
+                .collect();
+            run(scenario, I2cMock::new(&expected)).done();
+        }
+    }
+
+    struct Unlocked(ScriptedBus);
+
+    impl BusLock for Unlocked {
+        type Bus = ScriptedBus;
+
+        fn with_bus<R>(&mut self, _timeout_ms: u32, f: impl FnOnce(&mut ScriptedBus) -> R) -> Option<R> {
+            Some(f(&mut self.0))
+        }
+    }
+
+    ///A 1.0 `I2c` playing the script, the driver reaches it through `Eh1`.
+    #[cfg(feature = "eh1")]
+    struct ScriptedI2c(ScriptedBus);
+
+    #[cfg(feature = "eh1")]
+    impl eh1::i2c::ErrorType for ScriptedI2c {
+        type Error = eh1::i2c::ErrorKind;
+    }
+
+    #[cfg(feature = "eh1")]
+    impl eh1::i2c::I2c for ScriptedI2c {
+        fn transaction(
+            &mut self,
+            address: u8,
+            operations: &mut [eh1::i2c::Operation<'_>],
+            ) -> Result<(), Self::Error>
+        {
+            use eh1::i2c::{ErrorKind, NoAcknowledgeSource, Operation};
+
+            let nack = |()| ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown);
+            for op in operations {
+                match op {
+                    Operation::Write(bytes) => self.0.write_frame(address, bytes).map_err(nack)?,
+                    Operation::Read(buf) => self.0.read_frame(address, buf).map_err(nack)?,
+                }
+            }
+            Ok(())
+        }
+    }
+
+    ///A 1.0 `DelayNs` adding up what it was asked to wait.
+    #[cfg(feature = "eh1")]
+    struct TotalDelay(u64);
+
+    #[cfg(feature = "eh1")]
+    impl eh1::delay::DelayNs for TotalDelay {
+        fn delay_ns(&mut self, ns: u32) {
+            self.0 += u64::from(ns);
+        }
+    }
+
+    //Both halves of the 1.0 adapter, the bus and the delay. The waits have
+    //to add up to the 0.2 ones of the same scenario.
+    #[cfg(feature = "eh1")]
+    #[test]
+    fn eh1()
+    {
+        for scenario in SCENARIOS {
+            let mut log = crate::sensor_test::DelayLog::default();
+            run_with_delay(scenario, ScriptedBus::new(scenario.bus), &mut log).done();
+
+            let mut delay = crate::Eh1(TotalDelay(0));
+            let i2c = ScriptedI2c(ScriptedBus::new(scenario.bus));
+            let bus = run_with_delay(scenario, crate::Eh1(i2c), &mut delay);
+            bus.into_inner().0.done();
+            let waited: u64 = log.0.iter().map(|&ms| u64::from(ms)).sum();
+            assert_eq!(delay.into_inner().0, waited * 1_000_000, "{}", scenario.name);
+        }
+    }
+
+    ///The script behind an embedded-hal-async `I2c`.
+    #[cfg(feature = "async")]
+    struct AsyncScriptedI2c(ScriptedBus);
+
+    #[cfg(feature = "async")]
+    impl embedded_hal_async::i2c::ErrorType for AsyncScriptedI2c {
+        type Error = embedded_hal_async::i2c::ErrorKind;
+    }
+
+    #[cfg(feature = "async")]
+    impl embedded_hal_async::i2c::I2c for AsyncScriptedI2c {
+        async fn transaction(
+            &mut self,
+            address: u8,
+            operations: &mut [embedded_hal_async::i2c::Operation<'_>],
+            ) -> Result<(), Self::Error>
+        {
+            use embedded_hal_async::i2c::{ErrorKind, NoAcknowledgeSource, Operation};
+
+            let nack = |()| ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown);
+            for op in operations {
+                match op {
+                    Operation::Write(bytes) => self.0.write_frame(address, bytes).map_err(nack)?,
+                    Operation::Read(buf) => self.0.read_frame(address, buf).map_err(nack)?,
+                }
+            }
+            Ok(())
+        }
+    }
+
+    //`AsyncSensor` has its own run loop rather than a transport, so it gets
+    //the ops itself. The same bus traffic, outcomes and waits as the
+    //blocking driver.
+    #[cfg(feature = "async")]
+    #[test]
+    fn asynch()
+    {
+        use crate::adapters::asynch::asynch_tests::{block_on, DelayLog};
+        use crate::AsyncSensor;
+
+        for scenario in SCENARIOS {
+            let mut log = crate::sensor_test::DelayLog::default();
+            run_with_delay(scenario, ScriptedBus::new(scenario.bus), &mut log).done();
+
+            let mut delay = DelayLog::default();
+            let mut sensor = AsyncSensor::new(AsyncScriptedI2c(ScriptedBus::new(scenario.bus)), SENSOR_ADDR);
+            for (i, &(op, expected)) in scenario.ops.iter().enumerate() {
+                let outcome = match op {
+                    Op::Init => block_on(sensor.init(&mut delay)).map(|_| Outcome::Ok),
+                    Op::ReadStatus => block_on(sensor.read_status()).map(|s| Outcome::Status(s.status)),
+                    Op::Measure => block_on(sensor.measure(&mut delay))
+                        .map(|m| Outcome::Reading(centi(m.temperature.0), centi(m.humidity.0))),
+                    Op::SoftReset => block_on(sensor.soft_reset(&mut delay)).map(|s| Outcome::Status(s.status)),
+                };
+                let outcome = outcome.unwrap_or_else(|e| Outcome::Err(e.kind()));
+                assert_eq!(outcome, expected, "{}: op {} ({:?})", scenario.name, i, op);
+            }
+            sensor.release().0.done();
+            let waited: Vec<u32> = log.0.iter().map(|&ms| u32::from(ms)).collect();
+            assert_eq!(delay.0, waited, "{}", scenario.name);
+        }
+    }
+
+    #[cfg(feature = "ffi")]
+    #[test]
+    fn ffi()
+    {
+        use crate::adapters::ffi::ffi_tests::{delay_ms, read, write};
+        use crate::{FfiBus, FfiDelay};
+
+        for scenario in SCENARIOS {
+            let mut log = crate::sensor_test::DelayLog::default();
+            run_with_delay(scenario, ScriptedBus::new(scenario.bus), &mut log).done();
+
+            let mut script = ScriptedBus::new(scenario.bus);
+            let mut waited = 0u32;
+            let ctx = (&mut script as *mut ScriptedBus).cast();
+            let bus = unsafe { FfiBus::new(ctx, write::<ScriptedBus>, read::<ScriptedBus>) };
+            let mut delay = unsafe { FfiDelay::new((&mut waited as *mut u32).cast(), delay_ms) };
+            run_with_delay(scenario, bus, &mut delay);
+            script.done();
+            assert_eq!(waited, log.0.iter().map(|&ms| u32::from(ms)).sum::<u32>(), "{}", scenario.name);
+        }
+    }
+
+    ///The simulator answering, checked against the script as it goes. The
+    ///script only steers what the simulator can't know: the busy reads and
+    ///CRC of each measurement, the calibration bit after init and the
+    ///NACKs, which come from the bus rather than the sensor. The simulator
+    ///computes its own CRCs, so only the status byte of a read is compared,
+    ///the readings are checked through the outcomes.
+    struct CheckedSim {
+        sim: SimulatedSensor,
+        script: ScriptedBus,
+    }
+
+    impl CheckedSim {
+        fn new(script: &'static [Step]) -> Self {
+            let mut sim = SimulatedSensor::new(Celsius(22.88), RelativeHumidity(49.34));
+            sim.calibrated = true;
+            CheckedSim { sim, script: ScriptedBus::new(script) }
+        }
+
+        fn reads_ahead(&self) -> impl Iterator<Item = &'static [u8]> {
+            self.script.script[self.script.pos..].iter()
+                .filter(|s| !s.write)
+                .map(|s| s.bytes)
+        }
+    }
+
+    impl Aht2xTransport for CheckedSim {
+        type Error = ();
+
+        fn write_frame(&mut self, address: u8, bytes: &[u8]) -> Result<(), ()> {
+            let step = self.script.next(true);
+            assert_eq!(bytes, step.bytes, "transfer {}", self.script.pos - 1);
+            if step.nack {
+                return Err(());
+            }
+            if bytes[0] == TRIG_MESSURE {
+                let busy = self.reads_ahead().take_while(|r| r[0] & BUSY_BM != 0).count();
+                self.sim.busy_reads = busy as u8;
+                self.sim.corrupt_crc = self.reads_ahead().nth(busy)
+                    .is_some_and(|frame| crate::protocol::crc8(&frame[..6]) != frame[6]);
+            }
+            assert_eq!(self.sim.write_frame(address, bytes), Ok(()));
+            if bytes[0] == INIT_SENSOR {
+                self.sim.calibrated = self.reads_ahead().next()
+                    .is_some_and(|status| status[0] & CALENABLED_BM != 0);
+            }
+            Ok(())
+        }
+
+        fn read_frame(&mut self, address: u8, buf: &mut [u8]) -> Result<(), ()> {
+            let step = self.script.next(false);
+            assert_eq!(buf.len(), step.bytes.len(), "transfer {} length", self.script.pos - 1);
+            if step.nack {
+                return Err(());
+            }
+            assert_eq!(self.sim.read_frame(address, buf), Ok(()));
+            assert_eq!(buf[0], step.bytes[0], "transfer {} status", self.script.pos - 1);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn simulator()
+    {
+        for scenario in SCENARIOS {
+            run(scenario, CheckedSim::new(scenario.bus)).script.done();
+        }
+    }
+
+    #[test]
+    fn shared_bus()
+    {
+        for scenario in SCENARIOS {
+            let bus = run(scenario, SharedBus::new(Unlocked(ScriptedBus::new(scenario.bus)), 10));
+            bus.release().0.done();
+        }
+    }
+}
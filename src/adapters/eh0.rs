@@ -0,0 +1,32 @@
+/*
+ * Filename: eh0.rs
+ * Description: Every embedded-hal 0.2 blocking I2C peripheral is an
+ * `Aht2xTransport` and every `DelayMs<u16>` a `Delay`, only built with the
+ * `legacy` feature. The tests mock the bus with 0.2 mocks, so they always
+ * have it.
+ */
+
+use embedded_hal::blocking::delay::DelayMs;
+use embedded_hal::blocking::i2c;
+
+use crate::{Aht2xTransport, Delay};
+
+impl<T, E> Aht2xTransport for T
+where T: i2c::Read<Error = E> + i2c::Write<Error = E>,
+{
+    type Error = E;
+
+    fn write_frame(&mut self, address: u8, bytes: &[u8]) -> Result<(), E> {
+        self.write(address, bytes)
+    }
+
+    fn read_frame(&mut self, address: u8, buf: &mut [u8]) -> Result<(), E> {
+        self.read(address, buf)
+    }
+}
+
+impl<T: DelayMs<u16>> Delay for T {
+    fn delay_ms(&mut self, ms: u16) {
+        DelayMs::delay_ms(self, ms)
+    }
+}
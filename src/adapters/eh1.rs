@@ -0,0 +1,128 @@
+/*
+ * Filename: eh1.rs
+ * Description: embedded-hal 1.0 support, only built with the `eh1`
+ * feature. `Eh1` wraps a 1.0 `I2c` into an `Aht2xTransport`, and a 1.0
+ * `DelayNs` into the `Delay` the driver waits with. Neither needs
+ * embedded-hal 0.2, build with `default-features = false` to leave it out.
+ *
+ * It's a wrapper rather than a second blanket impl so a HAL type
+ * implementing both versions' traits can't give conflicting impls.
+ */
+
+use eh1::delay::DelayNs;
+use eh1::i2c::I2c;
+use crate::{Aht2xTransport, Delay, Sensor};
+
+///An embedded-hal 1.0 I2C peripheral or delay, in the form the driver
+///takes them.
+///
+///`Sensor::new(Eh1(i2c), SENSOR_ADDR).init(&mut Eh1(delay))`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Eh1<T>(pub T);
+
+impl<T> Eh1<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<I: I2c> Aht2xTransport for Eh1<I> {
+    type Error = I::Error;
+
+    fn write_frame(&mut self, address: u8, bytes: &[u8]) -> Result<(), I::Error> {
+        self.0.write(address, bytes)
+    }
+
+    fn read_frame(&mut self, address: u8, buf: &mut [u8]) -> Result<(), I::Error> {
+        self.0.read(address, buf)
+    }
+}
+
+impl<D: DelayNs> Delay for Eh1<D> {
+    fn delay_ms(&mut self, ms: u16) {
+        self.0.delay_ms(ms.into());
+    }
+}
+
+impl<I: I2c> Sensor<Eh1<I>> {
+    ///`new()` for an embedded-hal 1.0 I2C peripheral.
+    pub fn new_eh1(i2c: I, address: u8) -> Self {
+        Sensor::new(Eh1(i2c), address)
+    }
+}
+
+#[cfg(test)]
+mod eh1_tests {
+    use eh1::i2c::{ErrorKind, ErrorType, NoAcknowledgeSource, Operation};
+
+    use super::*;
+    use crate::{BusQuirks, Celsius, Error, RelativeHumidity, SimulatedSensor, SENSOR_ADDR};
+
+    ///An esp-hal style 1.0 `I2c` on an ESP32-C3: multi byte reads of a
+    ///sensor stretching the clock while it converts time out.
+    struct Esp32c3I2c {
+        sim: SimulatedSensor,
+    }
+
+    impl ErrorType for Esp32c3I2c {
+        type Error = ErrorKind;
+    }
+
+    impl I2c for Esp32c3I2c {
+        fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), ErrorKind> {
+            let nack = |_| ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown);
+            for op in operations {
+                match op {
+                    Operation::Write(bytes) => self.sim.write_frame(address, bytes).map_err(nack)?,
+                    Operation::Read(buf) => {
+                        self.sim.read_frame(address, buf).map_err(nack)?;
+                        if buf.len() > 1 && buf[0] & 0x80 != 0 {
+                            return Err(ErrorKind::Other);
+                        }
+                    },
+                }
+            }
+            Ok(())
+        }
+    }
+
+    fn esp_sensor() -> Sensor<Eh1<Esp32c3I2c>> {
+        let mut sim = SimulatedSensor::new(Celsius(24.0), RelativeHumidity(55.0));
+        sim.busy_reads = 2;
+        Sensor::new_eh1(Esp32c3I2c { sim }, SENSOR_ADDR)
+    }
+
+    ///Adds up what it was asked to wait.
+    struct TotalDelay(u64);
+
+    impl DelayNs for TotalDelay {
+        fn delay_ns(&mut self, ns: u32) {
+            self.0 += u64::from(ns);
+        }
+    }
+
+    #[test]
+    fn delay_in_ms()
+    {
+        let mut delay = Eh1(TotalDelay(0));
+        Delay::delay_ms(&mut delay, 80);
+        Delay::delay_ms(&mut delay, u16::MAX);
+        assert_eq!(delay.into_inner().0, (80 + 65_535) * 1_000_000);
+    }
+
+    #[test]
+    fn esp32_c3()
+    {
+        let mut delay = Eh1(TotalDelay(0));
+
+        let r = esp_sensor().init(&mut delay).unwrap().measure(&mut delay);
+        assert_eq!(r.err(), Some(Error::I2C(ErrorKind::Other)));
+
+        let mut sensor = esp_sensor();
+        sensor.set_bus_quirks(BusQuirks::ESP32_C3);
+        let mut inited = sensor.init(&mut delay).unwrap();
+        let m = inited.measure(&mut delay).unwrap();
+        assert!(m.temperature.abs_delta(Celsius(24.0)) < Celsius(0.01));
+        assert_eq!(inited.last_retries(), 2);
+    }
+}
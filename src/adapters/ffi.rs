@@ -0,0 +1,155 @@
+/*
+ * Filename: ffi.rs
+ * Description: A bus and a delay out of C callbacks, only built with the
+ * `ffi` feature. For firmware whose I2C driver is in C (a vendor SDK, an
+ * RTOS) and that links the driver in as a static library: the C side hands
+ * over its transfer and delay functions and a context pointer, the driver
+ * runs over them like over any other `Aht2xTransport`.
+ */
+
+use core::ffi::c_void;
+
+use crate::{Aht2xTransport, Delay};
+
+///Writes `len` bytes from `bytes` to the 7 bit `address`, 0 on success.
+pub type FfiWrite = unsafe extern "C" fn(ctx: *mut c_void, address: u8, bytes: *const u8, len: usize) -> i32;
+///Reads `len` bytes from the 7 bit `address` into `buf`, 0 on success.
+pub type FfiRead = unsafe extern "C" fn(ctx: *mut c_void, address: u8, buf: *mut u8, len: usize) -> i32;
+///Blocks for `ms` milliseconds.
+pub type FfiDelayMs = unsafe extern "C" fn(ctx: *mut c_void, ms: u16);
+
+///A bus driven by C callbacks. A non zero return from either callback is
+///the error, passed on as `Error::I2C(code)`.
+#[derive(Debug)]
+pub struct FfiBus {
+    ctx: *mut c_void,
+    write: FfiWrite,
+    read: FfiRead,
+}
+
+impl FfiBus {
+    ///`ctx` is passed to every call as is.
+    ///
+    ///# Safety
+    ///The callbacks must be safe to call with `ctx` and a valid buffer for
+    ///as long as the `FfiBus` lives, and must not keep the buffer pointer
+    ///past the call.
+    pub unsafe fn new(ctx: *mut c_void, write: FfiWrite, read: FfiRead) -> Self {
+        FfiBus { ctx, write, read }
+    }
+}
+
+impl Aht2xTransport for FfiBus {
+    type Error = i32;
+
+    fn write_frame(&mut self, address: u8, bytes: &[u8]) -> Result<(), i32> {
+        //Safety: the caller of `new()` vouched for the callback and `ctx`.
+        match unsafe { (self.write)(self.ctx, address, bytes.as_ptr(), bytes.len()) } {
+            0 => Ok(()),
+            code => Err(code),
+        }
+    }
+
+    fn read_frame(&mut self, address: u8, buf: &mut [u8]) -> Result<(), i32> {
+        //Safety: as in `write_frame()`.
+        match unsafe { (self.read)(self.ctx, address, buf.as_mut_ptr(), buf.len()) } {
+            0 => Ok(()),
+            code => Err(code),
+        }
+    }
+}
+
+///A delay driven by a C callback.
+#[derive(Debug)]
+pub struct FfiDelay {
+    ctx: *mut c_void,
+    delay_ms: FfiDelayMs,
+}
+
+impl FfiDelay {
+    ///`ctx` is passed to every call as is.
+    ///
+    ///# Safety
+    ///The callback must be safe to call with `ctx` for as long as the
+    ///`FfiDelay` lives.
+    pub unsafe fn new(ctx: *mut c_void, delay_ms: FfiDelayMs) -> Self {
+        FfiDelay { ctx, delay_ms }
+    }
+}
+
+impl Delay for FfiDelay {
+    fn delay_ms(&mut self, ms: u16) {
+        //Safety: the caller of `new()` vouched for the callback and `ctx`.
+        unsafe { (self.delay_ms)(self.ctx, ms) }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod ffi_tests {
+    use super::*;
+    use crate::{Celsius, RelativeHumidity, Sensor, SimulatedSensor, SENSOR_ADDR};
+
+    ///C callbacks over the `Aht2xTransport` passed as `ctx`, any error is
+    ///-1.
+    pub(crate) unsafe extern "C" fn write<T: Aht2xTransport>(
+        ctx: *mut c_void,
+        address: u8,
+        bytes: *const u8,
+        len: usize,
+        ) -> i32
+    {
+        let bus = &mut *ctx.cast::<T>();
+        match bus.write_frame(address, core::slice::from_raw_parts(bytes, len)) {
+            Ok(()) => 0,
+            Err(_) => -1,
+        }
+    }
+
+    pub(crate) unsafe extern "C" fn read<T: Aht2xTransport>(
+        ctx: *mut c_void,
+        address: u8,
+        buf: *mut u8,
+        len: usize,
+        ) -> i32
+    {
+        let bus = &mut *ctx.cast::<T>();
+        match bus.read_frame(address, core::slice::from_raw_parts_mut(buf, len)) {
+            Ok(()) => 0,
+            Err(_) => -1,
+        }
+    }
+
+    ///Adds up the waits in the `u32` passed as `ctx`.
+    pub(crate) unsafe extern "C" fn delay_ms(ctx: *mut c_void, ms: u16) {
+        *ctx.cast::<u32>() += u32::from(ms);
+    }
+
+    #[test]
+    fn init_and_measure()
+    {
+        let mut sim = SimulatedSensor::new(Celsius(22.0), RelativeHumidity(45.0));
+        sim.busy_reads = 1;
+        let mut waited = 0u32;
+        let ctx = (&mut sim as *mut SimulatedSensor).cast();
+        let bus = unsafe { FfiBus::new(ctx, write::<SimulatedSensor>, read::<SimulatedSensor>) };
+        let mut delay = unsafe { FfiDelay::new((&mut waited as *mut u32).cast(), delay_ms) };
+
+        let mut inited = Sensor::new(bus, SENSOR_ADDR).init(&mut delay).unwrap();
+        let m = inited.measure(&mut delay).unwrap();
+        assert!(m.temperature.abs_delta(Celsius(22.0)) < Celsius(0.01));
+        assert_eq!(waited, 40 + 80 + 20);
+    }
+
+    #[test]
+    fn error_code()
+    {
+        unsafe extern "C" fn nack(_: *mut c_void, _: u8, _: *const u8, _: usize) -> i32 {
+            -5
+        }
+        let mut sim = SimulatedSensor::new(Celsius(22.0), RelativeHumidity(45.0));
+        let ctx = (&mut sim as *mut SimulatedSensor).cast();
+        let mut bus = unsafe { FfiBus::new(ctx, nack, read::<SimulatedSensor>) };
+        assert_eq!(bus.write_frame(SENSOR_ADDR, &[0x71]), Err(-5));
+        assert_eq!(bus.read_frame(0x10, &mut [0]), Err(-1));
+    }
+}
@@ -0,0 +1,83 @@
+/*
+ * Filename: mod.rs
+ * Description: Binds the driver to HALs and buses through
+ * `Aht2xTransport`: embedded-hal 0.2 and 1.0, C callbacks, a lock shared
+ * with other tasks and the USB-HID bridges. Adapters only move bytes, the
+ * behaviour lives in the core and the driver, so it's the same whichever
+ * adapter is used. embedded-hal-async can't be a transport, `asynch` runs
+ * the core's machines itself.
+ */
+
+#[cfg(feature = "async")]
+pub(crate) mod asynch;
+#[cfg(any(feature = "legacy", test))]
+pub(crate) mod eh0;
+#[cfg(feature = "eh1")]
+pub(crate) mod eh1;
+#[cfg(feature = "ffi")]
+pub(crate) mod ffi;
+#[cfg(feature = "hid-bridge")]
+pub(crate) mod bridge;
+pub(crate) mod shared;
+#[cfg(test)]
+mod conformance;
+
+#[cfg(test)]
+mod adapters_tests {
+    use embedded_hal_mock::i2c::{Mock as I2cMock, Transaction as I2cTransaction};
+
+    use super::shared::{BusLock, SharedBus};
+    use crate::clock::clock_tests::FakeClock;
+    use crate::{
+        Aht2xTransport, Celsius, Direction, Measurement, RecordingTransport, RelativeHumidity,
+        Sensor, SimulatedSensor, SENSOR_ADDR,
+    };
+
+    struct Unlocked(SimulatedSensor);
+
+    impl BusLock for Unlocked {
+        type Bus = SimulatedSensor;
+
+        fn with_bus<R>(&mut self, _timeout_ms: u32, f: impl FnOnce(&mut SimulatedSensor) -> R) -> Option<R> {
+            Some(f(&mut self.0))
+        }
+    }
+
+    fn init_and_measure<T>(bus: T) -> (Measurement, T)
+    where T: Aht2xTransport,
+          T::Error: core::fmt::Debug,
+    {
+        let mut delay = embedded_hal_mock::delay::MockNoop;
+        let mut inited = Sensor::new(bus, SENSOR_ADDR).init(&mut delay).unwrap();
+        let m = inited.measure(&mut delay).unwrap();
+        (m, inited.downgrade().i2c)
+    }
+
+    #[test]
+    fn adapters_agree()
+    {
+        let sim = || {
+            let mut sim = SimulatedSensor::new(Celsius(22.88), RelativeHumidity(49.34));
+            sim.busy_reads = 1;
+            sim
+        };
+
+        //The bus traffic against the simulator, replayed to the eh0 mock.
+        let clock = FakeClock { now_ms: 0, step_ms: 1 };
+        let recorder: RecordingTransport<_, _, 32> = RecordingTransport::new(sim(), clock);
+        let (native, recorder) = init_and_measure(recorder);
+        let expected: Vec<I2cTransaction> = recorder.iter()
+            .map(|t| match t.direction {
+                Direction::Write => I2cTransaction::write(t.address, t.bytes().to_vec()),
+                Direction::Read => I2cTransaction::read(t.address, t.bytes().to_vec()),
+            })
+            .collect();
+
+        let (m, mut mock) = init_and_measure(I2cMock::new(&expected));
+        assert_eq!(m, native);
+        mock.done();
+
+        let (m, _) = init_and_measure(SharedBus::new(Unlocked(sim()), 10));
+        assert_eq!(m, native);
+    }
+}
@@ -0,0 +1,190 @@
+/*
+ * Filename: shared.rs
+ * Description: Sharing the bus with other tasks under an RTOS. The bus is
+ * locked for each frame written or read, not for a whole measurement, the
+ * sensor is addressed every time so other devices can use the bus while it
+ * converts. A lock that can't be taken in time ends up as
+ * `Error::I2C(SharedBusError::LockTimeout)`.
+ *
+ * FreeRTOS (`freertos-rust`) and RTIC shared resources are supported behind
+ * the `freertos` and `rtic` features, anything else implements `BusLock`.
+ */
+
+use crate::{Aht2xTransport, Error};
+
+///Something that hands out the bus for a while.
+pub trait BusLock {
+    type Bus: Aht2xTransport;
+
+    ///Runs `f` with the bus locked. None if the lock couldn't be taken
+    ///within `timeout_ms`.
+    fn with_bus<R>(&mut self, timeout_ms: u32, f: impl FnOnce(&mut Self::Bus) -> R) -> Option<R>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SharedBusError<E> {
+    ///Another task held the bus for longer than the timeout.
+    LockTimeout,
+    Bus(E),
+}
+
+impl<E> Error<SharedBusError<E>> {
+    pub fn is_lock_timeout(&self) -> bool {
+        matches!(self, Error::I2C(SharedBusError::LockTimeout))
+    }
+}
+
+///A transport that locks a shared bus for each frame.
+#[derive(Debug)]
+pub struct SharedBus<L> {
+    lock: L,
+    timeout_ms: u32,
+}
+
+impl<L: BusLock> SharedBus<L> {
+    pub fn new(lock: L, timeout_ms: u32) -> Self {
+        SharedBus { lock, timeout_ms }
+    }
+
+    pub fn release(self) -> L {
+        self.lock
+    }
+}
+
+impl<L: BusLock> Aht2xTransport for SharedBus<L> {
+    type Error = SharedBusError<<L::Bus as Aht2xTransport>::Error>;
+
+    fn write_frame(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.lock.with_bus(self.timeout_ms, |bus| bus.write_frame(address, bytes))
+            .ok_or(SharedBusError::LockTimeout)?
+            .map_err(SharedBusError::Bus)
+    }
+
+    fn read_frame(&mut self, address: u8, buf: &mut [u8]) -> Result<(), Self::Error> {
+        self.lock.with_bus(self.timeout_ms, |bus| bus.read_frame(address, buf))
+            .ok_or(SharedBusError::LockTimeout)?
+            .map_err(SharedBusError::Bus)
+    }
+}
+
+///A FreeRTOS mutex, usually a `static` or behind an `Arc` shared with the
+///other tasks on the bus.
+#[cfg(feature = "freertos")]
+impl<T: Aht2xTransport> BusLock for &freertos_rust::Mutex<T> {
+    type Bus = T;
+
+    fn with_bus<R>(&mut self, timeout_ms: u32, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        let mut guard = self.lock(freertos_rust::Duration::ms(timeout_ms)).ok()?;
+        Some(f(&mut guard))
+    }
+}
+
+#[cfg(feature = "freertos")]
+impl<T: Aht2xTransport> BusLock for alloc::sync::Arc<freertos_rust::Mutex<T>> {
+    type Bus = T;
+
+    fn with_bus<R>(&mut self, timeout_ms: u32, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        let mut guard = self.lock(freertos_rust::Duration::ms(timeout_ms)).ok()?;
+        Some(f(&mut guard))
+    }
+}
+
+///An RTIC shared resource, e.g. `SharedBus::new(Rtic(cx.shared.i2c), 0)`.
+///RTIC's priority ceiling never blocks, so the timeout is never hit.
+#[cfg(feature = "rtic")]
+#[derive(Debug)]
+pub struct Rtic<M>(pub M);
+
+#[cfg(feature = "rtic")]
+impl<M> BusLock for Rtic<M>
+where M: rtic_core::Mutex,
+      M::T: Aht2xTransport,
+{
+    type Bus = M::T;
+
+    fn with_bus<R>(&mut self, _timeout_ms: u32, f: impl FnOnce(&mut M::T) -> R) -> Option<R> {
+        Some(self.0.lock(f))
+    }
+}
+
+#[cfg(test)]
+mod shared_tests {
+    use super::*;
+    use crate::{Celsius, RelativeHumidity, Sensor, SimError, SimulatedSensor, SENSOR_ADDR};
+
+    ///A lock that can be held by "another task".
+    struct FakeLock {
+        bus: SimulatedSensor,
+        held_elsewhere: bool,
+        timeouts_seen: Vec<u32>,
+    }
+
+    impl BusLock for FakeLock {
+        type Bus = SimulatedSensor;
+
+        fn with_bus<R>(&mut self, timeout_ms: u32, f: impl FnOnce(&mut SimulatedSensor) -> R) -> Option<R> {
+            self.timeouts_seen.push(timeout_ms);
+            if self.held_elsewhere {
+                return None;
+            }
+            Some(f(&mut self.bus))
+        }
+    }
+
+    fn shared() -> Sensor<SharedBus<FakeLock>> {
+        let lock = FakeLock {
+            bus: SimulatedSensor::new(Celsius(21.0), RelativeHumidity(45.0)),
+            held_elsewhere: false,
+            timeouts_seen: vec![],
+        };
+        Sensor::new(SharedBus::new(lock, 50), SENSOR_ADDR)
+    }
+
+    #[test]
+    fn measures_through_the_lock()
+    {
+        let mut delay = embedded_hal_mock::delay::MockNoop;
+        let mut inited = shared().init(&mut delay).unwrap();
+        let m = inited.measure(&mut delay).unwrap();
+        assert!(m.temperature.abs_delta(Celsius(21.0)) < Celsius(0.01));
+        assert!(inited.sensor.i2c.lock.timeouts_seen.iter().all(|&t| t == 50));
+    }
+
+    #[test]
+    fn lock_timeout()
+    {
+        let mut sensor = shared();
+        let mut delay = embedded_hal_mock::delay::MockNoop;
+        sensor.i2c.lock.held_elsewhere = true;
+        let e = sensor.init(&mut delay).err().unwrap();
+        assert!(e.error.is_lock_timeout());
+        let mut sensor = e.sensor;
+
+        //Bus errors come through as they are.
+        sensor.i2c.lock.held_elsewhere = false;
+        sensor.address = 0x39;
+        let e = sensor.read_status().err().unwrap();
+        assert_eq!(e, Error::I2C(SharedBusError::Bus(SimError::Nack)));
+        assert!(!e.is_lock_timeout());
+    }
+
+    #[cfg(feature = "rtic")]
+    #[test]
+    fn rtic_resource()
+    {
+        //What RTIC generates for a shared resource, minus the ceiling.
+        struct Resource<'a>(&'a mut SimulatedSensor);
+
+        impl rtic_core::Mutex for Resource<'_> {
+            type T = SimulatedSensor;
+
+            fn lock<R>(&mut self, f: impl FnOnce(&mut SimulatedSensor) -> R) -> R {
+                f(self.0)
+            }
+        }
+
+        let mut sim = SimulatedSensor::new(Celsius(21.0), RelativeHumidity(45.0));
+        let mut sensor = Sensor::new(SharedBus::new(Rtic(Resource(&mut sim)), 0), SENSOR_ADDR);
+        assert!(sensor.read_status().is_ok());
+    }
+}
@@ -0,0 +1,172 @@
+/*
+ * Filename: advanced.rs
+ * Description: The extended command sequences from AOSONG's sample code,
+ * raw access to the registers behind the status byte. Only built with the
+ * `advanced` feature, these are for the verification tooling and can
+ * break the sensor's calibration if misused.
+ *
+ * The command (factory) mode entry/exit sequences aren't in AOSONG's
+ * public documents. They follow the AHT1x drivers that reverse engineered
+ * them: the calibrate command with the mode bits set enters it, 0xA8
+ * returns to normal mode. Both check the mode bits of the status byte
+ * afterwards, so a sensor that ignores them is an error rather than a
+ * silent no-op.
+ */
+
+use crate::{Aht2xTransport, Delay, Error, Indicator, Sensor, CAL_PARAM0, CAL_REGISTERS};
+use crate::core::commands;
+use crate::core::sensor_status::SensorStatus;
+
+///Mode bits of the calibrate command's first parameter that select
+///command mode.
+pub const CMD_MODE_PARAM: u8 = 0x40;
+///Returns from command (or cycle) mode to normal mode, with two zero
+///parameter bytes.
+pub const NORMAL_MODE: u8 = 0xA8;
+
+///Contents of the calibration registers along with the status byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterDump {
+    pub status: SensorStatus,
+    ///The two bytes of each register in `CAL_REGISTERS`, in that order.
+    pub registers: [(u8, [u8; 2]); 3],
+}
+
+impl<E, I2C, L> Sensor<I2C, L>
+where I2C: Aht2xTransport<Error = E>,
+      L: Indicator,
+{
+    ///Reads a register, returns the status byte and the two register bytes.
+    pub fn read_register(
+        &mut self,
+        reg: u8,
+        delay: &mut impl Delay,
+        ) -> Result<[u8; 3], Error<E>>
+    {
+        self.register_read(reg, delay)
+    }
+
+    ///Writes the two bytes of a register.
+    pub fn write_register(&mut self, reg: u8, value: [u8; 2]) -> Result<(), Error<E>> {
+        self.register_write(reg, value)
+    }
+
+    ///Reads the status byte and all the calibration registers.
+    pub fn dump_registers(
+        &mut self,
+        delay: &mut impl Delay,
+        ) -> Result<RegisterDump, Error<E>>
+    {
+        let status = self.read_status()?;
+        let mut registers = [(0u8, [0u8; 2]); 3];
+        for (slot, reg) in registers.iter_mut().zip(CAL_REGISTERS) {
+            let rbuf = self.register_read(reg, delay)?;
+            *slot = (reg, [rbuf[1], rbuf[2]]);
+        }
+        Ok(RegisterDump { status, registers })
+    }
+
+    ///Puts the sensor into command mode, where it takes register
+    ///commands but doesn't measure. Waits the calibration time and returns
+    ///the status byte, one not reporting command mode is
+    ///`Error::UnexpectedStatus`.
+    pub fn enter_command_mode(
+        &mut self,
+        delay: &mut impl Delay,
+        ) -> Result<SensorStatus, Error<E>>
+    {
+        self.write_command(&[commands::CALIBRATE, CMD_MODE_PARAM | CAL_PARAM0, 0x00])?;
+        delay.delay_ms(self.timings.calibrate_ms);
+        match self.read_status()? {
+            status if status.is_cmd_mode() => Ok(status),
+            status => Err(Error::UnexpectedStatus(status.status)),
+        }
+    }
+
+    ///Leaves command mode, the status byte has to report normal mode
+    ///afterwards or it's `Error::UnexpectedStatus`.
+    pub fn exit_command_mode(
+        &mut self,
+        delay: &mut impl Delay,
+        ) -> Result<SensorStatus, Error<E>>
+    {
+        self.write_command(&[NORMAL_MODE, 0x00, 0x00])?;
+        delay.delay_ms(self.timings.calibrate_ms);
+        match self.read_status()? {
+            status if status.is_normal_mode() => Ok(status),
+            status => Err(Error::UnexpectedStatus(status.status)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod advanced_tests {
+    use embedded_hal_mock::i2c::{
+        Mock as I2cMock,
+        Transaction as I2cTransaction,
+    };
+
+    use super::*;
+    use crate::core::commands;
+    use crate::{REG_WRITE, SENSOR_ADDR};
+
+    #[test]
+    fn read_write_register()
+    {
+        let expected = [
+            I2cTransaction::write(SENSOR_ADDR, vec![0x1B, 0x00, 0x00]),
+            I2cTransaction::read(SENSOR_ADDR, vec![0x18, 0x12, 0x34]),
+            I2cTransaction::write(SENSOR_ADDR, vec![REG_WRITE | 0x1B, 0x56, 0x78]),
+        ];
+
+        let mut sensor_instance = Sensor::new(I2cMock::new(&expected), SENSOR_ADDR);
+        let mut mock_delay = embedded_hal_mock::delay::MockNoop;
+        assert_eq!(sensor_instance.read_register(0x1B, &mut mock_delay), Ok([0x18, 0x12, 0x34]));
+        assert!(sensor_instance.write_register(0x1B, [0x56, 0x78]).is_ok());
+        sensor_instance.i2c.done();
+    }
+
+    #[test]
+    fn dump_registers()
+    {
+        let mut expected = vec![
+            I2cTransaction::write(SENSOR_ADDR, vec![commands::READ_STATUS]),
+            I2cTransaction::read(SENSOR_ADDR, vec![0x18]),
+        ];
+        for reg in CAL_REGISTERS {
+            expected.push(I2cTransaction::write(SENSOR_ADDR, vec![reg, 0x00, 0x00]));
+            expected.push(I2cTransaction::read(SENSOR_ADDR, vec![0x18, reg, 0xAA]));
+        }
+
+        let mut sensor_instance = Sensor::new(I2cMock::new(&expected), SENSOR_ADDR);
+        let mut mock_delay = embedded_hal_mock::delay::MockNoop;
+        let dump = sensor_instance.dump_registers(&mut mock_delay).unwrap();
+        assert_eq!(dump.status, SensorStatus::new(0x18));
+        assert_eq!(dump.registers[2], (0x1E, [0x1E, 0xAA]));
+        sensor_instance.i2c.done();
+    }
+
+    #[test]
+    fn command_mode()
+    {
+        let status = |byte| [
+            I2cTransaction::write(SENSOR_ADDR, vec![commands::READ_STATUS]),
+            I2cTransaction::read(SENSOR_ADDR, vec![byte]),
+        ];
+        let mut expected = vec![I2cTransaction::write(SENSOR_ADDR, vec![0xE1, 0x48, 0x00])];
+        expected.extend(status(0x48));
+        expected.push(I2cTransaction::write(SENSOR_ADDR, vec![NORMAL_MODE, 0x00, 0x00]));
+        expected.extend(status(0x18));
+        //A sensor that ignores the entry.
+        expected.push(I2cTransaction::write(SENSOR_ADDR, vec![0xE1, 0x48, 0x00]));
+        expected.extend(status(0x18));
+
+        let mut sensor_instance = Sensor::new(I2cMock::new(&expected), SENSOR_ADDR);
+        let mut delay = crate::sensor_test::DelayLog(vec![]);
+        assert!(sensor_instance.enter_command_mode(&mut delay).unwrap().is_cmd_mode());
+        assert!(sensor_instance.exit_command_mode(&mut delay).unwrap().is_normal_mode());
+        assert_eq!(sensor_instance.enter_command_mode(&mut delay), Err(Error::UnexpectedStatus(0x18)));
+        assert_eq!(delay.0, [10, 10, 10]);
+        sensor_instance.i2c.done();
+    }
+}
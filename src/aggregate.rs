@@ -0,0 +1,283 @@
+/*
+ * Filename: aggregate.rs
+ * Description: Reduces high rate measurements into fewer records before
+ * they are logged, saving flash wear on SD card and EEPROM log targets.
+ *
+ * Times are u32 milliseconds from a monotonic source, wrap around is handled.
+ */
+
+use crate::clock::{elapsed_ms, Clock};
+use crate::core::measurement::{Measurement, SensorId};
+
+///Min, max and mean of one value over an interval.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stats {
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+}
+
+///Running min/max/sum of one value.
+#[derive(Debug, Clone, Copy)]
+struct Running {
+    min: f32,
+    max: f32,
+    sum: f32,
+}
+
+impl Running {
+    fn new(v: f32) -> Self {
+        Running { min: v, max: v, sum: v }
+    }
+
+    fn add(&mut self, v: f32) {
+        self.min = self.min.min(v);
+        self.max = self.max.max(v);
+        self.sum += v;
+    }
+
+    fn stats(&self, count: u16) -> Stats {
+        Stats { min: self.min, max: self.max, mean: self.sum / count as f32 }
+    }
+}
+
+///One aggregated record, covering `count` measurements taken from
+///`start_ms` onwards.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IntervalRecord {
+    pub id: SensorId,
+    pub start_ms: u32,
+    pub count: u16,
+    pub temperature: Stats,
+    pub humidity: Stats,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Interval {
+    id: SensorId,
+    start_ms: u32,
+    count: u16,
+    temperature: Running,
+    humidity: Running,
+}
+
+///Aggregates measurements into one `IntervalRecord` per `interval_ms`.
+///Use one decimator per sensor, the record takes the id of the first
+///measurement in the interval.
+#[derive(Debug, Clone, Copy)]
+pub struct Decimator {
+    interval_ms: u32,
+    current: Option<Interval>,
+}
+
+impl Decimator {
+    pub fn new(interval_ms: u32) -> Self {
+        Decimator { interval_ms, current: None }
+    }
+
+    ///Adds a measurement, once it falls outside the running interval the
+    ///finished record is returned and a new interval starts with it.
+    ///NaN measurements are dropped.
+    pub fn push(&mut self, m: &Measurement, now_ms: u32) -> Option<IntervalRecord> {
+        let (t, h) = (m.temperature.0, m.humidity.0);
+        if t.is_nan() || h.is_nan() {
+            return None;
+        }
+
+        if let Some(cur) = self.current.as_mut() {
+            let in_interval = now_ms.wrapping_sub(cur.start_ms) < self.interval_ms;
+            if in_interval && cur.count < u16::MAX {
+                cur.count += 1;
+                cur.temperature.add(t);
+                cur.humidity.add(h);
+                return None;
+            }
+        }
+
+        let finished = self.flush();
+        self.current = Some(Interval {
+            id: m.id,
+            start_ms: now_ms,
+            count: 1,
+            temperature: Running::new(t),
+            humidity: Running::new(h),
+        });
+        finished
+    }
+
+    ///Closes the running interval early, e.g. before going to sleep.
+    pub fn flush(&mut self) -> Option<IntervalRecord> {
+        let cur = self.current.take()?;
+        Some(IntervalRecord {
+            id: cur.id,
+            start_ms: cur.start_ms,
+            count: cur.count,
+            temperature: cur.temperature.stats(cur.count),
+            humidity: cur.humidity.stats(cur.count),
+        })
+    }
+}
+
+///Time weighted mean of one value, for nodes whose sample interval varies
+///(duty cycling, adaptive rates). A plain mean would be biased towards the
+///periods that were sampled more often, this integrates the value over time
+///with the trapezoid rule instead.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimeWeightedMean {
+    last: Option<(f32, u32)>,
+    integral: f32,
+    total_ms: u32,
+}
+
+impl TimeWeightedMean {
+    pub fn new() -> Self {
+        TimeWeightedMean { last: None, integral: 0.0, total_ms: 0 }
+    }
+
+    ///Adds a sample taken at `timestamp_ms`, NaN samples are skipped.
+    pub fn add(&mut self, value: f32, timestamp_ms: u32) {
+        if value.is_nan() {
+            return;
+        }
+        if let Some((prev, prev_ms)) = self.last {
+            let dt = elapsed_ms(prev_ms, timestamp_ms);
+            self.integral += (prev + value) / 2.0 * dt as f32;
+            self.total_ms = self.total_ms.saturating_add(dt);
+        }
+        self.last = Some((value, timestamp_ms));
+    }
+
+    ///Adds a sample timestamped with the clock.
+    pub fn add_now(&mut self, value: f32, clock: &mut impl Clock) {
+        self.add(value, clock.now_ms());
+    }
+
+    ///Time covered by the samples so far.
+    pub fn duration_ms(&self) -> u32 {
+        self.total_ms
+    }
+
+    ///The time weighted mean, None before the first sample. With a single
+    ///sample that sample is returned.
+    pub fn mean(&self) -> Option<f32> {
+        let (last, _) = self.last?;
+        if self.total_ms == 0 {
+            return Some(last);
+        }
+        Some(self.integral / self.total_ms as f32)
+    }
+
+    ///Starts a new averaging period, the last sample is kept as its start
+    ///so no time is lost between periods.
+    pub fn restart(&mut self) {
+        self.integral = 0.0;
+        self.total_ms = 0;
+    }
+}
+
+#[cfg(test)]
+mod aggregate_tests {
+    use super::*;
+    use crate::core::measurement::Quality;
+    use crate::core::units::{Celsius, RelativeHumidity};
+
+    fn m(t: f32, h: f32) -> Measurement {
+        Measurement {
+            id: SensorId(1),
+            temperature: Celsius(t),
+            humidity: RelativeHumidity(h),
+            quality: Quality::GOOD,
+            pressure: None,
+            seq: None,
+        }
+    }
+
+    #[test]
+    fn decimate_per_interval()
+    {
+        let mut d = Decimator::new(60_000);
+
+        assert!(d.push(&m(20.0, 40.0), 0).is_none());
+        assert!(d.push(&m(22.0, 50.0), 20_000).is_none());
+        assert!(d.push(&m(24.0, 45.0), 59_999).is_none());
+
+        let r = d.push(&m(30.0, 60.0), 60_000).unwrap();
+        assert_eq!(r.id, SensorId(1));
+        assert_eq!(r.start_ms, 0);
+        assert_eq!(r.count, 3);
+        assert_eq!(r.temperature, Stats { min: 20.0, max: 24.0, mean: 22.0 });
+        assert_eq!(r.humidity, Stats { min: 40.0, max: 50.0, mean: 45.0 });
+
+        let r = d.flush().unwrap();
+        assert_eq!(r.start_ms, 60_000);
+        assert_eq!(r.count, 1);
+        assert_eq!(r.temperature.mean, 30.0);
+        assert!(d.flush().is_none());
+    }
+
+    #[test]
+    fn nan_dropped()
+    {
+        let mut d = Decimator::new(1000);
+        assert!(d.push(&m(f32::NAN, 40.0), 0).is_none());
+        assert!(d.flush().is_none());
+    }
+
+    #[test]
+    fn wrap_around()
+    {
+        let mut d = Decimator::new(1000);
+        assert!(d.push(&m(20.0, 40.0), u32::MAX - 100).is_none());
+        assert!(d.push(&m(20.0, 40.0), 800).is_none());
+        assert_eq!(d.push(&m(20.0, 40.0), 900).unwrap().count, 2);
+    }
+
+    #[test]
+    fn time_weighted()
+    {
+        let mut twm = TimeWeightedMean::new();
+        assert_eq!(twm.mean(), None);
+
+        //Lots of samples during a short warm spell.
+        twm.add(30.0, 0);
+        twm.add(30.0, 1000);
+        twm.add(30.0, 2000);
+        twm.add(30.0, 3000);
+        assert_eq!(twm.mean(), Some(30.0));
+
+        //Then a long cold period with only two samples.
+        twm.add(10.0, 3000);
+        twm.add(10.0, 33_000);
+        assert_eq!(twm.duration_ms(), 33_000);
+        let mean = twm.mean().unwrap();
+        assert!(mean > 11.8 && mean < 11.9);
+    }
+
+    #[test]
+    fn time_weighted_trapezoid()
+    {
+        let mut twm = TimeWeightedMean::new();
+        twm.add(20.0, 0);
+        assert_eq!(twm.mean(), Some(20.0));
+        twm.add(f32::NAN, 500);
+        twm.add(30.0, 1000);
+        assert_eq!(twm.mean(), Some(25.0));
+
+        twm.restart();
+        twm.add(40.0, 2000);
+        assert_eq!(twm.mean(), Some(35.0));
+    }
+
+    #[test]
+    fn time_weighted_clock()
+    {
+        use crate::clock::clock_tests::FakeClock;
+
+        let mut clock = FakeClock { now_ms: 0, step_ms: 100 };
+        let mut twm = TimeWeightedMean::new();
+        twm.add_now(10.0, &mut clock);
+        twm.add_now(20.0, &mut clock);
+        assert_eq!(twm.duration_ms(), 100);
+        assert_eq!(twm.mean(), Some(15.0));
+    }
+}
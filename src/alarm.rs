@@ -0,0 +1,222 @@
+/*
+ * Filename: alarm.rs
+ * Description: Threshold alarms with a debounce stage, a condition has to
+ * persist for a number of samples (or an amount of time) before the alarm
+ * raises or clears. This stops a relay chattering on single noisy readings.
+ *
+ * Times are u32 milliseconds from whatever monotonic source the application
+ * has, wrap around is handled.
+ */
+
+///The condition that puts the alarm in its active state.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Threshold {
+    Above(f32),
+    Below(f32),
+}
+
+impl Threshold {
+    ///Returns None for NaN values as they can't be compared.
+    pub fn check(&self, value: f32) -> Option<bool> {
+        if value.is_nan() {
+            return None;
+        }
+        match *self {
+            Threshold::Above(limit) => Some(value > limit),
+            Threshold::Below(limit) => Some(value < limit),
+        }
+    }
+}
+
+///How long a change has to persist before it's accepted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Debounce {
+    ///Number of consecutive samples, 0 and 1 both mean no debouncing.
+    Samples(u16),
+    ///Milliseconds since the first sample that showed the change.
+    Millis(u32),
+}
+
+///Emitted when the debounced alarm state changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AlarmEvent {
+    Raised,
+    Cleared,
+}
+
+///Debounces a boolean condition, it's used by `Alarm` but works on its
+///own for conditions that aren't a simple threshold.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Debouncer {
+    debounce: Debounce,
+    active: bool,
+    count: u16,
+    since_ms: u32,
+}
+
+impl Debouncer {
+    pub const fn new(debounce: Debounce) -> Self {
+        Debouncer { debounce, active: false, count: 0, since_ms: 0 }
+    }
+
+    ///The current debounced state.
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    ///Feeds one sample of the raw condition, returns an event if the
+    ///debounced state changed. `now_ms` is ignored for `Debounce::Samples`.
+    pub fn update(&mut self, condition: bool, now_ms: u32) -> Option<AlarmEvent> {
+        if condition == self.active {
+            self.count = 0;
+            return None;
+        }
+
+        if self.count == 0 {
+            self.since_ms = now_ms;
+        }
+        self.count = self.count.saturating_add(1);
+
+        let settled = match self.debounce {
+            Debounce::Samples(n) => self.count >= n,
+            Debounce::Millis(ms) => now_ms.wrapping_sub(self.since_ms) >= ms,
+        };
+        if !settled {
+            return None;
+        }
+
+        self.active = condition;
+        self.count = 0;
+        if self.active {
+            return Some(AlarmEvent::Raised);
+        }
+        Some(AlarmEvent::Cleared)
+    }
+}
+
+///Configuration of a single alarm.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AlarmConfig {
+    pub threshold: Threshold,
+    pub debounce: Debounce,
+}
+
+impl AlarmConfig {
+    pub const fn new(threshold: Threshold, debounce: Debounce) -> Self {
+        AlarmConfig { threshold, debounce }
+    }
+}
+
+///A threshold alarm on a single value(temperature or humidity).
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Alarm {
+    config: AlarmConfig,
+    debouncer: Debouncer,
+}
+
+impl Alarm {
+    pub const fn new(config: AlarmConfig) -> Self {
+        Alarm { config, debouncer: Debouncer::new(config.debounce) }
+    }
+
+    pub const fn config(&self) -> AlarmConfig {
+        self.config
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.debouncer.is_active()
+    }
+
+    ///Feeds one reading, NaN readings are skipped without touching the
+    ///debounce progress.
+    pub fn update(&mut self, value: f32, now_ms: u32) -> Option<AlarmEvent> {
+        let condition = self.config.threshold.check(value)?;
+        self.debouncer.update(condition, now_ms)
+    }
+}
+
+#[cfg(test)]
+mod alarm_tests {
+    use super::*;
+
+    #[test]
+    fn threshold_check()
+    {
+        assert_eq!(Threshold::Above(30.0).check(30.5), Some(true));
+        assert_eq!(Threshold::Above(30.0).check(30.0), Some(false));
+        assert_eq!(Threshold::Below(10.0).check(9.0), Some(true));
+        assert_eq!(Threshold::Below(10.0).check(f32::NAN), None);
+    }
+
+    #[test]
+    fn debounce_samples()
+    {
+        let mut d = Debouncer::new(Debounce::Samples(3));
+
+        assert_eq!(d.update(true, 0), None);
+        assert_eq!(d.update(true, 0), None);
+        //A single good sample restarts the count.
+        assert_eq!(d.update(false, 0), None);
+        assert_eq!(d.update(true, 0), None);
+        assert_eq!(d.update(true, 0), None);
+        assert_eq!(d.update(true, 0), Some(AlarmEvent::Raised));
+        assert!(d.is_active());
+        assert_eq!(d.update(true, 0), None);
+
+        assert_eq!(d.update(false, 0), None);
+        assert_eq!(d.update(false, 0), None);
+        assert_eq!(d.update(false, 0), Some(AlarmEvent::Cleared));
+        assert!(!d.is_active());
+    }
+
+    #[test]
+    fn no_debounce()
+    {
+        let mut d = Debouncer::new(Debounce::Samples(0));
+        assert_eq!(d.update(true, 0), Some(AlarmEvent::Raised));
+        assert_eq!(d.update(false, 0), Some(AlarmEvent::Cleared));
+    }
+
+    #[test]
+    fn debounce_time()
+    {
+        let mut d = Debouncer::new(Debounce::Millis(5000));
+
+        assert_eq!(d.update(true, 1000), None);
+        assert_eq!(d.update(true, 3000), None);
+        assert_eq!(d.update(true, 5999), None);
+        assert_eq!(d.update(true, 6000), Some(AlarmEvent::Raised));
+    }
+
+    #[test]
+    fn debounce_time_wraps()
+    {
+        let mut d = Debouncer::new(Debounce::Millis(100));
+
+        assert_eq!(d.update(true, u32::MAX - 50), None);
+        assert_eq!(d.update(true, 49), Some(AlarmEvent::Raised));
+    }
+
+    #[test]
+    fn alarm_ignores_nan()
+    {
+        let mut a = Alarm::new(AlarmConfig {
+            threshold: Threshold::Above(30.0),
+            debounce: Debounce::Samples(2),
+        });
+
+        assert_eq!(a.update(31.0, 0), None);
+        assert_eq!(a.update(f32::NAN, 0), None);
+        assert_eq!(a.update(31.0, 0), Some(AlarmEvent::Raised));
+        assert!(a.is_active());
+    }
+}
@@ -0,0 +1,434 @@
+/*
+ * Filename: aht20-soak.rs
+ * Description: Endurance runs on real hardware. Reads the sensor for days
+ * and logs every error, busy retry, CRC failure and slow reading as one
+ * JSON object per line, with a summary report rewritten as it goes so a
+ * killed run still leaves one behind.
+ *
+ *   aht20-soak --duration 72h --interval 2s --log soak.jsonl --report soak.json
+ *
+ * Build it with `cargo run --release --features soak --bin aht20-soak`.
+ */
+
+mod common;
+
+use std::fmt::Debug;
+use std::fs::File;
+use std::io::{self, Write};
+use std::process::ExitCode;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use sensor_lib_aht20::{
+    Aht2xTransport, BusQuirks, Celsius, Delay, ErrorKind, Measurement, RelativeHumidity, Sensor,
+    SimulatedSensor, Timings, SENSOR_ADDR,
+};
+
+use common::{open_bus, parse_duration, parse_quirks, StdDelay};
+
+const USAGE: &str = "\
+usage: aht20-soak [options]
+
+  --bus <path>          i2c-dev bus, default /dev/i2c-1
+  --sim                 use a simulated sensor instead
+  --quirks <name>       bus workarounds: none (default), pi or esp32-c3
+  --duration <time>     how long to run, e.g. 72h, 90m or 30s, default runs
+                        until killed
+  --count <n>           stop after n readings
+  --interval <time>     time between readings, default 2s
+  --outlier-ms <ms>     readings taking longer are logged as outliers,
+                        default the AHT20 maximum conversion time
+  --log <file>          event log, one JSON object per line, default stdout
+  --report <file>       summary report, default written to stderr at the end
+  --report-every <time> how often the report file is rewritten, default 10m";
+
+struct SoakOpts {
+    bus: String,
+    sim: bool,
+    quirks: BusQuirks,
+    duration: Option<Duration>,
+    count: Option<u64>,
+    interval: Duration,
+    outlier_ms: u32,
+    log: Option<String>,
+    report: Option<String>,
+    report_every: Duration,
+}
+
+fn soak_opts(args: &[String]) -> Result<SoakOpts, String> {
+    let mut opts = SoakOpts {
+        bus: "/dev/i2c-1".to_string(),
+        sim: false,
+        quirks: BusQuirks::NONE,
+        duration: None,
+        count: None,
+        interval: Duration::from_secs(2),
+        outlier_ms: Timings::AHT20.measure_max_ms.into(),
+        log: None,
+        report: None,
+        report_every: Duration::from_secs(600),
+    };
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        if arg == "--sim" {
+            opts.sim = true;
+            continue;
+        }
+        let value = it.next().ok_or(format!("{} needs a value", arg))?;
+        match arg.as_str() {
+            "--bus" => opts.bus = value.clone(),
+            "--quirks" => opts.quirks = parse_quirks(value)?,
+            "--duration" => opts.duration = Some(parse_duration(value)?),
+            "--count" => opts.count = Some(value.parse().map_err(|_| format!("bad count '{}'", value))?),
+            "--interval" => opts.interval = parse_duration(value)?,
+            "--outlier-ms" => {
+                opts.outlier_ms = value.parse().map_err(|_| format!("bad time '{}'", value))?;
+            },
+            "--log" => opts.log = Some(value.clone()),
+            "--report" => opts.report = Some(value.clone()),
+            "--report-every" => opts.report_every = parse_duration(value)?,
+            _ => return Err(format!("unknown option '{}'", arg)),
+        }
+    }
+    Ok(opts)
+}
+
+///Everything counted over a run, written out as the report.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct SoakStats {
+    readings: u64,
+    good: u64,
+    ///Errors by `ErrorKind::code()`.
+    errors: [u64; 16],
+    crc_failures: u64,
+    ///Readings that needed busy polls, and the polls in total.
+    retried: u64,
+    retries: u64,
+    max_retries: u16,
+    outliers: u64,
+    max_read_ms: u32,
+    reinits: u64,
+    min_temperature: Option<f32>,
+    max_temperature: Option<f32>,
+    min_humidity: Option<f32>,
+    max_humidity: Option<f32>,
+}
+
+//Keeps the smaller or larger of the two.
+fn extend(slot: &mut Option<f32>, v: f32, keep: fn(f32, f32) -> f32) {
+    *slot = Some(slot.map_or(v, |old| keep(old, v)));
+}
+
+fn json_opt(v: Option<f32>) -> String {
+    v.map_or("null".to_string(), |v| format!("{:.2}", v))
+}
+
+impl SoakStats {
+    ///Counts one reading and gives the events it should log, as the
+    ///`"event"` and the rest of the JSON fields.
+    fn record(
+        &mut self,
+        r: &Result<Measurement, ErrorKind>,
+        retries: u16,
+        read_ms: u32,
+        outlier_ms: u32,
+        ) -> Vec<(&'static str, String)>
+    {
+        let mut events = Vec::new();
+        self.readings += 1;
+        self.max_read_ms = self.max_read_ms.max(read_ms);
+
+        match r {
+            Ok(m) => {
+                if m.quality.is_good() {
+                    self.good += 1;
+                }
+                extend(&mut self.min_temperature, m.temperature.0, f32::min);
+                extend(&mut self.max_temperature, m.temperature.0, f32::max);
+                extend(&mut self.min_humidity, m.humidity.0, f32::min);
+                extend(&mut self.max_humidity, m.humidity.0, f32::max);
+            },
+            Err(kind) => {
+                self.errors[usize::from(kind.code()) % self.errors.len()] += 1;
+                if *kind == ErrorKind::InvalidChecksum {
+                    self.crc_failures += 1;
+                    events.push(("crc", String::new()));
+                } else {
+                    events.push(("error", format!(",\"kind\":\"{:?}\",\"code\":{}", kind, kind.code())));
+                }
+            },
+        }
+        if retries > 0 {
+            self.retried += 1;
+            self.retries += u64::from(retries);
+            self.max_retries = self.max_retries.max(retries);
+            events.push(("retry", format!(",\"polls\":{}", retries)));
+        }
+        if read_ms > outlier_ms {
+            self.outliers += 1;
+            events.push(("outlier", format!(",\"read_ms\":{}", read_ms)));
+        }
+        events
+    }
+
+    fn report(&self, elapsed: Duration) -> String {
+        let errors: Vec<String> = self.errors.iter().enumerate()
+            .filter(|(_, &n)| n > 0)
+            .filter_map(|(code, &n)| {
+                ErrorKind::from_code(code as u8).map(|kind| format!("\"{:?}\":{}", kind, n))
+            })
+            .collect();
+        format!(
+            "{{\"elapsed_s\":{},\"readings\":{},\"good\":{},\"errors\":{{{}}},\"crc_failures\":{},\
+             \"retried\":{},\"retries\":{},\"max_retries\":{},\"outliers\":{},\"max_read_ms\":{},\
+             \"reinits\":{},\"temperature\":[{},{}],\"humidity\":[{},{}]}}",
+            elapsed.as_secs(), self.readings, self.good, errors.join(","), self.crc_failures,
+            self.retried, self.retries, self.max_retries, self.outliers, self.max_read_ms,
+            self.reinits,
+            json_opt(self.min_temperature), json_opt(self.max_temperature),
+            json_opt(self.min_humidity), json_opt(self.max_humidity),
+            )
+    }
+}
+
+fn unix_ms() -> u128 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis())
+}
+
+fn log_event(log: &mut dyn Write, elapsed: Duration, event: &str, fields: &str) -> Result<(), String> {
+    writeln!(
+        log,
+        "{{\"elapsed_ms\":{},\"unix_ms\":{},\"event\":\"{}\"{}}}",
+        elapsed.as_millis(), unix_ms(), event, fields,
+        )
+        .and_then(|_| log.flush())
+        .map_err(|e| e.to_string())
+}
+
+//The soak loop. A failed init or a faulted sensor is logged and the sensor
+//initialized again, the run only ends on the duration or count.
+//`report` is handed the stats every `report_every` and at the end.
+fn soak<T>(
+    i2c: T,
+    delay: &mut impl Delay,
+    opts: &SoakOpts,
+    log: &mut dyn Write,
+    report: &mut dyn FnMut(&SoakStats, Duration) -> Result<(), String>,
+    ) -> Result<SoakStats, String>
+where
+    T: Aht2xTransport,
+    T::Error: Debug,
+{
+    let mut sensor = Sensor::new(i2c, SENSOR_ADDR);
+    sensor.set_bus_quirks(opts.quirks);
+    let mut stats = SoakStats::default();
+    let start = Instant::now();
+    let mut last_report = start;
+    let done = |stats: &SoakStats| {
+        opts.count.is_some_and(|n| stats.readings >= n)
+            || opts.duration.is_some_and(|d| start.elapsed() >= d)
+    };
+
+    log_event(log, start.elapsed(), "start", "")?;
+    while !done(&stats) {
+        //Recovering also leaves the faulted state.
+        let mut inited = match sensor.recover(delay) {
+            Ok(inited) => inited,
+            Err(e) => {
+                sensor = e.sensor;
+                stats.reinits += 1;
+                log_event(log, start.elapsed(), "init_failed", &format!(",\"kind\":\"{:?}\"", e.error.kind()))?;
+                thread::sleep(opts.interval);
+                continue;
+            },
+        };
+
+        while !done(&stats) {
+            let t0 = Instant::now();
+            let r = inited.measure(delay);
+            let read_ms = t0.elapsed().as_millis().min(u32::MAX.into()) as u32;
+            let retries = inited.last_retries();
+            let r = r.map_err(|e| e.kind());
+
+            for (event, fields) in stats.record(&r, retries, read_ms, opts.outlier_ms) {
+                log_event(log, start.elapsed(), event, &fields)?;
+            }
+            if last_report.elapsed() >= opts.report_every {
+                report(&stats, start.elapsed())?;
+                last_report = Instant::now();
+            }
+            if r == Err(ErrorKind::Faulted) || r == Err(ErrorKind::I2C) {
+                stats.reinits += 1;
+                log_event(log, start.elapsed(), "reinit", "")?;
+                break;
+            }
+            if !done(&stats) {
+                thread::sleep(opts.interval);
+            }
+        }
+        sensor = inited.downgrade();
+    }
+    log_event(log, start.elapsed(), "stop", "")?;
+    report(&stats, start.elapsed())?;
+    Ok(stats)
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    if args.iter().any(|a| a == "--help" || a == "-h") {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+    let opts = soak_opts(args)?;
+
+    let mut stdout = io::stdout();
+    let mut log_file = match &opts.log {
+        Some(path) => Some(File::create(path).map_err(|e| format!("{}: {}", path, e))?),
+        None => None,
+    };
+    let log: &mut dyn Write = match log_file.as_mut() {
+        Some(f) => f,
+        None => &mut stdout,
+    };
+
+    //The report file is replaced whole each time, never left half written.
+    let report = |stats: &SoakStats, elapsed: Duration| -> Result<(), String> {
+        let text = stats.report(elapsed);
+        match &opts.report {
+            Some(path) => {
+                let tmp = format!("{}.tmp", path);
+                std::fs::write(&tmp, text + "\n")
+                    .and_then(|_| std::fs::rename(&tmp, path))
+                    .map_err(|e| format!("{}: {}", path, e))
+            },
+            None => {
+                eprintln!("{}", text);
+                Ok(())
+            },
+        }
+    };
+    //Only the file is rewritten along the way, stderr gets the final one.
+    let mut periodic = |stats: &SoakStats, elapsed: Duration| match opts.report {
+        Some(_) => report(stats, elapsed),
+        None => Ok(()),
+    };
+
+    let mut delay = StdDelay;
+    let start = Instant::now();
+    let stats = if opts.sim {
+        let sim = SimulatedSensor::new(Celsius(22.0), RelativeHumidity(45.0));
+        soak(sim, &mut delay, &opts, log, &mut periodic)?
+    } else {
+        soak(open_bus(&opts.bus)?, &mut delay, &opts, log, &mut periodic)?
+    };
+    if opts.report.is_none() {
+        report(&stats, start.elapsed())?;
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        },
+    }
+}
+
+#[cfg(test)]
+mod soak_tests {
+    use super::*;
+    use sensor_lib_aht20::Quality;
+
+    fn args(s: &[&str]) -> Vec<String> {
+        s.iter().map(|a| a.to_string()).collect()
+    }
+
+    fn reading(t: f32, rh: f32) -> Result<Measurement, ErrorKind> {
+        Ok(Measurement {
+            id: Default::default(),
+            temperature: Celsius(t),
+            humidity: RelativeHumidity(rh),
+            quality: Quality::GOOD,
+            pressure: None,
+            seq: None,
+        })
+    }
+
+    #[test]
+    fn options()
+    {
+        let opts = soak_opts(&args(&["--sim", "--duration", "72h", "--outlier-ms", "120"])).unwrap();
+        assert!(opts.sim);
+        assert_eq!(opts.duration, Some(Duration::from_secs(72 * 3600)));
+        assert_eq!(opts.outlier_ms, 120);
+        assert_eq!(opts.interval, Duration::from_secs(2));
+
+        assert_eq!(parse_duration("2d").unwrap(), Duration::from_secs(2 * 86_400));
+        assert!(soak_opts(&args(&["--count"])).is_err());
+        assert!(soak_opts(&args(&["--bogus", "1"])).is_err());
+    }
+
+    #[test]
+    fn record_events()
+    {
+        let mut stats = SoakStats::default();
+        assert!(stats.record(&reading(22.0, 45.0), 0, 80, 140).is_empty());
+
+        let events = stats.record(&reading(23.5, 40.0), 2, 150, 140);
+        assert_eq!(events, [("retry", ",\"polls\":2".to_string()), ("outlier", ",\"read_ms\":150".to_string())]);
+
+        let events = stats.record(&Err(ErrorKind::InvalidChecksum), 0, 80, 140);
+        assert_eq!(events, [("crc", String::new())]);
+        let events = stats.record(&Err(ErrorKind::DeviceTimeOut), 3, 140, 140);
+        assert_eq!(events[0], ("error", ",\"kind\":\"DeviceTimeOut\",\"code\":5".to_string()));
+
+        assert_eq!(stats.readings, 4);
+        assert_eq!(stats.good, 2);
+        assert_eq!((stats.retried, stats.retries, stats.max_retries), (2, 5, 3));
+        assert_eq!(stats.outliers, 1);
+        assert_eq!(stats.max_read_ms, 150);
+        assert_eq!((stats.min_temperature, stats.max_temperature), (Some(22.0), Some(23.5)));
+
+        let report = stats.report(Duration::from_secs(60));
+        assert!(report.starts_with("{\"elapsed_s\":60,\"readings\":4,\"good\":2,"));
+        assert!(report.contains("\"errors\":{\"InvalidChecksum\":1,\"DeviceTimeOut\":1}"));
+        assert!(report.ends_with("\"temperature\":[22.00,23.50],\"humidity\":[40.00,45.00]}"));
+        assert!(SoakStats::default().report(Duration::ZERO).contains("\"temperature\":[null,null]"));
+    }
+
+    #[test]
+    fn soak_against_sim()
+    {
+        let mut opts = soak_opts(&args(&["--interval", "0s", "--count", "3", "--report-every", "0s"])).unwrap();
+        let mut sim = SimulatedSensor::new(Celsius(22.0), RelativeHumidity(45.0));
+        sim.busy_reads = 1;
+        let mut delay = embedded_hal_mock::delay::MockNoop;
+
+        let mut log = Vec::new();
+        let mut reports = 0;
+        let stats = soak(sim.clone(), &mut delay, &opts, &mut log, &mut |_, _| {
+            reports += 1;
+            Ok(())
+        }).unwrap();
+        assert_eq!((stats.readings, stats.good, stats.retried), (3, 3, 3));
+        assert_eq!(reports, 4);
+
+        let text = String::from_utf8(log).unwrap();
+        let events: Vec<&str> = text.lines().collect();
+        assert_eq!(events.len(), 5);
+        assert!(events[0].contains("\"event\":\"start\""));
+        assert!(events[1].ends_with("\"event\":\"retry\",\"polls\":1}"));
+        assert!(events[4].contains("\"event\":\"stop\""));
+
+        sim.busy_reads = 0;
+        sim.corrupt_crc = true;
+        opts.report_every = Duration::from_secs(600);
+        let mut log = Vec::new();
+        let stats = soak(sim, &mut delay, &opts, &mut log, &mut |_, _| Ok(())).unwrap();
+        assert_eq!(stats.crc_failures, 3);
+        assert_eq!(stats.errors[usize::from(ErrorKind::InvalidChecksum.code())], 3);
+    }
+}
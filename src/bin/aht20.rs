@@ -0,0 +1,531 @@
+/*
+ * Filename: aht20.rs
+ * Description: Verification tool for bench work on AHT20 sensors.
+ *
+ *   aht20 decode 18 7E 51 65 D4 A0 DA
+ *   aht20 monitor --interval 2s --csv out.csv
+ *   aht20 stress --delays 40,60,80 --polls 5,10,20 --samples 50
+ *
+ * Build it with `cargo run --features cli --bin aht20 -- <command>`.
+ */
+
+mod common;
+
+use std::fmt::{Debug, Write};
+use std::fs::File;
+use std::io;
+use std::process::ExitCode;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use sensor_lib_aht20::{
+    Aht2xTransport, BusQuirks, Celsius, ErrorKind, Measurement, Quality, RelativeHumidity, Sensor, SensorData,
+    SensorStatus, SimulatedSensor, Timings, SENSOR_ADDR,
+};
+
+use common::{open_bus, parse_duration, parse_quirks, StdDelay};
+
+const USAGE: &str = "\
+usage: aht20 <command> [args]
+
+commands:
+  decode <hex bytes>   decode a status byte or a 6/7 byte measurement frame,
+                       e.g. \"18 7E 51 65 D4 A0 DA\" or 187E5165D4A0DA
+  monitor [options]    read the sensor periodically, print CSV rows
+
+bus options:
+  --bus <path>         i2c-dev bus, default /dev/i2c-1
+  --sim                use a simulated sensor instead
+  --quirks <name>      bus workarounds: none (default), pi or esp32-c3
+
+monitor options:
+  --interval <time>    time between readings, e.g. 2s, 500ms or 1m, default 2s
+  --csv <file>         also write the rows to a file
+  --count <n>          stop after n readings, default runs until killed
+
+stress options:
+  --delays <ms,..>     conversion delays after the trigger, default 40,60,80,100
+  --polls <ms,..>      busy poll intervals, default 5,10,20
+  --samples <n>        readings per combination, default 20";
+
+const CSV_HEADER: &str = "elapsed_ms,unix_ms,temperature_c,humidity_rh,quality,error";
+
+struct BusOpts {
+    bus: String,
+    sim: bool,
+    quirks: BusQuirks,
+}
+
+struct MonitorOpts {
+    interval: Duration,
+    csv: Option<String>,
+    count: Option<u64>,
+}
+
+//Splits off the bus options, the rest is left for the command.
+fn bus_opts(args: &[String]) -> Result<(BusOpts, Vec<String>), String> {
+    let mut opts = BusOpts { bus: "/dev/i2c-1".to_string(), sim: false, quirks: BusQuirks::NONE };
+    let mut rest = Vec::new();
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--bus" => opts.bus = it.next().ok_or("--bus needs a path")?.clone(),
+            "--sim" => opts.sim = true,
+            "--quirks" => opts.quirks = parse_quirks(it.next().ok_or("--quirks needs a name")?)?,
+            _ => rest.push(arg.clone()),
+        }
+    }
+    Ok((opts, rest))
+}
+
+fn monitor_opts(args: &[String]) -> Result<MonitorOpts, String> {
+    let mut opts = MonitorOpts { interval: Duration::from_secs(2), csv: None, count: None };
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        let mut value = || it.next().ok_or(format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--interval" => opts.interval = parse_duration(value()?)?,
+            "--csv" => opts.csv = Some(value()?.clone()),
+            "--count" => {
+                let n = value()?;
+                opts.count = Some(n.parse().map_err(|_| format!("bad count '{}'", n))?);
+            },
+            _ => return Err(format!("unknown option '{}'", arg)),
+        }
+    }
+    Ok(opts)
+}
+
+struct StressOpts {
+    delays: Vec<u16>,
+    polls: Vec<u16>,
+    samples: u32,
+}
+
+const STRESS_HEADER: &str = "delay_ms,poll_ms,samples,good,crc_miss,busy_overrun,errors,failure_rate";
+
+fn parse_list(s: &str) -> Result<Vec<u16>, String> {
+    s.split(',')
+        .map(|v| v.trim().parse().map_err(|_| format!("bad value '{}' in '{}'", v, s)))
+        .collect()
+}
+
+fn stress_opts(args: &[String]) -> Result<StressOpts, String> {
+    let mut opts = StressOpts { delays: vec![40, 60, 80, 100], polls: vec![5, 10, 20], samples: 20 };
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        let mut value = || it.next().ok_or(format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--delays" => opts.delays = parse_list(value()?)?,
+            "--polls" => opts.polls = parse_list(value()?)?,
+            "--samples" => {
+                let n = value()?;
+                opts.samples = n.parse().map_err(|_| format!("bad sample count '{}'", n))?;
+            },
+            _ => return Err(format!("unknown option '{}'", arg)),
+        }
+    }
+    Ok(opts)
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct StressTally {
+    good: u32,
+    crc_miss: u32,
+    busy_overrun: u32,
+    errors: u32,
+}
+
+impl StressTally {
+    fn add(&mut self, r: &Result<Measurement, ErrorKind>) {
+        match r {
+            Ok(m) if m.quality.is_good() => self.good += 1,
+            Ok(m) => {
+                if m.quality.contains(Quality::CRC_MISS) {
+                    self.crc_miss += 1;
+                }
+                if m.quality.contains(Quality::BUSY_OVERRUN) {
+                    self.busy_overrun += 1;
+                }
+            },
+            Err(_) => self.errors += 1,
+        }
+    }
+
+    fn row(&self, delay_ms: u16, poll_ms: u16, samples: u32) -> String {
+        let failed = samples - self.good;
+        format!(
+            "{},{},{},{},{},{},{},{:.3}",
+            delay_ms, poll_ms, samples, self.good, self.crc_miss, self.busy_overrun, self.errors,
+            failed as f32 / samples.max(1) as f32,
+            )
+    }
+}
+
+//Every delay/poll pair gets `samples` readings. The busy polling is given
+//three polls past the delay, as in the AHT20 table, so a short delay shows
+//up as busy overruns or timeouts instead of being hidden by the polling.
+fn stress<T>(
+    i2c: T,
+    quirks: BusQuirks,
+    opts: &StressOpts,
+    out: &mut dyn io::Write,
+    ) -> Result<(), String>
+where
+    T: Aht2xTransport,
+    T::Error: Debug,
+{
+    let mut sensor = Sensor::new(i2c, SENSOR_ADDR);
+    sensor.set_bus_quirks(quirks);
+    let mut delay = StdDelay;
+    writeln!(out, "{}", STRESS_HEADER).map_err(|e| e.to_string())?;
+
+    for &delay_ms in &opts.delays {
+        for &poll_ms in &opts.polls {
+            sensor.set_timings(Timings {
+                measure_typ_ms: delay_ms,
+                measure_max_ms: delay_ms.saturating_add(poll_ms.saturating_mul(3)),
+                busy_poll_ms: poll_ms,
+                ..Timings::AHT20
+            });
+            let mut inited = sensor.init(&mut delay).map_err(|e| format!("init failed: {:?}", e.error))?;
+
+            let mut tally = StressTally::default();
+            for _ in 0..opts.samples {
+                tally.add(&inited.measure(&mut delay).map_err(|e| e.kind()));
+            }
+            sensor = inited.downgrade();
+            writeln!(out, "{}", tally.row(delay_ms, poll_ms, opts.samples))
+                .and_then(|_| out.flush())
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+fn run_stress(args: &[String]) -> Result<(), String> {
+    let (bus, rest) = bus_opts(args)?;
+    let opts = stress_opts(&rest)?;
+    let mut stdout = io::stdout();
+
+    if bus.sim {
+        let sim = SimulatedSensor::new(Celsius(22.0), RelativeHumidity(45.0));
+        return stress(sim, bus.quirks, &opts, &mut stdout);
+    }
+    stress(open_bus(&bus.bus)?, bus.quirks, &opts, &mut stdout)
+}
+
+fn quality_name(q: Quality) -> String {
+    if q.is_good() {
+        return "good".to_string();
+    }
+    let mut names = Vec::new();
+    if q.contains(Quality::CRC_MISS) {
+        names.push("crc_miss");
+    }
+    if q.contains(Quality::BUSY_OVERRUN) {
+        names.push("busy_overrun");
+    }
+    if q.contains(Quality::DUPLICATE) {
+        names.push("duplicate");
+    }
+    if q.contains(Quality::RECOVERING) {
+        names.push("recovering");
+    }
+    names.join("|")
+}
+
+fn csv_row(elapsed_ms: u128, unix_ms: u128, r: &Result<Measurement, ErrorKind>) -> String {
+    match r {
+        Ok(m) => format!(
+            "{},{},{:.2},{:.2},{},",
+            elapsed_ms, unix_ms, m.temperature.0, m.humidity.0, quality_name(m.quality),
+            ),
+        Err(e) => format!("{},{},,,,{:?}", elapsed_ms, unix_ms, e),
+    }
+}
+
+//Runs the monitor loop on any transport, rows go to every writer in `out`.
+fn monitor<T>(
+    i2c: T,
+    quirks: BusQuirks,
+    opts: &MonitorOpts,
+    out: &mut [&mut dyn io::Write],
+    ) -> Result<(), String>
+where
+    T: Aht2xTransport,
+    T::Error: Debug,
+{
+    let mut sensor = Sensor::new(i2c, SENSOR_ADDR);
+    sensor.set_bus_quirks(quirks);
+    let mut delay = StdDelay;
+    let mut inited = sensor.init(&mut delay).map_err(|e| format!("init failed: {:?}", e.error))?;
+
+    for w in out.iter_mut() {
+        writeln!(w, "{}", CSV_HEADER).map_err(|e| e.to_string())?;
+    }
+
+    let start = Instant::now();
+    let mut n = 0;
+    while opts.count.is_none_or(|count| n < count) {
+        if n > 0 {
+            thread::sleep(opts.interval);
+        }
+        //The kind only, a bus error's text could hold commas.
+        let r = inited.measure(&mut delay).map_err(|e| e.kind());
+        let unix_ms = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis());
+        let row = csv_row(start.elapsed().as_millis(), unix_ms, &r);
+        for w in out.iter_mut() {
+            writeln!(w, "{}", row).and_then(|_| w.flush()).map_err(|e| e.to_string())?;
+        }
+        n += 1;
+    }
+    Ok(())
+}
+
+fn run_monitor(args: &[String]) -> Result<(), String> {
+    let (bus, rest) = bus_opts(args)?;
+    let opts = monitor_opts(&rest)?;
+
+    let mut stdout = io::stdout();
+    let mut file = match &opts.csv {
+        Some(path) => Some(File::create(path).map_err(|e| format!("{}: {}", path, e))?),
+        None => None,
+    };
+    let mut out: Vec<&mut dyn io::Write> = vec![&mut stdout];
+    if let Some(f) = file.as_mut() {
+        out.push(f);
+    }
+
+    if bus.sim {
+        let sim = SimulatedSensor::new(Celsius(22.0), RelativeHumidity(45.0));
+        return monitor(sim, bus.quirks, &opts, &mut out);
+    }
+    monitor(open_bus(&bus.bus)?, bus.quirks, &opts, &mut out)
+}
+
+//Accepts bytes split by spaces or commas, with or without 0x, or one run
+//of hex digits.
+fn parse_hex(args: &[String]) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    for arg in args {
+        for word in arg.split(|c: char| c.is_whitespace() || c == ',') {
+            let word = word.trim_start_matches("0x").trim_start_matches("0X");
+            if word.len() % 2 != 0 {
+                return Err(format!("odd number of hex digits in '{}'", word));
+            }
+            for i in (0..word.len()).step_by(2) {
+                let byte = word.get(i..i + 2)
+                    .and_then(|d| u8::from_str_radix(d, 16).ok())
+                    .ok_or_else(|| format!("not hex: '{}'", word))?;
+                bytes.push(byte);
+            }
+        }
+    }
+    Ok(bytes)
+}
+
+fn describe_status(out: &mut String, status: u8) {
+    let s = SensorStatus::new(status);
+    let mode = if s.is_normal_mode() {
+        "normal"
+    } else if s.is_cmd_mode() {
+        "command"
+    } else {
+        "unknown"
+    };
+    writeln!(out, "status:      0x{:02X} ({:08b})", status, status).unwrap();
+    writeln!(out, "  busy:      {}", s.is_busy()).unwrap();
+    writeln!(out, "  mode:      {}", mode).unwrap();
+    writeln!(out, "  cal:       {}", s.is_calibration_enabled()).unwrap();
+}
+
+fn decode(bytes: &[u8]) -> Result<String, String> {
+    let mut out = String::new();
+    match bytes.len() {
+        1 => describe_status(&mut out, bytes[0]),
+        6 | 7 => {
+            let mut sd = SensorData::new();
+            sd.bytes[..bytes.len()].copy_from_slice(bytes);
+            describe_status(&mut out, bytes[0]);
+            writeln!(out, "raw rh:      0x{:05X} ({})", sd.get_humidity_bits(), sd.get_humidity_bits()).unwrap();
+            writeln!(out, "raw t:       0x{:05X} ({})", sd.get_temperature_bits(), sd.get_temperature_bits()).unwrap();
+            writeln!(out, "humidity:    {:.2} %RH", sd.calculate_humidity()).unwrap();
+            writeln!(out, "temperature: {:.2} C", sd.calculate_temperature()).unwrap();
+            if bytes.len() == 7 {
+                let good = sd.is_crc_good();
+                let verdict = if good { "good" } else { "BAD" };
+                writeln!(out, "crc:         0x{:02X}, expected 0x{:02X}, {}", bytes[6], sd.crc, verdict)
+                    .unwrap();
+            } else {
+                writeln!(out, "crc:         not captured").unwrap();
+            }
+        },
+        n => return Err(format!("expected 1, 6 or 7 bytes, got {}", n)),
+    }
+    Ok(out)
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    match args.first().map(String::as_str) {
+        Some("decode") => {
+            let bytes = parse_hex(&args[1..])?;
+            print!("{}", decode(&bytes)?);
+            Ok(())
+        },
+        Some("monitor") => run_monitor(&args[1..]),
+        Some("stress") => run_stress(&args[1..]),
+        Some("help") | Some("--help") | Some("-h") => {
+            println!("{}", USAGE);
+            Ok(())
+        },
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        },
+    }
+}
+
+#[cfg(test)]
+mod cli_tests {
+    use super::*;
+
+    fn args(s: &[&str]) -> Vec<String> {
+        s.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn hex_forms()
+    {
+        let expected = vec![0x18, 0x7E, 0x51, 0x65, 0xD4, 0xA0, 0xDA];
+        assert_eq!(parse_hex(&args(&["18 7E 51 65 D4 A0 DA"])).unwrap(), expected);
+        assert_eq!(parse_hex(&args(&["187E5165D4A0DA"])).unwrap(), expected);
+        assert_eq!(parse_hex(&args(&["0x18,0x7E,0x51", "65", "D4A0DA"])).unwrap(), expected);
+        assert!(parse_hex(&args(&["18 7"])).is_err());
+        assert!(parse_hex(&args(&["GG"])).is_err());
+    }
+
+    #[test]
+    fn decode_frame()
+    {
+        let out = decode(&[0x18, 0x7E, 0x51, 0x65, 0xD4, 0xA0, 0xDA]).unwrap();
+        assert!(out.contains("humidity:    49.34 %RH"));
+        assert!(out.contains("temperature: 22.88 C"));
+        assert!(out.contains("good"));
+
+        let out = decode(&[0x18, 0x7E, 0x51, 0x65, 0xD4, 0xA0, 0x00]).unwrap();
+        assert!(out.contains("BAD"));
+        assert!(decode(&[0x18, 0x7E]).is_err());
+    }
+
+    #[test]
+    fn monitor_options()
+    {
+        let (bus, rest) = bus_opts(&args(&["--sim", "--interval", "500ms", "--count", "3"])).unwrap();
+        assert!(bus.sim);
+        assert_eq!(bus.quirks, BusQuirks::NONE);
+        let opts = monitor_opts(&rest).unwrap();
+        assert_eq!(opts.interval, Duration::from_millis(500));
+        assert_eq!(opts.count, Some(3));
+        assert!(opts.csv.is_none());
+
+        assert_eq!(parse_duration("2s").unwrap(), Duration::from_secs(2));
+        assert_eq!(parse_duration("1m").unwrap(), Duration::from_secs(60));
+        assert_eq!(parse_duration("5").unwrap(), Duration::from_secs(5));
+        assert!(monitor_opts(&args(&["--interval"])).is_err());
+        assert!(monitor_opts(&args(&["--bogus"])).is_err());
+    }
+
+    #[test]
+    fn monitor_csv()
+    {
+        let opts = MonitorOpts { interval: Duration::ZERO, csv: None, count: Some(2) };
+        let mut sim = SimulatedSensor::new(Celsius(22.0), RelativeHumidity(45.0));
+        sim.busy_reads = 1;
+        let mut buf = Vec::new();
+        monitor(sim, BusQuirks::NONE, &opts, &mut [&mut buf]).unwrap();
+
+        let text = String::from_utf8(buf).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], CSV_HEADER);
+        assert!(lines[1].ends_with(",22.00,45.00,good,"));
+
+        assert_eq!(csv_row(5, 6, &Err(ErrorKind::DeviceTimeOut)), "5,6,,,,DeviceTimeOut");
+        assert_eq!(quality_name(Quality::CRC_MISS | Quality::BUSY_OVERRUN), "crc_miss|busy_overrun");
+    }
+
+    #[test]
+    fn stress_sweep()
+    {
+        let opts = stress_opts(&args(&["--delays", "40, 80", "--polls", "10", "--samples", "3"])).unwrap();
+        assert_eq!(opts.delays, [40, 80]);
+        assert!(stress_opts(&args(&["--polls", "10,x"])).is_err());
+
+        //Without a polling window the busy read is a timeout.
+        let opts = StressOpts { delays: vec![0], polls: vec![0, 1], samples: 3 };
+        let mut sim = SimulatedSensor::new(Celsius(22.0), RelativeHumidity(45.0));
+        sim.busy_reads = 1;
+        let mut buf = Vec::new();
+        stress(sim, BusQuirks::NONE, &opts, &mut buf).unwrap();
+        let text = String::from_utf8(buf).unwrap();
+        assert_eq!(text.lines().collect::<Vec<_>>(), [STRESS_HEADER, "0,0,3,0,0,0,3,1.000", "0,1,3,3,0,0,0,0.000"]);
+
+        let mut tally = StressTally::default();
+        tally.add(&Err(ErrorKind::DeviceTimeOut));
+        tally.add(&Ok(Measurement {
+            id: Default::default(),
+            temperature: Celsius(0.0),
+            humidity: RelativeHumidity(0.0),
+            quality: Quality::BUSY_OVERRUN,
+            pressure: None,
+            seq: None,
+        }));
+        assert_eq!(tally.row(40, 10, 4), "40,10,4,0,0,1,1,1.000");
+    }
+
+    #[test]
+    fn quirks_option()
+    {
+        let (bus, rest) = bus_opts(&args(&["--quirks", "pi", "--count", "1"])).unwrap();
+        assert_eq!(bus.quirks, BusQuirks::RASPBERRY_PI);
+        assert_eq!(rest, args(&["--count", "1"]));
+        assert!(bus_opts(&args(&["--quirks", "avr"])).is_err());
+        assert!(bus_opts(&args(&["--quirks"])).is_err());
+    }
+
+    //Needs a sensor on /dev/i2c-1, run on the Pi with
+    //`cargo test --features cli --bin aht20 -- --ignored`.
+    #[cfg(target_os = "linux")]
+    #[test]
+    #[ignore]
+    fn raspberry_pi_smoke()
+    {
+        let mut sensor = Sensor::new(open_bus("/dev/i2c-1").unwrap(), SENSOR_ADDR);
+        sensor.set_bus_quirks(BusQuirks::RASPBERRY_PI);
+        let mut delay = StdDelay;
+        let mut inited = sensor.init(&mut delay).unwrap();
+        for _ in 0..5 {
+            let m = inited.measure(&mut delay).unwrap();
+            assert!(m.quality.is_good());
+            assert!((-40.0..=85.0).contains(&m.temperature.0));
+            assert!((0.0..=100.0).contains(&m.humidity.0));
+        }
+    }
+
+    #[test]
+    fn decode_status()
+    {
+        let out = decode(&[0x98]).unwrap();
+        assert!(out.contains("busy:      true"));
+        assert!(out.contains("cal:       true"));
+    }
+}
@@ -0,0 +1,59 @@
+/*
+ * Filename: mod.rs
+ * Description: What the `aht20` and `aht20-soak` tools share: the std
+ * delay, the option parsers and opening the bus.
+ */
+
+use std::thread;
+use std::time::Duration;
+
+use sensor_lib_aht20::{BusQuirks, Delay};
+#[cfg(not(target_os = "linux"))]
+use sensor_lib_aht20::SimulatedSensor;
+
+///Delay backed by `thread::sleep`.
+pub struct StdDelay;
+
+impl Delay for StdDelay {
+    fn delay_ms(&mut self, ms: u16) {
+        thread::sleep(Duration::from_millis(ms.into()));
+    }
+}
+
+pub fn parse_quirks(s: &str) -> Result<BusQuirks, String> {
+    match s {
+        "none" => Ok(BusQuirks::NONE),
+        "pi" => Ok(BusQuirks::RASPBERRY_PI),
+        "esp32-c3" => Ok(BusQuirks::ESP32_C3),
+        _ => Err(format!("unknown quirks '{}'", s)),
+    }
+}
+
+///A time like 500ms, 2s, 90m, 72h or 3d, seconds without a unit.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let (digits, scale) = if let Some(d) = s.strip_suffix("ms") {
+        (d, 1)
+    } else if let Some(d) = s.strip_suffix('s') {
+        (d, 1000)
+    } else if let Some(d) = s.strip_suffix('m') {
+        (d, 60_000)
+    } else if let Some(d) = s.strip_suffix('h') {
+        (d, 3_600_000)
+    } else if let Some(d) = s.strip_suffix('d') {
+        (d, 86_400_000)
+    } else {
+        (s, 1000)
+    };
+    let n: u64 = digits.parse().map_err(|_| format!("bad duration '{}'", s))?;
+    Ok(Duration::from_millis(n * scale))
+}
+
+#[cfg(target_os = "linux")]
+pub fn open_bus(path: &str) -> Result<linux_embedded_hal::I2cdev, String> {
+    linux_embedded_hal::I2cdev::new(path).map_err(|e| format!("{}: {}", path, e))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn open_bus(path: &str) -> Result<SimulatedSensor, String> {
+    Err(format!("{}: i2c-dev buses are only supported on Linux, try --sim", path))
+}
@@ -0,0 +1,163 @@
+/*
+ * Filename: buildcfg.rs
+ * Description: Defaults baked in at build time from environment variables,
+ * so product variants get their configuration without runtime plumbing:
+ *
+ *   AHT20_ADDRESS       bus address for `Sensor::with_build_address()`,
+ *                       decimal or 0x hex, defaults to 0x38
+ *   AHT20_VARIANT       aht10, aht20 or aht21, picks the timing table
+ *   AHT20_MAX_ATTEMPTS  init attempts and busy polls, `MAX_ATTEMPTS`,
+ *                       1 to 255
+ *   AHT20_FAULT_LIMIT   default of `Sensor::set_fault_limit()`, unset is
+ *                       no limit
+ *
+ * e.g. `AHT20_VARIANT=aht10 AHT20_ADDRESS=0x39 cargo build`. Cargo rebuilds
+ * the crate when one of them changes, an invalid value fails the build.
+ */
+
+use crate::core::retry::FixedRetry;
+use crate::core::variant::ChipVariant;
+
+//Parses a decimal or 0x prefixed hex number, None if it isn't one or
+//doesn't fit a u32.
+const fn parse_number(s: &str) -> Option<u32> {
+    let bytes = s.as_bytes();
+    let (radix, mut i) = match bytes {
+        [b'0', b'x' | b'X', _, ..] => (16, 2),
+        [_, ..] => (10, 0),
+        [] => return None,
+    };
+
+    let mut value: u32 = 0;
+    while i < bytes.len() {
+        let digit = match bytes[i] {
+            b @ b'0'..=b'9' => (b - b'0') as u32,
+            b @ b'a'..=b'f' if radix == 16 => (b - b'a' + 10) as u32,
+            b @ b'A'..=b'F' if radix == 16 => (b - b'A' + 10) as u32,
+            _ => return None,
+        };
+        value = match value.checked_mul(radix) {
+            Some(v) => match v.checked_add(digit) {
+                Some(v) => v,
+                None => return None,
+            },
+            None => return None,
+        };
+        i += 1;
+    }
+    Some(value)
+}
+
+const fn parse_variant(s: &str) -> Option<ChipVariant> {
+    match s.as_bytes() {
+        b"aht10" | b"AHT10" => Some(ChipVariant::Aht10),
+        b"aht20" | b"AHT20" => Some(ChipVariant::Aht20),
+        b"aht21" | b"AHT21" => Some(ChipVariant::Aht21),
+        _ => None,
+    }
+}
+
+//The number in `value` if it's set, checked against `min..=max`.
+const fn number_or(value: Option<&str>, default: u32, min: u32, max: u32) -> u32 {
+    match optional_number(value, min, max) {
+        Some(n) => n,
+        None => default,
+    }
+}
+
+//`number_or()` without a default, None if `value` isn't set.
+const fn optional_number(value: Option<&str>, min: u32, max: u32) -> Option<u32> {
+    let s = match value {
+        Some(s) => s,
+        None => return None,
+    };
+    match parse_number(s) {
+        Some(n) if n >= min && n <= max => Some(n),
+        _ => panic!("an AHT20_* build variable isn't a number in range"),
+    }
+}
+
+///`AHT20_ADDRESS`, or `SENSOR_ADDR`.
+pub const BUILD_ADDRESS: u8 = number_or(option_env!("AHT20_ADDRESS"), 0x38, 0, 0x7F) as u8;
+
+///`AHT20_VARIANT`, or the AHT20. `Sensor::new()` starts out with it and its
+///timing table.
+pub const BUILD_VARIANT: ChipVariant = match option_env!("AHT20_VARIANT") {
+    None => ChipVariant::Aht20,
+    Some(s) => match parse_variant(s) {
+        Some(v) => v,
+        None => panic!("AHT20_VARIANT must be aht10, aht20 or aht21"),
+    },
+};
+
+///`AHT20_MAX_ATTEMPTS`, or 3. At least one, zero attempts would never
+///talk to the sensor.
+pub const BUILD_MAX_ATTEMPTS: usize = number_or(option_env!("AHT20_MAX_ATTEMPTS"), 3, 1, 255) as usize;
+
+//Here rather than next to `FixedRetry`, `core` doesn't see the build
+//settings.
+impl Default for FixedRetry {
+    ///The AHT20 busy poll interval, `MAX_ATTEMPTS` times.
+    fn default() -> Self {
+        FixedRetry { interval_ms: 20, max_retries: BUILD_MAX_ATTEMPTS as u16 }
+    }
+}
+
+///`AHT20_FAULT_LIMIT`, or None. 0 is None as well, see
+///`Sensor::set_fault_limit()`.
+pub const BUILD_FAULT_LIMIT: Option<u8> = match optional_number(option_env!("AHT20_FAULT_LIMIT"), 0, 255) {
+    Some(n) if n > 0 => Some(n as u8),
+    _ => None,
+};
+
+#[cfg(test)]
+mod buildcfg_tests {
+    use super::*;
+
+    #[test]
+    fn numbers()
+    {
+        assert_eq!(parse_number("56"), Some(56));
+        assert_eq!(parse_number("0x38"), Some(0x38));
+        assert_eq!(parse_number("0XfF"), Some(0xFF));
+        assert_eq!(parse_number("4294967295"), Some(u32::MAX));
+        assert_eq!(parse_number("4294967296"), None);
+        assert_eq!(parse_number("0x"), None);
+        assert_eq!(parse_number("ff"), None);
+        assert_eq!(parse_number(""), None);
+        assert_eq!(number_or(None, 7, 0, 10), 7);
+        assert_eq!(number_or(Some("9"), 7, 0, 10), 9);
+        assert_eq!(number_or(Some("1"), 3, 1, 255), 1);
+        assert_eq!(optional_number(None, 0, 255), None);
+        assert_eq!(optional_number(Some("0"), 0, 255), Some(0));
+    }
+
+    #[test]
+    fn variants()
+    {
+        assert_eq!(parse_variant("aht10"), Some(ChipVariant::Aht10));
+        assert_eq!(parse_variant("AHT21"), Some(ChipVariant::Aht21));
+        assert_eq!(parse_variant("dht22"), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn out_of_range()
+    {
+        number_or(Some("0x80"), 0x38, 0, 0x7F);
+    }
+
+    #[test]
+    #[should_panic]
+    fn below_minimum()
+    {
+        number_or(Some("0"), 3, 1, 255);
+    }
+
+    #[test]
+    #[should_panic]
+    fn not_a_number()
+    {
+        optional_number(Some("three"), 0, 255);
+    }
+}
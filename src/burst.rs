@@ -0,0 +1,121 @@
+/*
+ * Filename: burst.rs
+ * Description: Back to back measurements for a bounded window, for the
+ * production test that checks the response time against the spec after
+ * conformal coating: the part is stepped between two humidities and the
+ * timestamped series shows how fast it follows.
+ */
+
+use crate::clock::{elapsed_ms, Clock};
+use crate::core::measurement::Measurement;
+use crate::{Aht2xTransport, Delay, Error, Indicator, InitializedSensor};
+
+///One reading of a burst.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BurstSample {
+    ///When the measurement was done, on the burst's clock.
+    pub at_ms: u32,
+    pub measurement: Measurement,
+}
+
+impl<E, I2C, L> InitializedSensor<I2C, L>
+where I2C: Aht2xTransport<Error = E>,
+      L: Indicator,
+{
+    ///Measures back to back, as fast as the conversion time allows, until
+    ///`window_ms` has passed on `clock` or `samples` is full. Returns the
+    ///number of samples taken, the first error ends the burst.
+    pub fn burst(
+        &mut self,
+        delay: &mut impl Delay,
+        clock: &mut impl Clock,
+        window_ms: u32,
+        samples: &mut [BurstSample],
+        ) -> Result<usize, Error<E>>
+    {
+        let start = clock.now_ms();
+        let mut count = 0;
+        while count < samples.len() {
+            let measurement = self.measure(delay)?;
+            let at_ms = clock.now_ms();
+            samples[count] = BurstSample { at_ms, measurement };
+            count += 1;
+            if elapsed_ms(start, at_ms) >= window_ms {
+                break;
+            }
+        }
+        Ok(count)
+    }
+}
+
+///Time the humidity takes to cover `fraction` of the step between the
+///first and the last sample, e.g. 0.63 for the data sheet's tau 63%.
+///None if there is no step or it's never reached.
+pub fn humidity_response_ms(samples: &[BurstSample], fraction: f32) -> Option<u32> {
+    let (first, last) = (samples.first()?, samples.last()?);
+    let from = first.measurement.humidity.0;
+    let step = last.measurement.humidity.0 - from;
+    if step == 0.0 || step.is_nan() {
+        return None;
+    }
+
+    samples.iter()
+        .find(|s| (s.measurement.humidity.0 - from) / step >= fraction)
+        .map(|s| elapsed_ms(first.at_ms, s.at_ms))
+}
+
+#[cfg(test)]
+mod burst_tests {
+    use super::*;
+    use crate::clock::clock_tests::FakeClock;
+    use crate::core::data::SensorData;
+    use crate::core::measurement::SensorId;
+    use crate::core::units::{Celsius, RelativeHumidity};
+    use crate::{Sensor, SimulatedSensor, SENSOR_ADDR};
+
+    #[test]
+    fn bounded_by_window_and_buffer()
+    {
+        let sim = SimulatedSensor::new(Celsius(22.0), RelativeHumidity(45.0));
+        let sensor = Sensor::new(sim, SENSOR_ADDR);
+        let mut delay = embedded_hal_mock::delay::MockNoop;
+        let mut inited = sensor.init(&mut delay).unwrap();
+
+        let blank = BurstSample {
+            at_ms: 0,
+            measurement: Measurement::from_data(SensorId(0), &SensorData::new()),
+        };
+        let mut samples = [blank; 16];
+        let mut clock = FakeClock { now_ms: 0, step_ms: 100 };
+        let n = inited.burst(&mut delay, &mut clock, 500, &mut samples).unwrap();
+        assert_eq!(n, 5);
+        assert_eq!(samples[0].at_ms, 100);
+        assert_eq!(samples[4].at_ms, 500);
+        assert_eq!(samples[4].measurement.humidity, samples[0].measurement.humidity);
+
+        let n = inited.burst(&mut delay, &mut clock, 10_000, &mut samples[..3]).unwrap();
+        assert_eq!(n, 3);
+
+        inited.sensor.i2c.corrupt_crc = true;
+        assert!(inited.burst(&mut delay, &mut clock, 500, &mut samples).is_err());
+    }
+
+    #[test]
+    fn response_time()
+    {
+        let series = [40.0, 40.0, 50.0, 60.0, 65.0, 68.0, 70.0];
+        let samples: Vec<BurstSample> = series.iter().enumerate()
+            .map(|(i, &rh)| {
+                let mut measurement = Measurement::from_data(SensorId(0), &SensorData::new());
+                measurement.humidity = RelativeHumidity(rh);
+                BurstSample { at_ms: 1000 + i as u32 * 100, measurement }
+            })
+            .collect();
+
+        //63% of the 30% RH step is 58.9% RH, reached by the fourth sample.
+        assert_eq!(humidity_response_ms(&samples, 0.63), Some(300));
+        assert_eq!(humidity_response_ms(&samples, 1.0), Some(600));
+        assert_eq!(humidity_response_ms(&samples[..2], 0.63), None);
+        assert_eq!(humidity_response_ms(&[], 0.63), None);
+    }
+}
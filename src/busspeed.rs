@@ -0,0 +1,113 @@
+/*
+ * Filename: busspeed.rs
+ * Description: The bus clock the application configured, declared with
+ * `Sensor::set_bus_frequency()`. The sensor supports standard and fast
+ * mode, up to 400kHz. Faster buses get more margin on the busy polling
+ * and a `JournalEvent::BusOutOfSpec` entry in the journal.
+ */
+
+use crate::journal::JournalEvent;
+use crate::{Aht2xTransport, Indicator, Sensor};
+
+///Highest clock the data sheet allows, fast mode.
+pub const MAX_BUS_HZ: u32 = 400_000;
+///Standard mode, where the data sheet timings hold as they are.
+pub const STANDARD_BUS_HZ: u32 = 100_000;
+
+///What the driver made of the declared bus clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BusSpeed {
+    ///Up to 100kHz.
+    Standard,
+    ///Up to 400kHz, the sensor stretches the clock more often.
+    Fast,
+    ///Above 400kHz or 0, not supported by the sensor.
+    OutOfSpec,
+}
+
+impl BusSpeed {
+    pub fn classify(hz: u32) -> Self {
+        if hz == 0 || hz > MAX_BUS_HZ {
+            BusSpeed::OutOfSpec
+        } else if hz > STANDARD_BUS_HZ {
+            BusSpeed::Fast
+        } else {
+            BusSpeed::Standard
+        }
+    }
+
+    ///Busy poll intervals added to the measurement deadline.
+    pub fn extra_polls(self) -> u16 {
+        match self {
+            BusSpeed::Standard => 0,
+            BusSpeed::Fast => 1,
+            BusSpeed::OutOfSpec => 2,
+        }
+    }
+}
+
+impl<E, I2C, L> Sensor<I2C, L>
+where I2C: Aht2xTransport<Error = E>,
+      L: Indicator,
+{
+    ///Declares the bus clock the I2C peripheral was set up with. Nothing on
+    ///the bus changes, the driver only widens its busy polling deadline for
+    ///fast buses and journals `JournalEvent::BusOutOfSpec` for ones the
+    ///sensor doesn't support.
+    pub fn set_bus_frequency(&mut self, hz: u32) -> BusSpeed {
+        let speed = BusSpeed::classify(hz);
+        if speed == BusSpeed::OutOfSpec {
+            self.journal.push(JournalEvent::BusOutOfSpec, self.last_seen_ms);
+        }
+        self.bus_speed = Some(speed);
+        speed
+    }
+
+    ///None until `set_bus_frequency()` is called.
+    pub fn bus_speed(&self) -> Option<BusSpeed> {
+        self.bus_speed
+    }
+}
+
+#[cfg(test)]
+mod busspeed_tests {
+    use super::*;
+    use crate::{Celsius, RelativeHumidity, SimulatedSensor, SENSOR_ADDR};
+
+    #[test]
+    fn classify()
+    {
+        assert_eq!(BusSpeed::classify(10_000), BusSpeed::Standard);
+        assert_eq!(BusSpeed::classify(100_000), BusSpeed::Standard);
+        assert_eq!(BusSpeed::classify(100_001), BusSpeed::Fast);
+        assert_eq!(BusSpeed::classify(400_000), BusSpeed::Fast);
+        assert_eq!(BusSpeed::classify(1_000_000), BusSpeed::OutOfSpec);
+        assert_eq!(BusSpeed::classify(0), BusSpeed::OutOfSpec);
+    }
+
+    #[test]
+    fn margin_and_warning()
+    {
+        //Busy for four polls: one past the AHT20 deadline.
+        let mut sim = SimulatedSensor::new(Celsius(20.0), RelativeHumidity(40.0));
+        sim.busy_reads = 4;
+        let sensor = Sensor::new(sim, SENSOR_ADDR);
+        let mut delay = embedded_hal_mock::delay::MockNoop;
+        assert_eq!(sensor.bus_speed(), None);
+        let mut inited = sensor.init(&mut delay).unwrap();
+
+        assert_eq!(inited.sensor_mut().set_bus_frequency(100_000), BusSpeed::Standard);
+        assert!(inited.measure(&mut delay).is_err());
+        assert_eq!(inited.sensor_mut().set_bus_frequency(400_000), BusSpeed::Fast);
+        assert!(inited.measure(&mut delay).is_ok());
+
+        let mut sensor = inited.downgrade();
+        assert_ne!(sensor.journal().latest().unwrap().event, JournalEvent::BusOutOfSpec);
+
+        assert_eq!(sensor.set_bus_frequency(1_000_000), BusSpeed::OutOfSpec);
+        assert_eq!(sensor.journal().latest().unwrap().event, JournalEvent::BusOutOfSpec);
+        assert_eq!(sensor.bus_speed(), Some(BusSpeed::OutOfSpec));
+    }
+}
@@ -0,0 +1,263 @@
+/*
+ * Filename: can.rs
+ * Description: Packs readings into CAN frames as fixed scaling integer
+ * signals, the way DBC files, CANopen PDO mappings and J1939 SPNs describe
+ * them: a start bit, a length, a byte order, a factor and an offset.
+ *
+ * Bits are numbered as in DBC files, bit n is bit n % 8 of byte n / 8. An
+ * Intel (little endian) signal starts at its least significant bit and
+ * counts up, a Motorola (big endian) one starts at its most significant
+ * bit and counts down within a byte, then carries on at bit 7 of the next.
+ */
+
+use crate::core::measurement::Measurement;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ByteOrder {
+    ///Little endian, CANopen and J1939.
+    Intel,
+    ///Big endian.
+    Motorola,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PackError {
+    ///The value doesn't fit the signal's range.
+    OutOfRange,
+    ///The signal runs past the end of the frame, or is longer than 32 bits.
+    DoesNotFit,
+}
+
+///One signal in a CAN frame, physical value = raw * factor + offset.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CanSignal {
+    pub start_bit: u16,
+    ///1 to 32 bits.
+    pub length: u8,
+    pub order: ByteOrder,
+    ///Two's complement raw values.
+    pub signed: bool,
+    pub factor: f32,
+    pub offset: f32,
+}
+
+//Rounds to the nearest integer, half away from zero (no `f32::round`
+//without std).
+fn round(x: f32) -> i64 {
+    if x >= 0.0 {
+        (x + 0.5) as i64
+    } else {
+        (x - 0.5) as i64
+    }
+}
+
+impl CanSignal {
+    ///J1939 temperature, 0.03125 C/bit from -273 C, 16 bits unsigned (as
+    ///SPN 171, ambient air temperature).
+    pub const fn j1939_temperature(start_bit: u16) -> Self {
+        CanSignal {
+            start_bit,
+            length: 16,
+            order: ByteOrder::Intel,
+            signed: false,
+            factor: 0.03125,
+            offset: -273.0,
+        }
+    }
+
+    ///J1939 relative humidity, 0.4 %/bit, 8 bits unsigned (as SPN 354).
+    pub const fn j1939_humidity(start_bit: u16) -> Self {
+        CanSignal {
+            start_bit,
+            length: 8,
+            order: ByteOrder::Intel,
+            signed: false,
+            factor: 0.4,
+            offset: 0.0,
+        }
+    }
+
+    fn raw_range(&self) -> (i64, i64) {
+        let bits = u32::from(self.length);
+        if self.signed {
+            (-(1i64 << (bits - 1)), (1i64 << (bits - 1)) - 1)
+        } else {
+            (0, (1i64 << bits) - 1)
+        }
+    }
+
+    //Frame bit positions from the least significant bit of the signal up.
+    fn bit_positions(&self, frame_len: usize) -> Result<impl Iterator<Item = usize>, PackError> {
+        if self.length == 0 || self.length > 32 {
+            return Err(PackError::DoesNotFit);
+        }
+        let start = usize::from(self.start_bit);
+        let len = usize::from(self.length);
+        let (order, total) = (self.order, frame_len * 8);
+
+        //Motorola: walk down from the msb, then reverse.
+        let msb_first = move |i: usize| {
+            let byte = start / 8 + (7 - start % 8 + i) / 8;
+            byte * 8 + 7 - (7 - start % 8 + i) % 8
+        };
+        let last = match order {
+            ByteOrder::Intel => start + len - 1,
+            ByteOrder::Motorola => msb_first(len - 1),
+        };
+        if last >= total || start >= total {
+            return Err(PackError::DoesNotFit);
+        }
+        Ok((0..len).map(move |i| match order {
+            ByteOrder::Intel => start + i,
+            ByteOrder::Motorola => msb_first(len - 1 - i),
+        }))
+    }
+
+    ///The raw integer for `value`, as it goes on the bus.
+    pub fn raw(&self, value: f32) -> Result<u32, PackError> {
+        if !(1..=32).contains(&self.length) {
+            return Err(PackError::DoesNotFit);
+        }
+        let raw = round((value - self.offset) / self.factor);
+        let (min, max) = self.raw_range();
+        if !value.is_finite() || raw < min || raw > max {
+            return Err(PackError::OutOfRange);
+        }
+        Ok(raw as u32)
+    }
+
+    ///Writes `value` into its bits of `frame`, the other bits are left as
+    ///they are.
+    pub fn pack(&self, value: f32, frame: &mut [u8]) -> Result<(), PackError> {
+        let raw = self.raw(value)?;
+        for (i, pos) in self.bit_positions(frame.len())?.enumerate() {
+            let mask = 1 << (pos % 8);
+            if raw & (1 << i) != 0 {
+                frame[pos / 8] |= mask;
+            } else {
+                frame[pos / 8] &= !mask;
+            }
+        }
+        Ok(())
+    }
+
+    ///Reads the signal back out of `frame`, for checking a mapping.
+    pub fn unpack(&self, frame: &[u8]) -> Result<f32, PackError> {
+        let mut raw: u32 = 0;
+        for (i, pos) in self.bit_positions(frame.len())?.enumerate() {
+            if frame[pos / 8] & (1 << (pos % 8)) != 0 {
+                raw |= 1 << i;
+            }
+        }
+        let raw = if self.signed && self.length < 32 && raw & (1 << (self.length - 1)) != 0 {
+            i64::from(raw) - (1i64 << self.length)
+        } else if self.signed {
+            i64::from(raw as i32)
+        } else {
+            i64::from(raw)
+        };
+        Ok(raw as f32 * self.factor + self.offset)
+    }
+}
+
+impl Measurement {
+    ///Packs the temperature and humidity into `frame`.
+    pub fn pack_can(
+        &self,
+        temperature: &CanSignal,
+        humidity: &CanSignal,
+        frame: &mut [u8],
+        ) -> Result<(), PackError>
+    {
+        temperature.pack(self.temperature.0, frame)?;
+        humidity.pack(self.humidity.0, frame)
+    }
+}
+
+#[cfg(test)]
+mod can_tests {
+    use super::*;
+    use crate::{Celsius, RelativeHumidity};
+
+    #[test]
+    fn j1939()
+    {
+        let m = Measurement {
+            id: Default::default(),
+            temperature: Celsius(22.88),
+            humidity: RelativeHumidity(49.34),
+            quality: Default::default(),
+            pressure: None,
+            seq: None,
+        };
+        let mut frame = [0xFF; 8];
+        let (t, rh) = (CanSignal::j1939_temperature(24), CanSignal::j1939_humidity(8));
+        m.pack_can(&t, &rh, &mut frame).unwrap();
+
+        //(22.88 + 273) / 0.03125 = 9468 = 0x24FC, 49.34 / 0.4 = 123 = 0x7B.
+        assert_eq!(frame, [0xFF, 0x7B, 0xFF, 0xFC, 0x24, 0xFF, 0xFF, 0xFF]);
+        assert!((t.unpack(&frame).unwrap() - 22.88).abs() < 0.016);
+    }
+
+    #[test]
+    fn signed_and_motorola()
+    {
+        //0.01 C/bit, 16 bits signed big endian at byte 2.
+        let s = CanSignal {
+            start_bit: 23,
+            length: 16,
+            order: ByteOrder::Motorola,
+            signed: true,
+            factor: 0.01,
+            offset: 0.0,
+        };
+        let mut frame = [0u8; 8];
+        s.pack(-12.5, &mut frame).unwrap();
+        assert_eq!(frame[2..4], (-1250i16).to_be_bytes());
+        assert!((s.unpack(&frame).unwrap() + 12.5).abs() < 0.001);
+
+        //12 bits across a byte boundary, msb at bit 3 of byte 0.
+        let s = CanSignal { start_bit: 3, length: 12, signed: false, factor: 1.0, ..s };
+        let mut frame = [0u8; 2];
+        s.pack(0xABC as f32, &mut frame).unwrap();
+        assert_eq!(frame, [0x0A, 0xBC]);
+        assert_eq!(s.unpack(&frame).unwrap(), 0xABC as f32);
+    }
+
+    #[test]
+    fn intel_bit_offsets()
+    {
+        let s = CanSignal {
+            start_bit: 4,
+            length: 10,
+            order: ByteOrder::Intel,
+            signed: true,
+            factor: 0.5,
+            offset: 0.0,
+        };
+        let mut frame = [0u8; 3];
+        s.pack(-1.0, &mut frame).unwrap();
+        //-2 in 10 bits is 0x3FE, bits 4..14.
+        assert_eq!(frame, [0xE0, 0x3F, 0x00]);
+        assert_eq!(s.unpack(&frame).unwrap(), -1.0);
+    }
+
+    #[test]
+    fn errors()
+    {
+        let s = CanSignal::j1939_humidity(56);
+        let mut frame = [0u8; 8];
+        assert_eq!(s.pack(120.0, &mut frame), Err(PackError::OutOfRange));
+        assert_eq!(s.pack(-1.0, &mut frame), Err(PackError::OutOfRange));
+        assert_eq!(s.pack(f32::NAN, &mut frame), Err(PackError::OutOfRange));
+        assert_eq!(s.pack(50.0, &mut frame[..7]), Err(PackError::DoesNotFit));
+
+        let long = CanSignal { length: 33, ..s };
+        assert_eq!(long.pack(1.0, &mut frame), Err(PackError::DoesNotFit));
+    }
+}
@@ -0,0 +1,61 @@
+//!Time source used by the parts of the driver that need timestamps.
+//!
+//!Times are u32 milliseconds, which wraps after ~49 days. Everything in the
+//!crate compares times with `wrapping_sub`, so only the differences need to
+//!be smaller than that.
+//!
+
+///A monotonic millisecond clock, e.g. a SysTick counter or an RTC.
+pub trait Clock {
+    fn now_ms(&mut self) -> u32;
+}
+
+impl<C: Clock + ?Sized> Clock for &mut C {
+    fn now_ms(&mut self) -> u32 {
+        (**self).now_ms()
+    }
+}
+
+///Milliseconds from `since` to `now`, correct across a wrap around.
+pub fn elapsed_ms(since: u32, now: u32) -> u32 {
+    now.wrapping_sub(since)
+}
+
+#[cfg(test)]
+pub(crate) mod clock_tests {
+    use super::*;
+
+    ///Clock for the tests, advances by `step_ms` on every read.
+    pub struct FakeClock {
+        pub now_ms: u32,
+        pub step_ms: u32,
+    }
+
+    impl Clock for FakeClock {
+        fn now_ms(&mut self) -> u32 {
+            let now = self.now_ms;
+            self.now_ms = self.now_ms.wrapping_add(self.step_ms);
+            now
+        }
+    }
+
+    #[test]
+    fn elapsed_wraps()
+    {
+        assert_eq!(elapsed_ms(10, 25), 15);
+        assert_eq!(elapsed_ms(u32::MAX - 4, 5), 10);
+    }
+
+    #[test]
+    fn fake_clock_steps()
+    {
+        fn by_value(mut c: impl Clock) -> u32 {
+            c.now_ms()
+        }
+
+        let mut c = FakeClock { now_ms: 0, step_ms: 5 };
+        assert_eq!(c.now_ms(), 0);
+        assert_eq!(by_value(&mut c), 5);
+        assert_eq!(c.now_ms(), 10);
+    }
+}
@@ -0,0 +1,249 @@
+/*
+ * Filename: condensation.rs
+ * Description: Counts condensation events, excursions above a humidity
+ * threshold that last long enough(by default >95% RH for 5 minutes).
+ * AOSONG asks for a recovery and recalibration after the sensor has been
+ * exposed to condensation, so the count survives power cycles through
+ * `Storage`. Stored as 10 bytes:
+ *
+ *   0      marker, `CONDENSATION_MARKER`
+ *   1..5   events, u32 little endian
+ *   5..9   seconds spent above the threshold, u32 little endian
+ *   9      CRC-8/MAXIM over bytes 0..9
+ */
+
+use crate::alarm::{AlarmEvent, Debounce, Debouncer};
+use crate::core::data::crc8_maxim;
+use crate::core::measurement::Measurement;
+use crate::storage::Storage;
+use crate::core::units::RelativeHumidity;
+
+pub const CONDENSATION_RECORD_LEN: usize = 10;
+pub const CONDENSATION_MARKER: u8 = 0xC5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CondensationConfig {
+    pub threshold: RelativeHumidity,
+    ///How long the humidity has to stay above the threshold to count.
+    pub min_duration_ms: u32,
+    ///An event ends once the humidity is this far below the threshold.
+    pub hysteresis: RelativeHumidity,
+}
+
+impl CondensationConfig {
+    ///Above 95% RH for 5 minutes, ends below 93% RH.
+    pub const DEFAULT: CondensationConfig = CondensationConfig {
+        threshold: RelativeHumidity(95.0),
+        min_duration_ms: 5 * 60 * 1000,
+        hysteresis: RelativeHumidity(2.0),
+    };
+}
+
+impl Default for CondensationConfig {
+    fn default() -> Self {
+        CondensationConfig::DEFAULT
+    }
+}
+
+///Counts condensation events, feed it every measurement with `update()`.
+#[derive(Debug, Clone, Copy)]
+pub struct CondensationCounter {
+    config: CondensationConfig,
+    debouncer: Debouncer,
+    events: u32,
+    exposure_s: u32,
+    started_ms: u32,
+}
+
+impl CondensationCounter {
+    pub fn new(config: CondensationConfig) -> Self {
+        CondensationCounter::with_counts(config, 0, 0)
+    }
+
+    //Sets up a counter carrying on from stored counts.
+    fn with_counts(config: CondensationConfig, events: u32, exposure_s: u32) -> Self {
+        CondensationCounter {
+            config,
+            debouncer: Debouncer::new(Debounce::Millis(config.min_duration_ms)),
+            events,
+            exposure_s,
+            started_ms: 0,
+        }
+    }
+
+    ///Carries on from the counts in `storage`, a blank or corrupt record
+    ///starts from zero.
+    pub fn load<S: Storage>(config: CondensationConfig, storage: &mut S) -> Result<Self, S::Error> {
+        let mut r = [0u8; CONDENSATION_RECORD_LEN];
+        storage.read(&mut r)?;
+        let crc = crc8_maxim(&r, CONDENSATION_RECORD_LEN - 1);
+        if r[0] != CONDENSATION_MARKER || r[CONDENSATION_RECORD_LEN - 1] != crc {
+            return Ok(CondensationCounter::new(config));
+        }
+        let events = u32::from_le_bytes([r[1], r[2], r[3], r[4]]);
+        let exposure_s = u32::from_le_bytes([r[5], r[6], r[7], r[8]]);
+        Ok(CondensationCounter::with_counts(config, events, exposure_s))
+    }
+
+    pub fn to_bytes(&self) -> [u8; CONDENSATION_RECORD_LEN] {
+        let mut r = [0u8; CONDENSATION_RECORD_LEN];
+        r[0] = CONDENSATION_MARKER;
+        r[1..5].copy_from_slice(&self.events.to_le_bytes());
+        r[5..9].copy_from_slice(&self.exposure_s.to_le_bytes());
+        r[9] = crc8_maxim(&r, CONDENSATION_RECORD_LEN - 1);
+        r
+    }
+
+    pub fn save<S: Storage>(&self, storage: &mut S) -> Result<(), S::Error> {
+        storage.write(&self.to_bytes())
+    }
+
+    pub fn config(&self) -> CondensationConfig {
+        self.config
+    }
+
+    ///Feeds one measurement. `Raised` when an event is counted, `Cleared`
+    ///when it ends. NaN readings are skipped.
+    pub fn update(&mut self, m: &Measurement, now_ms: u32) -> Option<AlarmEvent> {
+        let rh = m.humidity.0;
+        if rh.is_nan() {
+            return None;
+        }
+        let limit = if self.debouncer.is_active() {
+            self.config.threshold.0 - self.config.hysteresis.0
+        } else {
+            self.config.threshold.0
+        };
+
+        let event = self.debouncer.update(rh > limit, now_ms)?;
+        match event {
+            AlarmEvent::Raised => {
+                self.events = self.events.saturating_add(1);
+                self.started_ms = now_ms.wrapping_sub(self.config.min_duration_ms);
+            },
+            AlarmEvent::Cleared => {
+                let s = now_ms.wrapping_sub(self.started_ms) / 1000;
+                self.exposure_s = self.exposure_s.saturating_add(s);
+            },
+        }
+        Some(event)
+    }
+
+    ///Same as `update()`, the counts are saved whenever an event starts
+    ///or ends.
+    pub fn update_persisted<S: Storage>(
+        &mut self,
+        m: &Measurement,
+        now_ms: u32,
+        storage: &mut S,
+        ) -> Result<Option<AlarmEvent>, S::Error>
+    {
+        let event = self.update(m, now_ms);
+        if event.is_some() {
+            self.save(storage)?;
+        }
+        Ok(event)
+    }
+
+    ///True while the humidity is above the threshold past the minimum
+    ///duration.
+    pub fn is_condensing(&self) -> bool {
+        self.debouncer.is_active()
+    }
+
+    ///Events counted, including the ones before the last `load()`.
+    pub fn events(&self) -> u32 {
+        self.events
+    }
+
+    ///Seconds spent in finished events.
+    pub fn exposure_s(&self) -> u32 {
+        self.exposure_s
+    }
+
+    ///Zeroes the counts, e.g. after the sensor was swapped.
+    pub fn clear(&mut self) {
+        *self = CondensationCounter::new(self.config);
+    }
+}
+
+#[cfg(test)]
+mod condensation_tests {
+    use super::*;
+    use crate::core::data::SensorData;
+    use crate::core::measurement::SensorId;
+
+    #[derive(Default)]
+    struct Eeprom {
+        bytes: Vec<u8>,
+        writes: usize,
+    }
+
+    impl Storage for Eeprom {
+        type Error = ();
+
+        fn read(&mut self, buf: &mut [u8]) -> Result<(), ()> {
+            buf.fill(0xFF);
+            let n = buf.len().min(self.bytes.len());
+            buf[..n].copy_from_slice(&self.bytes[..n]);
+            Ok(())
+        }
+
+        fn write(&mut self, bytes: &[u8]) -> Result<(), ()> {
+            self.bytes = bytes.to_vec();
+            self.writes += 1;
+            Ok(())
+        }
+    }
+
+    fn m(rh: f32) -> Measurement {
+        let mut m = Measurement::from_data(SensorId(0), &SensorData::new());
+        m.humidity = RelativeHumidity(rh);
+        m
+    }
+
+    #[test]
+    fn counts_long_excursions()
+    {
+        let mut c = CondensationCounter::new(CondensationConfig::DEFAULT);
+        let min = CondensationConfig::DEFAULT.min_duration_ms;
+
+        //Too short to count.
+        assert_eq!(c.update(&m(97.0), 0), None);
+        assert_eq!(c.update(&m(90.0), min / 2), None);
+
+        assert_eq!(c.update(&m(97.0), 1000), None);
+        assert_eq!(c.update(&m(f32::NAN), 2000), None);
+        assert_eq!(c.update(&m(97.0), 1000 + min), Some(AlarmEvent::Raised));
+        assert!(c.is_condensing());
+
+        //Within the hysteresis it carries on.
+        assert_eq!(c.update(&m(94.0), 2 * min), None);
+        assert_eq!(c.update(&m(92.0), 3 * min), None);
+        assert_eq!(c.update(&m(92.0), 4 * min), Some(AlarmEvent::Cleared));
+        assert_eq!(c.events(), 1);
+        assert_eq!(c.exposure_s(), (4 * min - 1000) / 1000);
+    }
+
+    #[test]
+    fn persisted()
+    {
+        let mut eeprom = Eeprom::default();
+        let config = CondensationConfig { min_duration_ms: 10, ..CondensationConfig::DEFAULT };
+
+        let mut c = CondensationCounter::load(config, &mut eeprom).unwrap();
+        assert_eq!(c.events(), 0);
+        c.update_persisted(&m(99.0), 0, &mut eeprom).unwrap();
+        c.update_persisted(&m(99.0), 10, &mut eeprom).unwrap();
+        c.update_persisted(&m(50.0), 1000, &mut eeprom).unwrap();
+        c.update_persisted(&m(50.0), 3010, &mut eeprom).unwrap();
+        assert_eq!(eeprom.writes, 2);
+
+        let c = CondensationCounter::load(config, &mut eeprom).unwrap();
+        assert_eq!((c.events(), c.exposure_s()), (1, 3));
+
+        eeprom.bytes[2] ^= 0x01;
+        let c = CondensationCounter::load(config, &mut eeprom).unwrap();
+        assert_eq!(c.events(), 0);
+    }
+}
@@ -0,0 +1,323 @@
+/*
+ * Filename: control.rs
+ * Description: Small control helpers driven by the sensor readings, e.g.
+ * switching a humidifier in a terrarium or a dehumidifier in a humidor.
+ *
+ * Times are u32 milliseconds from a monotonic source, wrap around is handled.
+ */
+
+use crate::core::data::SensorData;
+use crate::core::units::RelativeHumidity;
+
+///What the controlled device does to the humidity when switched on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Actuator {
+    Humidifier,
+    Dehumidifier,
+}
+
+///Configuration for the `HysteresisController`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HysteresisConfig {
+    pub actuator: Actuator,
+    ///Target humidity in %RH.
+    pub setpoint: f32,
+    ///Total width of the band around the setpoint where nothing switches.
+    pub deadband: f32,
+    ///Minimum time the output stays on once switched on.
+    pub min_on_ms: u32,
+    ///Minimum time the output stays off once switched off.
+    pub min_off_ms: u32,
+}
+
+///Bang-bang controller with a deadband and minimum on/off times.
+#[derive(Debug, Clone, Copy)]
+pub struct HysteresisController {
+    config: HysteresisConfig,
+    on: bool,
+    switched_ms: Option<u32>,
+}
+
+impl HysteresisController {
+    ///The output starts off, the first switch isn't held back by the
+    ///minimum times.
+    pub fn new(config: HysteresisConfig) -> Self {
+        HysteresisController { config, on: false, switched_ms: None }
+    }
+
+    pub fn config(&self) -> HysteresisConfig {
+        self.config
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.on
+    }
+
+    ///Feeds a reading and returns whether the output should be on.
+    ///NaN readings keep the current output.
+    pub fn update(&mut self, rh: RelativeHumidity, now_ms: u32) -> bool {
+        let half_band = self.config.deadband / 2.0;
+        let too_dry = rh.0 < self.config.setpoint - half_band;
+        let too_wet = rh.0 > self.config.setpoint + half_band;
+
+        let (turn_on, turn_off) = match self.config.actuator {
+            Actuator::Humidifier => (too_dry, too_wet),
+            Actuator::Dehumidifier => (too_wet, too_dry),
+        };
+
+        let wanted = if turn_on {
+            true
+        } else if turn_off {
+            false
+        } else {
+            self.on
+        };
+
+        if wanted != self.on && self.min_time_elapsed(now_ms) {
+            self.on = wanted;
+            self.switched_ms = Some(now_ms);
+        }
+        self.on
+    }
+
+    fn min_time_elapsed(&self, now_ms: u32) -> bool {
+        let since = match self.switched_ms {
+            Some(t) => t,
+            None => return true,
+        };
+        let min = if self.on {
+            self.config.min_on_ms
+        } else {
+            self.config.min_off_ms
+        };
+        now_ms.wrapping_sub(since) >= min
+    }
+}
+
+///Which reading a `ProcessVariable` follows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PvSource {
+    Temperature,
+    Humidity,
+}
+
+///How a reading is turned into the process variable.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PvScaling {
+    ///Maps `min..max` onto `0.0..1.0`, clamped at both ends. `min` and
+    ///`max` have to differ, see `ProcessVariable::new()`.
+    Normalized { min: f32, max: f32 },
+    ///`value * gain + offset`, e.g. gain 1.8 offset 32 for Fahrenheit.
+    Engineering { gain: f32, offset: f32 },
+}
+
+///Adapts readings into a process variable for a PID loop(e.g. the `pid`
+///crate), which expects to be updated at a fixed sample time.
+///
+///`sample()` only hands out a value once per `sample_time_ms`, so the
+///caller can read the sensor as often as it likes and still keep the
+///loop's timing contract.
+#[derive(Debug, Clone, Copy)]
+pub struct ProcessVariable {
+    source: PvSource,
+    scaling: PvScaling,
+    sample_time_ms: u32,
+    next_ms: Option<u32>,
+}
+
+impl ProcessVariable {
+    ///None for a `PvScaling::Normalized` range that's empty(`min == max`)
+    ///or not finite, it would scale every reading to NaN.
+    pub fn new(source: PvSource, scaling: PvScaling, sample_time_ms: u32) -> Option<Self> {
+        if let PvScaling::Normalized { min, max } = scaling {
+            if !(max - min).is_normal() {
+                return None;
+            }
+        }
+        Some(ProcessVariable { source, scaling, sample_time_ms, next_ms: None })
+    }
+
+    pub fn sample_time_ms(&self) -> u32 {
+        self.sample_time_ms
+    }
+
+    ///Applies the scaling to a value in C or %RH.
+    pub fn scale(&self, value: f32) -> f32 {
+        match self.scaling {
+            PvScaling::Normalized { min, max } => {
+                let v = (value - min) / (max - min);
+                v.clamp(0.0, 1.0)
+            },
+            PvScaling::Engineering { gain, offset } => value * gain + offset,
+        }
+    }
+
+    ///Returns true if a new sample is due.
+    pub fn is_due(&self, now_ms: u32) -> bool {
+        match self.next_ms {
+            Some(next) => now_ms.wrapping_sub(next) < u32::MAX / 2,
+            None => true,
+        }
+    }
+
+    ///Returns the scaled process variable if a sample is due, None if it's
+    ///too early or the reading is NaN. The due time advances by whole
+    ///sample times so jitter in the calls doesn't add up, if a whole period
+    ///was missed it restarts from `now_ms`.
+    pub fn sample(&mut self, data: &SensorData, now_ms: u32) -> Option<f32> {
+        if !self.is_due(now_ms) {
+            return None;
+        }
+
+        let value = match self.source {
+            PvSource::Temperature => data.calculate_temperature(),
+            PvSource::Humidity => data.calculate_humidity(),
+        };
+        if value.is_nan() {
+            return None;
+        }
+
+        let next = match self.next_ms {
+            Some(next) if now_ms.wrapping_sub(next) < self.sample_time_ms => {
+                next.wrapping_add(self.sample_time_ms)
+            },
+            _ => now_ms.wrapping_add(self.sample_time_ms),
+        };
+        self.next_ms = Some(next);
+        Some(self.scale(value))
+    }
+}
+
+#[cfg(test)]
+mod control_tests {
+    use super::*;
+
+    fn humidifier() -> HysteresisController {
+        HysteresisController::new(HysteresisConfig {
+            actuator: Actuator::Humidifier,
+            setpoint: 70.0,
+            deadband: 4.0,
+            min_on_ms: 0,
+            min_off_ms: 0,
+        })
+    }
+
+    #[test]
+    fn humidifier_band()
+    {
+        let mut c = humidifier();
+
+        assert!(!c.update(RelativeHumidity(69.0), 0));
+        assert!(c.update(RelativeHumidity(67.9), 0));
+        //Inside the band, stays on.
+        assert!(c.update(RelativeHumidity(71.0), 0));
+        assert!(!c.update(RelativeHumidity(72.1), 0));
+        assert!(!c.update(RelativeHumidity(69.0), 0));
+    }
+
+    #[test]
+    fn dehumidifier_band()
+    {
+        let mut c = HysteresisController::new(HysteresisConfig {
+            actuator: Actuator::Dehumidifier,
+            setpoint: 65.0,
+            deadband: 2.0,
+            min_on_ms: 0,
+            min_off_ms: 0,
+        });
+
+        assert!(c.update(RelativeHumidity(66.5), 0));
+        assert!(c.update(RelativeHumidity(65.0), 0));
+        assert!(!c.update(RelativeHumidity(63.9), 0));
+    }
+
+    #[test]
+    fn minimum_times()
+    {
+        let mut c = HysteresisController::new(HysteresisConfig {
+            min_on_ms: 60_000,
+            min_off_ms: 30_000,
+            ..humidifier().config()
+        });
+
+        assert!(c.update(RelativeHumidity(60.0), 1000));
+        //Too wet but the minimum on time hasn't passed.
+        assert!(c.update(RelativeHumidity(80.0), 30_000));
+        assert!(!c.update(RelativeHumidity(80.0), 61_000));
+        assert!(!c.update(RelativeHumidity(60.0), 90_999));
+        assert!(c.update(RelativeHumidity(60.0), 91_000));
+    }
+
+    #[test]
+    fn nan_holds_output()
+    {
+        let mut c = humidifier();
+        assert!(c.update(RelativeHumidity(60.0), 0));
+        assert!(c.update(RelativeHumidity(f32::NAN), 0));
+    }
+
+    fn sample_data() -> SensorData {
+        let mut sd = SensorData::new();
+        sd.bytes = [0x18, 0x7E, 0x51, 0x65, 0xD4, 0xA0, 0xDA];
+        sd
+    }
+
+    #[test]
+    fn pv_scaling()
+    {
+        let pv = ProcessVariable::new(
+            PvSource::Humidity,
+            PvScaling::Normalized { min: 40.0, max: 60.0 },
+            1000,
+            ).unwrap();
+        assert_eq!(pv.scale(50.0), 0.5);
+        assert_eq!(pv.scale(20.0), 0.0);
+        assert_eq!(pv.scale(90.0), 1.0);
+
+        let pv = ProcessVariable::new(
+            PvSource::Temperature,
+            PvScaling::Engineering { gain: 1.8, offset: 32.0 },
+            1000,
+            ).unwrap();
+        assert_eq!(pv.scale(100.0), 212.0);
+    }
+
+    #[test]
+    fn pv_empty_range()
+    {
+        let scaled = |min, max| ProcessVariable::new(
+            PvSource::Humidity,
+            PvScaling::Normalized { min, max },
+            1000,
+            );
+        assert!(scaled(50.0, 50.0).is_none());
+        assert!(scaled(f32::NAN, 60.0).is_none());
+        assert!(scaled(0.0, f32::INFINITY).is_none());
+        //A falling range is fine, it inverts the output.
+        assert_eq!(scaled(60.0, 40.0).unwrap().scale(45.0), 0.75);
+    }
+
+    #[test]
+    fn pv_sample_time()
+    {
+        let sd = sample_data();
+        let mut pv = ProcessVariable::new(
+            PvSource::Temperature,
+            PvScaling::Engineering { gain: 1.0, offset: 0.0 },
+            1000,
+            ).unwrap();
+
+        let t = pv.sample(&sd, 0).unwrap();
+        assert!(t > 22.87 && t < 22.89);
+        assert!(pv.sample(&sd, 999).is_none());
+        //Late by 20ms, the next one is still due at 2000.
+        assert!(pv.sample(&sd, 1020).is_some());
+        assert!(!pv.is_due(1999));
+        assert!(pv.is_due(2000));
+
+        //Missed whole periods, restarts from now.
+        assert!(pv.sample(&sd, 5500).is_some());
+        assert!(pv.sample(&sd, 6000).is_none());
+        assert!(pv.sample(&sd, 6500).is_some());
+    }
+}
@@ -0,0 +1,153 @@
+/*
+ * Filename: comfort.rs
+ * Description: Qualitative comfort state for thermostat UIs, in the spirit
+ * of ASHRAE 55. The default bands are the usual indoor targets: 20C to 26C
+ * and 30% up to 60% RH. They are a starting point, not the standard's full
+ * model, which also needs air speed, clothing and activity.
+ */
+
+use crate::core::measurement::Measurement;
+use crate::core::units::{Celsius, RelativeHumidity};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemperatureComfort {
+    Cold,
+    Cool,
+    Comfortable,
+    Warm,
+    Hot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HumidityComfort {
+    TooDry,
+    Comfortable,
+    TooHumid,
+}
+
+///The classified state, `None` for NaN readings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Comfort {
+    pub temperature: Option<TemperatureComfort>,
+    pub humidity: Option<HumidityComfort>,
+}
+
+impl Comfort {
+    ///Both temperature and humidity are in their comfortable bands.
+    pub fn is_comfortable(&self) -> bool {
+        self.temperature == Some(TemperatureComfort::Comfortable)
+            && self.humidity == Some(HumidityComfort::Comfortable)
+    }
+}
+
+///Band edges, each is the lower bound of the band above it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ComfortBands {
+    pub cool_from: Celsius,
+    pub comfortable_from: Celsius,
+    pub warm_from: Celsius,
+    pub hot_from: Celsius,
+    pub comfortable_rh_from: RelativeHumidity,
+    pub humid_from: RelativeHumidity,
+}
+
+impl ComfortBands {
+    pub const INDOOR: ComfortBands = ComfortBands {
+        cool_from: Celsius(18.0),
+        comfortable_from: Celsius(20.0),
+        warm_from: Celsius(26.0),
+        hot_from: Celsius(28.0),
+        comfortable_rh_from: RelativeHumidity(30.0),
+        humid_from: RelativeHumidity(60.0),
+    };
+
+    pub fn classify(&self, t: Celsius, rh: RelativeHumidity) -> Comfort {
+        let temperature = if !t.is_valid() {
+            None
+        } else if t >= self.hot_from {
+            Some(TemperatureComfort::Hot)
+        } else if t >= self.warm_from {
+            Some(TemperatureComfort::Warm)
+        } else if t >= self.comfortable_from {
+            Some(TemperatureComfort::Comfortable)
+        } else if t >= self.cool_from {
+            Some(TemperatureComfort::Cool)
+        } else {
+            Some(TemperatureComfort::Cold)
+        };
+
+        let humidity = if !rh.is_valid() {
+            None
+        } else if rh >= self.humid_from {
+            Some(HumidityComfort::TooHumid)
+        } else if rh >= self.comfortable_rh_from {
+            Some(HumidityComfort::Comfortable)
+        } else {
+            Some(HumidityComfort::TooDry)
+        };
+
+        Comfort { temperature, humidity }
+    }
+}
+
+impl Default for ComfortBands {
+    fn default() -> Self {
+        ComfortBands::INDOOR
+    }
+}
+
+impl Measurement {
+    ///Comfort state with the default indoor bands.
+    pub fn comfort(&self) -> Comfort {
+        ComfortBands::INDOOR.classify(self.temperature, self.humidity)
+    }
+}
+
+#[cfg(test)]
+mod comfort_tests {
+    use super::*;
+
+    #[test]
+    fn indoor_bands()
+    {
+        let b = ComfortBands::default();
+        let c = b.classify(Celsius(22.0), RelativeHumidity(45.0));
+        assert!(c.is_comfortable());
+
+        let c = b.classify(Celsius(19.0), RelativeHumidity(25.0));
+        assert_eq!(c.temperature, Some(TemperatureComfort::Cool));
+        assert_eq!(c.humidity, Some(HumidityComfort::TooDry));
+        assert!(!c.is_comfortable());
+
+        assert_eq!(b.classify(Celsius(29.0), RelativeHumidity(70.0)), Comfort {
+            temperature: Some(TemperatureComfort::Hot),
+            humidity: Some(HumidityComfort::TooHumid),
+        });
+        assert_eq!(b.classify(Celsius(26.0), RelativeHumidity(50.0)).temperature,
+            Some(TemperatureComfort::Warm));
+    }
+
+    #[test]
+    fn band_edges()
+    {
+        //Every edge belongs to the band above it, like the temperatures.
+        let b = ComfortBands::INDOOR;
+        assert_eq!(b.classify(Celsius(22.0), RelativeHumidity(60.0)).humidity,
+            Some(HumidityComfort::TooHumid));
+        assert_eq!(b.classify(Celsius(22.0), RelativeHumidity(59.9)).humidity,
+            Some(HumidityComfort::Comfortable));
+        assert_eq!(b.classify(Celsius(22.0), RelativeHumidity(30.0)).humidity,
+            Some(HumidityComfort::Comfortable));
+        assert_eq!(b.classify(Celsius(20.0), RelativeHumidity(45.0)).temperature,
+            Some(TemperatureComfort::Comfortable));
+    }
+
+    #[test]
+    fn nan_readings()
+    {
+        let c = ComfortBands::INDOOR.classify(Celsius(f32::NAN), RelativeHumidity(45.0));
+        assert_eq!(c.temperature, None);
+        assert_eq!(c.humidity, Some(HumidityComfort::Comfortable));
+        assert!(!c.is_comfortable());
+    }
+}
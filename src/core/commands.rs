@@ -0,0 +1,60 @@
+
+//We have sepreate consts and enums for the puporse of being used during
+//testing(consts) or as parameters(enum).
+pub const READ_STATUS: u8 = 0x71;
+pub const INIT_SENSOR: u8 = 0xBE;
+pub const CALIBRATE: u8 = 0xE1;
+pub const TRIG_MESSURE: u8 = 0xAC;
+pub const SOFT_RESET: u8 = 0xBA;
+
+/// Trig Measure Parameter 0(unknown) 
+pub const TRIG_MEASURE_PARAM0: u8 = 0x33;
+/// Trig Measure Parameter 1(unknown) 
+pub const TRIG_MEASURE_PARAM1: u8 = 0x00;
+
+/// Calibration Paramter 0(unknown)
+pub const CAL_PARAM0: u8 = 0x08;
+/// Calibration Paramter 1(unknown)
+pub const CAL_PARAM1: u8 = 0x00;
+
+///Status bits that should both be set after power on (bit 4 is reserved in
+///the data sheet), if not the calibration registers need rewriting.
+pub const CAL_CHECK_MASK: u8 = 0x18;
+///The calibration registers rewritten by AOSONG's sample code.
+pub const CAL_REGISTERS: [u8; 3] = [0x1B, 0x1C, 0x1E];
+///OR'd with a register address to write it.
+pub const REG_WRITE: u8 = 0xB0;
+
+#[repr(u8)]
+#[allow(dead_code)]
+pub enum Command {
+    ReadStatus = READ_STATUS,
+    InitSensor = INIT_SENSOR,
+    Calibrate = CALIBRATE,
+    TrigMessure = TRIG_MESSURE,
+    SoftReset = SOFT_RESET,
+}
+
+
+//The bytes of each command as sent, the parameter bytes come from
+//`CommandParams`.
+
+pub const fn read_status() -> [u8; 1] {
+    [READ_STATUS]
+}
+
+pub const fn init_sensor() -> [u8; 1] {
+    [INIT_SENSOR]
+}
+
+pub const fn soft_reset() -> [u8; 1] {
+    [SOFT_RESET]
+}
+
+pub const fn calibrate(params: [u8; 2]) -> [u8; 3] {
+    [CALIBRATE, params[0], params[1]]
+}
+
+pub const fn trigger(params: [u8; 2]) -> [u8; 3] {
+    [TRIG_MESSURE, params[0], params[1]]
+}
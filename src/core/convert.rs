@@ -0,0 +1,325 @@
+/*
+ * Filename: convert.rs
+ * Description: The data-sheet formulas for turning the raw 20bit readings
+ * into physical values. These are free functions so software that only has
+ * the raw values(e.g. a gateway decoding packed records) uses the same math.
+ */
+
+///The sensor's readings are 20bits wide, so everything is divided by 2^20.
+pub const AHT20_DIVISOR: f32 = 1048576.0;
+
+///Uses the sensor's data-sheet formula for relative humidity %.
+pub fn raw_to_rh(raw: u32) -> f32 {
+    ((raw as f32) / AHT20_DIVISOR) * 100.0
+}
+
+///Uses the sensor's data-sheet formula for temperature in C.
+pub fn raw_to_celsius(raw: u32) -> f32 {
+    ((raw as f32) / AHT20_DIVISOR) * 200.0 - 50.0
+}
+
+///The sensor resolution is 0.01, more decimals than this are just noise.
+pub const MAX_DECIMALS: u8 = 2;
+
+///The largest raw reading, the sensor's values are 20 bits.
+pub const MAX_RAW: u32 = (1 << 20) - 1;
+
+//200 / 2^20 = 25 / 2^17 and 100 / 2^20 = 25 / 2^18, using the reduced
+//fractions keeps `raw * 25 * 100` inside a u32. That only holds for 20 bit
+//readings and `MAX_DECIMALS`, so both are capped.
+
+///Temperature scaled by 10^decimals and rounded, e.g. 2288 for 22.88C
+///with 2 decimals. `decimals` is capped at `MAX_DECIMALS`, `raw` at
+///`MAX_RAW`.
+pub const fn raw_to_celsius_scaled(raw: u32, decimals: u8) -> i32 {
+    let factor = scale_factor(decimals);
+    let t = ((capped_raw(raw) * 25 * factor) + (1 << 16)) >> 17;
+    t as i32 - (50 * factor) as i32
+}
+
+///Relative humidity scaled by 10^decimals and rounded, e.g. 493 for 49.3%
+///with 1 decimal. `decimals` is capped at `MAX_DECIMALS`, `raw` at
+///`MAX_RAW`.
+pub const fn raw_to_rh_scaled(raw: u32, decimals: u8) -> u32 {
+    let factor = scale_factor(decimals);
+    ((capped_raw(raw) * 25 * factor) + (1 << 17)) >> 18
+}
+
+const fn capped_raw(raw: u32) -> u32 {
+    if raw > MAX_RAW {
+        return MAX_RAW;
+    }
+    raw
+}
+
+pub(crate) const fn scale_factor(decimals: u8) -> u32 {
+    if decimals > MAX_DECIMALS {
+        return 10u32.pow(MAX_DECIMALS as u32);
+    }
+    10u32.pow(decimals as u32)
+}
+
+///A raw reading and the values the data sheet formulas give for it, in
+///hundredths.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConversionVector {
+    pub raw: u32,
+    pub celsius_centi: i32,
+    pub rh_centi: u32,
+}
+
+///The ends and middle of the 20bit range, plus the readings of a captured
+///frame (22.88C, 49.34%).
+pub const CONVERSION_VECTORS: [ConversionVector; 5] = [
+    ConversionVector { raw: 0, celsius_centi: -5000, rh_centi: 0 },
+    ConversionVector { raw: 0x40000, celsius_centi: 0, rh_centi: 2500 },
+    ConversionVector { raw: 0x80000, celsius_centi: 5000, rh_centi: 5000 },
+    ConversionVector { raw: 382112, celsius_centi: 2288, rh_centi: 3644 },
+    ConversionVector { raw: 517398, celsius_centi: 4869, rh_centi: 4934 },
+];
+
+//True if the integer path gives every vector, evaluated at compile time
+//below so a change to the formulas doesn't build.
+const fn vectors_hold(vectors: &[ConversionVector]) -> bool {
+    let mut i = 0;
+    while i < vectors.len() {
+        let v = vectors[i];
+        if raw_to_celsius_scaled(v.raw, 2) != v.celsius_centi
+            || raw_to_rh_scaled(v.raw, 2) != v.rh_centi
+        {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+const _: () = assert!(vectors_hold(&CONVERSION_VECTORS));
+//Raising `MAX_DECIMALS` needs wider math than this.
+const _: () = assert!((MAX_RAW * 25).checked_mul(10u32.pow(MAX_DECIMALS as u32)).is_some());
+const _: () = assert!(raw_to_celsius_scaled((1 << 20) - 1, 2) == 15000);
+const _: () = assert!(raw_to_rh_scaled((1 << 20) - 1, 2) == 10000);
+
+///A numeric type the raw readings can be converted into.
+///
+///`f32` is the default used everywhere else in the crate, `f64` is there
+///for host side analysis and `fixed::types::I18F14` (feature `fixed`) for
+///MCUs without an FPU, it only uses integer operations.
+pub trait Numeric: Copy {
+    fn from_raw_rh(raw: u32) -> Self;
+    fn from_raw_celsius(raw: u32) -> Self;
+}
+
+impl Numeric for f32 {
+    fn from_raw_rh(raw: u32) -> Self {
+        raw_to_rh(raw)
+    }
+
+    fn from_raw_celsius(raw: u32) -> Self {
+        raw_to_celsius(raw)
+    }
+}
+
+impl Numeric for f64 {
+    fn from_raw_rh(raw: u32) -> Self {
+        ((raw as f64) / (AHT20_DIVISOR as f64)) * 100.0
+    }
+
+    fn from_raw_celsius(raw: u32) -> Self {
+        ((raw as f64) / (AHT20_DIVISOR as f64)) * 200.0 - 50.0
+    }
+}
+
+//The raw value is 20 fractional bits, I18F14 has 14 of them so the scaled
+//value gets shifted down by 6. raw * 200 only fits in an i32 for a capped
+//raw value.
+#[cfg(feature = "fixed")]
+impl Numeric for fixed::types::I18F14 {
+    fn from_raw_rh(raw: u32) -> Self {
+        Self::from_bits(((capped_raw(raw) * 100) >> 6) as i32)
+    }
+
+    fn from_raw_celsius(raw: u32) -> Self {
+        Self::from_bits(((capped_raw(raw) * 200) >> 6) as i32) - Self::from_num(50)
+    }
+}
+
+///Generic version of `raw_to_rh()`, e.g. `raw_to_rh_as::<f64>(raw)`.
+pub fn raw_to_rh_as<N: Numeric>(raw: u32) -> N {
+    N::from_raw_rh(raw)
+}
+
+///Generic version of `raw_to_celsius()`.
+pub fn raw_to_celsius_as<N: Numeric>(raw: u32) -> N {
+    N::from_raw_celsius(raw)
+}
+
+#[cfg(test)]
+mod convert_tests {
+    use super::*;
+
+    #[test]
+    fn rh_limits()
+    {
+        assert_eq!(raw_to_rh(0), 0.0);
+        assert_eq!(raw_to_rh(1 << 20), 100.0);
+    }
+
+    #[test]
+    fn celsius_limits()
+    {
+        assert_eq!(raw_to_celsius(0), -50.0);
+        assert_eq!(raw_to_celsius(1 << 20), 150.0);
+    }
+
+    #[test]
+    fn captured_values()
+    {
+        let h = raw_to_rh(517398);
+        assert!(h < 49.35 && h > 49.34);
+
+        let t = raw_to_celsius(382112);
+        assert!(t < 22.89 && t > 22.87);
+    }
+
+    #[test]
+    fn scaled_values()
+    {
+        assert_eq!(raw_to_celsius_scaled(382112, 2), 2288);
+        assert_eq!(raw_to_celsius_scaled(382112, 1), 229);
+        assert_eq!(raw_to_celsius_scaled(382112, 0), 23);
+        assert_eq!(raw_to_rh_scaled(517398, 2), 4934);
+        assert_eq!(raw_to_rh_scaled(517398, 1), 493);
+        assert_eq!(raw_to_rh_scaled(517398, 0), 49);
+    }
+
+    #[test]
+    fn scaled_limits()
+    {
+        assert_eq!(raw_to_celsius_scaled(0, 2), -5000);
+        assert_eq!(raw_to_celsius_scaled((1 << 20) - 1, 2), 15000);
+        assert_eq!(raw_to_rh_scaled(0, 2), 0);
+        assert_eq!(raw_to_rh_scaled((1 << 20) - 1, 2), 10000);
+
+        //More decimals than the resolution are capped, so are readings
+        //wider than 20 bits.
+        assert_eq!(raw_to_rh_scaled(517398, 6), 4934);
+        assert_eq!(raw_to_celsius_scaled(382112, u8::MAX), 2288);
+        assert_eq!(raw_to_rh_scaled(u32::MAX, u8::MAX), 10000);
+        assert_eq!(raw_to_celsius_scaled(u32::MAX, 2), 15000);
+    }
+
+    //What an AVR (Arduino Uno class) build should use, the scaled integer
+    //path fits the 16 bit types and stays within rounding of the formula.
+    #[test]
+    fn avr_integer_only()
+    {
+        for raw in (0..1u32 << 20).step_by(61).chain([(1 << 20) - 1]) {
+            let t = raw_to_celsius_scaled(raw, 2);
+            let h = raw_to_rh_scaled(raw, 2);
+            assert!(i16::try_from(t).is_ok());
+            assert!(u16::try_from(h).is_ok());
+
+            let exact_t = (raw as f64) * 20000.0 / 1048576.0 - 5000.0;
+            let exact_h = (raw as f64) * 10000.0 / 1048576.0;
+            assert!((t as f64 - exact_t).abs() <= 0.5);
+            assert!((h as f64 - exact_h).abs() <= 0.5);
+        }
+    }
+
+    #[test]
+    fn float_path_matches_vectors()
+    {
+        assert!(!vectors_hold(&[ConversionVector { raw: 0, celsius_centi: 0, rh_centi: 0 }]));
+
+        for v in CONVERSION_VECTORS {
+            assert!((raw_to_celsius(v.raw) - v.celsius_centi as f32 / 100.0).abs() < 0.005);
+            assert!((raw_to_rh(v.raw) - v.rh_centi as f32 / 100.0).abs() < 0.005);
+            let t: f64 = raw_to_celsius_as(v.raw);
+            assert!((t - v.celsius_centi as f64 / 100.0).abs() < 0.005);
+        }
+    }
+
+    #[test]
+    fn f64_backend()
+    {
+        let h: f64 = raw_to_rh_as(517398);
+        assert!(h < 49.35 && h > 49.34);
+
+        let t: f64 = raw_to_celsius_as(382112);
+        assert!(t < 22.89 && t > 22.87);
+        assert_eq!(raw_to_celsius_as::<f64>(1 << 20), 150.0);
+    }
+
+    #[cfg(feature = "fixed")]
+    #[test]
+    fn fixed_backend()
+    {
+        use fixed::types::I18F14;
+
+        let h: I18F14 = raw_to_rh_as(517398);
+        assert!(h < I18F14::from_num(49.35) && h > I18F14::from_num(49.34));
+
+        let t: I18F14 = raw_to_celsius_as(382112);
+        assert!(t < I18F14::from_num(22.89) && t > I18F14::from_num(22.87));
+
+        assert_eq!(raw_to_celsius_as::<I18F14>(0), I18F14::from_num(-50));
+        assert!(raw_to_rh_as::<I18F14>(MAX_RAW) > I18F14::from_num(99.99));
+
+        //Capped at `MAX_RAW` like the other paths, rather than overflowing.
+        assert_eq!(raw_to_rh_as::<I18F14>(u32::MAX), raw_to_rh_as::<I18F14>(MAX_RAW));
+        assert_eq!(raw_to_celsius_as::<I18F14>(u32::MAX), raw_to_celsius_as::<I18F14>(MAX_RAW));
+    }
+
+    //Every raw reading with the results of both conversion paths, the
+    //sweep tests below check the formulas over the full 20 bit range.
+    fn sweep() -> impl Iterator<Item = (u32, f32, f32, i32, u32)> {
+        (0..1u32 << 20).map(|raw| {
+            (raw, raw_to_celsius(raw), raw_to_rh(raw),
+             raw_to_celsius_scaled(raw, 2), raw_to_rh_scaled(raw, 2))
+        })
+    }
+
+    #[test]
+    fn sweep_monotonic()
+    {
+        let mut prev: Option<(u32, f32, f32, i32, u32)> = None;
+        for cur in sweep() {
+            if let Some(p) = prev {
+                assert!(cur.1 >= p.1 && cur.2 >= p.2, "float path at raw {}", cur.0);
+                assert!(cur.3 >= p.3 && cur.4 >= p.4, "integer path at raw {}", cur.0);
+                //One count is 0.019C or 0.0095%, never more than 2 hundredths.
+                assert!(cur.3 - p.3 <= 2 && cur.4 - p.4 <= 1, "step at raw {}", cur.0);
+            }
+            prev = Some(cur);
+        }
+    }
+
+    #[test]
+    fn sweep_bounds()
+    {
+        for (raw, t, h, t_centi, h_centi) in sweep() {
+            assert!((-50.0..150.0).contains(&t), "raw {} gave {}C", raw, t);
+            assert!((0.0..100.0).contains(&h), "raw {} gave {}%", raw, h);
+            assert!((-5000..=15000).contains(&t_centi), "raw {}", raw);
+            assert!(h_centi <= 10000, "raw {}", raw);
+        }
+    }
+
+    #[test]
+    fn sweep_paths_agree()
+    {
+        for (raw, t, h, t_centi, h_centi) in sweep() {
+            //Both paths round the same formula, they can only differ by the
+            //half hundredth of the rounding plus the f32 error.
+            assert!((t * 100.0 - t_centi as f32).abs() <= 0.51, "raw {}", raw);
+            assert!((h * 100.0 - h_centi as f32).abs() <= 0.51, "raw {}", raw);
+
+            //And the float path inverts back to the raw reading.
+            let t_raw = ((t + 50.0) / 200.0 * AHT20_DIVISOR).round() as u32;
+            let h_raw = (h / 100.0 * AHT20_DIVISOR).round() as u32;
+            assert_eq!(t_raw, raw);
+            assert_eq!(h_raw, raw);
+        }
+    }
+}
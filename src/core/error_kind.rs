@@ -0,0 +1,68 @@
+/*
+ * Filename: error_kind.rs
+ * Description: What went wrong, without the bus error inside. Small and
+ * `Copy`, for the journal, the AT responses and telemetry channels that
+ * carry it as a stable number.
+ */
+
+///`Error` without the bus error inside, small enough to keep around.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ErrorKind {
+    I2C,
+    InvalidChecksum,
+    UnexpectedBusy,
+    Internal,
+    DeviceTimeOut,
+    TooSoon,
+    UnexpectedStatus,
+    BudgetExceeded,
+    Faulted,
+    DuplicateFrame,
+    VerificationFailed,
+    GeneralCallDisabled,
+}
+
+//The numeric codes below are part of the API: telemetry decoders out in
+//the field depend on them, so they are never reused or renumbered. New
+//variants get the next free code.
+
+impl ErrorKind {
+    ///A stable number for the kind, for Modbus registers, CAN frames and
+    ///other channels without room for text. 0 is never used.
+    pub const fn code(self) -> u8 {
+        match self {
+            ErrorKind::I2C => 1,
+            ErrorKind::InvalidChecksum => 2,
+            ErrorKind::UnexpectedBusy => 3,
+            ErrorKind::Internal => 4,
+            ErrorKind::DeviceTimeOut => 5,
+            ErrorKind::TooSoon => 6,
+            ErrorKind::UnexpectedStatus => 7,
+            ErrorKind::BudgetExceeded => 8,
+            ErrorKind::Faulted => 9,
+            ErrorKind::DuplicateFrame => 10,
+            ErrorKind::VerificationFailed => 11,
+            ErrorKind::GeneralCallDisabled => 12,
+        }
+    }
+
+    pub const fn from_code(code: u8) -> Option<Self> {
+        Some(match code {
+            1 => ErrorKind::I2C,
+            2 => ErrorKind::InvalidChecksum,
+            3 => ErrorKind::UnexpectedBusy,
+            4 => ErrorKind::Internal,
+            5 => ErrorKind::DeviceTimeOut,
+            6 => ErrorKind::TooSoon,
+            7 => ErrorKind::UnexpectedStatus,
+            8 => ErrorKind::BudgetExceeded,
+            9 => ErrorKind::Faulted,
+            10 => ErrorKind::DuplicateFrame,
+            11 => ErrorKind::VerificationFailed,
+            12 => ErrorKind::GeneralCallDisabled,
+            _ => return None,
+        })
+    }
+}
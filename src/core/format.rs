@@ -0,0 +1,220 @@
+/*
+ * Filename: format.rs
+ * Description: Renders scaled integer values as fixed point text("22.9C")
+ * into a caller supplied buffer. Only integer operations are used, pulling
+ * in core::fmt's float formatting costs several KB of flash on small MCUs.
+ */
+
+use crate::core::error_kind::ErrorKind;
+use crate::core::measurement::{Measurement, Quality};
+
+///Prefix of the AT style responses.
+pub const AT_PREFIX: &str = "+AHT20: ";
+
+///Errors from the fixed point formatter.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FormatError {
+    BufferTooSmall,
+    ///More than `MAX_FIXED_DECIMALS` decimals.
+    TooManyDecimals,
+}
+
+///The most decimals `format_fixed()` takes, an i32 has 10 digits.
+pub const MAX_FIXED_DECIMALS: u8 = 10;
+
+///Writes `value` scaled by 10^decimals as text followed by `unit`.
+///
+///e.g. `format_fixed(229, 1, "C", &mut buf)` gives `"22.9C"` and
+///`format_fixed(-5, 1, "C", &mut buf)` gives `"-0.5C"`. More than
+///`MAX_FIXED_DECIMALS` decimals is `FormatError::TooManyDecimals`.
+pub fn format_fixed<'b>(
+    value: i32,
+    decimals: u8,
+    unit: &str,
+    buf: &'b mut [u8],
+    ) -> Result<&'b str, FormatError>
+{
+    if decimals > MAX_FIXED_DECIMALS {
+        return Err(FormatError::TooManyDecimals);
+    }
+
+    //Digits are generated backwards into a scratch buffer, an i32 has at
+    //most 10 of them plus the leading zero of a value below one.
+    let mut digits = [0u8; MAX_FIXED_DECIMALS as usize + 1];
+    let mut count = 0;
+    let mut v = value.unsigned_abs();
+
+    while v > 0 || count <= decimals as usize {
+        digits[count] = b'0' + (v % 10) as u8;
+        v /= 10;
+        count += 1;
+    }
+
+    let sign = if value < 0 { 1 } else { 0 };
+    let point = if decimals > 0 { 1 } else { 0 };
+    let len = sign + count + point + unit.len();
+    if len > buf.len() {
+        return Err(FormatError::BufferTooSmall);
+    }
+
+    let mut i = 0;
+    if sign == 1 {
+        buf[i] = b'-';
+        i += 1;
+    }
+    for d in (0..count).rev() {
+        buf[i] = digits[d];
+        i += 1;
+        if point == 1 && d == decimals as usize {
+            buf[i] = b'.';
+            i += 1;
+        }
+    }
+    buf[i..len].copy_from_slice(unit.as_bytes());
+
+    Ok(core::str::from_utf8(&buf[..len]).unwrap_or_default())
+}
+
+//Tenths of a float value clamped to `min..=max`, rounded half away from
+//zero. NaN is 0.
+fn tenths(x: f32, min: f32, max: f32) -> i32 {
+    let x = x.clamp(min, max) * 10.0;
+    if x >= 0.0 {
+        (x + 0.5) as i32
+    } else {
+        (x - 0.5) as i32
+    }
+}
+
+//Copies `s` to `buf[at..]`, returns the index after it.
+fn put(s: &str, buf: &mut [u8], at: usize) -> Result<usize, FormatError> {
+    let end = at + s.len();
+    buf.get_mut(at..end)
+        .ok_or(FormatError::BufferTooSmall)?
+        .copy_from_slice(s.as_bytes());
+    Ok(end)
+}
+
+///Writes a measurement as a compact AT style response for modem firmware
+///tunneling readings through an AT pipe, e.g. `"+AHT20: 22.9,49.3,OK"`.
+///
+///The status is `OK`, `CRC` on a CRC miss, `BUSY` on a busy overrun or
+///`DUP` on a duplicate frame, the first that applies. The values are
+///clamped to what the sensor can report, -50..150C and 0..100% (NaN is
+///written as 0.0), so 32 bytes always fit.
+pub fn format_at_response<'b>(
+    m: &Measurement,
+    buf: &'b mut [u8],
+    ) -> Result<&'b str, FormatError>
+{
+    let status = if m.quality.contains(Quality::CRC_MISS) {
+        "CRC"
+    } else if m.quality.contains(Quality::BUSY_OVERRUN) {
+        "BUSY"
+    } else if m.quality.contains(Quality::DUPLICATE) {
+        "DUP"
+    } else {
+        "OK"
+    };
+
+    let mut len = put(AT_PREFIX, buf, 0)?;
+    len += format_fixed(tenths(m.temperature.0, -50.0, 150.0), 1, ",", &mut buf[len..])?.len();
+    len += format_fixed(tenths(m.humidity.0, 0.0, 100.0), 1, ",", &mut buf[len..])?.len();
+    len = put(status, buf, len)?;
+
+    Ok(core::str::from_utf8(&buf[..len]).unwrap_or_default())
+}
+
+///Writes the AT style response of a failed read, `"+AHT20: ERROR,<code>"`
+///with the `ErrorKind::code()`, e.g. `"+AHT20: ERROR,5"` on a time out.
+pub fn format_at_error(kind: ErrorKind, buf: &mut [u8]) -> Result<&str, FormatError> {
+    let mut len = put(AT_PREFIX, buf, 0)?;
+    len = put("ERROR,", buf, len)?;
+    len += format_fixed(kind.code() as i32, 0, "", &mut buf[len..])?.len();
+
+    Ok(core::str::from_utf8(&buf[..len]).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod format_tests {
+    use super::*;
+
+    #[test]
+    fn temperature_text()
+    {
+        let mut buf = [0u8; 8];
+        assert_eq!(format_fixed(229, 1, "C", &mut buf), Ok("22.9C"));
+        assert_eq!(format_fixed(-5, 1, "C", &mut buf), Ok("-0.5C"));
+        assert_eq!(format_fixed(-500, 1, "C", &mut buf), Ok("-50.0C"));
+        assert_eq!(format_fixed(2288, 2, "C", &mut buf), Ok("22.88C"));
+    }
+
+    #[test]
+    fn humidity_text()
+    {
+        let mut buf = [0u8; 8];
+        assert_eq!(format_fixed(493, 1, "%", &mut buf), Ok("49.3%"));
+        assert_eq!(format_fixed(0, 1, "%", &mut buf), Ok("0.0%"));
+        assert_eq!(format_fixed(49, 0, "%", &mut buf), Ok("49%"));
+        assert_eq!(format_fixed(7, 2, "", &mut buf), Ok("0.07"));
+    }
+
+    #[test]
+    fn buffer_too_small()
+    {
+        let mut buf = [0u8; 4];
+        assert_eq!(
+            format_fixed(229, 1, "C", &mut buf),
+            Err(FormatError::BufferTooSmall)
+            );
+        assert_eq!(format_fixed(22, 0, "C", &mut buf), Ok("22C"));
+    }
+
+    #[test]
+    fn extremes()
+    {
+        let mut buf = [0u8; 16];
+        assert_eq!(format_fixed(i32::MIN, 0, "", &mut buf), Ok("-2147483648"));
+        assert_eq!(format_fixed(i32::MAX, 2, "", &mut buf), Ok("21474836.47"));
+        assert_eq!(format_fixed(i32::MIN, 10, "", &mut buf), Ok("-0.2147483648"));
+        assert_eq!(format_fixed(1, 10, "", &mut buf), Ok("0.0000000001"));
+        assert_eq!(format_fixed(1, 11, "", &mut buf), Err(FormatError::TooManyDecimals));
+        assert_eq!(format_fixed(1, u8::MAX, "", &mut buf), Err(FormatError::TooManyDecimals));
+    }
+
+    #[test]
+    fn at_responses()
+    {
+        use crate::core::data::SensorData;
+        use crate::core::measurement::SensorId;
+        use crate::core::units::{Celsius, RelativeHumidity};
+
+        let mut sd = SensorData::new();
+        sd.bytes = [0x18, 0x7E, 0x51, 0x65, 0xD4, 0xA0, 0xDA];
+        let mut m = Measurement::from_data(SensorId(0), &sd);
+
+        let mut buf = [0u8; 32];
+        assert_eq!(format_at_response(&m, &mut buf), Ok("+AHT20: 22.9,49.3,OK"));
+
+        m.temperature = Celsius(-40.04);
+        m.quality = Quality::CRC_MISS | Quality::BUSY_OVERRUN;
+        assert_eq!(format_at_response(&m, &mut buf), Ok("+AHT20: -40.0,49.3,CRC"));
+
+        m.quality = Quality::BUSY_OVERRUN;
+        assert_eq!(format_at_response(&m, &mut buf[..21]), Err(FormatError::BufferTooSmall));
+
+        //Out of range and non-finite values are clamped, the longest
+        //response still fits.
+        m.temperature = Celsius(f32::NEG_INFINITY);
+        m.humidity = RelativeHumidity(f32::INFINITY);
+        assert_eq!(format_at_response(&m, &mut buf), Ok("+AHT20: -50.0,100.0,BUSY"));
+        m.temperature = Celsius(f32::NAN);
+        m.humidity = RelativeHumidity(f32::NAN);
+        assert_eq!(format_at_response(&m, &mut buf), Ok("+AHT20: 0.0,0.0,BUSY"));
+        m.temperature = Celsius(f32::INFINITY);
+        assert_eq!(format_at_response(&m, &mut buf), Ok("+AHT20: 150.0,0.0,BUSY"));
+
+        assert_eq!(format_at_error(ErrorKind::DeviceTimeOut, &mut buf), Ok("+AHT20: ERROR,5"));
+        assert_eq!(format_at_error(ErrorKind::I2C, &mut buf[..10]), Err(FormatError::BufferTooSmall));
+    }
+}
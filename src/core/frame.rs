@@ -0,0 +1,124 @@
+/*
+ * Filename: frame.rs
+ * Description: The layout of the 7 byte measurement frame in one place.
+ * Everything is sent MSB first, the two 20bit readings share the middle
+ * byte:
+ *
+ *  byte:  0       1       2       3           4       5       6
+ *        status  RH19:12 RH11:4  RH3:0|T19:16 T15:8   T7:0    CRC
+ */
+
+use crate::core::data::crc8_maxim;
+use crate::core::sansio::FRAME_LEN;
+use crate::core::sensor_status::SensorStatus;
+
+///A measurement frame as it came off the bus, the CRC isn't checked until
+///asked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Frame(pub [u8; FRAME_LEN]);
+
+impl Frame {
+    pub const fn new(bytes: [u8; FRAME_LEN]) -> Self {
+        Frame(bytes)
+    }
+
+    pub const fn status(&self) -> SensorStatus {
+        SensorStatus::new(self.0[0])
+    }
+
+    ///The 20bit humidity reading, bytes 1, 2 and the high nibble of 3.
+    pub const fn humidity_raw(&self) -> u32 {
+        ((self.0[1] as u32) << 12) | ((self.0[2] as u32) << 4) | ((self.0[3] as u32) >> 4)
+    }
+
+    ///The 20bit temperature reading, the low nibble of byte 3, 4 and 5.
+    pub const fn temperature_raw(&self) -> u32 {
+        (((self.0[3] & 0x0F) as u32) << 16) | ((self.0[4] as u32) << 8) | (self.0[5] as u32)
+    }
+
+    ///The CRC byte as received.
+    pub const fn crc(&self) -> u8 {
+        self.0[FRAME_LEN - 1]
+    }
+
+    ///The CRC-8/MAXIM of the status and data bytes.
+    pub const fn expected_crc(&self) -> u8 {
+        crc8_maxim(&self.0, FRAME_LEN - 1)
+    }
+
+    pub const fn is_crc_good(&self) -> bool {
+        self.crc() == self.expected_crc()
+    }
+
+    pub const fn bytes(&self) -> [u8; FRAME_LEN] {
+        self.0
+    }
+
+    ///True if both frames carry the same raw humidity and temperature.
+    pub const fn same_readings(&self, other: &Frame) -> bool {
+        self.humidity_raw() == other.humidity_raw()
+            && self.temperature_raw() == other.temperature_raw()
+    }
+}
+
+impl From<[u8; FRAME_LEN]> for Frame {
+    fn from(bytes: [u8; FRAME_LEN]) -> Self {
+        Frame(bytes)
+    }
+}
+
+#[cfg(test)]
+mod frame_tests {
+    use super::*;
+    use crate::core::convert::{raw_to_celsius_scaled, raw_to_rh_scaled};
+
+    //Frames saved over a logic analyzer, with the values worked out by hand
+    //from the data sheet's bit layout.
+    const CAPTURES: [([u8; FRAME_LEN], u32, u32, i32, u32); 2] = [
+        ([0x18, 0x7E, 0x51, 0x65, 0xD4, 0xA0, 0xDA], 0x7E516, 0x5D4A0, 2288, 4934),
+        ([0x18, 0x80, 0x2F, 0x25, 0xD2, 0x6A, 0x13], 0x802F2, 0x5D26A, 2277, 5007),
+    ];
+
+    #[test]
+    fn captures()
+    {
+        for (bytes, rh, t, celsius_centi, rh_centi) in CAPTURES {
+            let f = Frame::new(bytes);
+            assert_eq!(f.status().status, 0x18);
+            assert_eq!(f.humidity_raw(), rh);
+            assert_eq!(f.temperature_raw(), t);
+            assert_eq!(f.crc(), bytes[6]);
+            assert!(f.is_crc_good());
+            assert_eq!(raw_to_celsius_scaled(f.temperature_raw(), 2), celsius_centi);
+            assert_eq!(raw_to_rh_scaled(f.humidity_raw(), 2), rh_centi);
+        }
+    }
+
+    #[test]
+    fn bit_order()
+    {
+        //One bit at a time, so a swapped nibble or byte shows up.
+        let f = Frame::new([0, 0x80, 0, 0, 0, 0, 0]);
+        assert_eq!((f.humidity_raw(), f.temperature_raw()), (1 << 19, 0));
+        let f = Frame::new([0, 0, 0, 0x10, 0, 0, 0]);
+        assert_eq!((f.humidity_raw(), f.temperature_raw()), (1, 0));
+        let f = Frame::new([0, 0, 0, 0x08, 0, 0, 0]);
+        assert_eq!((f.humidity_raw(), f.temperature_raw()), (0, 1 << 19));
+        let f = Frame::new([0, 0, 0, 0, 0, 0x01, 0]);
+        assert_eq!((f.humidity_raw(), f.temperature_raw()), (0, 1));
+        let f = Frame::new([0xFF; FRAME_LEN]);
+        assert_eq!((f.humidity_raw(), f.temperature_raw()), (0xFFFFF, 0xFFFFF));
+    }
+
+    #[test]
+    fn bad_crc()
+    {
+        let mut bytes = CAPTURES[0].0;
+        bytes[2] ^= 0x01;
+        let f = Frame::from(bytes);
+        assert!(!f.is_crc_good());
+        assert_eq!(f.crc(), 0xDA);
+    }
+}
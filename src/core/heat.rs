@@ -0,0 +1,101 @@
+/*
+ * Filename: heat.rs
+ * Description: How hot warm humid air feels, for comfort monitoring. The
+ * heat index is the US National Weather Service's: Steadman's simple
+ * formula below about 80F, the Rothfusz regression above it with the NWS
+ * adjustments for very dry and very humid air. The humidex is Environment
+ * Canada's, from the dew point.
+ *
+ * Both are meant for warm conditions, below about 20C they just track the
+ * temperature and mean little.
+ */
+
+use crate::core::math;
+use crate::core::measurement::Measurement;
+use crate::core::psychro::dew_point;
+use crate::core::units::{Celsius, RelativeHumidity};
+
+//Steadman's simple formula is used while its average with the temperature
+//stays below this, in F.
+const ROTHFUSZ_FROM_F: f32 = 80.0;
+
+fn to_fahrenheit(t: Celsius) -> f32 {
+    t.0 * 1.8 + 32.0
+}
+
+fn rothfusz(t: f32, rh: f32) -> f32 {
+    let hi = -42.379 + 2.049_015_2 * t + 10.143_331 * rh
+        - 0.224_755_4 * t * rh
+        - 6.837_83e-3 * t * t
+        - 5.481_717e-2 * rh * rh
+        + 1.228_74e-3 * t * t * rh
+        + 8.5282e-4 * t * rh * rh
+        - 1.99e-6 * t * t * rh * rh;
+    if rh < 13.0 && (80.0..=112.0).contains(&t) {
+        let dry = (17.0 - (t - 95.0).abs()) / 17.0;
+        return hi - (13.0 - rh) / 4.0 * math::sqrt(dry);
+    }
+    if rh > 85.0 && (80.0..=87.0).contains(&t) {
+        return hi + (rh - 85.0) / 10.0 * (87.0 - t) / 5.0;
+    }
+    hi
+}
+
+///NWS heat index, the apparent temperature in the shade.
+pub fn heat_index(t: Celsius, rh: RelativeHumidity) -> Celsius {
+    let t_f = to_fahrenheit(t);
+    let simple = 0.5 * (t_f + 61.0 + (t_f - 68.0) * 1.2 + rh.0 * 0.094);
+    let hi = match (simple + t_f) / 2.0 < ROTHFUSZ_FROM_F {
+        true => simple,
+        false => rothfusz(t_f, rh.0),
+    };
+    Celsius((hi - 32.0) / 1.8)
+}
+
+///Humidex, a dimensionless number on the Celsius scale. Environment
+///Canada calls 30-39 some discomfort, 40-45 great discomfort and above 45
+///dangerous.
+pub fn humidex(t: Celsius, rh: RelativeHumidity) -> f32 {
+    let td = dew_point(t, rh).0 + 273.15;
+    let e = 6.11 * math::exp(5417.753 * (1.0 / 273.16 - 1.0 / td));
+    t.0 + 0.5555 * (e - 10.0)
+}
+
+impl Measurement {
+    pub fn heat_index(&self) -> Celsius {
+        heat_index(self.temperature, self.humidity)
+    }
+
+    pub fn humidex(&self) -> f32 {
+        humidex(self.temperature, self.humidity)
+    }
+}
+
+#[cfg(test)]
+mod heat_tests {
+    use super::*;
+
+    fn close(a: f32, b: f32, tolerance: f32) -> bool {
+        (a - b).abs() < tolerance
+    }
+
+    #[test]
+    fn heat_index_reference_values()
+    {
+        //The NWS table: 90F/70% feels like 106F.
+        assert!(close(heat_index(Celsius(32.22), RelativeHumidity(70.0)).0, 41.07, 0.1));
+        //Below 80F the simple formula.
+        assert!(close(heat_index(Celsius(20.0), RelativeHumidity(50.0)).0, 19.36, 0.05));
+        //Both adjustments.
+        assert!(close(heat_index(Celsius(40.0), RelativeHumidity(10.0)).0, 36.71, 0.1));
+        assert!(close(heat_index(Celsius(28.0), RelativeHumidity(90.0)).0, 34.0, 0.1));
+    }
+
+    #[test]
+    fn humidex_reference_values()
+    {
+        //Environment Canada's table: 30C with a 15C dew point is 34.
+        assert!(close(humidex(Celsius(30.0), RelativeHumidity(40.0)), 33.92, 0.2));
+        assert!(close(humidex(Celsius(35.0), RelativeHumidity(60.0)), 48.54, 0.3));
+    }
+}
@@ -0,0 +1,107 @@
+//!`ln`/`exp` (and `sqrt` for the heat index) for the derived metrics.
+//!
+//!`core` doesn't provide either of them, so one of the math backend
+//!features has to be enabled to get the derived metrics:
+//!
+//! * `libm`: a port of musl's libm, accurate to about 3e-7 absolute for
+//!   `ln` and 7e-8 relative for `exp`. The bigger option code size wise.
+//! * `micromath`: fast approximations, about 6e-5 absolute for `ln` and
+//!   2.4e-3 relative for `exp`. Its `ln` is off by up to 0.12 below 1.0,
+//!   so those inputs are reflected as `-ln(1/x)` here. Its `sqrt` is off
+//!   by up to 6.1% relative, that only moves the heat index's dry air
+//!   adjustment by about 0.1C.
+//!
+//!If both are enabled `libm` is used. `derived` needs one of them too, it
+//!doesn't pick one.
+//!
+
+#[cfg(feature = "libm")]
+pub fn ln(x: f32) -> f32 {
+    libm::logf(x)
+}
+
+#[cfg(feature = "libm")]
+pub fn exp(x: f32) -> f32 {
+    libm::expf(x)
+}
+
+#[cfg(all(feature = "derived", feature = "libm"))]
+pub fn sqrt(x: f32) -> f32 {
+    libm::sqrtf(x)
+}
+
+#[cfg(all(feature = "micromath", not(feature = "libm")))]
+pub fn ln(x: f32) -> f32 {
+    use micromath::F32Ext;
+
+    if x < 1.0 {
+        return -F32Ext::ln(1.0 / x);
+    }
+    F32Ext::ln(x)
+}
+
+#[cfg(all(feature = "micromath", not(feature = "libm")))]
+pub fn exp(x: f32) -> f32 {
+    micromath::F32Ext::exp(x)
+}
+
+#[cfg(all(feature = "derived", feature = "micromath", not(feature = "libm")))]
+pub fn sqrt(x: f32) -> f32 {
+    micromath::F32Ext::sqrt(x)
+}
+
+#[cfg(test)]
+mod math_tests {
+    use super::*;
+
+    #[cfg(feature = "libm")]
+    const LN_TOLERANCE: f64 = 1e-6;
+    #[cfg(feature = "libm")]
+    const EXP_TOLERANCE: f64 = 1e-6;
+    #[cfg(all(feature = "derived", feature = "libm"))]
+    const SQRT_TOLERANCE: f64 = 1e-6;
+
+    #[cfg(all(feature = "micromath", not(feature = "libm")))]
+    const LN_TOLERANCE: f64 = 1e-4;
+    #[cfg(all(feature = "micromath", not(feature = "libm")))]
+    const EXP_TOLERANCE: f64 = 3e-3;
+    #[cfg(all(feature = "derived", feature = "micromath", not(feature = "libm")))]
+    const SQRT_TOLERANCE: f64 = 0.065;
+
+    //Inputs in the range the derived metrics use: RH fractions and
+    //vapour pressures in hPa.
+    const LN_INPUTS: [f32; 8] = [0.01, 0.2, 0.4934, 0.75, 1.0, 6.112, 23.4, 101.325];
+    const EXP_INPUTS: [f32; 7] = [-4.0, -1.0, -0.25, 0.0, 0.5, 1.65, 3.9];
+
+    #[test]
+    fn ln_reference_values()
+    {
+        for x in LN_INPUTS {
+            let reference = (x as f64).ln();
+            let err = (ln(x) as f64 - reference).abs();
+            assert!(err < LN_TOLERANCE, "ln({}) off by {}", x, err);
+        }
+    }
+
+    #[cfg(feature = "derived")]
+    #[test]
+    fn sqrt_reference_values()
+    {
+        //The heat index takes it of 0..1.
+        for x in [0.05f32, 0.1, 0.25, 0.5, 0.75, 1.0] {
+            let reference = (x as f64).sqrt();
+            let err = ((sqrt(x) as f64 - reference) / reference).abs();
+            assert!(err < SQRT_TOLERANCE, "sqrt({}) off by {}", x, err);
+        }
+    }
+
+    #[test]
+    fn exp_reference_values()
+    {
+        for x in EXP_INPUTS {
+            let reference = (x as f64).exp();
+            let err = ((exp(x) as f64 - reference) / reference).abs();
+            assert!(err < EXP_TOLERANCE, "exp({}) off by {}", x, err);
+        }
+    }
+}
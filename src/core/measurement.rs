@@ -0,0 +1,134 @@
+/*
+ * Filename: measurement.rs
+ * Description: The higher level reading handed out by `measure()`, the
+ * converted values tagged with the id of the sensor they came from.
+ */
+
+use crate::core::data::SensorData;
+use crate::core::units::{Celsius, Hectopascal, RelativeHumidity};
+
+///User chosen id of a sensor instance, so loggers with several probes can
+///tell the channels apart. Defaults to 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SensorId(pub u16);
+
+///Quality flags of a measurement, a set of bits where no bits is good.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Quality(pub u8);
+
+impl Quality {
+    pub const GOOD: Quality = Quality(0);
+    ///The CRC of the frame didn't match.
+    pub const CRC_MISS: Quality = Quality(1 << 0);
+    ///The sensor was still busy once the busy polling ran out.
+    pub const BUSY_OVERRUN: Quality = Quality(1 << 1);
+    ///The frame repeats the last one, no conversion ran in between. See
+    ///`DuplicatePolicy`.
+    pub const DUPLICATE: Quality = Quality(1 << 2);
+    ///Taken during a recovery, see `Sensor::begin_recovery()`.
+    pub const RECOVERING: Quality = Quality(1 << 3);
+
+    pub fn is_good(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn contains(self, flags: Quality) -> bool {
+        (self.0 & flags.0) == flags.0
+    }
+
+    pub fn insert(&mut self, flags: Quality) {
+        self.0 |= flags.0;
+    }
+}
+
+impl core::ops::BitOr for Quality {
+    type Output = Quality;
+
+    fn bitor(self, rhs: Quality) -> Quality {
+        Quality(self.0 | rhs.0)
+    }
+}
+
+///A converted reading from a single sensor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Measurement {
+    pub id: SensorId,
+    pub temperature: Celsius,
+    pub humidity: RelativeHumidity,
+    pub quality: Quality,
+    ///Air pressure fed in with `Sensor::set_pressure()`, if any.
+    pub pressure: Option<Hectopascal>,
+    ///Sequence number, see `Sensor::enable_sequence_numbers()`.
+    pub seq: Option<u32>,
+}
+
+impl Measurement {
+    ///Converts the raw data, the CRC isn't checked here.
+    pub fn from_data(id: SensorId, data: &SensorData) -> Self {
+        Measurement {
+            id,
+            temperature: data.temperature(),
+            humidity: data.humidity(),
+            quality: Quality::GOOD,
+            pressure: None,
+            seq: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod measurement_tests {
+    use super::*;
+
+    #[test]
+    fn from_data()
+    {
+        let mut sd = SensorData::new();
+        sd.bytes = [0x18, 0x7E, 0x51, 0x65, 0xD4, 0xA0, 0xDA];
+
+        let m = Measurement::from_data(SensorId(3), &sd);
+        assert_eq!(m.id, SensorId(3));
+        assert_eq!(m.temperature, sd.temperature());
+        assert_eq!(m.humidity, sd.humidity());
+        assert!(m.quality.is_good());
+    }
+
+    #[test]
+    fn quality_flags()
+    {
+        let mut q = Quality::GOOD;
+        assert!(q.is_good());
+
+        q.insert(Quality::CRC_MISS);
+        assert!(!q.is_good());
+        assert!(q.contains(Quality::CRC_MISS));
+        assert!(!q.contains(Quality::BUSY_OVERRUN));
+
+        let both = Quality::CRC_MISS | Quality::BUSY_OVERRUN;
+        assert!(both.contains(q));
+        assert!(both.contains(Quality::BUSY_OVERRUN));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn postcard_round_trip()
+    {
+        let mut sd = SensorData::new();
+        sd.bytes = [0x18, 0x7E, 0x51, 0x65, 0xD4, 0xA0, 0xDA];
+        let mut m = Measurement::from_data(SensorId(3), &sd);
+        m.quality = Quality::CRC_MISS;
+        m.seq = Some(7);
+
+        let mut buf = [0u8; 64];
+        let used = postcard::to_slice(&m, &mut buf).unwrap();
+        assert_eq!(postcard::from_bytes::<Measurement>(used).unwrap(), m);
+        let used = postcard::to_slice(&sd, &mut buf).unwrap();
+        assert_eq!(postcard::from_bytes::<SensorData>(used).unwrap(), sd);
+    }
+}
@@ -0,0 +1,34 @@
+/*
+ * Filename: mod.rs
+ * Description: The HAL independent part of the driver: command encoding,
+ * status and frame parsing, CRC, the sans-IO protocol machines, the
+ * conversions and derived metrics. Nothing in here depends on
+ * embedded-hal or the rest of the crate, the blocking driver and the
+ * adapters are built on top and re-export what they need.
+ */
+
+pub(crate) mod commands;
+pub(crate) mod comfort;
+pub(crate) mod convert;
+pub(crate) mod data;
+pub(crate) mod error_kind;
+pub(crate) mod format;
+pub(crate) mod frame;
+#[cfg(feature = "derived")]
+pub(crate) mod heat;
+#[cfg(all(feature = "derived", not(any(feature = "libm", feature = "micromath"))))]
+compile_error!("`derived` needs a math backend, enable `libm` or `micromath`");
+#[cfg(any(feature = "libm", feature = "micromath"))]
+pub(crate) mod math;
+pub(crate) mod measurement;
+pub(crate) mod params;
+#[cfg(any(feature = "libm", feature = "micromath"))]
+pub(crate) mod psychro;
+pub(crate) mod quirks;
+pub(crate) mod retry;
+pub(crate) mod sansio;
+pub(crate) mod sensor_status;
+pub(crate) mod specs;
+pub(crate) mod timings;
+pub(crate) mod units;
+pub(crate) mod variant;
@@ -0,0 +1,33 @@
+/*
+ * Filename: params.rs
+ * Description: The parameter bytes sent after the calibrate and trigger
+ * commands. The sans-IO machines take them, `CommandParams::DATASHEET` is
+ * what the driver sends. The data sheet revisions disagree on them, so with
+ * the `experimental-params` feature the driver's can be swapped out to A/B
+ * test sequences against real hardware.
+ */
+
+use crate::core::commands::{CAL_PARAM0, CAL_PARAM1, TRIG_MEASURE_PARAM0, TRIG_MEASURE_PARAM1};
+
+///The two bytes following the calibrate and the trigger measure commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CommandParams {
+    pub calibrate: [u8; 2],
+    pub trigger: [u8; 2],
+}
+
+impl CommandParams {
+    ///The bytes from the v1.1 data sheet, `CAL_PARAM0/1` and
+    ///`TRIG_MEASURE_PARAM0/1`.
+    pub const DATASHEET: CommandParams = CommandParams {
+        calibrate: [CAL_PARAM0, CAL_PARAM1],
+        trigger: [TRIG_MEASURE_PARAM0, TRIG_MEASURE_PARAM1],
+    };
+}
+
+impl Default for CommandParams {
+    fn default() -> Self {
+        CommandParams::DATASHEET
+    }
+}
@@ -0,0 +1,299 @@
+/*
+ * Filename: psychro.rs
+ * Description: Derived values of moist air from a temperature/humidity
+ * reading, with the air pressure where it matters (mixing ratio and
+ * enthalpy). The saturation vapour pressure is the Magnus formula over
+ * water with the Sonntag (1990) constants, good to about 0.1% from -45C to
+ * 60C. The WMO enhancement factor for moist air is applied to the vapour
+ * pressure when the pressure is known.
+ *
+ * The dew point doesn't depend on the pressure: the enhancement factor is
+ * the same on both sides of the equation and cancels, so it's the same at
+ * any altitude for a given temperature/RH.
+ *
+ * The wet bulb functions use the psychrometer equation with the WMO
+ * coefficient for aspirated (Assmann or sling) psychrometers, for checking
+ * the sensor against one during commissioning.
+ */
+
+use crate::core::math;
+use crate::core::measurement::Measurement;
+use crate::core::units::{Celsius, Hectopascal, RelativeHumidity, Units};
+
+///Standard atmosphere at sea level, used when no pressure is fed in.
+pub const STANDARD_PRESSURE: Hectopascal = Hectopascal(1013.25);
+
+const MAGNUS_A: f32 = 6.112;
+const MAGNUS_B: f32 = 17.62;
+const MAGNUS_C: f32 = 243.12;
+///Psychrometer coefficient for aspirated psychrometers, 1/K.
+const PSYCHROMETER_A: f32 = 6.53e-4;
+///Bisection steps for the wet bulb, halves a 100K bracket to below 0.001K.
+const WET_BULB_STEPS: u8 = 20;
+///Ratio of the molar masses of water and dry air, times 1000 for g/kg.
+const EPSILON_G_KG: f32 = 621.98;
+///Specific gas constant of water vapour, J/(kg K).
+const R_VAPOUR: f32 = 461.5;
+
+//WMO enhancement factor, how much more vapour moist air holds than pure
+//vapour at the same temperature.
+fn enhancement_factor(p: Hectopascal) -> f32 {
+    1.0016 + 3.15e-6 * p.0 - 0.074 / p.0
+}
+
+///Saturation vapour pressure over water.
+pub fn saturation_vapour_pressure(t: Celsius) -> Hectopascal {
+    Hectopascal(MAGNUS_A * math::exp(MAGNUS_B * t.0 / (MAGNUS_C + t.0)))
+}
+
+///Partial pressure of the water vapour.
+pub fn vapour_pressure(t: Celsius, rh: RelativeHumidity) -> Hectopascal {
+    Hectopascal(saturation_vapour_pressure(t).0 * rh.0 / 100.0)
+}
+
+///Dew point, the inverse of the Magnus formula.
+pub fn dew_point(t: Celsius, rh: RelativeHumidity) -> Celsius {
+    let gamma = math::ln(rh.0 / 100.0) + MAGNUS_B * t.0 / (MAGNUS_C + t.0);
+    Celsius(MAGNUS_C * gamma / (MAGNUS_B - gamma))
+}
+
+///Absolute humidity in g/m³.
+pub fn absolute_humidity(t: Celsius, rh: RelativeHumidity) -> f32 {
+    //hPa to Pa and kg to g cancel out.
+    vapour_pressure(t, rh).0 * 100_000.0 / (R_VAPOUR * (t.0 + 273.15))
+}
+
+///Mixing ratio in g of water per kg of dry air.
+pub fn mixing_ratio(t: Celsius, rh: RelativeHumidity, p: Hectopascal) -> f32 {
+    let e = enhancement_factor(p) * vapour_pressure(t, rh).0;
+    EPSILON_G_KG * e / (p.0 - e)
+}
+
+///Specific enthalpy in kJ per kg of dry air, 0 for dry air at 0C.
+pub fn enthalpy(t: Celsius, rh: RelativeHumidity, p: Hectopascal) -> f32 {
+    let w = mixing_ratio(t, rh, p) / 1000.0;
+    1.006 * t.0 + w * (2501.0 + 1.86 * t.0)
+}
+
+///RH of air at `t` with the dew point `dew_point`.
+pub fn rh_from_dew_point(t: Celsius, dew_point: Celsius) -> RelativeHumidity {
+    let ratio = saturation_vapour_pressure(dew_point).0 / saturation_vapour_pressure(t).0;
+    RelativeHumidity(100.0 * ratio)
+}
+
+///RH the same air has at `target`, e.g. the room RH from a sensor in a
+///warm enclosure. The vapour pressure stays the same, only the saturation
+///pressure changes. Over 100% means the air would condense at `target`.
+pub fn humidity_at_temperature(
+    t: Celsius,
+    rh: RelativeHumidity,
+    target: Celsius,
+    ) -> RelativeHumidity
+{
+    RelativeHumidity(vapour_pressure(t, rh).0 * 100.0 / saturation_vapour_pressure(target).0)
+}
+
+//Vapour pressure the psychrometer equation gives for a dry/wet bulb pair.
+fn psychrometer_vapour_pressure(t: Celsius, wet_bulb: Celsius, p: Hectopascal) -> f32 {
+    let a = PSYCHROMETER_A * (1.0 + 0.000944 * wet_bulb.0);
+    saturation_vapour_pressure(wet_bulb).0 - a * p.0 * (t.0 - wet_bulb.0)
+}
+
+///RH from a dry bulb/wet bulb pair, e.g. read off a sling psychrometer.
+pub fn rh_from_wet_bulb(t: Celsius, wet_bulb: Celsius, p: Hectopascal) -> RelativeHumidity {
+    let e = psychrometer_vapour_pressure(t, wet_bulb, p);
+    RelativeHumidity(100.0 * e / saturation_vapour_pressure(t).0)
+}
+
+///Wet bulb temperature, what a sling psychrometer should show for this
+///temperature/RH. Solved by bisection between the dew point and `t`.
+pub fn wet_bulb(t: Celsius, rh: RelativeHumidity, p: Hectopascal) -> Celsius {
+    let e = vapour_pressure(t, rh).0;
+    let (mut low, mut high) = (dew_point(t, rh).0, t.0);
+    for _ in 0..WET_BULB_STEPS {
+        let mid = (low + high) / 2.0;
+        //The psychrometer vapour pressure rises with the wet bulb.
+        if psychrometer_vapour_pressure(t, Celsius(mid), p) < e {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    Celsius((low + high) / 2.0)
+}
+
+///Every derived value of a measurement in one unit system.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Derived {
+    pub units: Units,
+    pub temperature: f32,
+    pub dew_point: f32,
+    pub wet_bulb: f32,
+    ///g/m³ or gr/ft³.
+    pub absolute_humidity: f32,
+    ///g/kg or gr/lb.
+    pub mixing_ratio: f32,
+    ///kJ/kg from dry air at 0°C, or BTU/lb from dry air at 0°F as in the
+    ///ASHRAE tables, so the two don't convert with a plain factor.
+    pub enthalpy: f32,
+}
+
+//ASHRAE's inch-pound moist air enthalpy, `w` in lb/lb.
+fn enthalpy_imperial(t_f: f32, w: f32) -> f32 {
+    0.240 * t_f + w * (1061.0 + 0.444 * t_f)
+}
+
+impl Measurement {
+    ///All the derived values in `units`, at the fed in pressure or the
+    ///standard atmosphere.
+    pub fn derived(&self, units: Units) -> Derived {
+        let mixing_ratio = self.mixing_ratio();
+        let temperature = units.temperature(self.temperature);
+        let enthalpy = match units {
+            Units::Si => self.enthalpy(),
+            Units::Imperial => enthalpy_imperial(temperature, mixing_ratio / 1000.0),
+        };
+        Derived {
+            units,
+            temperature,
+            dew_point: units.temperature(self.dew_point()),
+            wet_bulb: units.temperature(self.wet_bulb()),
+            absolute_humidity: units.density(self.absolute_humidity()),
+            mixing_ratio: units.ratio(mixing_ratio),
+            enthalpy,
+        }
+    }
+
+    pub fn dew_point(&self) -> Celsius {
+        dew_point(self.temperature, self.humidity)
+    }
+
+    ///In g/m³.
+    pub fn absolute_humidity(&self) -> f32 {
+        absolute_humidity(self.temperature, self.humidity)
+    }
+
+    ///In g/kg, at the fed in pressure or the standard atmosphere.
+    pub fn mixing_ratio(&self) -> f32 {
+        mixing_ratio(self.temperature, self.humidity, self.pressure.unwrap_or(STANDARD_PRESSURE))
+    }
+
+    ///In kJ/kg, at the fed in pressure or the standard atmosphere.
+    pub fn enthalpy(&self) -> f32 {
+        enthalpy(self.temperature, self.humidity, self.pressure.unwrap_or(STANDARD_PRESSURE))
+    }
+
+    ///See the free function `humidity_at_temperature()`.
+    pub fn humidity_at_temperature(&self, target_c: Celsius) -> RelativeHumidity {
+        humidity_at_temperature(self.temperature, self.humidity, target_c)
+    }
+
+    ///At the fed in pressure or the standard atmosphere.
+    pub fn wet_bulb(&self) -> Celsius {
+        wet_bulb(self.temperature, self.humidity, self.pressure.unwrap_or(STANDARD_PRESSURE))
+    }
+}
+
+#[cfg(test)]
+mod psychro_tests {
+    use super::*;
+    use crate::core::measurement::{Quality, SensorId};
+
+    //Loose enough for the micromath backend.
+    fn close(a: f32, b: f32, tolerance: f32) -> bool {
+        (a - b).abs() < tolerance
+    }
+
+    #[test]
+    fn reference_values()
+    {
+        //20C/50%: 23.37hPa saturated, 9.26C dew point, 8.64g/m3.
+        let (t, rh) = (Celsius(20.0), RelativeHumidity(50.0));
+        assert!(close(saturation_vapour_pressure(t).0, 23.37, 0.1));
+        assert!(close(dew_point(t, rh).0, 9.26, 0.1));
+        assert!(close(absolute_humidity(t, rh), 8.64, 0.05));
+        assert!(close(mixing_ratio(t, rh, STANDARD_PRESSURE), 7.29, 0.05));
+        assert!(close(enthalpy(t, rh, STANDARD_PRESSURE), 38.6, 0.2));
+
+        //Saturated air is at its dew point.
+        assert!(close(dew_point(Celsius(15.0), RelativeHumidity(100.0)).0, 15.0, 0.05));
+    }
+
+    #[test]
+    fn psychrometer_pairs()
+    {
+        //20C/50% at sea level has a wet bulb of about 13.7C.
+        let (t, rh) = (Celsius(20.0), RelativeHumidity(50.0));
+        let twb = wet_bulb(t, rh, STANDARD_PRESSURE);
+        assert!(close(twb.0, 13.7, 0.2));
+        assert!(close(rh_from_wet_bulb(t, twb, STANDARD_PRESSURE).0, 50.0, 0.1));
+
+        let td = dew_point(t, rh);
+        assert!(close(rh_from_dew_point(t, td).0, 50.0, 0.1));
+
+        //No depression means saturated air.
+        assert!(close(rh_from_wet_bulb(t, t, STANDARD_PRESSURE).0, 100.0, 0.01));
+        assert!(close(wet_bulb(t, RelativeHumidity(100.0), STANDARD_PRESSURE).0, 20.0, 0.05));
+    }
+
+    #[test]
+    fn re_referenced_humidity()
+    {
+        //30C/30% in an enclosure is about 51% in a 21C room.
+        let rh = humidity_at_temperature(Celsius(30.0), RelativeHumidity(30.0), Celsius(21.0));
+        assert!(close(rh.0, 51.2, 0.3));
+
+        let t = Celsius(25.0);
+        let same = humidity_at_temperature(t, RelativeHumidity(40.0), t);
+        assert!(close(same.0, 40.0, 0.001));
+
+        //Cooling below the dew point condenses.
+        let td = dew_point(t, RelativeHumidity(40.0));
+        assert!(humidity_at_temperature(t, RelativeHumidity(40.0), td - Celsius(1.0)).0 > 100.0);
+    }
+
+    #[test]
+    fn derived_units()
+    {
+        let m = Measurement {
+            id: SensorId(0),
+            temperature: Celsius(20.0),
+            humidity: RelativeHumidity(50.0),
+            quality: Quality::GOOD,
+            pressure: None,
+            seq: None,
+        };
+        let si = m.derived(Units::Si);
+        assert_eq!(si.temperature, 20.0);
+        assert_eq!(si.enthalpy, m.enthalpy());
+
+        //68F/50%: 48.7F dew point, 3.78gr/ft3, 51gr/lb, 24.3BTU/lb.
+        let ip = m.derived(Units::Imperial);
+        assert!(close(ip.temperature, 68.0, 0.001));
+        assert!(close(ip.dew_point, 48.7, 0.2));
+        assert!(close(ip.absolute_humidity, 3.78, 0.03));
+        assert!(close(ip.mixing_ratio, 51.0, 0.5));
+        assert!(close(ip.enthalpy, 24.3, 0.2));
+    }
+
+    #[test]
+    fn pressure_from_the_sensor()
+    {
+        let mut m = Measurement {
+            id: SensorId(0),
+            temperature: Celsius(20.0),
+            humidity: RelativeHumidity(50.0),
+            quality: Quality::GOOD,
+            pressure: None,
+            seq: None,
+        };
+        let sea_level = m.mixing_ratio();
+        let dew_point = m.dew_point();
+
+        //About 1500m up, the same RH is more water per kg of air.
+        m.pressure = Some(Hectopascal(850.0));
+        assert!(m.mixing_ratio() > sea_level * 1.15);
+        assert!(m.enthalpy() > enthalpy(m.temperature, m.humidity, STANDARD_PRESSURE));
+        assert_eq!(m.dew_point(), dew_point);
+    }
+}
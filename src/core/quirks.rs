@@ -0,0 +1,146 @@
+/*
+ * Filename: quirks.rs
+ * Description: Workarounds for bus controllers that don't get along with
+ * the sensor, selected with `Sensor::set_bus_quirks()`. The split reads
+ * only change the measurement cycle, the other commands are single bytes.
+ *
+ * The driver never uses a repeated start, every write and read is its own
+ * transfer ending in a STOP. Controllers that mishandle repeated starts
+ * (the Raspberry Pi's among them) need nothing extra for that.
+ */
+
+///How the measurement frame is read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BusQuirks {
+    ///Poll with single status byte reads and only read the whole frame
+    ///once the sensor is idle. Long reads of a busy sensor are where it
+    ///stretches the clock the most.
+    pub split_reads: bool,
+    ///Extra delay before every read, for controllers that time out when
+    ///the sensor is slow to answer straight after the previous transfer.
+    pub settle_ms: u16,
+    ///Retries of a write or read the bus reported as failed, `settle_ms`
+    ///apart, during init, calibration and measurements.
+    pub transfer_retries: u8,
+}
+
+impl BusQuirks {
+    ///No workarounds, the bus traffic of the data sheet.
+    pub const NONE: BusQuirks = BusQuirks { split_reads: false, settle_ms: 0, transfer_retries: 0 };
+
+    ///The ESP32-C3 I2C peripheral gives up on clock stretching after a short
+    ///timeout, seen as sporadic `I2C(Timeout)` errors. With esp-hal also
+    ///raise the peripheral's timeout (`Config::with_timeout()`) if your
+    ///version has it.
+    pub const ESP32_C3: BusQuirks = BusQuirks { split_reads: true, settle_ms: 1, transfer_retries: 0 };
+
+    ///The Raspberry Pi's (BCM283x) I2C controller corrupts or drops
+    ///transfers when a slave stretches the clock at the wrong moment. Keep
+    ///the stretching to short reads, give the sensor time between
+    ///transfers and retry the odd failed one. Lowering the bus clock
+    ///(`dtparam=i2c_arm_baudrate=50000`) helps as well.
+    pub const RASPBERRY_PI: BusQuirks = BusQuirks { split_reads: true, settle_ms: 5, transfer_retries: 2 };
+}
+
+impl Default for BusQuirks {
+    fn default() -> Self {
+        BusQuirks::NONE
+    }
+}
+
+#[cfg(test)]
+mod quirks_tests {
+    use super::*;
+    use crate::{
+        Aht2xTransport, Celsius, Error, RelativeHumidity, Sensor, SimError, SimulatedSensor,
+        SENSOR_ADDR,
+    };
+
+    ///Behaves like an ESP32-C3 on a sensor that stretches the clock while
+    ///converting: multi byte reads of a busy sensor time out.
+    struct Esp32c3Bus {
+        sim: SimulatedSensor,
+    }
+
+    impl Aht2xTransport for Esp32c3Bus {
+        type Error = &'static str;
+
+        fn write_frame(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+            self.sim.write_frame(address, bytes).map_err(|SimError::Nack| "nack")
+        }
+
+        fn read_frame(&mut self, address: u8, buf: &mut [u8]) -> Result<(), Self::Error> {
+            self.sim.read_frame(address, buf).map_err(|SimError::Nack| "nack")?;
+            if buf.len() > 1 && buf[0] & 0x80 != 0 {
+                return Err("timeout");
+            }
+            Ok(())
+        }
+    }
+
+    fn esp_sensor() -> Sensor<Esp32c3Bus> {
+        let mut sim = SimulatedSensor::new(Celsius(24.0), RelativeHumidity(55.0));
+        sim.busy_reads = 2;
+        Sensor::new(Esp32c3Bus { sim }, SENSOR_ADDR)
+    }
+
+    ///Fails the first `flaky` transfers, like a Pi that lost the clock.
+    struct FlakyBus {
+        sim: SimulatedSensor,
+        flaky: u8,
+    }
+
+    impl Aht2xTransport for FlakyBus {
+        type Error = SimError;
+
+        fn write_frame(&mut self, address: u8, bytes: &[u8]) -> Result<(), SimError> {
+            if self.flaky > 0 {
+                self.flaky -= 1;
+                return Err(SimError::Nack);
+            }
+            self.sim.write_frame(address, bytes)
+        }
+
+        fn read_frame(&mut self, address: u8, buf: &mut [u8]) -> Result<(), SimError> {
+            if self.flaky > 0 {
+                self.flaky -= 1;
+                return Err(SimError::Nack);
+            }
+            self.sim.read_frame(address, buf)
+        }
+    }
+
+    #[test]
+    fn transfer_retries()
+    {
+        let mut delay = embedded_hal_mock::delay::MockNoop;
+        let sim = SimulatedSensor::new(Celsius(24.0), RelativeHumidity(55.0));
+        let mut sensor = Sensor::new(FlakyBus { sim, flaky: 0 }, SENSOR_ADDR);
+        sensor.set_bus_quirks(BusQuirks::RASPBERRY_PI);
+        let mut inited = sensor.init(&mut delay).unwrap();
+
+        inited.sensor.i2c.flaky = 2;
+        assert!(inited.measure(&mut delay).is_ok());
+        inited.sensor.i2c.flaky = 3;
+        assert_eq!(inited.measure(&mut delay).err(), Some(Error::I2C(SimError::Nack)));
+    }
+
+    #[test]
+    fn esp32_c3()
+    {
+        let mut delay = embedded_hal_mock::delay::MockNoop;
+
+        let r = esp_sensor().init(&mut delay).unwrap().measure(&mut delay);
+        assert_eq!(r.err(), Some(Error::I2C("timeout")));
+
+        let mut sensor = esp_sensor();
+        sensor.set_bus_quirks(BusQuirks::ESP32_C3);
+        assert_eq!(sensor.bus_quirks(), BusQuirks::ESP32_C3);
+        let mut inited = sensor.init(&mut delay).unwrap();
+        let m = inited.measure(&mut delay).unwrap();
+        assert!(m.temperature.abs_delta(Celsius(24.0)) < Celsius(0.01));
+        assert_eq!(inited.sensor_mut().diagnostic_dump().last_retries, 2);
+    }
+}
@@ -0,0 +1,134 @@
+/*
+ * Filename: retry.rs
+ * Description: Policies for how long to wait between polls of a busy sensor
+ * and when to give up, so products with strict latency budgets can pick or
+ * write their own.
+ */
+
+///Decides the wait before the next attempt, or that it's time to give up.
+pub trait RetryStrategy {
+    ///Called after a failed attempt, `failed` counts the failed attempts so
+    ///far (starting at 1) and `waited_ms` the time already spent waiting.
+    ///Returns the ms to wait before trying again, None to give up.
+    fn next_delay_ms(&mut self, failed: u16, waited_ms: u32) -> Option<u16>;
+}
+
+impl<R: RetryStrategy + ?Sized> RetryStrategy for &mut R {
+    fn next_delay_ms(&mut self, failed: u16, waited_ms: u32) -> Option<u16> {
+        (**self).next_delay_ms(failed, waited_ms)
+    }
+}
+
+///The same wait every time, up to `max_retries` retries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedRetry {
+    pub interval_ms: u16,
+    pub max_retries: u16,
+}
+
+impl RetryStrategy for FixedRetry {
+    fn next_delay_ms(&mut self, failed: u16, _waited_ms: u32) -> Option<u16> {
+        if failed > self.max_retries {
+            return None;
+        }
+        Some(self.interval_ms)
+    }
+}
+
+///Starts at `initial_ms` and doubles the wait every retry, capped at
+///`max_ms`, up to `max_retries` retries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExponentialBackoff {
+    pub initial_ms: u16,
+    pub max_ms: u16,
+    pub max_retries: u16,
+}
+
+impl RetryStrategy for ExponentialBackoff {
+    fn next_delay_ms(&mut self, failed: u16, _waited_ms: u32) -> Option<u16> {
+        if failed > self.max_retries {
+            return None;
+        }
+        let shift = u32::from(failed - 1).min(15);
+        let wait = u32::from(self.initial_ms) << shift;
+        Some(wait.min(u32::from(self.max_ms)) as u16)
+    }
+}
+
+///Gives up straight away, a single attempt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NoRetry;
+
+impl RetryStrategy for NoRetry {
+    fn next_delay_ms(&mut self, _failed: u16, _waited_ms: u32) -> Option<u16> {
+        None
+    }
+}
+
+//Polls every `interval_ms` until `deadline_ms` has been waited, the last
+//wait is cut short so the deadline is never overrun. What `read_sensor()`
+//and `wait_until_idle()` use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Deadline {
+    pub(crate) interval_ms: u16,
+    pub(crate) deadline_ms: u16,
+}
+
+impl RetryStrategy for Deadline {
+    fn next_delay_ms(&mut self, _failed: u16, waited_ms: u32) -> Option<u16> {
+        let left = u32::from(self.deadline_ms).checked_sub(waited_ms)?;
+        if left == 0 {
+            return None;
+        }
+        //At least 1ms so a zero interval can't spin forever.
+        Some(u32::from(self.interval_ms.max(1)).min(left) as u16)
+    }
+}
+
+#[cfg(test)]
+mod retry_tests {
+    use super::*;
+
+    fn schedule(mut r: impl RetryStrategy) -> Vec<u16> {
+        let mut waits = vec![];
+        let mut waited = 0;
+        let mut failed = 1;
+        while let Some(w) = r.next_delay_ms(failed, waited) {
+            waits.push(w);
+            waited += u32::from(w);
+            failed += 1;
+        }
+        waits
+    }
+
+    #[test]
+    fn fixed()
+    {
+        assert_eq!(schedule(FixedRetry::default()), [20, 20, 20]);
+        assert!(schedule(FixedRetry { interval_ms: 5, max_retries: 0 }).is_empty());
+    }
+
+    #[test]
+    fn exponential()
+    {
+        let r = ExponentialBackoff { initial_ms: 5, max_ms: 30, max_retries: 5 };
+        assert_eq!(schedule(r), [5, 10, 20, 30, 30]);
+
+        let r = ExponentialBackoff { initial_ms: 1000, max_ms: u16::MAX, max_retries: 20 };
+        assert_eq!(*schedule(r).last().unwrap(), u16::MAX);
+    }
+
+    #[test]
+    fn no_retry()
+    {
+        assert!(schedule(NoRetry).is_empty());
+    }
+
+    #[test]
+    fn deadline()
+    {
+        assert_eq!(schedule(Deadline { interval_ms: 20, deadline_ms: 50 }), [20, 20, 10]);
+        assert_eq!(schedule(Deadline { interval_ms: 0, deadline_ms: 2 }), [1, 1]);
+        assert!(schedule(Deadline { interval_ms: 20, deadline_ms: 0 }).is_empty());
+    }
+}
@@ -0,0 +1,504 @@
+/*
+ * Filename: sansio.rs
+ * Description: The command sequences as state machines that do no I/O
+ * themselves. Each `poll()` takes the result of the last action and returns
+ * the next one (write these bytes, read n bytes, wait, done), so blocking,
+ * async, FFI and simulator frontends can all share this one implementation.
+ *
+ * The driver's init, calibration, measurements and resets all run these
+ * machines, the blocking ones with a delay, `MeasurementPoll` against a
+ * clock.
+ */
+
+use crate::core::commands;
+use crate::core::frame::Frame;
+use crate::core::params::CommandParams;
+use crate::core::quirks::BusQuirks;
+use crate::core::retry::RetryStrategy;
+use crate::core::sensor_status::SensorStatus;
+use crate::core::timings::Timings;
+
+///Length of a measurement frame, status, 5 data bytes and the CRC.
+pub const FRAME_LEN: usize = 7;
+
+///A command of up to three bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriteBuf {
+    buf: [u8; 3],
+    len: u8,
+}
+
+impl WriteBuf {
+    fn one([cmd]: [u8; 1]) -> Self {
+        WriteBuf { buf: [cmd, 0, 0], len: 1 }
+    }
+
+    fn three(buf: [u8; 3]) -> Self {
+        WriteBuf { buf, len: 3 }
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.buf[..self.len as usize]
+    }
+}
+
+///What the I/O layer hands to `poll()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input<'a> {
+    ///Start, or the last write/delay is done.
+    Ready,
+    ///The bytes asked for by the last `Action::Read`.
+    Data(&'a [u8]),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ProtocolError {
+    ///Still busy once the retry strategy gave up.
+    TimedOut,
+    ///The calibration bit didn't come on after calibrating.
+    NotCalibrated,
+    ///The input doesn't fit the machine's state, e.g. a short read.
+    UnexpectedInput,
+    ///Still busy where the sensor has to be idle, e.g. after a reset.
+    Busy,
+}
+
+///What the I/O layer should do next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Write(WriteBuf),
+    Read(usize),
+    DelayMs(u16),
+    Done,
+    Fail(ProtocolError),
+}
+
+///A sans-IO command sequence.
+pub trait Machine {
+    fn poll(&mut self, input: Input<'_>) -> Action;
+}
+
+fn status_of(data: &[u8], len: usize) -> Option<SensorStatus> {
+    match data {
+        [status, ..] if data.len() == len => Some(SensorStatus::new(*status)),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CalibrateState {
+    Start,
+    Sent,
+    Waited,
+    StatusRequested,
+    Finished,
+}
+
+///Calibrate command, wait, then check the calibration bit.
+#[derive(Debug, Clone, Copy)]
+pub struct CalibrateCycle {
+    state: CalibrateState,
+    params: [u8; 2],
+    wait_ms: u16,
+    status: Option<SensorStatus>,
+}
+
+impl CalibrateCycle {
+    pub fn new(timings: &Timings, params: &CommandParams) -> Self {
+        CalibrateCycle {
+            state: CalibrateState::Start,
+            params: params.calibrate,
+            wait_ms: timings.calibrate_ms,
+            status: None,
+        }
+    }
+
+    ///The status byte read after calibrating.
+    pub fn status(&self) -> Option<SensorStatus> {
+        self.status
+    }
+}
+
+impl Machine for CalibrateCycle {
+    fn poll(&mut self, input: Input<'_>) -> Action {
+        use CalibrateState::*;
+        let (next, action) = match (self.state, input) {
+            (Start, Input::Ready) => (Sent, Action::Write(WriteBuf::three(commands::calibrate(self.params)))),
+            (Sent, Input::Ready) => (Waited, Action::DelayMs(self.wait_ms)),
+            (Waited, Input::Ready) => (StatusRequested, Action::Write(WriteBuf::one(commands::read_status()))),
+            (StatusRequested, Input::Ready) => (StatusRequested, Action::Read(1)),
+            (StatusRequested, Input::Data(data)) => match status_of(data, 1) {
+                Some(status) => {
+                    self.status = Some(status);
+                    if status.is_calibration_enabled() {
+                        (Finished, Action::Done)
+                    } else {
+                        (Finished, Action::Fail(ProtocolError::NotCalibrated))
+                    }
+                },
+                None => (Finished, Action::Fail(ProtocolError::UnexpectedInput)),
+            },
+            _ => (Finished, Action::Fail(ProtocolError::UnexpectedInput)),
+        };
+        self.state = next;
+        action
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ResetState {
+    Start,
+    Sent,
+    Waited,
+    StatusRequested,
+    Finished,
+}
+
+///Soft reset, wait the reset time, then read the status back. A sensor
+///still busy then fails with `ProtocolError::Busy`.
+#[derive(Debug, Clone, Copy)]
+pub struct ResetCycle {
+    state: ResetState,
+    wait_ms: u16,
+    status: Option<SensorStatus>,
+}
+
+impl ResetCycle {
+    pub fn new(timings: &Timings) -> Self {
+        ResetCycle { state: ResetState::Start, wait_ms: timings.reset_ms, status: None }
+    }
+
+    ///The status byte read after the reset.
+    pub fn status(&self) -> Option<SensorStatus> {
+        self.status
+    }
+}
+
+impl Machine for ResetCycle {
+    fn poll(&mut self, input: Input<'_>) -> Action {
+        use ResetState::*;
+        let (next, action) = match (self.state, input) {
+            (Start, Input::Ready) => (Sent, Action::Write(WriteBuf::one(commands::soft_reset()))),
+            (Sent, Input::Ready) => (Waited, Action::DelayMs(self.wait_ms)),
+            (Waited, Input::Ready) => (StatusRequested, Action::Write(WriteBuf::one(commands::read_status()))),
+            (StatusRequested, Input::Ready) => (StatusRequested, Action::Read(1)),
+            (StatusRequested, Input::Data(data)) => match status_of(data, 1) {
+                Some(status) => {
+                    self.status = Some(status);
+                    if status.is_busy() {
+                        (Finished, Action::Fail(ProtocolError::Busy))
+                    } else {
+                        (Finished, Action::Done)
+                    }
+                },
+                None => (Finished, Action::Fail(ProtocolError::UnexpectedInput)),
+            },
+            _ => (Finished, Action::Fail(ProtocolError::UnexpectedInput)),
+        };
+        self.state = next;
+        action
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InitState {
+    Start,
+    StartedUp,
+    InitSent,
+    StatusRequested,
+    Calibrating,
+    Finished,
+}
+
+///Startup delay, init command, then calibrate if the status byte says so.
+#[derive(Debug, Clone, Copy)]
+pub struct InitCycle {
+    state: InitState,
+    startup_ms: u16,
+    calibrate: CalibrateCycle,
+}
+
+impl InitCycle {
+    pub fn new(timings: &Timings, params: &CommandParams) -> Self {
+        InitCycle {
+            state: InitState::Start,
+            startup_ms: timings.startup_ms,
+            calibrate: CalibrateCycle::new(timings, params),
+        }
+    }
+
+    ///Leaves out the startup delay, for an init resumed after the sensor
+    ///already had its time to start up.
+    pub fn skip_startup(mut self) -> Self {
+        self.state = InitState::StartedUp;
+        self
+    }
+
+    ///True once the init command was handed out and nothing after it, a
+    ///failed write now means the sensor didn't acknowledge it.
+    pub fn is_init_pending(&self) -> bool {
+        self.state == InitState::InitSent
+    }
+}
+
+impl Machine for InitCycle {
+    fn poll(&mut self, input: Input<'_>) -> Action {
+        use InitState::*;
+        let (next, action) = match (self.state, input) {
+            (Start, Input::Ready) => (StartedUp, Action::DelayMs(self.startup_ms)),
+            (StartedUp, Input::Ready) => (InitSent, Action::Write(WriteBuf::one(commands::init_sensor()))),
+            (InitSent, Input::Ready) => (StatusRequested, Action::Write(WriteBuf::one(commands::read_status()))),
+            (StatusRequested, Input::Ready) => (StatusRequested, Action::Read(1)),
+            (StatusRequested, Input::Data(data)) => match status_of(data, 1) {
+                Some(s) if s.is_calibration_enabled() => (Finished, Action::Done),
+                Some(_) => (Calibrating, self.calibrate.poll(Input::Ready)),
+                None => (Finished, Action::Fail(ProtocolError::UnexpectedInput)),
+            },
+            (Calibrating, input) => (Calibrating, self.calibrate.poll(input)),
+            _ => (Finished, Action::Fail(ProtocolError::UnexpectedInput)),
+        };
+        self.state = next;
+        action
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MeasureState {
+    Start,
+    Triggered,
+    Waiting,
+    //Waiting `settle_ms`, true if a status read comes next.
+    Settling(bool),
+    Polling,
+    Reading,
+    Finished,
+}
+
+///Trigger, wait the typical conversion time, then read the frame until the
+///sensor isn't busy, `retry` decides the waits between reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MeasureCycle<R> {
+    state: MeasureState,
+    trigger: [u8; 2],
+    typ_ms: u16,
+    retry: R,
+    frame: Option<[u8; FRAME_LEN]>,
+    failed: u16,
+    waited_ms: u32,
+    quirks: BusQuirks,
+}
+
+impl<R: RetryStrategy> MeasureCycle<R> {
+    pub fn new(timings: &Timings, params: &CommandParams, retry: R) -> Self {
+        MeasureCycle {
+            state: MeasureState::Start,
+            trigger: params.trigger,
+            typ_ms: timings.measure_typ_ms,
+            retry,
+            frame: None,
+            failed: 0,
+            waited_ms: 0,
+            quirks: BusQuirks::NONE,
+        }
+    }
+
+    ///Works around a bus controller, see `BusQuirks`.
+    pub fn with_quirks(self, quirks: BusQuirks) -> Self {
+        MeasureCycle { quirks, ..self }
+    }
+
+    //The next read, a status byte or the whole frame, after the settle
+    //delay if `settle` and there is one.
+    fn read(&self, status: bool, settle: bool) -> (MeasureState, Action) {
+        if settle && self.quirks.settle_ms > 0 {
+            (MeasureState::Settling(status), Action::DelayMs(self.quirks.settle_ms))
+        } else if status {
+            (MeasureState::Polling, Action::Read(1))
+        } else {
+            (MeasureState::Reading, Action::Read(FRAME_LEN))
+        }
+    }
+
+    fn still_busy(&mut self) -> (MeasureState, Action) {
+        self.failed = self.failed.saturating_add(1);
+        match self.retry.next_delay_ms(self.failed, self.waited_ms) {
+            Some(ms) => {
+                self.waited_ms = self.waited_ms.saturating_add(u32::from(ms));
+                (MeasureState::Waiting, Action::DelayMs(ms))
+            },
+            None => (MeasureState::Finished, Action::Fail(ProtocolError::TimedOut)),
+        }
+    }
+
+    ///The last frame read, busy or not. The CRC isn't checked here.
+    pub fn frame(&self) -> Option<[u8; FRAME_LEN]> {
+        self.frame
+    }
+
+    ///Reads that came back busy.
+    pub fn busy_reads(&self) -> u16 {
+        self.failed
+    }
+}
+
+impl<R: RetryStrategy> Machine for MeasureCycle<R> {
+    fn poll(&mut self, input: Input<'_>) -> Action {
+        use MeasureState::*;
+        let (next, action) = match (self.state, input) {
+            (Start, Input::Ready) => (Triggered, Action::Write(WriteBuf::three(commands::trigger(self.trigger)))),
+            (Triggered, Input::Ready) => (Waiting, Action::DelayMs(self.typ_ms)),
+            (Waiting, Input::Ready) => self.read(self.quirks.split_reads, true),
+            (Settling(status), Input::Ready) => self.read(status, false),
+            (Polling, Input::Data(data)) if data.len() == 1 => {
+                if !SensorStatus::new(data[0]).is_busy() {
+                    self.read(false, true)
+                } else {
+                    self.still_busy()
+                }
+            },
+            (Reading, Input::Data(data)) if data.len() == FRAME_LEN => {
+                let mut frame = [0u8; FRAME_LEN];
+                frame.copy_from_slice(data);
+                self.frame = Some(frame);
+
+                if !Frame(frame).status().is_busy() {
+                    (Finished, Action::Done)
+                } else {
+                    self.still_busy()
+                }
+            },
+            _ => (Finished, Action::Fail(ProtocolError::UnexpectedInput)),
+        };
+        self.state = next;
+        action
+    }
+}
+
+#[cfg(test)]
+mod sansio_tests {
+    use super::*;
+    use crate::core::retry::{FixedRetry, NoRetry};
+
+    const FRAME: [u8; 7] = [0x18, 0x7E, 0x51, 0x65, 0xD4, 0xA0, 0xDA];
+    const BUSY: [u8; 7] = [0x98, 0, 0, 0, 0, 0, 0];
+
+    fn write(bytes: &[u8]) -> Action {
+        let mut buf = [0u8; 3];
+        buf[..bytes.len()].copy_from_slice(bytes);
+        Action::Write(WriteBuf { buf, len: bytes.len() as u8 })
+    }
+
+    #[test]
+    fn measure()
+    {
+        let t = Timings::AHT20;
+        let retry = FixedRetry { interval_ms: 20, max_retries: 3 };
+        let mut m = MeasureCycle::new(&t, &CommandParams::DATASHEET, retry);
+
+        assert_eq!(m.poll(Input::Ready), write(&[0xAC, 0x33, 0x00]));
+        assert_eq!(m.poll(Input::Ready), Action::DelayMs(80));
+        assert_eq!(m.poll(Input::Ready), Action::Read(7));
+        assert_eq!(m.poll(Input::Data(&BUSY)), Action::DelayMs(20));
+        assert_eq!(m.poll(Input::Ready), Action::Read(7));
+        assert_eq!(m.poll(Input::Data(&FRAME)), Action::Done);
+        assert_eq!(m.frame(), Some(FRAME));
+        assert_eq!(m.busy_reads(), 1);
+    }
+
+    #[test]
+    fn measure_split_reads()
+    {
+        let quirks = BusQuirks { split_reads: true, settle_ms: 2, transfer_retries: 0 };
+        let retry = FixedRetry { interval_ms: 20, max_retries: 3 };
+        let mut m = MeasureCycle::new(&Timings::AHT20, &CommandParams::DATASHEET, retry)
+            .with_quirks(quirks);
+
+        m.poll(Input::Ready);
+        assert_eq!(m.poll(Input::Ready), Action::DelayMs(80));
+        assert_eq!(m.poll(Input::Ready), Action::DelayMs(2));
+        assert_eq!(m.poll(Input::Ready), Action::Read(1));
+        assert_eq!(m.poll(Input::Data(&[0x98])), Action::DelayMs(20));
+        assert_eq!(m.poll(Input::Ready), Action::DelayMs(2));
+        assert_eq!(m.poll(Input::Ready), Action::Read(1));
+        assert_eq!(m.poll(Input::Data(&[0x18])), Action::DelayMs(2));
+        assert_eq!(m.poll(Input::Ready), Action::Read(7));
+        assert_eq!(m.poll(Input::Data(&FRAME)), Action::Done);
+        assert_eq!(m.busy_reads(), 1);
+    }
+
+    #[test]
+    fn measure_timeout()
+    {
+        let mut m = MeasureCycle::new(&Timings::AHT20, &CommandParams::DATASHEET, NoRetry);
+        m.poll(Input::Ready);
+        m.poll(Input::Ready);
+        m.poll(Input::Ready);
+        assert_eq!(m.poll(Input::Data(&BUSY)), Action::Fail(ProtocolError::TimedOut));
+        assert_eq!(m.frame(), Some(BUSY));
+    }
+
+    #[test]
+    fn unexpected_input()
+    {
+        let mut m = MeasureCycle::new(&Timings::AHT20, &CommandParams::DATASHEET, NoRetry);
+        assert_eq!(m.poll(Input::Data(&FRAME)), Action::Fail(ProtocolError::UnexpectedInput));
+
+        let mut m = MeasureCycle::new(&Timings::AHT20, &CommandParams::DATASHEET, NoRetry);
+        m.poll(Input::Ready);
+        m.poll(Input::Ready);
+        m.poll(Input::Ready);
+        assert_eq!(m.poll(Input::Data(&FRAME[..3])), Action::Fail(ProtocolError::UnexpectedInput));
+    }
+
+    #[test]
+    fn init_calibrated()
+    {
+        let mut m = InitCycle::new(&Timings::AHT20, &CommandParams::DATASHEET);
+        assert_eq!(m.poll(Input::Ready), Action::DelayMs(40));
+        assert_eq!(m.poll(Input::Ready), write(&[0xBE]));
+        assert_eq!(m.poll(Input::Ready), write(&[0x71]));
+        assert_eq!(m.poll(Input::Ready), Action::Read(1));
+        assert_eq!(m.poll(Input::Data(&[0x18])), Action::Done);
+    }
+
+    #[test]
+    fn init_resumed()
+    {
+        let mut m = InitCycle::new(&Timings::AHT20, &CommandParams::DATASHEET).skip_startup();
+        assert_eq!(m.poll(Input::Ready), write(&[0xBE]));
+    }
+
+    #[test]
+    fn init_calibrates()
+    {
+        let mut m = InitCycle::new(&Timings::AHT20, &CommandParams::DATASHEET);
+        m.poll(Input::Ready);
+        m.poll(Input::Ready);
+        m.poll(Input::Ready);
+        m.poll(Input::Ready);
+        assert_eq!(m.poll(Input::Data(&[0x10])), write(&[0xE1, 0x08, 0x00]));
+        assert_eq!(m.poll(Input::Ready), Action::DelayMs(10));
+        assert_eq!(m.poll(Input::Ready), write(&[0x71]));
+        assert_eq!(m.poll(Input::Ready), Action::Read(1));
+        assert_eq!(m.poll(Input::Data(&[0x10])), Action::Fail(ProtocolError::NotCalibrated));
+    }
+
+    #[test]
+    fn reset()
+    {
+        let mut m = ResetCycle::new(&Timings::AHT20);
+        assert_eq!(m.poll(Input::Ready), write(&[0xBA]));
+        assert_eq!(m.poll(Input::Ready), Action::DelayMs(20));
+        assert_eq!(m.poll(Input::Ready), write(&[0x71]));
+        assert_eq!(m.poll(Input::Ready), Action::Read(1));
+        assert_eq!(m.poll(Input::Data(&[0x18])), Action::Done);
+        assert_eq!(m.status(), Some(SensorStatus::new(0x18)));
+
+        let mut m = ResetCycle::new(&Timings::AHT20);
+        for _ in 0..4 {
+            m.poll(Input::Ready);
+        }
+        assert_eq!(m.poll(Input::Data(&[0x98])), Action::Fail(ProtocolError::Busy));
+    }
+}
@@ -21,6 +21,9 @@ pub const CMDMODE_BM: u8 = 1<<6;
 pub const CALENABLED_BM: u8 = 1<<3;
 
 #[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 /// The Sensor status struct is a wraper around a u8(unsigned 8 bit integer).
 /// It abstracts the needed bitwise operations into methods that can simply 
 /// return a boolean.
@@ -30,7 +33,7 @@ pub struct SensorStatus {
 
 #[allow(dead_code)]
 impl SensorStatus{
-    pub fn new(status: u8) -> SensorStatus {
+    pub const fn new(status: u8) -> SensorStatus {
         SensorStatus{ status }  
     }
 
@@ -53,6 +56,41 @@ impl SensorStatus{
     pub fn is_cmd_mode(&self) -> bool {
         (self.status & CMDMODE_BM) == CMDMODE_VALUE 
     }
+
+    ///What an initialized sensor in normal operation should report, NOR
+    ///mode with the calibration enabled. Clones have been seen reporting
+    ///CMD/CYC mode bits or losing the CAL bit.
+    pub fn is_expected_initialized(&self) -> bool {
+        self.is_normal_mode() && self.is_calibration_enabled()
+    }
+}
+
+#[cfg(feature = "heapless")]
+impl SensorStatus {
+    ///Renders the status as short text, e.g. "NOR,CAL" or "BUSY,CMD".
+    pub fn to_heapless_string<const N: usize>(
+        &self,
+        ) -> Result<heapless::String<N>, crate::core::format::FormatError>
+    {
+        let mut s = heapless::String::new();
+        let mode = if self.is_cmd_mode() {
+            "CMD"
+        } else if self.is_cyc_mode() {
+            "CYC"
+        } else {
+            "NOR"
+        };
+
+        let too_small = |_| crate::core::format::FormatError::BufferTooSmall;
+        if self.is_busy() {
+            s.push_str("BUSY,").map_err(too_small)?;
+        }
+        s.push_str(mode).map_err(too_small)?;
+        if self.is_calibration_enabled() {
+            s.push_str(",CAL").map_err(too_small)?;
+        }
+        Ok(s)
+    }
 }
 
 #[cfg(test)]
@@ -104,7 +142,7 @@ mod sensor_status_tests {
         let mut s = SensorStatus::new(0x18);
         assert!(s.is_normal_mode());
 
-        s.status = s.status | (1<<6); //Hex: 0x58, DEC: 88
+        s.status |= 1<<6; //Hex: 0x58, DEC: 88
         assert!(!s.is_normal_mode());
     }
 
@@ -114,7 +152,7 @@ mod sensor_status_tests {
         let mut s = SensorStatus::new(0x18);
         assert!(!s.is_cyc_mode());
 
-        s.status = s.status | (1<<5); //Hex: 0x38, DEC: 56 
+        s.status |= 1<<5; //Hex: 0x38, DEC: 56 
         assert!(s.is_cyc_mode());
     }
 
@@ -123,7 +161,16 @@ mod sensor_status_tests {
         let mut s = SensorStatus::new(0x18);
         assert!(!s.is_cmd_mode());
 
-        s.status = s.status | (1<<6); //Hex: 0x58, DEC: 88
+        s.status |= 1<<6; //Hex: 0x58, DEC: 88
         assert!(s.is_cmd_mode());
     }
+
+    #[test]
+    fn expected_initialized_status() {
+        assert!(SensorStatus::new(0x18).is_expected_initialized());
+        assert!(SensorStatus::new(0x98).is_expected_initialized());
+        assert!(!SensorStatus::new(0x58).is_expected_initialized());
+        assert!(!SensorStatus::new(0x38).is_expected_initialized());
+        assert!(!SensorStatus::new(0x10).is_expected_initialized());
+    }
 }
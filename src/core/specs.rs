@@ -0,0 +1,88 @@
+//!Rated accuracy, resolution, response time and operating range of the
+//!sensor family, taken from the data sheets.
+//!
+//!The accuracy values are the typical ones, the data sheets only give the
+//!max error as a graph over the range.
+//!
+
+/// Datasheet performance figures for a single chip variant.
+/// Use [`crate::ChipVariant::specs`] to get the one matching your sensor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Specs {
+    /// Typical humidity accuracy in +/- %RH.
+    pub rh_accuracy: f32,
+    /// Typical temperature accuracy in +/- C.
+    pub temp_accuracy: f32,
+    /// Humidity resolution in %RH.
+    pub rh_resolution: f32,
+    /// Temperature resolution in C.
+    pub temp_resolution: f32,
+    /// Humidity response time (tau 63%) in seconds.
+    pub rh_response_s: u8,
+    /// Temperature response time (tau 63%) in seconds, lower bound.
+    pub temp_response_s: u8,
+    /// Operating temperature range in C, (min, max).
+    pub temp_range: (f32, f32),
+    /// Operating humidity range in %RH, (min, max).
+    pub rh_range: (f32, f32),
+}
+
+impl Specs {
+    ///AHT10 values, taken from the AHT10 data sheet.
+    pub const AHT10: Specs = Specs {
+        rh_accuracy: 2.0,
+        temp_accuracy: 0.3,
+        rh_resolution: 0.024,
+        temp_resolution: 0.01,
+        rh_response_s: 8,
+        temp_response_s: 5,
+        temp_range: (-40.0, 85.0),
+        rh_range: (0.0, 100.0),
+    };
+
+    ///AHT20 values, taken from the v1.1 data sheet.
+    pub const AHT20: Specs = Specs {
+        rh_accuracy: 2.0,
+        temp_accuracy: 0.3,
+        rh_resolution: 0.024,
+        temp_resolution: 0.01,
+        rh_response_s: 8,
+        temp_response_s: 5,
+        temp_range: (-40.0, 85.0),
+        rh_range: (0.0, 100.0),
+    };
+
+    ///AHT21 values, same element as the AHT20 but rated up to 120C.
+    pub const AHT21: Specs = Specs {
+        temp_range: (-40.0, 120.0),
+        ..Specs::AHT20
+    };
+
+    ///Returns true if the temperature is inside the rated operating range.
+    pub fn is_temperature_in_range(&self, celsius: f32) -> bool {
+        celsius >= self.temp_range.0 && celsius <= self.temp_range.1
+    }
+
+    ///Returns true if the humidity is inside the rated operating range.
+    pub fn is_humidity_in_range(&self, rh: f32) -> bool {
+        rh >= self.rh_range.0 && rh <= self.rh_range.1
+    }
+}
+
+#[cfg(test)]
+mod specs_tests {
+    use super::*;
+
+    #[test]
+    fn ranges()
+    {
+        let s = Specs::AHT20;
+        assert!(s.is_temperature_in_range(22.88));
+        assert!(!s.is_temperature_in_range(100.0));
+        assert!(Specs::AHT21.is_temperature_in_range(100.0));
+        assert!(!s.is_temperature_in_range(f32::NAN));
+
+        assert!(s.is_humidity_in_range(49.34));
+        assert!(!s.is_humidity_in_range(-0.1));
+    }
+}
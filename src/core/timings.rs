@@ -0,0 +1,177 @@
+//!Datasheet timing values for the AHT sensor family, all in milliseconds.
+//!
+//!The v1.0 and v1.1 data sheets (and the translated ones) don't agree on
+//!all of these numbers, where they differ the longer value is used.
+//!
+//! * startup: the v1.0 data sheet says 40ms before the first command.
+//! * power on to idle: the v1.1 data sheet says at least 100ms.
+//! * measure: 80ms typical for the AHT2X, 75ms for the AHT10.
+//! * calibrate: 10ms before the status byte is checked again.
+//! * reset: a soft reset takes "no more than 20ms".
+//! * register: AOSONG's sample code waits 5ms before reading a register
+//!   back in the power on self check.
+//!
+
+/// Timing table for a single chip variant.
+/// Use [`crate::ChipVariant::timings`] to get the one matching your sensor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Timings {
+    /// Delay before the first command is sent to the sensor.
+    pub startup_ms: u16,
+    /// Typical conversion time after a trigger measurement command.
+    pub measure_typ_ms: u16,
+    /// Upper bound on the conversion time, used to bound the busy polling.
+    pub measure_max_ms: u16,
+    /// Delay between reads while the sensor still reports busy.
+    pub busy_poll_ms: u16,
+    /// Delay after the calibrate command before reading the status.
+    pub calibrate_ms: u16,
+    /// Time the sensor needs to come back after a soft reset.
+    pub reset_ms: u16,
+    /// Time from power on until the sensor settles in the idle state.
+    pub power_on_idle_ms: u16,
+    /// Delay between a register read command and reading the register back.
+    pub register_ms: u16,
+}
+
+impl Timings {
+    ///AHT10 values, taken from the AHT10 data sheet.
+    pub const AHT10: Timings = Timings {
+        startup_ms: 40,
+        measure_typ_ms: 75,
+        measure_max_ms: 135,
+        busy_poll_ms: 20,
+        calibrate_ms: 10,
+        reset_ms: 20,
+        power_on_idle_ms: 40,
+        register_ms: 5,
+    };
+
+    ///AHT20 values, the max measure time is the typical value plus three
+    ///busy polls as the data sheets only give a typical value.
+    pub const AHT20: Timings = Timings {
+        startup_ms: 40,
+        measure_typ_ms: 80,
+        measure_max_ms: 140,
+        busy_poll_ms: 20,
+        calibrate_ms: 10,
+        reset_ms: 20,
+        power_on_idle_ms: 100,
+        register_ms: 5,
+    };
+
+    ///AHT21 values, these match the AHT20.
+    pub const AHT21: Timings = Timings::AHT20;
+}
+
+impl Default for Timings {
+    fn default() -> Self {
+        Timings::AHT20
+    }
+}
+
+///Observed trigger to not busy times, to check the table above against
+///real parts. The times include the bus traffic and are rounded up to the
+///busy poll interval, so they are upper bounds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ConversionDurations {
+    min_ms: u32,
+    max_ms: u32,
+    //u32 so recording doesn't pull in 64 bit math on 8 bit parts.
+    sum_ms: u32,
+    count: u32,
+}
+
+impl ConversionDurations {
+    pub fn new() -> Self {
+        ConversionDurations { min_ms: 0, max_ms: 0, sum_ms: 0, count: 0 }
+    }
+
+    pub fn record(&mut self, ms: u32) {
+        if self.count == 0 {
+            self.min_ms = ms;
+            self.max_ms = ms;
+        } else {
+            self.min_ms = self.min_ms.min(ms);
+            self.max_ms = self.max_ms.max(ms);
+        }
+        //On overflow half the history is dropped, keeping the mean.
+        if self.sum_ms.checked_add(ms).is_none() || self.count == u32::MAX {
+            let mean = self.sum_ms / self.count;
+            self.count /= 2;
+            self.sum_ms = mean * self.count;
+        }
+        self.sum_ms = self.sum_ms.saturating_add(ms);
+        self.count += 1;
+    }
+
+    ///Number of conversions recorded.
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    pub fn min_ms(&self) -> Option<u32> {
+        (self.count > 0).then_some(self.min_ms)
+    }
+
+    pub fn max_ms(&self) -> Option<u32> {
+        (self.count > 0).then_some(self.max_ms)
+    }
+
+    ///The mean of the recorded times.
+    pub fn typ_ms(&self) -> Option<u32> {
+        (self.count > 0).then(|| self.sum_ms / self.count)
+    }
+}
+
+#[cfg(test)]
+mod timings_tests {
+    use super::*;
+
+    #[test]
+    fn conversion_durations()
+    {
+        let mut d = ConversionDurations::new();
+        assert_eq!(d.min_ms(), None);
+        assert_eq!(d.typ_ms(), None);
+
+        for ms in [80, 100, 90] {
+            d.record(ms);
+        }
+        assert_eq!(d.count(), 3);
+        assert_eq!(d.min_ms(), Some(80));
+        assert_eq!(d.typ_ms(), Some(90));
+        assert_eq!(d.max_ms(), Some(100));
+    }
+
+    #[test]
+    fn conversion_durations_overflow()
+    {
+        let mut d = ConversionDurations::new();
+        for _ in 0..(u32::MAX / 80_000) + 10 {
+            d.record(80_000);
+        }
+        assert_eq!(d.typ_ms(), Some(80_000));
+        assert_eq!(d.max_ms(), Some(80_000));
+    }
+
+    #[test]
+    fn default_is_aht20()
+    {
+        assert_eq!(Timings::default(), Timings::AHT20);
+    }
+
+    #[test]
+    fn measure_bounds()
+    {
+        for t in [Timings::AHT10, Timings::AHT20, Timings::AHT21] {
+            assert!(t.measure_typ_ms <= t.measure_max_ms);
+            assert!(t.startup_ms <= t.power_on_idle_ms);
+            assert!(t.busy_poll_ms > 0);
+        }
+    }
+}
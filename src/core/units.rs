@@ -0,0 +1,193 @@
+/*
+ * Filename: units.rs
+ * Description: Newtypes for the converted values, so a temperature can't be
+ * compared against a humidity by mistake.
+ *
+ * Comparisons follow IEEE float rules: anything compared with a NaN is
+ * false, so a NaN reading never passes a threshold check like
+ * `temp > Celsius(30.0)` nor its opposite `temp <= Celsius(30.0)`.
+ */
+
+use core::ops::{Add, Sub};
+
+///Temperature in degrees C.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Celsius(pub f32);
+
+///Relative humidity in %.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RelativeHumidity(pub f32);
+
+///Pressure in hPa, e.g. from a BMP280 next to the sensor.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Hectopascal(pub f32);
+
+macro_rules! impl_unit {
+    ($unit:ident) => {
+        impl $unit {
+            ///Absolute difference between two values, NaN if either is NaN.
+            pub fn abs_delta(self, other: $unit) -> $unit {
+                let d = self.0 - other.0;
+                if d < 0.0 {
+                    return $unit(-d);
+                }
+                $unit(d)
+            }
+
+            ///Returns false for NaN.
+            pub fn is_valid(self) -> bool {
+                !self.0.is_nan()
+            }
+        }
+
+        impl Add for $unit {
+            type Output = $unit;
+
+            fn add(self, rhs: $unit) -> $unit {
+                $unit(self.0 + rhs.0)
+            }
+        }
+
+        impl Sub for $unit {
+            type Output = $unit;
+
+            fn sub(self, rhs: $unit) -> $unit {
+                $unit(self.0 - rhs.0)
+            }
+        }
+
+        impl From<$unit> for f32 {
+            fn from(v: $unit) -> f32 {
+                v.0
+            }
+        }
+    };
+}
+
+///Unit system for the values handed to users, see `Measurement::derived()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Units {
+    ///°C, g/m³, g/kg and kJ/kg.
+    #[default]
+    Si,
+    ///°F, gr/ft³, gr/lb and BTU/lb, as used in US HVAC.
+    Imperial,
+}
+
+impl Units {
+    pub fn temperature(self, t: Celsius) -> f32 {
+        match self {
+            Units::Si => t.0,
+            Units::Imperial => t.0 * 9.0 / 5.0 + 32.0,
+        }
+    }
+
+    ///Converts a density given in g/m³.
+    pub fn density(self, g_m3: f32) -> f32 {
+        match self {
+            Units::Si => g_m3,
+            Units::Imperial => g_m3 * GRAINS_FT3_PER_G_M3,
+        }
+    }
+
+    ///Converts a ratio given in g/kg.
+    pub fn ratio(self, g_kg: f32) -> f32 {
+        match self {
+            Units::Si => g_kg,
+            Units::Imperial => g_kg * GRAINS_LB_PER_G_KG,
+        }
+    }
+
+    pub fn temperature_symbol(self) -> &'static str {
+        match self {
+            Units::Si => "°C",
+            Units::Imperial => "°F",
+        }
+    }
+
+    pub fn density_symbol(self) -> &'static str {
+        match self {
+            Units::Si => "g/m³",
+            Units::Imperial => "gr/ft³",
+        }
+    }
+
+    pub fn ratio_symbol(self) -> &'static str {
+        match self {
+            Units::Si => "g/kg",
+            Units::Imperial => "gr/lb",
+        }
+    }
+
+    pub fn enthalpy_symbol(self) -> &'static str {
+        match self {
+            Units::Si => "kJ/kg",
+            Units::Imperial => "BTU/lb",
+        }
+    }
+}
+
+const GRAINS_FT3_PER_G_M3: f32 = 0.436_996;
+const GRAINS_LB_PER_G_KG: f32 = 7.0;
+
+impl_unit!(Celsius);
+impl_unit!(RelativeHumidity);
+impl_unit!(Hectopascal);
+
+#[cfg(test)]
+mod units_tests {
+    use super::*;
+
+    #[test]
+    fn comparisons()
+    {
+        let temp = Celsius(31.5);
+        assert!(temp > Celsius(30.0));
+        assert!(RelativeHumidity(40.0) < RelativeHumidity(49.3));
+        assert_eq!(Celsius(22.0), Celsius(22.0));
+    }
+
+    #[test]
+    fn nan_comparisons()
+    {
+        let nan = Celsius(f32::NAN);
+        let over = nan > Celsius(30.0);
+        let under = nan <= Celsius(30.0);
+        assert!(!over && !under);
+        assert_eq!(nan.partial_cmp(&Celsius(30.0)), None);
+        assert!(!nan.is_valid());
+        assert!(Celsius(0.0).is_valid());
+        assert!(!nan.abs_delta(Celsius(1.0)).is_valid());
+    }
+
+    #[test]
+    fn unit_systems()
+    {
+        assert_eq!(Units::default(), Units::Si);
+        assert_eq!(Units::Si.temperature(Celsius(20.0)), 20.0);
+        assert_eq!(Units::Imperial.temperature(Celsius(100.0)), 212.0);
+        assert_eq!(Units::Imperial.temperature(Celsius(-40.0)), -40.0);
+        assert!((Units::Imperial.density(10.0) - 4.37).abs() < 0.01);
+        assert_eq!(Units::Imperial.ratio(10.0), 70.0);
+        assert_eq!(Units::Imperial.enthalpy_symbol(), "BTU/lb");
+    }
+
+    #[test]
+    fn arithmetic()
+    {
+        assert_eq!(Celsius(22.5) - Celsius(20.0), Celsius(2.5));
+        assert_eq!(Celsius(20.0) + Celsius(2.5), Celsius(22.5));
+        assert_eq!(Celsius(20.0).abs_delta(Celsius(22.5)), Celsius(2.5));
+        assert_eq!(
+            RelativeHumidity(40.0).abs_delta(RelativeHumidity(45.0)),
+            RelativeHumidity(5.0)
+            );
+        assert_eq!(f32::from(Celsius(1.5)), 1.5);
+    }
+}
@@ -0,0 +1,96 @@
+//!The different chips in the AOSONG AHT family that this driver can talk to.
+//!They share the same command set, but not the same timings.
+
+use crate::core::specs::Specs;
+use crate::core::timings::Timings;
+
+///The address every AHT part answers on by default.
+pub const AHT_DEFAULT_ADDR: u8 = 0x38;
+///The AHT10's second address, picked with its ADR pin strapped high. The
+///AHT20 and AHT21 have no address pin.
+pub const AHT10_ALT_ADDR: u8 = 0x39;
+///Every address an AOSONG AHT part can have, the default first.
+pub const AOSONG_ADDRESSES: [u8; 2] = [AHT_DEFAULT_ADDR, AHT10_ALT_ADDR];
+
+/// The chip variant of the sensor on the bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ChipVariant {
+    Aht10,
+    #[default]
+    Aht20,
+    Aht21,
+}
+
+impl ChipVariant {
+    ///Returns the datasheet timing table for this variant.
+    pub const fn timings(self) -> Timings {
+        match self {
+            ChipVariant::Aht10 => Timings::AHT10,
+            ChipVariant::Aht20 => Timings::AHT20,
+            ChipVariant::Aht21 => Timings::AHT21,
+        }
+    }
+
+    ///Returns the datasheet accuracy/resolution/range figures for this variant.
+    pub const fn specs(self) -> Specs {
+        match self {
+            ChipVariant::Aht10 => Specs::AHT10,
+            ChipVariant::Aht20 => Specs::AHT20,
+            ChipVariant::Aht21 => Specs::AHT21,
+        }
+    }
+
+    ///The addresses this variant can be strapped to, the default first.
+    pub const fn addresses(self) -> &'static [u8] {
+        match self {
+            ChipVariant::Aht10 => &AOSONG_ADDRESSES,
+            ChipVariant::Aht20 | ChipVariant::Aht21 => &[AHT_DEFAULT_ADDR],
+        }
+    }
+
+    ///True if a genuine part of this variant can answer on `address`.
+    pub const fn is_valid_address(self, address: u8) -> bool {
+        let addresses = self.addresses();
+        let mut i = 0;
+        while i < addresses.len() {
+            if addresses[i] == address {
+                return true;
+            }
+            i += 1;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod variant_tests {
+    use super::*;
+
+    #[test]
+    fn variant_timings()
+    {
+        assert_eq!(ChipVariant::default(), ChipVariant::Aht20);
+        assert_eq!(ChipVariant::Aht20.timings(), Timings::AHT20);
+        assert_eq!(ChipVariant::Aht10.timings().measure_typ_ms, 75);
+    }
+
+    #[test]
+    fn variant_specs()
+    {
+        assert_eq!(ChipVariant::Aht20.specs(), Specs::AHT20);
+        assert_eq!(ChipVariant::Aht21.specs().temp_range, (-40.0, 120.0));
+        assert_eq!(ChipVariant::Aht21.specs().rh_accuracy, 2.0);
+    }
+
+    #[test]
+    fn variant_addresses()
+    {
+        assert!(ChipVariant::Aht10.is_valid_address(AHT10_ALT_ADDR));
+        assert!(ChipVariant::Aht10.is_valid_address(AHT_DEFAULT_ADDR));
+        assert!(!ChipVariant::Aht20.is_valid_address(AHT10_ALT_ADDR));
+        assert!(!ChipVariant::Aht21.is_valid_address(0x40));
+        assert_eq!(ChipVariant::Aht21.addresses(), [AHT_DEFAULT_ADDR]);
+    }
+}
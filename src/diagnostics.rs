@@ -0,0 +1,137 @@
+/*
+ * Filename: diagnostics.rs
+ * Description: A snapshot of everything the driver knows about the sensor,
+ * for attaching to bug reports or sending home from a field unit. With the
+ * `serde` or `defmt` features it can be serialized or logged as is.
+ */
+
+use crate::{Aht2xTransport, Indicator, Sensor};
+use crate::core::sensor_status::SensorStatus;
+use crate::state::DriverState;
+use crate::core::timings::{ConversionDurations, Timings};
+use crate::core::variant::ChipVariant;
+
+///The report returned by `Sensor::diagnostic_dump()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DiagnosticDump {
+    pub address: u8,
+    pub state: DriverState,
+    ///None if the status byte couldn't be read.
+    pub status: Option<SensorStatus>,
+    ///The last measurement frame read, busy or not.
+    pub last_frame: Option<[u8; 7]>,
+    pub last_crc_good: Option<bool>,
+    ///Busy polls that came back busy during the last wait.
+    pub last_retries: u16,
+    pub consecutive_failures: u16,
+    pub spacing_violations: u16,
+    pub status_anomalies: u16,
+    pub conversion_durations: ConversionDurations,
+    pub timings: Timings,
+    pub variant: ChipVariant,
+}
+
+impl<E, I2C, L> Sensor<I2C, L>
+where I2C: Aht2xTransport<Error = E>,
+      L: Indicator,
+{
+    ///Reads the status byte and gathers the driver's state and counters.
+    ///Never fails, a status byte that can't be read is left out.
+    pub fn diagnostic_dump(&mut self) -> DiagnosticDump {
+        let status = self.read_status().ok();
+        let last_crc_good = self.last_frame.map(|bytes| {
+            let mut sd = crate::core::data::SensorData::new();
+            sd.bytes = bytes;
+            sd.is_crc_good()
+        });
+
+        DiagnosticDump {
+            address: self.address,
+            state: self.state,
+            status,
+            last_frame: self.last_frame,
+            last_crc_good,
+            last_retries: self.last_retries,
+            consecutive_failures: self.consecutive_failures,
+            spacing_violations: self.spacing_violations,
+            status_anomalies: self.status_anomalies,
+            conversion_durations: self.durations,
+            timings: self.timings,
+            variant: self.variant,
+        }
+    }
+}
+
+#[cfg(test)]
+mod diagnostics_tests {
+    use embedded_hal_mock::i2c::{
+        Mock as I2cMock,
+        Transaction as I2cTransaction,
+    };
+
+    use super::*;
+    use crate::core::{commands, sensor_status};
+    use crate::{InitializedSensor, SENSOR_ADDR};
+    use crate::{TRIG_MEASURE_PARAM0, TRIG_MEASURE_PARAM1};
+
+    #[test]
+    fn dump_after_read()
+    {
+        let frame = vec![0x18, 0x7E, 0x51, 0x65, 0xD4, 0xA0, 0xDA];
+        let busy = vec![sensor_status::BUSY_BM, 0, 0, 0, 0, 0, 0];
+        let expected = [
+            I2cTransaction::write(
+                SENSOR_ADDR,
+                vec![commands::TRIG_MESSURE, TRIG_MEASURE_PARAM0, TRIG_MEASURE_PARAM1],
+                ),
+            I2cTransaction::read(SENSOR_ADDR, busy),
+            I2cTransaction::read(SENSOR_ADDR, frame.clone()),
+            I2cTransaction::write(SENSOR_ADDR, vec![commands::READ_STATUS]),
+            I2cTransaction::read(SENSOR_ADDR, vec![0x18]),
+        ];
+
+        let mut sensor_instance = Sensor::new(I2cMock::new(&expected), SENSOR_ADDR);
+        sensor_instance.set_variant(ChipVariant::Aht21);
+        let mut inited_sensor = InitializedSensor { sensor: sensor_instance };
+        let mut mock_delay = embedded_hal_mock::delay::MockNoop;
+        assert!(inited_sensor.read_sensor(&mut mock_delay).is_ok());
+
+        let mut sensor_instance = inited_sensor.downgrade();
+        let dump = sensor_instance.diagnostic_dump();
+        assert_eq!(dump.status, Some(SensorStatus::new(0x18)));
+        assert_eq!(dump.last_frame.map(|f| f.to_vec()), Some(frame));
+        assert_eq!(dump.last_crc_good, Some(true));
+        assert_eq!(dump.last_retries, 1);
+        assert_eq!(dump.state, DriverState::Idle);
+        assert_eq!(dump.variant, ChipVariant::Aht21);
+        assert_eq!(dump.timings, Timings::AHT21);
+        sensor_instance.i2c.done();
+    }
+
+    #[test]
+    fn dump_without_bus()
+    {
+        let expected = [
+            I2cTransaction::write(SENSOR_ADDR, vec![commands::READ_STATUS])
+                .with_error(embedded_hal_mock::MockError::Io(std::io::ErrorKind::Other)),
+        ];
+
+        let mut sensor_instance = Sensor::new(I2cMock::new(&expected), SENSOR_ADDR);
+        let dump = sensor_instance.diagnostic_dump();
+        assert_eq!(dump.status, None);
+        assert_eq!(dump.state, DriverState::Uninitialized);
+        assert_eq!(dump.last_frame, None);
+        assert_eq!(dump.last_crc_good, None);
+        sensor_instance.i2c.done();
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serializable()
+    {
+        fn is_serde<T: serde::Serialize + serde::de::DeserializeOwned>() {}
+        is_serde::<DiagnosticDump>();
+    }
+}
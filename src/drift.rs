@@ -0,0 +1,223 @@
+/*
+ * Filename: drift.rs
+ * Description: Long horizon drift estimate of the humidity channel. Once
+ * a night, when the room has settled, the night's readings are compared
+ * against a reference model, e.g. a climate controlled room's setpoint or
+ * a trusted sensor next to this one. A moving average of the differences
+ * gives the drift, so maintenance hears about it before the readings go
+ * out of spec.
+ *
+ * The nights come in as `IntervalRecord`s, a `Decimator` with a night long
+ * interval gives them.
+ */
+
+use crate::aggregate::IntervalRecord;
+use crate::core::units::{Celsius, RelativeHumidity};
+
+///What the humidity should be, given the temperature.
+pub trait ReferenceModel {
+    fn expected_humidity(&self, temperature: Celsius) -> RelativeHumidity;
+}
+
+///A fixed humidity, e.g. a climate controlled room or a salt reference.
+impl ReferenceModel for RelativeHumidity {
+    fn expected_humidity(&self, _temperature: Celsius) -> RelativeHumidity {
+        *self
+    }
+}
+
+impl<F> ReferenceModel for F
+where F: Fn(Celsius) -> RelativeHumidity,
+{
+    fn expected_humidity(&self, temperature: Celsius) -> RelativeHumidity {
+        self(temperature)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DriftConfig {
+    ///Largest humidity spread(max - min) of a night still counted as
+    ///equilibrium.
+    pub max_humidity_spread: RelativeHumidity,
+    ///Largest temperature spread of a night still counted as equilibrium.
+    pub max_temperature_spread: Celsius,
+    ///Nights with fewer readings are skipped.
+    pub min_samples: u16,
+    ///Weight of a new night in the moving average, 0.0 to 1.0.
+    pub weight: f32,
+    ///Drift beyond this is out of spec, the data sheet gives +-2% RH.
+    pub spec_limit: RelativeHumidity,
+}
+
+impl DriftConfig {
+    ///A few weeks to settle, nights within a 2% RH and 0.5C spread count.
+    pub const DEFAULT: DriftConfig = DriftConfig {
+        max_humidity_spread: RelativeHumidity(2.0),
+        max_temperature_spread: Celsius(0.5),
+        min_samples: 10,
+        weight: 0.1,
+        spec_limit: RelativeHumidity(2.0),
+    };
+}
+
+impl Default for DriftConfig {
+    fn default() -> Self {
+        DriftConfig::DEFAULT
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DriftEstimate {
+    ///Measured minus expected, positive means the sensor reads high.
+    pub drift: RelativeHumidity,
+    ///Nights that went into the estimate.
+    pub nights: u16,
+    ///The drift is beyond `DriftConfig::spec_limit`.
+    pub out_of_spec: bool,
+}
+
+///Moving average of the nightly differences against a `ReferenceModel`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DriftEstimator {
+    config: DriftConfig,
+    drift: Option<f32>,
+    nights: u16,
+    skipped: u16,
+}
+
+impl DriftEstimator {
+    pub const fn new(config: DriftConfig) -> Self {
+        DriftEstimator { config, drift: None, nights: 0, skipped: 0 }
+    }
+
+    ///Starts from an estimate stored before a power cycle.
+    pub const fn resume(config: DriftConfig, estimate: DriftEstimate) -> Self {
+        DriftEstimator {
+            config,
+            drift: Some(estimate.drift.0),
+            nights: estimate.nights,
+            skipped: 0,
+        }
+    }
+
+    pub fn config(&self) -> DriftConfig {
+        self.config
+    }
+
+    ///True if the night was settled enough to compare against a model.
+    pub fn is_equilibrium(&self, night: &IntervalRecord) -> bool {
+        let rh = night.humidity.max - night.humidity.min;
+        let t = night.temperature.max - night.temperature.min;
+        night.count >= self.config.min_samples
+            && rh <= self.config.max_humidity_spread.0
+            && t <= self.config.max_temperature_spread.0
+    }
+
+    ///Adds a night, the mean humidity is compared against `reference` at
+    ///the mean temperature. Nights that weren't settled are skipped and
+    ///give None.
+    pub fn add_night(
+        &mut self,
+        night: &IntervalRecord,
+        reference: &impl ReferenceModel,
+        ) -> Option<DriftEstimate>
+    {
+        let expected = reference.expected_humidity(Celsius(night.temperature.mean));
+        let error = night.humidity.mean - expected.0;
+        if !self.is_equilibrium(night) || error.is_nan() {
+            self.skipped = self.skipped.saturating_add(1);
+            return None;
+        }
+
+        let drift = match self.drift {
+            Some(d) => d + self.config.weight * (error - d),
+            None => error,
+        };
+        self.drift = Some(drift);
+        self.nights = self.nights.saturating_add(1);
+        self.estimate()
+    }
+
+    ///None until a night was added.
+    pub fn estimate(&self) -> Option<DriftEstimate> {
+        let drift = RelativeHumidity(self.drift?);
+        Some(DriftEstimate {
+            drift,
+            nights: self.nights,
+            out_of_spec: drift.abs_delta(RelativeHumidity(0.0)) > self.config.spec_limit,
+        })
+    }
+
+    ///Nights skipped as not settled.
+    pub fn skipped(&self) -> u16 {
+        self.skipped
+    }
+
+    pub fn reset(&mut self) {
+        *self = DriftEstimator::new(self.config);
+    }
+}
+
+#[cfg(test)]
+mod drift_tests {
+    use super::*;
+    use crate::aggregate::Stats;
+    use crate::core::measurement::SensorId;
+
+    fn night(t: f32, rh: f32, rh_spread: f32) -> IntervalRecord {
+        IntervalRecord {
+            id: SensorId(0),
+            start_ms: 0,
+            count: 60,
+            temperature: Stats { min: t - 0.1, max: t + 0.1, mean: t },
+            humidity: Stats { min: rh, max: rh + rh_spread, mean: rh + rh_spread / 2.0 },
+        }
+    }
+
+    #[test]
+    fn converges_on_the_drift()
+    {
+        let config = DriftConfig { weight: 0.5, ..DriftConfig::DEFAULT };
+        let mut est = DriftEstimator::new(config);
+        assert_eq!(est.estimate(), None);
+
+        let reference = RelativeHumidity(45.0);
+        let first = est.add_night(&night(21.0, 46.0, 0.0), &reference).unwrap();
+        assert_eq!(first.drift, RelativeHumidity(1.0));
+        assert!(!first.out_of_spec);
+
+        for _ in 0..10 {
+            est.add_night(&night(21.0, 48.0, 0.0), &reference);
+        }
+        let e = est.estimate().unwrap();
+        assert!(e.drift.abs_delta(RelativeHumidity(3.0)) < RelativeHumidity(0.01));
+        assert_eq!(e.nights, 11);
+        assert!(e.out_of_spec);
+
+        let resumed = DriftEstimator::resume(config, e);
+        assert_eq!(resumed.estimate(), Some(e));
+    }
+
+    #[test]
+    fn skips_unsettled_nights()
+    {
+        let mut est = DriftEstimator::new(DriftConfig::DEFAULT);
+        assert_eq!(est.add_night(&night(21.0, 40.0, 5.0), &RelativeHumidity(40.0)), None);
+
+        let mut short = night(21.0, 40.0, 0.0);
+        short.count = 3;
+        assert_eq!(est.add_night(&short, &RelativeHumidity(40.0)), None);
+        assert_eq!(est.skipped(), 2);
+        assert_eq!(est.estimate(), None);
+    }
+
+    #[test]
+    fn temperature_dependent_model()
+    {
+        //A sealed box: the humidity falls by about 3% RH per degree C.
+        let model = |t: Celsius| RelativeHumidity(50.0 - 3.0 * (t.0 - 20.0));
+        let mut est = DriftEstimator::new(DriftConfig::DEFAULT);
+        let e = est.add_night(&night(22.0, 43.5, 0.0), &model).unwrap();
+        assert!(e.drift.abs_delta(RelativeHumidity(-0.5)) < RelativeHumidity(0.01));
+    }
+}
@@ -0,0 +1,174 @@
+/*
+ * Filename: fingerprint.rs
+ * Description: Heuristics telling genuine AHT20s from the clones that have
+ * been seen misbehaving on CRC, timing or reserved bits. Only benign
+ * commands are used, a single measurement and a few status reads.
+ */
+
+use crate::{Aht2xTransport, Delay, Error, Indicator, InitializedSensor};
+use crate::core::data::SensorData;
+use crate::core::sensor_status::{SensorStatus, CALENABLED_BM};
+
+///Status bits 2:0, reserved in the data sheet and zero on genuine parts.
+pub const RESERVED_LOW_BM: u8 = 0b0000_0111;
+///Status bit 4, reserved in the data sheet but set on genuine parts.
+pub const RESERVED_BIT4_BM: u8 = 1 << 4;
+
+///Misbehaviour seen on clone parts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClonePattern {
+    ///The reserved low status bits are set.
+    ReservedBitsSet,
+    ///The CRC byte doesn't match, or isn't sent at all.
+    BadCrc,
+    ///The sensor didn't report busy straight after a trigger.
+    NoBusyAfterTrigger,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceClass {
+    ///Behaved like the genuine parts we have tested.
+    GenuineLike,
+    KnownClone(ClonePattern),
+    ///None of the known patterns, but not genuine-like either.
+    Unknown,
+}
+
+///What `fingerprint()` saw, attach it to bug reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FingerprintReport {
+    pub class: DeviceClass,
+    ///Status byte before the trigger.
+    pub idle_status: SensorStatus,
+    ///Status byte straight after the trigger.
+    pub trigger_status: SensorStatus,
+    pub crc_good: bool,
+}
+
+impl FingerprintReport {
+    ///Classifies the probe results, the clone patterns are checked first.
+    pub fn classify(
+        idle_status: SensorStatus,
+        trigger_status: SensorStatus,
+        crc_good: bool,
+        ) -> Self
+    {
+        let genuine_bits = RESERVED_BIT4_BM | CALENABLED_BM;
+        let class = if (idle_status.status & RESERVED_LOW_BM) != 0 {
+            DeviceClass::KnownClone(ClonePattern::ReservedBitsSet)
+        } else if !crc_good {
+            DeviceClass::KnownClone(ClonePattern::BadCrc)
+        } else if !trigger_status.is_busy() {
+            DeviceClass::KnownClone(ClonePattern::NoBusyAfterTrigger)
+        } else if idle_status.is_normal_mode()
+            && (idle_status.status & genuine_bits) == genuine_bits
+        {
+            DeviceClass::GenuineLike
+        } else {
+            DeviceClass::Unknown
+        };
+
+        FingerprintReport { class, idle_status, trigger_status, crc_good }
+    }
+}
+
+impl<E, I2C, L> InitializedSensor<I2C, L>
+where I2C: Aht2xTransport<Error = E>,
+      L: Indicator,
+{
+    ///Runs the probes and classifies the device: reads the idle status,
+    ///triggers a measurement, reads the status straight away and then the
+    ///frame once the conversion is done.
+    pub fn fingerprint(
+        &mut self,
+        delay: &mut impl Delay,
+        ) -> Result<FingerprintReport, Error<E>>
+    {
+        let idle_status = self.sensor.read_status()?;
+        self.trigger_measurement()?;
+        let trigger_status = self.sensor.read_status()?;
+
+        delay.delay_ms(self.sensor.timings.measure_typ_ms);
+
+        let mut sd = SensorData::new();
+        let mut retry = self.measure_deadline();
+        self.poll_until_idle(delay, &mut retry, |sensor| {
+            sensor.i2c.read_frame(sensor.address, &mut sd.bytes)
+                .map_err(Error::I2C)?;
+            Ok(sd.frame().status())
+        })?;
+
+        Ok(FingerprintReport::classify(idle_status, trigger_status, sd.is_crc_good()))
+    }
+}
+
+#[cfg(test)]
+mod fingerprint_tests {
+    use embedded_hal_mock::i2c::{
+        Mock as I2cMock,
+        Transaction as I2cTransaction,
+    };
+
+    use super::*;
+    use crate::core::commands;
+    use crate::{Sensor, SENSOR_ADDR, TRIG_MEASURE_PARAM0, TRIG_MEASURE_PARAM1};
+
+    const FRAME: [u8; 7] = [0x18, 0x7E, 0x51, 0x65, 0xD4, 0xA0, 0xDA];
+
+    fn probe(idle: u8, after_trigger: u8, frame: [u8; 7]) -> FingerprintReport {
+        let read_status = I2cTransaction::write(SENSOR_ADDR, vec![commands::READ_STATUS]);
+        let expected = [
+            read_status.clone(),
+            I2cTransaction::read(SENSOR_ADDR, vec![idle]),
+            I2cTransaction::write(
+                SENSOR_ADDR,
+                vec![commands::TRIG_MESSURE, TRIG_MEASURE_PARAM0, TRIG_MEASURE_PARAM1],
+                ),
+            read_status,
+            I2cTransaction::read(SENSOR_ADDR, vec![after_trigger]),
+            I2cTransaction::read(SENSOR_ADDR, frame.to_vec()),
+        ];
+
+        let sensor_instance = Sensor::new(I2cMock::new(&expected), SENSOR_ADDR);
+        let mut inited_sensor = InitializedSensor { sensor: sensor_instance };
+        let mut mock_delay = embedded_hal_mock::delay::MockNoop;
+        let report = inited_sensor.fingerprint(&mut mock_delay).unwrap();
+        inited_sensor.sensor.i2c.done();
+        report
+    }
+
+    #[test]
+    fn genuine_like()
+    {
+        let r = probe(0x18, 0x98, FRAME);
+        assert_eq!(r.class, DeviceClass::GenuineLike);
+        assert!(r.crc_good);
+    }
+
+    #[test]
+    fn clone_patterns()
+    {
+        let r = probe(0x18, 0x18, FRAME);
+        assert_eq!(r.class, DeviceClass::KnownClone(ClonePattern::NoBusyAfterTrigger));
+
+        let mut bad = FRAME;
+        bad[6] = 0xFF;
+        let r = probe(0x18, 0x98, bad);
+        assert_eq!(r.class, DeviceClass::KnownClone(ClonePattern::BadCrc));
+
+        let r = probe(0x1C, 0x98, FRAME);
+        assert_eq!(r.class, DeviceClass::KnownClone(ClonePattern::ReservedBitsSet));
+    }
+
+    #[test]
+    fn unknown()
+    {
+        //Calibrated and well behaved, but bit 4 isn't set.
+        let r = FingerprintReport::classify(
+            SensorStatus::new(0x08),
+            SensorStatus::new(0x88),
+            true,
+            );
+        assert_eq!(r.class, DeviceClass::Unknown);
+    }
+}
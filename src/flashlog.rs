@@ -0,0 +1,193 @@
+/*
+ * Filename: flashlog.rs
+ * Description: Fixed size, CRC protected records of `TimedMeasurement`s for
+ * append only ring logs on raw flash or EEPROM. A record is 24 bytes, a
+ * multiple of the usual 4/8 byte flash write granularity:
+ *
+ *   0      marker, `LOG_RECORD_MARKER`
+ *   1..5   sequence number, u32 little endian
+ *   5..21  the `TimedMeasurement` bytes
+ *   21     reserved, 0
+ *   22..24 CRC-16/CCITT-FALSE over bytes 0..22, little endian
+ *
+ * A slot of all 0xFF is erased. After a reset the log is scanned to find
+ * the newest record and carry on writing after it.
+ */
+
+use crate::record::{TimedMeasurement, TIMED_MEASUREMENT_LEN};
+
+pub const LOG_RECORD_LEN: usize = 24;
+pub const LOG_RECORD_MARKER: u8 = 0xA5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordError {
+    ///Never written since the last erase.
+    Erased,
+    ///Bad marker or CRC, e.g. a write cut short by a power loss.
+    Corrupt,
+    ///Valid CRC but a `TimedMeasurement` layout this crate doesn't know.
+    UnknownVersion,
+}
+
+fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for &b in bytes {
+        crc ^= (b as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+pub fn encode_record(seq: u32, m: &TimedMeasurement) -> [u8; LOG_RECORD_LEN] {
+    let mut r = [0u8; LOG_RECORD_LEN];
+    r[0] = LOG_RECORD_MARKER;
+    r[1..5].copy_from_slice(&seq.to_le_bytes());
+    r[5..5 + TIMED_MEASUREMENT_LEN].copy_from_slice(&m.to_bytes());
+    let crc = crc16(&r[..22]);
+    r[22..24].copy_from_slice(&crc.to_le_bytes());
+    r
+}
+
+///Gives the sequence number and the measurement back.
+pub fn decode_record(r: &[u8; LOG_RECORD_LEN]) -> Result<(u32, TimedMeasurement), RecordError> {
+    if r.iter().all(|&b| b == 0xFF) {
+        return Err(RecordError::Erased);
+    }
+    if r[0] != LOG_RECORD_MARKER || crc16(&r[..22]).to_le_bytes() != [r[22], r[23]] {
+        return Err(RecordError::Corrupt);
+    }
+    let seq = u32::from_le_bytes([r[1], r[2], r[3], r[4]]);
+    let mut m = [0u8; TIMED_MEASUREMENT_LEN];
+    m.copy_from_slice(&r[5..5 + TIMED_MEASUREMENT_LEN]);
+    let m = TimedMeasurement::from_bytes(&m).ok_or(RecordError::UnknownVersion)?;
+    Ok((seq, m))
+}
+
+fn slot(region: &[u8], i: usize) -> &[u8; LOG_RECORD_LEN] {
+    region[i * LOG_RECORD_LEN..(i + 1) * LOG_RECORD_LEN].try_into().unwrap()
+}
+
+///What a scan of a log region found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LogScan {
+    ///Slot to write the next record to, the one after the newest.
+    pub next_slot: usize,
+    ///Sequence number for the next record.
+    pub next_seq: u32,
+    pub valid: usize,
+    pub corrupt: usize,
+    pub erased: usize,
+}
+
+///Scans a region of `LOG_RECORD_LEN` slots, a partial slot at the end is
+///ignored. The newest record is the one with the highest sequence number,
+///compared with wrap around.
+pub fn scan_log(region: &[u8]) -> LogScan {
+    let slots = region.len() / LOG_RECORD_LEN;
+    let mut s = LogScan::default();
+    let mut newest: Option<(usize, u32)> = None;
+
+    for i in 0..slots {
+        match decode_record(slot(region, i)) {
+            Ok((seq, _)) => {
+                s.valid += 1;
+                let newer = match newest {
+                    None => true,
+                    Some((_, n)) => (seq.wrapping_sub(n) as i32) > 0,
+                };
+                if newer {
+                    newest = Some((i, seq));
+                }
+            },
+            Err(RecordError::Erased) => s.erased += 1,
+            Err(_) => s.corrupt += 1,
+        }
+    }
+
+    if let Some((i, seq)) = newest {
+        s.next_slot = (i + 1) % slots;
+        s.next_seq = seq.wrapping_add(1);
+    }
+    s
+}
+
+///The valid records of a ring log from the oldest to the newest.
+pub fn log_records(region: &[u8]) -> impl Iterator<Item = (u32, TimedMeasurement)> + '_ {
+    let slots = region.len() / LOG_RECORD_LEN;
+    let start = scan_log(region).next_slot;
+    (0..slots)
+        .map(move |i| (start + i) % slots)
+        .filter_map(move |i| decode_record(slot(region, i)).ok())
+}
+
+#[cfg(test)]
+mod flashlog_tests {
+    use super::*;
+
+    fn tm(timestamp: i64) -> TimedMeasurement {
+        TimedMeasurement {
+            timestamp,
+            id: 1,
+            temperature_centi: 2288,
+            humidity_centi: 4934,
+            quality: 0,
+        }
+    }
+
+    fn write(region: &mut [u8], slot: usize, seq: u32) {
+        let r = encode_record(seq, &tm(seq as i64 * 1000));
+        region[slot * LOG_RECORD_LEN..(slot + 1) * LOG_RECORD_LEN].copy_from_slice(&r);
+    }
+
+    #[test]
+    fn round_trip()
+    {
+        let r = encode_record(7, &tm(42));
+        assert_eq!(r[0], LOG_RECORD_MARKER);
+        assert_eq!(decode_record(&r), Ok((7, tm(42))));
+
+        assert_eq!(decode_record(&[0xFF; LOG_RECORD_LEN]), Err(RecordError::Erased));
+
+        let mut torn = r;
+        torn[10] ^= 0x01;
+        assert_eq!(decode_record(&torn), Err(RecordError::Corrupt));
+
+        //CCITT-FALSE check value.
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+    }
+
+    #[test]
+    fn ring_scan()
+    {
+        let mut region = [0xFFu8; 4 * LOG_RECORD_LEN];
+        assert_eq!(scan_log(&region), LogScan { erased: 4, ..LogScan::default() });
+
+        write(&mut region, 0, 10);
+        write(&mut region, 1, 11);
+        let s = scan_log(&region);
+        assert_eq!((s.next_slot, s.next_seq, s.valid, s.erased), (2, 12, 2, 2));
+
+        //Wrapped, slot 0 was overwritten and slot 2 is torn.
+        write(&mut region, 2, 12);
+        write(&mut region, 3, 13);
+        write(&mut region, 0, 14);
+        region[2 * LOG_RECORD_LEN + 8] ^= 0xFF;
+        let s = scan_log(&region);
+        assert_eq!((s.next_slot, s.next_seq, s.corrupt), (1, 15, 1));
+
+        let seqs: Vec<u32> = log_records(&region).map(|(seq, _)| seq).collect();
+        assert_eq!(seqs, [11, 13, 14]);
+    }
+
+    #[test]
+    fn sequence_wraps()
+    {
+        let mut region = [0xFFu8; 2 * LOG_RECORD_LEN];
+        write(&mut region, 0, u32::MAX);
+        write(&mut region, 1, 0);
+        let s = scan_log(&region);
+        assert_eq!((s.next_slot, s.next_seq), (0, 1));
+    }
+}
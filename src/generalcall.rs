@@ -0,0 +1,91 @@
+/*
+ * Filename: generalcall.rs
+ * Description: The I2C general call reset, 0x06 written to address 0x00.
+ * Refused unless `Sensor::allow_general_call()` turned it on.
+ *
+ * WARNING: every device on the bus that implements general call resets,
+ * not just the sensor. EEPROMs, PMICs and other controllers can lose their
+ * state or abort a write in progress. Only allow it on boards
+ * where the sensor is alone on its bus, or everything else on it is known
+ * to ignore or survive the reset. `soft_reset()` is the tool to reach for
+ * first, this is for a sensor that no longer answers on its own address.
+ */
+
+use crate::{Aht2xTransport, Delay, DriverState, Error, Indicator, JournalEvent, Sensor};
+
+///The reserved address every device listening for general calls answers.
+pub const GENERAL_CALL_ADDR: u8 = 0x00;
+///The general call "reset and write programmable part of slave address".
+pub const GENERAL_CALL_RESET: u8 = 0x06;
+
+impl<E, I2C, L> Sensor<I2C, L>
+where I2C: Aht2xTransport<Error = E>,
+      L: Indicator,
+{
+    ///Lets `general_call_reset()` through, off by default. Only for buses
+    ///where resetting every device is safe, see the warning in
+    ///`generalcall.rs`.
+    pub fn allow_general_call(&mut self, allow: bool) {
+        self.general_call = allow;
+    }
+
+    ///Resets EVERY device on the bus that implements general call, see the
+    ///warning in `generalcall.rs`. Waits the power on time afterwards, the
+    ///sensor has to be initialized again with `init()`. Without
+    ///`allow_general_call()` it's `Error::GeneralCallDisabled` and nothing
+    ///is sent.
+    ///
+    ///The AHT20 data sheet doesn't mention general call, if the sensor
+    ///ignores it this still succeeds as long as some device acknowledged.
+    pub fn general_call_reset(&mut self, delay: &mut impl Delay) -> Result<(), Error<E>> {
+        if !self.general_call {
+            return Err(Error::GeneralCallDisabled);
+        }
+        self.take_sequence_number();
+        self.status_cache = None;
+        self.state = DriverState::Uninitialized;
+        let r = self.i2c.write_frame(GENERAL_CALL_ADDR, &[GENERAL_CALL_RESET])
+            .map_err(Error::I2C);
+        if r.is_ok() {
+            delay.delay_ms(self.timings.power_on_idle_ms);
+        }
+        self.journal_result(Some(JournalEvent::Reset), r)
+    }
+}
+
+#[cfg(test)]
+mod generalcall_tests {
+    use embedded_hal_mock::i2c::{
+        Mock as I2cMock,
+        Transaction as I2cTransaction,
+    };
+    use embedded_hal_mock::MockError;
+
+    use super::*;
+    use crate::{ErrorKind, SENSOR_ADDR};
+
+    #[test]
+    fn reset_all()
+    {
+        let expected = [
+            I2cTransaction::write(GENERAL_CALL_ADDR, vec![GENERAL_CALL_RESET]),
+            I2cTransaction::write(GENERAL_CALL_ADDR, vec![GENERAL_CALL_RESET])
+                .with_error(MockError::Io(std::io::ErrorKind::Other)),
+        ];
+        let mut sensor = Sensor::new(I2cMock::new(&expected), SENSOR_ADDR);
+        let mut delay = embedded_hal_mock::delay::MockNoop;
+        sensor.state = DriverState::Idle;
+        assert_eq!(sensor.general_call_reset(&mut delay), Err(Error::GeneralCallDisabled));
+        assert_eq!(sensor.state, DriverState::Idle);
+        sensor.allow_general_call(true);
+
+        assert!(sensor.general_call_reset(&mut delay).is_ok());
+        assert_eq!(sensor.state, DriverState::Uninitialized);
+        assert_eq!(sensor.journal().latest().unwrap().event, JournalEvent::Reset);
+
+        //Nobody acknowledged.
+        assert!(matches!(sensor.general_call_reset(&mut delay), Err(Error::I2C(_))));
+        assert_eq!(sensor.journal().latest().unwrap().event, JournalEvent::Error(ErrorKind::I2C));
+        sensor.i2c.done();
+    }
+}
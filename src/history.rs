@@ -0,0 +1,163 @@
+/*
+ * Filename: history.rs
+ * Description: The last two complete frames, kept by the sensor when asked
+ * to. Enough for deltas between readings and for spotting a sensor that
+ * keeps sending the same frame, which is how a stuck part shows up.
+ *
+ * Reading faster than the sensor converts (cyclic mode, aggressive
+ * polling) gives back the previous frame, see `DuplicatePolicy`.
+ */
+
+use crate::core::frame::Frame;
+
+///What `measure()` does with a duplicate frame: one with the same raw
+///values as the last good frame, read without the sensor ever reporting
+///busy in between. That means no conversion ran since the last reading.
+///See `Sensor::set_duplicate_policy()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DuplicatePolicy {
+    ///Return it with `Quality::DUPLICATE` set.
+    Flag,
+    ///Don't return it, give `Error::DuplicateFrame`.
+    Suppress,
+}
+
+///The newest and the one before it, both complete (not busy) with a good
+///CRC. See `Sensor::enable_frame_history()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FrameHistory {
+    latest: Option<Frame>,
+    previous: Option<Frame>,
+}
+
+impl FrameHistory {
+    pub const fn new() -> Self {
+        FrameHistory { latest: None, previous: None }
+    }
+
+    pub fn push(&mut self, frame: Frame) {
+        self.previous = self.latest.replace(frame);
+    }
+
+    pub fn latest(&self) -> Option<Frame> {
+        self.latest
+    }
+
+    pub fn previous(&self) -> Option<Frame> {
+        self.previous
+    }
+
+    ///True if the last two frames carry the same readings. A real sensor
+    ///practically never repeats both 20bit values.
+    pub fn is_duplicate(&self) -> bool {
+        match (self.latest, self.previous) {
+            (Some(a), Some(b)) => a.same_readings(&b),
+            _ => false,
+        }
+    }
+
+    ///Latest minus previous raw humidity, None until there are two frames.
+    pub fn humidity_raw_delta(&self) -> Option<i32> {
+        let (a, b) = (self.latest?, self.previous?);
+        Some(a.humidity_raw() as i32 - b.humidity_raw() as i32)
+    }
+
+    ///Latest minus previous raw temperature.
+    pub fn temperature_raw_delta(&self) -> Option<i32> {
+        let (a, b) = (self.latest?, self.previous?);
+        Some(a.temperature_raw() as i32 - b.temperature_raw() as i32)
+    }
+
+    pub fn clear(&mut self) {
+        *self = FrameHistory::new();
+    }
+}
+
+#[cfg(test)]
+mod history_tests {
+    use super::*;
+    use crate::{encode_frame, Celsius, RelativeHumidity, Sensor, SimulatedSensor, SENSOR_ADDR};
+
+    #[test]
+    fn deltas_and_duplicates()
+    {
+        let mut h = FrameHistory::new();
+        assert_eq!(h.temperature_raw_delta(), None);
+        assert!(!h.is_duplicate());
+
+        let a = encode_frame(0x18, Celsius(20.0), RelativeHumidity(40.0)).frame();
+        let b = encode_frame(0x18, Celsius(20.5), RelativeHumidity(39.0)).frame();
+        h.push(a);
+        assert!(!h.is_duplicate());
+        h.push(b);
+        assert_eq!(h.previous(), Some(a));
+        assert_eq!(h.latest(), Some(b));
+        assert!(h.temperature_raw_delta().unwrap() > 0);
+        assert!(h.humidity_raw_delta().unwrap() < 0);
+
+        h.push(b);
+        assert!(h.is_duplicate());
+        assert_eq!(h.temperature_raw_delta(), Some(0));
+    }
+
+    #[test]
+    fn kept_by_the_sensor()
+    {
+        let mut sim = SimulatedSensor::new(Celsius(20.0), RelativeHumidity(40.0));
+        sim.busy_reads = 1;
+        let sensor = Sensor::new(sim, SENSOR_ADDR);
+        let mut delay = embedded_hal_mock::delay::MockNoop;
+
+        let mut inited = sensor.init(&mut delay).unwrap();
+        inited.measure(&mut delay).unwrap();
+        assert_eq!(inited.sensor.frame_history(), None);
+
+        inited.sensor.enable_frame_history();
+        inited.measure(&mut delay).unwrap();
+        inited.sensor.i2c.temperature = Celsius(21.0);
+        inited.measure(&mut delay).unwrap();
+        let h = inited.sensor.frame_history().unwrap();
+        assert!(!h.is_duplicate());
+        assert!(!h.latest().unwrap().status().is_busy());
+
+        //Bad frames aren't kept.
+        inited.sensor.i2c.corrupt_crc = true;
+        assert!(inited.measure(&mut delay).is_err());
+        assert_eq!(inited.sensor.frame_history(), Some(h));
+
+        inited.sensor.i2c.corrupt_crc = false;
+        inited.measure(&mut delay).unwrap();
+        assert!(inited.sensor.frame_history().unwrap().is_duplicate());
+    }
+
+    #[test]
+    fn duplicate_policy()
+    {
+        use crate::{Error, Quality};
+
+        let sim = SimulatedSensor::new(Celsius(20.0), RelativeHumidity(40.0));
+        let sensor = Sensor::new(sim, SENSOR_ADDR);
+        let mut delay = embedded_hal_mock::delay::MockNoop;
+        let mut inited = sensor.init(&mut delay).unwrap();
+
+        inited.sensor.set_duplicate_policy(Some(DuplicatePolicy::Flag));
+        assert!(inited.measure(&mut delay).unwrap().quality.is_good());
+        let m = inited.measure(&mut delay).unwrap();
+        assert_eq!(m.quality, Quality::DUPLICATE);
+        assert_eq!(inited.sensor.duplicates(), 1);
+
+        //Seen busy, so a conversion did run and the repeat is genuine.
+        inited.sensor.i2c.busy_reads = 1;
+        assert!(inited.measure(&mut delay).unwrap().quality.is_good());
+
+        inited.sensor.i2c.busy_reads = 0;
+        inited.sensor.set_duplicate_policy(Some(DuplicatePolicy::Suppress));
+        assert_eq!(inited.measure(&mut delay).err(), Some(Error::DuplicateFrame));
+        inited.sensor.i2c.humidity = RelativeHumidity(41.0);
+        assert!(inited.measure(&mut delay).unwrap().quality.is_good());
+        assert_eq!(inited.sensor.duplicates(), 2);
+    }
+}
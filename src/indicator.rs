@@ -0,0 +1,69 @@
+/*
+ * Filename: indicator.rs
+ * Description: A status LED (or buzzer, or display icon) driven by the
+ * driver itself, see `Sensor::with_indicator()`. The driver shows busy
+ * when it starts an init or a measurement, and ok or error when the
+ * operation finishes, so the application doesn't track that itself.
+ */
+
+///What the driver wants shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Signal {
+    ///The last operation worked.
+    Ok,
+    ///An init or a measurement is under way.
+    Busy,
+    ///The last operation failed, or the sensor is faulted.
+    Error,
+}
+
+///Shows the driver's signals, e.g. as LED patterns. Closures taking a
+///`Signal` work as they are.
+pub trait Indicator {
+    fn show(&mut self, signal: Signal);
+}
+
+///The default, shows nothing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NoIndicator;
+
+impl Indicator for NoIndicator {
+    fn show(&mut self, _signal: Signal) {}
+}
+
+impl<F: FnMut(Signal)> Indicator for F {
+    fn show(&mut self, signal: Signal) {
+        self(signal)
+    }
+}
+
+#[cfg(test)]
+mod indicator_tests {
+    use super::*;
+    use crate::{Celsius, RelativeHumidity, Sensor, SimulatedSensor, SENSOR_ADDR};
+
+    #[test]
+    fn driven_by_the_driver()
+    {
+        let mut shown = Vec::new();
+        let sim = SimulatedSensor::new(Celsius(20.0), RelativeHumidity(40.0));
+        let sensor = Sensor::with_indicator(sim, SENSOR_ADDR, |s| shown.push(s));
+        let mut delay = embedded_hal_mock::delay::MockNoop;
+
+        let mut inited = sensor.init(&mut delay).unwrap();
+        inited.measure(&mut delay).unwrap();
+        inited.sensor.i2c.corrupt_crc = true;
+        assert!(inited.measure(&mut delay).is_err());
+
+        let mut sensor = inited.downgrade();
+        sensor.set_fault_limit(Some(1));
+        sensor.address = 0x39;
+        let sensor = sensor.init(&mut delay).err().unwrap().sensor;
+        assert!(sensor.init(&mut delay).is_err());
+
+        use Signal::*;
+        assert_eq!(shown, [Busy, Ok, Busy, Ok, Busy, Error, Busy, Error, Error]);
+    }
+}
@@ -0,0 +1,200 @@
+/*
+ * Filename: journal.rs
+ * Description: A tiny ring buffer of the last driver events, so when a
+ * field unit comes back the maintenance tool can read why the sensor
+ * channel went quiet.
+ */
+
+pub use crate::core::error_kind::ErrorKind;
+use crate::Error;
+
+///Number of entries kept by the sensor's journal.
+pub const JOURNAL_LEN: usize = 8;
+
+impl<E> Error<E> {
+    ///`ErrorKind::code()` of the error.
+    pub fn code(&self) -> u8 {
+        self.kind().code()
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::I2C(_) => ErrorKind::I2C,
+            Error::InvalidChecksum => ErrorKind::InvalidChecksum,
+            Error::UnexpectedBusy => ErrorKind::UnexpectedBusy,
+            Error::Internal => ErrorKind::Internal,
+            Error::DeviceTimeOut => ErrorKind::DeviceTimeOut,
+            Error::TooSoon => ErrorKind::TooSoon,
+            Error::UnexpectedStatus(_) => ErrorKind::UnexpectedStatus,
+            Error::BudgetExceeded => ErrorKind::BudgetExceeded,
+            Error::Faulted => ErrorKind::Faulted,
+            Error::DuplicateFrame => ErrorKind::DuplicateFrame,
+            Error::VerificationFailed => ErrorKind::VerificationFailed,
+            Error::GeneralCallDisabled => ErrorKind::GeneralCallDisabled,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum JournalEvent {
+    Init,
+    Calibrated,
+    Reset,
+    Error(ErrorKind),
+    ///`Sensor::set_bus_frequency()` was given a clock the sensor doesn't
+    ///support.
+    BusOutOfSpec,
+}
+
+impl JournalEvent {
+    ///A stable number for the event, the error's code in the low byte and
+    ///0x01 in the high byte for errors.
+    pub const fn code(self) -> u16 {
+        match self {
+            JournalEvent::Init => 0x0001,
+            JournalEvent::Calibrated => 0x0002,
+            JournalEvent::Reset => 0x0003,
+            JournalEvent::BusOutOfSpec => 0x0004,
+            JournalEvent::Error(kind) => 0x0100 | kind.code() as u16,
+        }
+    }
+
+    pub const fn from_code(code: u16) -> Option<Self> {
+        Some(match code {
+            0x0001 => JournalEvent::Init,
+            0x0002 => JournalEvent::Calibrated,
+            0x0003 => JournalEvent::Reset,
+            0x0004 => JournalEvent::BusOutOfSpec,
+            0x0100..=0x01FF => match ErrorKind::from_code(code as u8) {
+                Some(kind) => JournalEvent::Error(kind),
+                None => return None,
+            },
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct JournalEntry {
+    ///Counts up with every entry, shows how many were overwritten.
+    pub seq: u16,
+    ///The last time the driver was told, see `Sensor::tick()`. None if it
+    ///never was.
+    pub at_ms: Option<u32>,
+    pub event: JournalEvent,
+}
+
+///Ring buffer of the last `N` entries, the oldest are overwritten.
+#[derive(Debug, Clone)]
+pub struct Journal<const N: usize> {
+    entries: [Option<JournalEntry>; N],
+    next: usize,
+    seq: u16,
+}
+
+impl<const N: usize> Journal<N> {
+    pub const fn new() -> Self {
+        Journal { entries: [None; N], next: 0, seq: 0 }
+    }
+
+    pub fn push(&mut self, event: JournalEvent, at_ms: Option<u32>) {
+        if N == 0 {
+            return;
+        }
+        self.entries[self.next] = Some(JournalEntry { seq: self.seq, at_ms, event });
+        self.next = (self.next + 1) % N;
+        self.seq = self.seq.wrapping_add(1);
+    }
+
+    ///The entries from the oldest to the newest.
+    pub fn iter(&self) -> impl Iterator<Item = &JournalEntry> {
+        let (newer, older) = self.entries.split_at(self.next);
+        older.iter().chain(newer.iter()).flatten()
+    }
+
+    pub fn latest(&self) -> Option<&JournalEntry> {
+        self.iter().last()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.iter().flatten().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&mut self) {
+        *self = Journal { seq: self.seq, ..Journal::new() };
+    }
+}
+
+impl<const N: usize> Default for Journal<N> {
+    fn default() -> Self {
+        Journal::new()
+    }
+}
+
+#[cfg(test)]
+mod journal_tests {
+    use super::*;
+
+    #[test]
+    fn ring_order()
+    {
+        let mut j: Journal<3> = Journal::new();
+        assert!(j.is_empty());
+
+        j.push(JournalEvent::Init, None);
+        j.push(JournalEvent::Reset, Some(10));
+        assert_eq!(j.len(), 2);
+        assert_eq!(j.latest().unwrap().event, JournalEvent::Reset);
+
+        j.push(JournalEvent::Error(ErrorKind::I2C), Some(20));
+        j.push(JournalEvent::Error(ErrorKind::DeviceTimeOut), Some(30));
+        let seqs: Vec<u16> = j.iter().map(|e| e.seq).collect();
+        assert_eq!(seqs, [1, 2, 3]);
+        assert_eq!(j.latest().unwrap().at_ms, Some(30));
+
+        j.clear();
+        assert!(j.is_empty());
+        j.push(JournalEvent::Init, None);
+        assert_eq!(j.latest().unwrap().seq, 4);
+    }
+
+    #[test]
+    fn codes()
+    {
+        //Pinned, these must never change.
+        assert_eq!(ErrorKind::I2C.code(), 1);
+        assert_eq!(ErrorKind::Faulted.code(), 9);
+        assert_eq!(Error::<()>::DeviceTimeOut.code(), 5);
+        assert_eq!(JournalEvent::Reset.code(), 0x0003);
+        assert_eq!(JournalEvent::Error(ErrorKind::TooSoon).code(), 0x0106);
+
+        for code in 0..=u8::MAX {
+            if let Some(kind) = ErrorKind::from_code(code) {
+                assert_eq!(kind.code(), code);
+            }
+        }
+        for code in 0..=u16::MAX {
+            if let Some(event) = JournalEvent::from_code(code) {
+                assert_eq!(event.code(), code);
+            }
+        }
+        assert_eq!(ErrorKind::from_code(0), None);
+        assert_eq!(JournalEvent::from_code(0x0100), None);
+        assert_eq!(JournalEvent::from_code(0x0005), None);
+    }
+
+    #[test]
+    fn error_kind()
+    {
+        assert_eq!(Error::I2C(5u8).kind(), ErrorKind::I2C);
+        assert_eq!(Error::<()>::UnexpectedStatus(0x58).kind(), ErrorKind::UnexpectedStatus);
+    }
+}
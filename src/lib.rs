@@ -28,7 +28,7 @@
 //!     
 //!     /*--SNIP--*/
 //!
-//!    let mut sensor_instance = aht20::Sensor::new(i2c, aht20::SENSOR_ADDR);
+//!    let sensor_instance = aht20::Sensor::new(i2c, aht20::SENSOR_ADDR);
 //!
 //!    let mut inited_sensor = sensor_instance.init(&mut delay).unwrap();
 //!
@@ -56,130 +56,808 @@
 //! The above example leaves out the actual configuration of the i2c peripheral
 //! as it's more of a uC/platform specific item.
 //!
+//! ## Blocking API
+//!
+//! The blocking, delay driven API above (`init()`, `read_sensor()`,
+//! `measure()`, `soft_reset()`, ...) waits on the `Delay` passed in, no
+//! `block_on`. It's a thin layer over the sans-IO machines in `protocol`,
+//! which `MeasurementPoll` and `AsyncSensor` drive as well, so the bus
+//! traffic, delays and errors are the same whichever way they're driven.
+//! `protocol_tests::blocking_driver_agrees` checks that for init and a
+//! measurement.
+//!
+//! Coming from 0.1: `init()` now takes the `Sensor` by value and returns
+//! an `InitializedSensor` owning it (`downgrade()` hands it back, a failed
+//! init returns it in the `InitError`). `read_sensor()` is still there,
+//! `measure()` is new and returns a converted `Measurement`.
+//!
 
-#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
 
 #[allow(unused_imports)]
 #[macro_use]
 extern crate alloc;
 
 
-use embedded_hal::blocking::{
-    i2c,
-    delay::DelayMs,
+//The protocol, parsing, CRC and conversions, nothing in there touches
+//embedded-hal. The adapters bind the driver to the HAL traits and buses.
+mod core;
+use crate::core::commands;
+#[allow(unused_imports)]
+pub use crate::core::sensor_status::SensorStatus;
+pub use crate::core::commands::Command;
+#[allow(unused_imports)]
+pub use crate::core::data::SensorData;
+pub use crate::core::convert::{
+    raw_to_celsius, raw_to_rh, raw_to_celsius_as, raw_to_rh_as, raw_to_celsius_scaled,
+    raw_to_rh_scaled, ConversionVector, Numeric, AHT20_DIVISOR, CONVERSION_VECTORS,
+    MAX_DECIMALS, MAX_RAW,
+};
+pub use crate::core::comfort::{Comfort, ComfortBands, HumidityComfort, TemperatureComfort};
+pub use crate::core::format::{
+    format_at_error, format_at_response, format_fixed, FormatError, AT_PREFIX,
+    MAX_FIXED_DECIMALS,
+};
+#[cfg(feature = "derived")]
+pub use crate::core::heat::{heat_index, humidex};
+#[cfg(any(feature = "libm", feature = "micromath"))]
+pub use crate::core::psychro::{
+    absolute_humidity, dew_point, enthalpy, humidity_at_temperature, mixing_ratio, rh_from_dew_point, rh_from_wet_bulb,
+    saturation_vapour_pressure, vapour_pressure, wet_bulb, Derived, STANDARD_PRESSURE,
+};
+pub use crate::core::units::{Celsius, Hectopascal, RelativeHumidity, Units};
+pub use crate::core::timings::{ConversionDurations, Timings};
+pub use crate::core::measurement::{Measurement, Quality, SensorId};
+pub use crate::core::params::CommandParams;
+pub use crate::core::sansio::{
+    Action, CalibrateCycle, InitCycle, Input, Machine, MeasureCycle, ProtocolError, ResetCycle,
+    WriteBuf, FRAME_LEN,
+};
+pub use crate::core::frame::Frame;
+pub use crate::core::quirks::BusQuirks;
+pub use crate::core::retry::{ExponentialBackoff, FixedRetry, NoRetry, RetryStrategy};
+use crate::core::retry::Deadline;
+pub use crate::core::specs::Specs;
+pub use crate::core::variant::{ChipVariant, AHT10_ALT_ADDR, AOSONG_ADDRESSES};
+
+pub mod protocol;
+
+mod adapters;
+#[cfg(feature = "async")]
+pub use crate::adapters::asynch::AsyncSensor;
+#[cfg(feature = "eh1")]
+pub use crate::adapters::eh1::Eh1;
+#[cfg(feature = "ffi")]
+pub use crate::adapters::ffi::{FfiBus, FfiDelay, FfiDelayMs, FfiRead, FfiWrite};
+pub use crate::adapters::shared::{BusLock, SharedBus, SharedBusError};
+#[cfg(feature = "rtic")]
+pub use crate::adapters::shared::Rtic;
+#[cfg(feature = "hid-bridge")]
+pub use crate::adapters::bridge::{BridgeError, Cp2112, Ft260, HidDevice};
+
+mod aggregate;
+pub use crate::aggregate::{Decimator, IntervalRecord, Stats, TimeWeightedMean};
+
+mod alarm;
+pub use crate::alarm::{Alarm, AlarmConfig, AlarmEvent, Debounce, Debouncer, Threshold};
+
+mod condensation;
+pub use crate::condensation::{
+    CondensationConfig, CondensationCounter, CONDENSATION_MARKER, CONDENSATION_RECORD_LEN,
 };
 
-mod sensor_status;
-#[allow(unused_imports)]
-pub use crate::sensor_status::SensorStatus;
+mod burst;
+pub use crate::burst::{humidity_response_ms, BurstSample};
 
-mod commands;
-pub use crate::commands::Command;
+mod recovery;
+pub use crate::recovery::{RecoveryPhase, RecoveryProcedure, RecoveryProgress, RecoveryStage};
+use crate::recovery::Recovery;
 
-mod data;
-#[allow(unused_imports)]
-pub use data::SensorData;
+mod drift;
+pub use crate::drift::{DriftConfig, DriftEstimate, DriftEstimator, ReferenceModel};
+
+mod clock;
+pub use crate::clock::{elapsed_ms, Clock};
+
+mod control;
+pub use crate::control::{
+    Actuator, HysteresisConfig, HysteresisController, ProcessVariable, PvScaling, PvSource,
+};
+
+#[cfg(feature = "advanced")]
+mod advanced;
+#[cfg(feature = "advanced")]
+pub use crate::advanced::{RegisterDump, CMD_MODE_PARAM, NORMAL_MODE};
+
+mod diagnostics;
+pub use crate::diagnostics::DiagnosticDump;
+
+mod fingerprint;
+pub use crate::fingerprint::{ClonePattern, DeviceClass, FingerprintReport};
+
+mod journal;
+pub use crate::journal::{ErrorKind, Journal, JournalEntry, JournalEvent, JOURNAL_LEN};
+
+mod record;
+pub use crate::record::{
+    TimedMeasurement, HUMIDITY_INVALID, TEMPERATURE_INVALID, TIMED_MEASUREMENT_LEN,
+    TIMED_MEASUREMENT_VERSION,
+};
+
+mod flashlog;
+pub use crate::flashlog::{
+    decode_record, encode_record, log_records, scan_log, LogScan, RecordError, LOG_RECORD_LEN,
+    LOG_RECORD_MARKER,
+};
+
+mod storage;
+pub use crate::storage::Storage;
+
+mod sink;
+pub use crate::sink::{LogError, RecordSink};
+
+mod timeout;
+pub use crate::timeout::{with_timeout, TimedOut, WithTimeout};
+#[cfg(feature = "async")]
+pub use crate::timeout::{AsyncTimer, TimeoutError, TimeoutI2c};
+
+mod transport;
+pub use crate::transport::{Aht2xTransport, Delay};
+
+mod transaction;
+pub use crate::transaction::{Direction, Transaction, MAX_TRANSACTION_LEN};
+
+mod recorder;
+pub use crate::recorder::RecordingTransport;
+
+#[cfg(feature = "std")]
+mod replay;
+#[cfg(feature = "std")]
+pub use crate::replay::{ReplayError, ReplayTransport};
 
+mod sim;
+pub use crate::sim::{encode_frame, SimError, SimulatedSensor, SIM_IDLE_STATUS};
 
-/// AHT20 Sensor Address
-pub const SENSOR_ADDR: u8 = 0b0011_1000; // = 0x38
+mod history;
+pub use crate::history::{DuplicatePolicy, FrameHistory};
 
-pub const STARTUP_DELAY_MS: u16 = 40;
-pub const BUSY_DELAY_MS: u16 = 20;
-pub const MEASURE_DELAY_MS: u16 = 80;
-pub const CALIBRATE_DELAY_MS: u16 = 10;
+mod indicator;
+pub use crate::indicator::{Indicator, NoIndicator, Signal};
 
-///Number retry attempts before assuming hardware issues
-pub const MAX_ATTEMPTS: usize = 3;
+mod can;
+pub use crate::can::{ByteOrder, CanSignal, PackError};
 
-/// Trig Measure Parameter 0(unknown) 
-pub const TRIG_MEASURE_PARAM0: u8 = 0x33;
-/// Trig Measure Parameter 1(unknown) 
-pub const TRIG_MEASURE_PARAM1: u8 = 0x00;
+mod scpi;
+pub use crate::scpi::{ScpiAdapter, ScpiError, SCPI_QUEUE_LEN};
 
-/// Calibration Paramter 0(unknown)
-pub const CAL_PARAM0: u8 = 0x08;
-/// Calibration Paramter 1(unknown)
-pub const CAL_PARAM1: u8 = 0x00;
+mod busspeed;
+pub use crate::busspeed::{BusSpeed, MAX_BUS_HZ, STANDARD_BUS_HZ};
+
+mod state;
+pub use crate::state::DriverState;
+
+mod redundant;
+pub use crate::redundant::{FusePolicy, PairReading, RedundantPair, Tolerance};
+
+mod watchdog;
+pub use crate::watchdog::{WatchdogDelay, WatchdogFeeder};
+
+mod generalcall;
+pub use crate::generalcall::{GENERAL_CALL_ADDR, GENERAL_CALL_RESET};
+
+mod multi;
+pub use crate::multi::{fetch_all, scan_all, trigger_all};
+
+mod scheduler;
+pub use crate::scheduler::{Scheduler, WorkId};
+
+mod probe;
+pub use crate::probe::probe_address;
+
+mod resetcheck;
+pub use crate::resetcheck::ResetVerification;
+
+mod poll;
+pub use crate::poll::{MeasurementPoll, Poll, PollState};
+
+mod progress;
+pub use crate::progress::MeasurementProgress;
+
+#[cfg(feature = "nb")]
+mod superloop;
+#[cfg(feature = "nb")]
+pub use nb;
+
+mod pipeline;
+pub use crate::pipeline::{
+    Offset, Pipeline, PipelineAlarms, PipelineConfig, Processed, MEDIAN_MAX,
+};
+
+mod reconfig;
+pub use crate::reconfig::{ConfigError, ConfigField, ConfigUpdate, RejectReason, Rejections};
+
+mod snapshot;
+pub use crate::snapshot::StateSnapshot;
+
+mod buildcfg;
+pub use crate::buildcfg::{
+    BUILD_ADDRESS, BUILD_FAULT_LIMIT, BUILD_MAX_ATTEMPTS, BUILD_VARIANT,
+};
+
+
+/// AHT20 Sensor Address, see `AOSONG_ADDRESSES` for the AHT10's second one.
+pub const SENSOR_ADDR: u8 = crate::core::variant::AHT_DEFAULT_ADDR; // = 0x38
+
+///Number retry attempts before assuming hardware issues, the AHT20 timings
+///allow this many busy polls after the typical measure time. 3 unless
+///`AHT20_MAX_ATTEMPTS` was set at build time.
+pub const MAX_ATTEMPTS: usize = BUILD_MAX_ATTEMPTS;
+
+///Times the first `init()` sends the init command again when the sensor
+///doesn't acknowledge it, `EARLY_NACK_DELAY_MS` apart. Covers an MCU that
+///boots faster than the sensor's power on window, another 40ms in all.
+pub const EARLY_NACK_RETRIES: u8 = 4;
+///Wait before each `EARLY_NACK_RETRIES` retry.
+pub const EARLY_NACK_DELAY_MS: u16 = 10;
+
+pub use crate::core::commands::{
+    CAL_CHECK_MASK, CAL_PARAM0, CAL_PARAM1, CAL_REGISTERS, REG_WRITE, TRIG_MEASURE_PARAM0,
+    TRIG_MEASURE_PARAM1,
+};
 
 
 ///Impliment Error type for the AHT on i2c
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Error<E> {
     I2C(E),
     InvalidChecksum,
     UnexpectedBusy,
     Internal,
-    DeviceTimeOut
+    DeviceTimeOut,
+    ///A measurement was triggered sooner than the configured minimum
+    ///spacing, see `Sensor::set_min_spacing()`.
+    TooSoon,
+    ///Strict mode saw a status byte that doesn't fit the driver's state,
+    ///see `Sensor::set_strict()`.
+    UnexpectedStatus(u8),
+    ///`Sensor::init_bounded()` ran out of its time budget.
+    BudgetExceeded,
+    ///The sensor is in the faulted state, see `Sensor::set_fault_limit()`.
+    Faulted,
+    ///The frame repeats the last one, see `DuplicatePolicy::Suppress`.
+    DuplicateFrame,
+    ///A reset's checks failed, see `Sensor::set_reset_verification()`.
+    VerificationFailed,
+    ///`Sensor::general_call_reset()` wasn't allowed, see
+    ///`Sensor::allow_general_call()`.
+    GeneralCallDisabled,
+}
+
+impl<E> From<ProtocolError> for Error<E> {
+    fn from(e: ProtocolError) -> Self {
+        match e {
+            ProtocolError::TimedOut => Error::DeviceTimeOut,
+            ProtocolError::Busy => Error::UnexpectedBusy,
+            ProtocolError::NotCalibrated | ProtocolError::UnexpectedInput => Error::Internal,
+        }
+    }
+}
+
+///What happens to a measurement triggered sooner than the minimum spacing.
+///The data sheet says the sensor should be measuring no more than 10% of
+///the time to keep self heating below 0.1C, with the AHT20's 80ms that is
+///one measurement every 800ms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SpacingPolicy {
+    ///Delay until the minimum spacing has passed, then trigger.
+    Wait,
+    ///Don't trigger, return `Error::TooSoon`.
+    Reject,
+    ///Trigger anyway but count it, see `Sensor::spacing_violations()`.
+    Flag,
 }
 
 
 #[allow(dead_code)]
 /// The uninitialized sensor struct, consumes an i2c instance.
 /// The fields of it are all privite as you shouldn't access them directly.
-pub struct Sensor<I2C>
-where I2C: i2c::Read + i2c::Write,
+pub struct Sensor<I2C, L = NoIndicator>
+where I2C: Aht2xTransport,
 {
     i2c: I2C,
+    indicator: L,
     address: u8,
     buffer: [u8; 4],
+    timings: Timings,
+    id: SensorId,
+    status_max_age_ms: Option<u32>,
+    status_cache: Option<(SensorStatus, u32)>,
+    min_spacing: Option<(u32, SpacingPolicy)>,
+    last_trigger_ms: Option<u32>,
+    spacing_violations: u16,
+    degrade_limit: Option<u8>,
+    consecutive_failures: u16,
+    params: CommandParams,
+    durations: ConversionDurations,
+    strict: bool,
+    status_anomalies: u16,
+    variant: ChipVariant,
+    last_frame: Option<[u8; 7]>,
+    last_retries: u16,
+    journal: Journal<JOURNAL_LEN>,
+    last_seen_ms: Option<u32>,
+    pressure: Option<Hectopascal>,
+    started_up: bool,
+    state: DriverState,
+    fault_limit: Option<u8>,
+    bus_failures: u8,
+    frames: Option<FrameHistory>,
+    quirks: BusQuirks,
+    bus_speed: Option<BusSpeed>,
+    seq: Option<u32>,
+    duplicate_policy: Option<DuplicatePolicy>,
+    last_good_frame: Option<Frame>,
+    duplicates: u16,
+    recovery: Option<Recovery>,
+    #[cfg(feature = "nb")]
+    nb_poll: MeasurementPoll,
+    reset_verification: ResetVerification,
+    calibration_check: bool,
+    general_call: bool,
 }
 
-//Impliment functions for the sensor that require the embedded-hal
-//I2C.
-impl<E, I2C> Sensor<I2C>
-where I2C: i2c::Read<Error = E> + i2c::Write<Error = E>,
+impl<I2C> Sensor<I2C>
+where I2C: Aht2xTransport,
 {
-
     ///Returns an instance of the sensor structure.
     ///It takes an i2c instance and a i2c address as input.
     ///The address itself is a pub const in the crate but is left as a 
     ///parameter to allow for alternate usage of the driver.
+    ///The AHT20 timings are used(or `BUILD_VARIANT`'s), see `set_timings()`
+    ///for other variants.
     pub fn new(i2c: I2C, address: u8) -> Self {
+        Sensor::with_indicator(i2c, address, NoIndicator)
+    }
+
+    ///`new()` with the address baked in at build time, `BUILD_ADDRESS`.
+    pub fn with_build_address(i2c: I2C) -> Self {
+        Sensor::new(i2c, BUILD_ADDRESS)
+    }
+}
+
+//Impliment functions for the sensor that require the embedded-hal
+//I2C.
+impl<E, I2C, L> Sensor<I2C, L>
+where I2C: Aht2xTransport<Error = E>,
+      L: Indicator,
+{
+    ///Same as `new()`, the driver shows its health on `indicator`.
+    pub fn with_indicator(i2c: I2C, address: u8, indicator: L) -> Self {
         let buf = [0, 0, 0, 0];
-        Sensor{i2c, address, buffer: buf}
+        Sensor{
+            i2c,
+            indicator,
+            address,
+            buffer: buf,
+            timings: BUILD_VARIANT.timings(),
+            id: SensorId(0),
+            status_max_age_ms: None,
+            status_cache: None,
+            min_spacing: None,
+            last_trigger_ms: None,
+            spacing_violations: 0,
+            degrade_limit: None,
+            consecutive_failures: 0,
+            params: CommandParams::DATASHEET,
+            durations: ConversionDurations::new(),
+            strict: false,
+            status_anomalies: 0,
+            variant: BUILD_VARIANT,
+            last_frame: None,
+            last_retries: 0,
+            journal: Journal::new(),
+            last_seen_ms: None,
+            pressure: None,
+            started_up: false,
+            calibration_check: false,
+            general_call: false,
+            state: DriverState::Uninitialized,
+            fault_limit: BUILD_FAULT_LIMIT,
+            bus_failures: 0,
+            frames: None,
+            quirks: BusQuirks::NONE,
+            bus_speed: None,
+            seq: None,
+            duplicate_policy: None,
+            last_good_frame: None,
+            duplicates: 0,
+            recovery: None,
+            #[cfg(feature = "nb")]
+            nb_poll: MeasurementPoll::new(),
+            reset_verification: ResetVerification::Quick,
+        }
+    }
+
+    ///Hands the bus back, e.g. to another driver sharing it in turn. The
+    ///sensor is left as it is, a new `Sensor` on the bus has to `init()`.
+    pub fn destroy(self) -> I2C {
+        self.i2c
+    }
+
+    ///Tags the sensor with an id that is copied into every `Measurement`.
+    pub fn set_id(&mut self, id: SensorId) {
+        self.id = id;
+    }
+
+    ///Returns the id set with `set_id()`, defaults to 0.
+    pub fn id(&self) -> SensorId {
+        self.id
+    }
+
+    ///Feeds in the air pressure from a co-located pressure sensor, it's
+    ///copied into every `Measurement` for the pressure dependent derived
+    ///values. None falls back to the standard atmosphere.
+    pub fn set_pressure(&mut self, pressure: Option<Hectopascal>) {
+        self.pressure = pressure;
+    }
+
+    pub fn pressure(&self) -> Option<Hectopascal> {
+        self.pressure
+    }
+
+    ///Numbers every read from `start` on, `measure()`, `fetch()` and
+    ///`MeasurementPoll` alike, the number is copied into the `Measurement`.
+    ///Failed reads and soft resets use up a number too, so they show as
+    ///gaps downstream. A fetch or poll finding the sensor still converting
+    ///doesn't, the read isn't over yet. Pass the last number stored before
+    ///a power cycle to carry on from it.
+    pub fn enable_sequence_numbers(&mut self, start: u32) {
+        self.seq = Some(start);
+    }
+
+    pub fn disable_sequence_numbers(&mut self) {
+        self.seq = None;
+    }
+
+    ///The number the next `measure()` gets, None if disabled.
+    pub fn next_sequence_number(&self) -> Option<u32> {
+        self.seq
+    }
+
+    //Hands out the next sequence number, wrapping at u32::MAX.
+    fn take_sequence_number(&mut self) -> Option<u32> {
+        let n = self.seq?;
+        self.seq = Some(n.wrapping_add(1));
+        Some(n)
+    }
+
+    ///Overrides the parameter bytes of the calibrate and trigger commands.
+    ///Only meant for verifying the data sheet sequences on real hardware.
+    #[cfg(feature = "experimental-params")]
+    pub fn set_command_params(&mut self, params: CommandParams) {
+        self.params = params;
+    }
+
+    ///Returns the parameter bytes in use.
+    #[cfg(feature = "experimental-params")]
+    pub fn command_params(&self) -> CommandParams {
+        self.params
+    }
+
+    ///Sets the chip variant and switches to its timing table.
+    pub fn set_variant(&mut self, variant: ChipVariant) {
+        self.variant = variant;
+        self.timings = variant.timings();
+    }
+
+    ///Returns the chip variant, defaults to the AHT20.
+    pub fn variant(&self) -> ChipVariant {
+        self.variant
+    }
+
+    ///False if a genuine part of the set variant can't be on the sensor's
+    ///address, e.g. an AHT20 at 0x39. Address translators and muxes can
+    ///make any address legitimate, so the driver only reports this.
+    pub fn address_is_valid(&self) -> bool {
+        self.variant.is_valid_address(self.address)
+    }
+
+    ///Replaces the timing table, e.g. with `ChipVariant::Aht10.timings()`.
+    pub fn set_timings(&mut self, timings: Timings) {
+        self.timings = timings;
+    }
+
+    ///Returns the timing table currently in use.
+    pub fn timings(&self) -> Timings {
+        self.timings
     }
 
     ///Initializes the AHT sensor and returns an initialized version or
     ///encapsulated sensor that gives access to more methods.
+    ///If it fails it can simply be called again on the `InitError`'s
+    ///sensor, the startup delay isn't repeated and the rest of the
+    ///sequence starts over.
+    //The sensor comes back by value in the error, no heap needed.
+    #[allow(clippy::result_large_err)]
     pub fn init(
-        &mut self,
-        delay: &mut impl DelayMs<u16>,
-        ) -> Result<InitializedSensor<I2C>, Error<E>>
+        mut self,
+        delay: &mut impl Delay,
+        ) -> Result<InitializedSensor<I2C, L>, InitError<E, I2C, L>>
     {
-        //we need a startup delay according to the datasheet.
-        delay.delay_ms(STARTUP_DELAY_MS); 
+        if let Err(error) = self.check_faulted() {
+            return Err(InitError { error, sensor: self });
+        }
+        self.state = DriverState::Initializing;
+        self.indicator.show(Signal::Busy);
+        let r = self.init_sequence(delay);
+        self.finish_init(r)
+    }
 
-       let tmp_buf = [Command::InitSensor as u8,];
-        self.i2c.write(self.address, &tmp_buf).map_err(Error::I2C)?;
+    //Sets the state after an init attempt and journals it.
+    #[allow(clippy::result_large_err)]
+    fn finish_init(
+        mut self,
+        r: Result<(), Error<E>>,
+        ) -> Result<InitializedSensor<I2C, L>, InitError<E, I2C, L>>
+    {
+        self.state = match r {
+            Ok(()) => DriverState::Idle,
+            Err(_) => DriverState::Uninitialized,
+        };
+        match self.journal_result(Some(JournalEvent::Init), r) {
+            Ok(()) => Ok(InitializedSensor {sensor: self}),
+            Err(error) => Err(InitError { error, sensor: self }),
+        }
+    }
 
-        let status = self.read_status()?;
-        if !status.is_calibration_enabled() {
-            self.calibrate(delay)?;
+    ///`init()` with a bound on its total time, for boot time watchdog
+    ///budgets. A failed init (e.g. a calibration that didn't take) is
+    ///retried up to `MAX_ATTEMPTS` times. Before each attempt the time
+    ///left of `budget_ms` on `clock` is checked against the attempt's
+    ///worst case delays, an attempt that might not fit isn't started and
+    ///`Error::BudgetExceeded` is returned instead.
+    ///
+    ///The delays of all attempts add up to `init_worst_case_ms()`, a budget
+    ///of that plus some bus time never cuts the retries short.
+    #[allow(clippy::result_large_err)]
+    pub fn init_bounded(
+        mut self,
+        delay: &mut impl Delay,
+        clock: &mut impl Clock,
+        budget_ms: u32,
+        ) -> Result<InitializedSensor<I2C, L>, InitError<E, I2C, L>>
+    {
+        if let Err(error) = self.check_faulted() {
+            return Err(InitError { error, sensor: self });
+        }
+        let start = clock.now_ms();
+        self.tick(start);
+        self.state = DriverState::Initializing;
+        self.indicator.show(Signal::Busy);
+        let mut attempts = 0;
+        let mut now = start;
+        let r = loop {
+            let left = budget_ms.saturating_sub(elapsed_ms(start, now));
+            if self.attempt_worst_case_ms() > left {
+                break Err(Error::BudgetExceeded);
+            }
+            let r = self.init_sequence(delay);
+            attempts += 1;
+            now = clock.now_ms();
+            self.tick(now);
+            match r {
+                Ok(()) => break Ok(()),
+                Err(e) if attempts >= MAX_ATTEMPTS => break Err(e),
+                Err(_) => {},
+            }
+        };
+        self.finish_init(r)
+    }
+
+    ///Sum of the delays of `init_bounded()` when the sensor NACKs the
+    ///init command until the last early retry and every attempt needs a
+    ///calibration, the bus traffic comes on top.
+    pub fn init_worst_case_ms(&self) -> u32 {
+        self.startup_worst_case_ms() + MAX_ATTEMPTS as u32 * self.retry_worst_case_ms()
+    }
+
+    //The delays only the first init attempt after power on has.
+    fn startup_worst_case_ms(&self) -> u32 {
+        self.timings.startup_ms as u32 + EARLY_NACK_RETRIES as u32 * EARLY_NACK_DELAY_MS as u32
+    }
+
+    //The delays every init attempt may have.
+    fn retry_worst_case_ms(&self) -> u32 {
+        let t = self.timings;
+        let check = match self.calibration_check {
+            true => CAL_REGISTERS.len() as u32 * (t.register_ms as u32 + t.calibrate_ms as u32)
+                + t.calibrate_ms as u32,
+            false => 0,
+        };
+        t.calibrate_ms as u32 + check
+    }
+
+    //Worst case delays of the next `init_sequence()`.
+    fn attempt_worst_case_ms(&self) -> u32 {
+        match self.started_up {
+            true => self.retry_worst_case_ms(),
+            false => self.startup_worst_case_ms() + self.retry_worst_case_ms(),
+        }
+    }
+
+    //Startup delay, init command and calibration if needed, see `InitCycle`.
+    //
+    //Safe to call again after it failed at any point: the startup delay
+    //only runs the first time (it's the first action, so it has always
+    //passed by the time anything can fail), the rest is repeated in full.
+    //Sending the init command again is harmless and the status byte read
+    //afterwards decides on calibrating, so nothing depends on how far the
+    //failed attempt got.
+    //A sensor still powering up NACKs the init command, so on the first
+    //init that alone is retried, see `EARLY_NACK_RETRIES`. With
+    //`set_calibration_check()` the register check runs between the startup
+    //delay and the init command, its first status read gets the same
+    //early retries.
+    fn init_sequence(&mut self, delay: &mut impl Delay) -> Result<(), Error<E>> {
+        let first = !self.started_up;
+        let mut early_nacks = 0;
+        if self.calibration_check {
+            if first {
+                delay.delay_ms(self.timings.startup_ms);
+                self.started_up = true;
+            }
+            loop {
+                match self.check_calibration_registers(delay) {
+                    Err(Error::I2C(_)) if first && early_nacks < EARLY_NACK_RETRIES => {
+                        early_nacks += 1;
+                        delay.delay_ms(EARLY_NACK_DELAY_MS);
+                    },
+                    r => break r.map(|_| ())?,
+                }
+            }
+        }
+        let mut machine = InitCycle::new(&self.timings, &self.params);
+        if self.started_up {
+            machine = machine.skip_startup();
+        }
+        loop {
+            let r = self.run(&mut machine, delay, |sensor, data| {
+                sensor.check_status(SensorStatus::new(data[0])).map(|_| ())
+            });
+            self.started_up = true;
+            match r {
+                Err(Error::I2C(_)) if first && machine.is_init_pending() && early_nacks < EARLY_NACK_RETRIES => {
+                    early_nacks += 1;
+                    delay.delay_ms(EARLY_NACK_DELAY_MS);
+                    machine = InitCycle::new(&self.timings, &self.params).skip_startup();
+                },
+                r => return r,
+            }
         }
-        
-        return Ok(InitializedSensor {sensor: self}); 
     }
 
     ///Called by the the Init function, Shouldn't be needed most the time.
     ///You can call this function manually if the sensor itself had lost power.
     pub fn calibrate<D>(&mut self, delay: &mut D) -> Result<SensorStatus, Error<E>>
-        where D:  DelayMs<u16>,
+        where D:  Delay,
     {
-        //0x08 and 0x00
-        let wbuf = vec![Command::Calibrate as u8, CAL_PARAM0, CAL_PARAM1];
-        self.i2c.write(self.address, &wbuf)
-            .map_err(Error::I2C)?;
-        
-        //we wait 10ms because the data sheet say to.
-        delay.delay_ms(CALIBRATE_DELAY_MS);
+        let r = self.calibrate_sequence(delay);
+        self.journal_result(Some(JournalEvent::Calibrated), r)
+    }
+
+    fn calibrate_sequence(
+        &mut self,
+        delay: &mut impl Delay,
+        ) -> Result<SensorStatus, Error<E>>
+    {
+        let mut machine = CalibrateCycle::new(&self.timings, &self.params);
+        self.run(&mut machine, delay, |_, _| Ok(()))?;
+        machine.status().ok_or(Error::Internal)
+    }
 
+    //Drives a sans-IO machine over the bus, `on_read` gets to see (and
+    //reject) every read before the machine does.
+    fn run<M, F>(
+        &mut self,
+        machine: &mut M,
+        delay: &mut impl Delay,
+        mut on_read: F,
+        ) -> Result<(), Error<E>>
+        where M: Machine,
+              F: FnMut(&mut Self, &[u8]) -> Result<(), Error<E>>,
+    {
+        let mut buf = [0u8; FRAME_LEN];
+        let mut read = None;
+        loop {
+            let input = match read.take() {
+                Some(n) => Input::Data(&buf[..n]),
+                None => Input::Ready,
+            };
+            match machine.poll(input) {
+                Action::Write(wbuf) => {
+                    self.retry_transfer(delay, |sensor| sensor.write_command(wbuf.as_slice()))?
+                },
+                Action::Read(n) => {
+                    let n = n.min(FRAME_LEN);
+                    self.retry_transfer(delay, |sensor| {
+                        sensor.i2c.read_frame(sensor.address, &mut buf[..n]).map_err(Error::I2C)
+                    })?;
+                    on_read(self, &buf[..n])?;
+                    read = Some(n);
+                },
+                Action::DelayMs(ms) => delay.delay_ms(ms),
+                Action::Done => return Ok(()),
+                Action::Fail(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    //Runs a bus transfer, retrying a failed one as `BusQuirks` asks.
+    fn retry_transfer(
+        &mut self,
+        delay: &mut impl Delay,
+        mut transfer: impl FnMut(&mut Self) -> Result<(), Error<E>>,
+        ) -> Result<(), Error<E>>
+    {
+        let mut left = self.quirks.transfer_retries;
+        loop {
+            match transfer(self) {
+                Err(Error::I2C(_)) if left > 0 => {
+                    left -= 1;
+                    delay.delay_ms(self.quirks.settle_ms);
+                },
+                r => return r,
+            }
+        }
+    }
+
+    ///The power on self check from AOSONG's sample code: if the status
+    ///byte doesn't have both `CAL_CHECK_MASK` bits set, each register in
+    ///`CAL_REGISTERS` is read back and written again. Call it after the
+    ///startup delay and before `init()`, or let `init()` run it with
+    ///`set_calibration_check()`. The waits are `Timings::register_ms`
+    ///before a register is read back and `Timings::calibrate_ms` after
+    ///each write.
+    ///Returns whether the rewrite was needed.
+    pub fn check_calibration_registers(
+        &mut self,
+        delay: &mut impl Delay,
+        ) -> Result<bool, Error<E>>
+    {
         let status = self.read_status()?;
-        
-        if status.is_calibration_enabled() {
-            return Ok(status);
+        if (status.status & CAL_CHECK_MASK) == CAL_CHECK_MASK {
+            return Ok(false);
         }
-        return Err(Error::Internal);
+
+        for reg in CAL_REGISTERS {
+            let rbuf = self.register_read(reg, delay)?;
+            delay.delay_ms(self.timings.calibrate_ms);
+            self.register_write(reg, [rbuf[1], rbuf[2]])?;
+        }
+        delay.delay_ms(self.timings.calibrate_ms);
+        Ok(true)
+    }
+
+    //Register access as in AOSONG's sample code, the reply is the status
+    //byte followed by the two register bytes.
+    fn register_read(
+        &mut self,
+        reg: u8,
+        delay: &mut impl Delay,
+        ) -> Result<[u8; 3], Error<E>>
+    {
+        self.write_command(&[reg, 0x00, 0x00])?;
+        delay.delay_ms(self.timings.register_ms);
+
+        let mut rbuf = [0u8; 3];
+        self.i2c.read_frame(self.address, &mut rbuf).map_err(Error::I2C)?;
+        Ok(rbuf)
+    }
+
+    fn register_write(&mut self, reg: u8, value: [u8; 2]) -> Result<(), Error<E>> {
+        self.write_command(&[REG_WRITE | reg, value[0], value[1]])
     }
 
     ///Reads the status byte of the AHT sensor and returns either an Error
@@ -187,17 +865,216 @@ where I2C: i2c::Read<Error = E> + i2c::Write<Error = E>,
     pub fn read_status(&mut self) -> Result<SensorStatus, Error<E>>
     {
         self.i2c 
-            .write(self.address, &[Command::ReadStatus as u8])
+            .write_frame(self.address, &commands::read_status())
             .map_err(Error::I2C)?;
         
 
         let mut buf = [0];
         //now try to read it.
         self.i2c
-            .read(self.address, &mut buf)
+            .read_frame(self.address, &mut buf)
             .map_err(Error::I2C)?;
 
-        Ok(SensorStatus{ status: buf[0]})
+        Ok(SensorStatus::new(buf[0]))
+    }
+
+    ///Enables caching of the status byte, `read_status_cached()` then only
+    ///goes to the bus if the cached byte is older than `max_age_ms`.
+    ///`None` turns the cache off.
+    pub fn set_status_cache(&mut self, max_age_ms: Option<u32>) {
+        self.status_max_age_ms = max_age_ms;
+        self.status_cache = None;
+    }
+
+    ///Drops the cached status byte, this happens automatically after every
+    ///command written to the sensor.
+    pub fn invalidate_status_cache(&mut self) {
+        self.status_cache = None;
+    }
+
+    ///Same as `read_status()` but answered from the cache when it's enabled
+    ///and the cached byte is young enough. So a UI refresh and a logger
+    ///both asking "is it busy?" don't each hit the bus.
+    pub fn read_status_cached(
+        &mut self,
+        clock: &mut impl Clock,
+        ) -> Result<SensorStatus, Error<E>>
+    {
+        let max_age = match self.status_max_age_ms {
+            Some(ms) => ms,
+            None => return self.read_status(),
+        };
+
+        let now = clock.now_ms();
+        self.tick(now);
+        if let Some((status, at)) = self.status_cache {
+            if elapsed_ms(at, now) <= max_age {
+                return Ok(status);
+            }
+        }
+
+        let status = self.read_status()?;
+        self.status_cache = Some((status, now));
+        Ok(status)
+    }
+
+    ///Sets a minimum time between measurements triggered through
+    ///`read_sensor_timed()`, `None` turns the check off.
+    pub fn set_min_spacing(&mut self, spacing: Option<(u32, SpacingPolicy)>) {
+        self.min_spacing = spacing;
+    }
+
+    ///Number of measurements triggered too soon under `SpacingPolicy::Flag`.
+    pub fn spacing_violations(&self) -> u16 {
+        self.spacing_violations
+    }
+
+    ///The conversion times observed by `read_sensor_timed()`.
+    pub fn conversion_durations(&self) -> ConversionDurations {
+        self.durations
+    }
+
+    pub fn reset_conversion_durations(&mut self) {
+        self.durations = ConversionDurations::new();
+    }
+
+    ///In strict mode every status byte read during init, measurements,
+    ///resets and status reads is checked against the driver state with
+    ///`DriverState::expects()`, one that doesn't fit gives
+    ///`Error::UnexpectedStatus`. Catches clone sensors early. Off by
+    ///default.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    ///Runs `check_calibration_registers()` as part of `init()`, after the
+    ///startup delay and before the init command. Off by default.
+    pub fn set_calibration_check(&mut self, check: bool) {
+        self.calibration_check = check;
+    }
+
+    ///Number of status bytes strict mode rejected.
+    pub fn status_anomalies(&self) -> u16 {
+        self.status_anomalies
+    }
+
+    //Passes the status through, unless strict mode rejects it for the
+    //current state.
+    fn check_status(&mut self, status: SensorStatus) -> Result<SensorStatus, Error<E>> {
+        if self.strict && !self.state.expects(status) {
+            self.status_anomalies = self.status_anomalies.saturating_add(1);
+            return Err(Error::UnexpectedStatus(status.status));
+        }
+        Ok(status)
+    }
+
+    ///Tells the driver the time, the journal entries are stamped with the
+    ///last time it was told. The methods taking a clock do this themselves.
+    pub fn tick(&mut self, now_ms: u32) {
+        self.last_seen_ms = Some(now_ms);
+    }
+
+    ///The last driver events: init, calibration, resets and errors.
+    pub fn journal(&self) -> &Journal<JOURNAL_LEN> {
+        &self.journal
+    }
+
+    pub fn clear_journal(&mut self) {
+        self.journal.clear();
+    }
+
+    ///Starts keeping the last two good frames, see `FrameHistory`. Off by
+    ///default, calling it again clears the history.
+    pub fn enable_frame_history(&mut self) {
+        self.frames = Some(FrameHistory::new());
+    }
+
+    pub fn disable_frame_history(&mut self) {
+        self.frames = None;
+    }
+
+    ///The indicator given to `with_indicator()`.
+    pub fn indicator(&mut self) -> &mut L {
+        &mut self.indicator
+    }
+
+    ///Works around bus controllers that time out on the sensor, e.g.
+    ///`BusQuirks::ESP32_C3`. The default is `BusQuirks::NONE`.
+    pub fn set_bus_quirks(&mut self, quirks: BusQuirks) {
+        self.quirks = quirks;
+    }
+
+    pub fn bus_quirks(&self) -> BusQuirks {
+        self.quirks
+    }
+
+    ///None unless `enable_frame_history()` was called.
+    pub fn frame_history(&self) -> Option<FrameHistory> {
+        self.frames
+    }
+
+    ///Turns on duplicate frame detection in `measure()`, `None` turns it
+    ///off. Off by default.
+    pub fn set_duplicate_policy(&mut self, policy: Option<DuplicatePolicy>) {
+        if policy.is_none() {
+            self.last_good_frame = None;
+        }
+        self.duplicate_policy = policy;
+    }
+
+    ///Number of duplicate frames `measure()` has flagged or suppressed.
+    pub fn duplicates(&self) -> u16 {
+        self.duplicates
+    }
+
+    //Journals the error, or `ok_event` on success, and passes `r` through.
+    //Also counts the bus failures towards the fault limit.
+    fn journal_result<T>(
+        &mut self,
+        ok_event: Option<JournalEvent>,
+        r: Result<T, Error<E>>,
+        ) -> Result<T, Error<E>>
+    {
+        self.count_failure(r.as_ref().err());
+        self.indicator.show(if r.is_ok() { Signal::Ok } else { Signal::Error });
+        let event = match &r {
+            Ok(_) => ok_event,
+            Err(e) => Some(JournalEvent::Error(e.kind())),
+        };
+        if let Some(event) = event {
+            self.journal.push(event, self.last_seen_ms);
+        }
+        r
+    }
+
+    ///Lets `measure()` degrade instead of failing: a CRC miss or a busy
+    ///overrun gives a `Measurement` with the matching `Quality` flag set.
+    ///Only once more than `max_consecutive` measurements in a row had a
+    ///problem is the error returned, so even 255 escalates on the 256th.
+    ///`None` (the default) always errors.
+    pub fn set_degradation(&mut self, max_consecutive: Option<u8>) {
+        self.degrade_limit = max_consecutive;
+        self.consecutive_failures = 0;
+    }
+
+    //Every command changes the sensor's state, so the cached status byte
+    //is dropped before it's sent.
+    fn write_command(&mut self, wbuf: &[u8]) -> Result<(), Error<E>> {
+        self.status_cache = None;
+        self.i2c.write_frame(self.address, wbuf).map_err(Error::I2C)?;
+        if wbuf.first() == Some(&commands::TRIG_MESSURE) {
+            self.state = DriverState::Measuring;
+            self.indicator.show(Signal::Busy);
+        }
+        Ok(())
+    }
+
+    //A read that failed or timed out ends the conversion as far as the
+    //driver is concerned, the next measurement triggers a fresh one.
+    fn end_conversion(&mut self) {
+        if self.state == DriverState::Measuring {
+            self.state = DriverState::Idle;
+        }
     }
 
 
@@ -206,36 +1083,168 @@ where I2C: i2c::Read<Error = E> + i2c::Write<Error = E>,
 
 #[allow(dead_code)]
 /// The initialized sensor struct, enforces correct method availability.
-pub struct InitializedSensor<'a, I2C>
-where I2C: i2c::Read + i2c::Write,
+/// It owns the sensor, so it can be stored in a struct or moved into a
+/// task, `downgrade()` gives the sensor back.
+pub struct InitializedSensor<I2C, L = NoIndicator>
+where I2C: Aht2xTransport,
+{
+    sensor: Sensor<I2C, L>,
+}
+
+///A failed `init()`, with the sensor to try again with. Converts into the
+///`Error` so `?` works, dropping the sensor.
+pub struct InitError<E, I2C, L = NoIndicator>
+where I2C: Aht2xTransport,
 {
-    sensor: &'a mut Sensor<I2C>,
+    pub error: Error<E>,
+    pub sensor: Sensor<I2C, L>,
 }
 
+impl<E, I2C, L> From<InitError<E, I2C, L>> for Error<E>
+where I2C: Aht2xTransport,
+{
+    fn from(e: InitError<E, I2C, L>) -> Self {
+        e.error
+    }
+}
 
+impl<E: ::core::fmt::Debug, I2C, L> ::core::fmt::Debug for InitError<E, I2C, L>
+where I2C: Aht2xTransport,
+{
+    fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+        f.debug_struct("InitError").field("error", &self.error).finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<E: defmt::Format, I2C, L> defmt::Format for InitError<E, I2C, L>
+where I2C: Aht2xTransport,
+{
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "InitError {{ error: {} }}", self.error)
+    }
+}
 
-impl <'a, E, I2C> InitializedSensor<'a, I2C>
-where I2C: i2c::Read<Error = E> + i2c::Write<Error = E>,
+impl<I2C, L> InitializedSensor<I2C, L>
+where I2C: Aht2xTransport,
 {
+    ///Gives the sensor back, e.g. to change settings that need `Sensor`.
+    ///It stays initialized, `init()` on it again is only needed after a
+    ///reset.
+    pub fn downgrade(self) -> Sensor<I2C, L> {
+        self.sensor
+    }
+
+    ///`downgrade()` then `Sensor::destroy()`, hands the bus back.
+    pub fn release(self) -> I2C {
+        self.sensor.i2c
+    }
+
+    pub fn sensor(&self) -> &Sensor<I2C, L> {
+        &self.sensor
+    }
+
+    ///The sensor's settings, e.g. `set_degradation()`.
+    pub fn sensor_mut(&mut self) -> &mut Sensor<I2C, L> {
+        &mut self.sensor
+    }
+}
+
+impl<E, I2C, L> InitializedSensor<I2C, L>
+where I2C: Aht2xTransport<Error = E>,
+      L: Indicator,
+{
+    ///Busy polls the last read needed before the sensor was idle, 0 when
+    ///the first read already was.
+    pub fn last_retries(&self) -> u16 {
+        self.sensor.last_retries
+    }
+
     ///Returns SensorStatus as a structure with methods to abstract the
     ///needed bitwise operations.
     pub fn get_status(&mut self) -> Result<SensorStatus, Error<E> >{ 
         let s = self.sensor.read_status()?;
-        Ok(s)
+        if self.sensor.state == DriverState::Measuring && !s.is_busy() {
+            self.sensor.state = DriverState::Idle;
+        }
+        self.sensor.check_status(s)
+    }
+   
+    ///Same as `get_status()` but uses the status cache, see
+    ///`Sensor::set_status_cache()`.
+    pub fn get_status_cached(
+        &mut self,
+        clock: &mut impl Clock,
+        ) -> Result<SensorStatus, Error<E>>
+    {
+        let s = self.sensor.read_status_cached(clock)?;
+        self.sensor.check_status(s)
     }
    
     ///Sends the special three byte sequence to the AHT sensor in order to 
     ///start the measurement proscess.
+    ///Returns straight away, for schedulers that time every step
+    ///themselves (TDMA slots, camera sync). Call `fetch()` once the
+    ///conversion time has passed, nothing waits in between.
     pub fn trigger_measurement(&mut self) -> Result<(), Error<E>> 
     {
-        let wbuf = vec![Command::TrigMessure as u8,
-            TRIG_MEASURE_PARAM0,
-            TRIG_MEASURE_PARAM1];
-        self.sensor.i2c
-            .write(self.sensor.address, &wbuf)
-            .map_err(Error::I2C)?;
-        
-        Ok(())
+        self.sensor.check_faulted()?;
+        let wbuf = commands::trigger(self.sensor.params.trigger);
+        let r = self.sensor.write_command(&wbuf);
+        self.sensor.journal_result(None, r)
+    }
+
+    ///Reads the frame of a conversion started with `trigger_measurement()`, a single
+    ///read with no waiting or polling. A sensor still converting gives
+    ///`Error::UnexpectedBusy` and can be fetched again later. Bus quirks
+    ///needing delays aren't applied, and frames aren't checked for
+    ///duplicates.
+    pub fn fetch(&mut self) -> Result<Measurement, Error<E>> {
+        self.sensor.check_faulted()?;
+        let r = self.fetch_frame();
+        self.sensor.journal_result(None, r)
+    }
+
+    fn fetch_frame(&mut self) -> Result<Measurement, Error<E>> {
+        let r = self.fetch_data();
+        if let Err(Error::UnexpectedBusy) = r {
+            return Err(Error::UnexpectedBusy);
+        }
+        let seq = self.sensor.take_sequence_number();
+        let mut sd = r?;
+        if !sd.is_crc_good() {
+            return Err(Error::InvalidChecksum);
+        }
+        if let Some(frames) = self.sensor.frames.as_mut() {
+            frames.push(sd.frame());
+        }
+        Ok(self.measurement_of(&sd, seq))
+    }
+
+    //The measurement of a frame with a good CRC.
+    fn measurement_of(&self, sd: &SensorData, seq: Option<u32>) -> Measurement {
+        let mut m = Measurement::from_data(self.sensor.id, sd);
+        m.pressure = self.sensor.pressure;
+        m.seq = seq;
+        m
+    }
+
+    //A single frame read, `Error::UnexpectedBusy` while converting. The
+    //CRC isn't checked.
+    fn fetch_data(&mut self) -> Result<SensorData, Error<E>> {
+        let sensor = &mut self.sensor;
+        let mut sd = SensorData::new();
+        if let Err(e) = sensor.i2c.read_frame(sensor.address, &mut sd.bytes) {
+            sensor.end_conversion();
+            return Err(Error::I2C(e));
+        }
+        sensor.last_frame = Some(sd.bytes);
+        sensor.last_retries = 0;
+        if sensor.check_status(sd.frame().status())?.is_busy() {
+            return Err(Error::UnexpectedBusy);
+        }
+        sensor.state = DriverState::Idle;
+        Ok(sd)
     }
 
     /// # Attempts to read the 7 needed bytes of data.
@@ -248,51 +1257,282 @@ where I2C: i2c::Read<Error = E> + i2c::Write<Error = E>,
     /// - Byte 6 --> CRC value
     pub fn read_sensor(
         &mut self,
-        delay: &mut impl DelayMs<u16>,
+        delay: &mut impl Delay,
         ) -> Result<SensorData, Error<E>> {
         
-        self.trigger_measurement()?;
-        
-        delay.delay_ms(MEASURE_DELAY_MS);
+        let mut retry = self.measure_deadline();
+        self.read_sensor_with_retry(delay, &mut retry)
+    }
 
+    ///Same as `read_sensor()` but the busy polling after the typical measure
+    ///time follows `retry` instead of the timings' deadline.
+    pub fn read_sensor_with_retry(
+        &mut self,
+        delay: &mut impl Delay,
+        retry: &mut impl RetryStrategy,
+        ) -> Result<SensorData, Error<E>> {
+
+        self.sensor.check_faulted()?;
         let mut sd = SensorData::new();
+        let r = self.read_frame(delay, retry, &mut sd);
+        self.sensor.journal_result(None, r)?;
 
-        //Limits the number of times it tries to get status
-        for attempt in 0..MAX_ATTEMPTS{
-            
-            self.sensor.i2c.read(self.sensor.address, &mut sd.bytes)
-                .map_err(Error::I2C)?;
+        //check against the CRC?
+        Ok(sd)
+    }
+
+    //Polls every `busy_poll_ms` until the max measure time has passed,
+    //plus the margin for the declared bus speed.
+    fn measure_deadline(&self) -> Deadline {
+        let t = self.sensor.timings;
+        let extra_polls = self.sensor.bus_speed.map_or(0, BusSpeed::extra_polls);
+        Deadline {
+            interval_ms: t.busy_poll_ms,
+            deadline_ms: t.measure_max_ms.saturating_sub(t.measure_typ_ms)
+                .saturating_add(t.busy_poll_ms.saturating_mul(extra_polls)),
+        }
+    }
 
-            let senstat = SensorStatus::new(sd.bytes[0].clone());
-            if !senstat.is_busy() { 
-                break;
+    //Triggers a measurement and reads the frame into `sd`, on a timeout
+    //`sd` holds the last (busy) frame that was read. See `MeasureCycle`.
+    fn read_frame(
+        &mut self,
+        delay: &mut impl Delay,
+        retry: &mut impl RetryStrategy,
+        sd: &mut SensorData,
+        ) -> Result<(), Error<E>> {
+
+        let sensor = &mut self.sensor;
+        let mut machine = MeasureCycle::new(&sensor.timings, &sensor.params, retry)
+            .with_quirks(sensor.quirks);
+        let r = sensor.run(&mut machine, delay, |sensor, data| {
+            if let Ok(frame) = data.try_into() {
+                sensor.last_frame = Some(frame);
             }
-            else if attempt == MAX_ATTEMPTS {
-                return Err(Error::DeviceTimeOut);
+            sensor.check_status(SensorStatus::new(data[0])).map(|_| ())
+        });
+
+        if let Some(frame) = machine.frame() {
+            sd.bytes = frame;
+        }
+        sensor.last_retries = machine.busy_reads();
+        match r {
+            Ok(()) => {
+                sensor.state = DriverState::Idle;
+                let frame = sd.frame();
+                if let (Some(frames), true) = (sensor.frames.as_mut(), frame.is_crc_good()) {
+                    frames.push(frame);
+                }
+            },
+            Err(_) => sensor.end_conversion(),
+        }
+        r
+    }
+
+    ///Polls the status byte until the sensor stops reporting busy, waiting
+    ///`busy_poll_ms` from the timings between polls. Gives up with
+    ///`Error::DeviceTimeOut` once `deadline_ms` has been spent waiting.
+    ///
+    ///For users composing their own command sequences, `read_sensor()`
+    ///uses the same polling.
+    pub fn wait_until_idle(
+        &mut self,
+        delay: &mut impl Delay,
+        deadline_ms: u16,
+        ) -> Result<SensorStatus, Error<E>>
+    {
+        let mut retry = Deadline {
+            interval_ms: self.sensor.timings.busy_poll_ms,
+            deadline_ms,
+        };
+        let r = self.poll_until_idle(delay, &mut retry, |sensor| sensor.read_status());
+        self.sensor.journal_result(None, r)
+    }
+
+    //The busy wait loop shared by the read methods. The first poll happens
+    //straight away, after that `retry` decides the waits and when to give up.
+    fn poll_until_idle<F>(
+        &mut self,
+        delay: &mut impl Delay,
+        retry: &mut impl RetryStrategy,
+        mut poll: F,
+        ) -> Result<SensorStatus, Error<E>>
+        where F: FnMut(&mut Sensor<I2C, L>) -> Result<SensorStatus, Error<E>>,
+    {
+        let mut waited: u32 = 0;
+        let mut failed: u16 = 0;
+        loop {
+            self.sensor.last_retries = failed;
+            let status = poll(&mut self.sensor)?;
+            let status = self.sensor.check_status(status)?;
+            if !status.is_busy() {
+                return Ok(status);
             }
-            delay.delay_ms(BUSY_DELAY_MS);
+
+            failed = failed.saturating_add(1);
+            let step = retry.next_delay_ms(failed, waited)
+                .ok_or(Error::DeviceTimeOut)?;
+            delay.delay_ms(step);
+            waited = waited.saturating_add(u32::from(step));
         }
+    }
 
-        //check against the CRC?
+    ///Same as `read_sensor()` but timestamps the trigger with the clock,
+    ///which enforces the minimum spacing between measurements and records
+    ///how long the conversion took, see `Sensor::conversion_durations()`.
+    pub fn read_sensor_timed(
+        &mut self,
+        delay: &mut impl Delay,
+        clock: &mut impl Clock,
+        ) -> Result<SensorData, Error<E>> {
+
+        self.sensor.check_faulted()?;
+        let mut now = clock.now_ms();
+        self.sensor.tick(now);
+        if let (Some((min, policy)), Some(last)) =
+            (self.sensor.min_spacing, self.sensor.last_trigger_ms)
+        {
+            let since = elapsed_ms(last, now);
+            if since < min {
+                match policy {
+                    SpacingPolicy::Reject => {
+                        return self.sensor.journal_result(None, Err(Error::TooSoon));
+                    },
+                    SpacingPolicy::Flag => {
+                        self.sensor.spacing_violations =
+                            self.sensor.spacing_violations.saturating_add(1);
+                    },
+                    SpacingPolicy::Wait => {
+                        //Delay, so long waits are split up.
+                        let mut remaining = min - since;
+                        while remaining > 0 {
+                            let step = remaining.min(u16::MAX as u32);
+                            delay.delay_ms(step as u16);
+                            remaining -= step;
+                        }
+                        now = clock.now_ms();
+                    },
+                }
+            }
+        }
+
+        self.sensor.last_trigger_ms = Some(now);
+        let sd = self.read_sensor(delay)?;
+        let took = elapsed_ms(now, clock.now_ms());
+        self.sensor.durations.record(took);
         Ok(sd)
     }
 
-    /// Preforms a soft reset of the sensor itself.
-    pub fn soft_reset(&mut self, _delay: &mut impl DelayMs<u16>) ->
+    ///Reads the sensor, checks the CRC and converts the values into a
+    ///`Measurement` tagged with the sensor's id.
+    ///See `Sensor::set_degradation()` for returning flagged measurements
+    ///instead of errors.
+    pub fn measure(
+        &mut self,
+        delay: &mut impl Delay,
+        ) -> Result<Measurement, Error<E>> {
+        self.sensor.check_faulted()?;
+        let seq = self.sensor.take_sequence_number();
+        let mut sd = SensorData::new();
+        let mut quality = Quality::GOOD;
+
+        let mut retry = self.measure_deadline();
+        let error = match self.read_frame(delay, &mut retry, &mut sd) {
+            Err(Error::DeviceTimeOut) => {
+                quality.insert(Quality::BUSY_OVERRUN);
+                Error::DeviceTimeOut
+            },
+            Err(e) => return self.sensor.journal_result(None, Err(e)),
+            Ok(()) => Error::InvalidChecksum,
+        };
+        if !sd.is_crc_good() {
+            quality.insert(Quality::CRC_MISS);
+        }
+
+        if quality.is_good() {
+            self.sensor.consecutive_failures = 0;
+        } else {
+            let sensor = &mut self.sensor;
+            sensor.consecutive_failures = sensor.consecutive_failures.saturating_add(1);
+            match sensor.degrade_limit {
+                Some(limit) if sensor.consecutive_failures <= u16::from(limit) => {},
+                _ => return sensor.journal_result(None, Err(error)),
+            }
+        }
+
+        //Only complete frames with a good CRC are compared.
+        if let (Some(policy), true) = (self.sensor.duplicate_policy, quality.is_good()) {
+            let sensor = &mut self.sensor;
+            let frame = sd.frame();
+            let repeated = sensor.last_good_frame.replace(frame)
+                .is_some_and(|last| last.same_readings(&frame));
+            //Seen busy means a conversion did run, the repeat is genuine.
+            if repeated && sensor.last_retries == 0 {
+                sensor.duplicates = sensor.duplicates.saturating_add(1);
+                match policy {
+                    DuplicatePolicy::Flag => quality.insert(Quality::DUPLICATE),
+                    DuplicatePolicy::Suppress => {
+                        return sensor.journal_result(None, Err(Error::DuplicateFrame));
+                    },
+                }
+            }
+        }
+
+        if self.sensor.recovery.is_some() {
+            quality.insert(Quality::RECOVERING);
+        }
+
+        let mut m = Measurement::from_data(self.sensor.id, &sd);
+        m.quality = quality;
+        m.pressure = self.sensor.pressure;
+        m.seq = seq;
+        self.sensor.journal_result(None, Ok(m))
+    }
+
+    /// Preforms a soft reset of the sensor itself and waits the variant's
+    /// `reset_ms` before reading the status back. A sensor reporting busy
+    /// is refused with `Error::UnexpectedBusy`, see `force_reset()`.
+    pub fn soft_reset(&mut self, delay: &mut impl Delay) ->
         Result<SensorStatus, Error<E>>
     {
-        
-        let mut status =  self.get_status()?;
-        if status.is_busy() {
-            return Err(Error::UnexpectedBusy);
-        }
+        self.sensor.take_sequence_number();
+        let r = self.soft_reset_sequence(delay);
+        self.sensor.journal_result(Some(JournalEvent::Reset), r)
+    }
 
-        let wbuf = vec![Command::SoftReset as u8];
-        self.sensor.i2c.write(self.sensor.address, &wbuf)
-            .map_err(Error::I2C)?;
+    ///`soft_reset()` without the status read before it, for a sensor stuck
+    ///reporting busy, which `soft_reset()` refuses to reset. Waits the
+    ///variant's `reset_ms` before reading the status back, a sensor still
+    ///busy then is `Error::UnexpectedBusy` and left uninitialized.
+    pub fn force_reset(&mut self, delay: &mut impl Delay) ->
+        Result<SensorStatus, Error<E>>
+    {
+        self.sensor.take_sequence_number();
+        let r = self.force_reset_sequence(delay);
+        self.sensor.journal_result(Some(JournalEvent::Reset), r)
+    }
 
-        status =  self.get_status()?;
-        return Ok(status);
+    //See `ResetCycle`, the checks of the reset verification follow.
+    fn force_reset_sequence(&mut self, delay: &mut impl Delay) -> Result<SensorStatus, Error<E>> {
+        self.sensor.state = DriverState::Resetting;
+        let mut machine = ResetCycle::new(&self.sensor.timings);
+        let r = self.sensor.run(&mut machine, delay, |sensor, data| {
+            sensor.check_status(SensorStatus::new(data[0])).map(|_| ())
+        });
+        let r = r.and_then(|_| machine.status().ok_or(Error::Internal))
+            .and_then(|status| self.verify_reset(delay, status).map(|_| status));
+        self.sensor.state = match r {
+            Ok(_) => DriverState::Idle,
+            Err(_) => DriverState::Uninitialized,
+        };
+        r
+    }
+
+    fn soft_reset_sequence(&mut self, delay: &mut impl Delay) -> Result<SensorStatus, Error<E>> {
+        if self.get_status()?.is_busy() {
+            return Err(Error::UnexpectedBusy);
+        }
+        self.force_reset_sequence(delay)
     }
 
 }
@@ -306,11 +1546,25 @@ mod sensor_test {
         Transaction as I2cTransaction,
     };
     use super::*;
+    use crate::core::sensor_status;
 
     #[test]
     fn self_test()
     {
-        assert!(true);
+    }
+
+    #[cfg(feature = "defmt")]
+    #[test]
+    fn loggable()
+    {
+        fn is_format<T: defmt::Format>() {}
+        is_format::<Error<u8>>();
+        is_format::<InitError<u8, SimulatedSensor>>();
+        is_format::<SensorData>();
+        is_format::<SensorStatus>();
+        is_format::<Measurement>();
+        is_format::<MeasurementProgress>();
+        is_format::<Processed>();
     }
 
     #[test]
@@ -324,7 +1578,7 @@ mod sensor_test {
         let mut i2c = I2cMock::new(&expectations);
         let mut buf = vec![0u8; 2];
 
-        i2c.write(SENSOR_ADDR, &vec![1, 2]).unwrap();
+        i2c.write(SENSOR_ADDR, &[1, 2]).unwrap();
         i2c.read(SENSOR_ADDR, &mut buf).unwrap();
 
         assert_eq!(buf, vec![3, 4]);
@@ -358,16 +1612,41 @@ mod sensor_test {
         sensor_instance.i2c.done();
     }
 
+    #[test]
+    fn check_calibration_registers()
+    {
+        let read_status = I2cTransaction::write(SENSOR_ADDR, vec![Command::ReadStatus as u8]);
+        let mut expectations = vec![
+            read_status.clone(),
+            I2cTransaction::read(SENSOR_ADDR, vec![0x18]),
+            read_status,
+            I2cTransaction::read(SENSOR_ADDR, vec![0x08]),
+        ];
+        for reg in CAL_REGISTERS {
+            expectations.push(I2cTransaction::write(SENSOR_ADDR, vec![reg, 0x00, 0x00]));
+            expectations.push(I2cTransaction::read(SENSOR_ADDR, vec![0x1C, reg, 0x5A]));
+            expectations.push(I2cTransaction::write(SENSOR_ADDR, vec![0xB0 | reg, reg, 0x5A]));
+        }
+
+        let i2c = I2cMock::new(&expectations);
+        let mut sensor_instance = Sensor::new(i2c, SENSOR_ADDR);
+        let mut mock_delay = embedded_hal_mock::delay::MockNoop;
+
+        assert_eq!(sensor_instance.check_calibration_registers(&mut mock_delay), Ok(false));
+        assert_eq!(sensor_instance.check_calibration_registers(&mut mock_delay), Ok(true));
+        sensor_instance.i2c.done();
+    }
+
     #[test]
     fn calibrate()
     {
         let expectations = [
             I2cTransaction::write(SENSOR_ADDR, vec![Command::Calibrate as u8, CAL_PARAM0, CAL_PARAM1]),
             I2cTransaction::write(SENSOR_ADDR, vec![Command::ReadStatus as u8]),
-            I2cTransaction::read(SENSOR_ADDR, vec![sensor_status::BUSY_BM as u8]),
+            I2cTransaction::read(SENSOR_ADDR, vec![sensor_status::BUSY_BM]),
             I2cTransaction::write(SENSOR_ADDR, vec![Command::Calibrate as u8, CAL_PARAM0, CAL_PARAM1]),
             I2cTransaction::write(SENSOR_ADDR, vec![Command::ReadStatus as u8]),
-            I2cTransaction::read(SENSOR_ADDR, vec![sensor_status::CALENABLED_BM as u8]),
+            I2cTransaction::read(SENSOR_ADDR, vec![sensor_status::CALENABLED_BM]),
         ]; 
 
         let i2c = I2cMock::new(&expectations);
@@ -384,10 +1663,35 @@ mod sensor_test {
         sensor_instance.i2c.done();
     }
 
+    #[cfg(feature = "experimental-params")]
+    #[test]
+    fn command_params()
+    {
+        let params = CommandParams { calibrate: [0x08, 0x01], trigger: [0x33, 0x01] };
+        let expectations = [
+            I2cTransaction::write(SENSOR_ADDR, vec![Command::Calibrate as u8, 0x08, 0x01]),
+            I2cTransaction::write(SENSOR_ADDR, vec![Command::ReadStatus as u8]),
+            I2cTransaction::read(SENSOR_ADDR, vec![sensor_status::CALENABLED_BM]),
+            I2cTransaction::write(SENSOR_ADDR, vec![Command::TrigMessure as u8, 0x33, 0x01]),
+        ];
+
+        let i2c = I2cMock::new(&expectations);
+        let mut sensor_instance = Sensor::new(i2c, SENSOR_ADDR);
+        assert_eq!(sensor_instance.command_params(), CommandParams::default());
+        sensor_instance.set_command_params(params);
+
+        let mut mock_delay = embedded_hal_mock::delay::MockNoop;
+        assert!(sensor_instance.calibrate(&mut mock_delay).is_ok());
+        let mut inited_sensor = InitializedSensor { sensor: sensor_instance };
+        assert!(inited_sensor.trigger_measurement().is_ok());
+
+        inited_sensor.sensor.i2c.done();
+    }
+
     #[test]
     fn get_status_busy()
     {
-        let busy_status: u8 = sensor_status::BUSY_BM as u8;
+        let busy_status: u8 = sensor_status::BUSY_BM;
 
         let expectations = [
             I2cTransaction::write(
@@ -415,7 +1719,7 @@ mod sensor_test {
     {
 
         let calibrated = vec![
-           (sensor_status::CALENABLED_BM as u8)
+           (sensor_status::CALENABLED_BM)
         ];
         assert_eq!(calibrated[0], 0b0000_1000);
 
@@ -436,27 +1740,264 @@ mod sensor_test {
             I2cTransaction::read(
                 SENSOR_ADDR, calibrated.clone()),
         ];
-        
-        let i2c = I2cMock::new(&expectations);
+        
+        let i2c = I2cMock::new(&expectations);
+
+        let sensor_instance = Sensor::new(i2c, SENSOR_ADDR);
+
+        let mut mock_delay = embedded_hal_mock::delay::MockNoop;
+        let initialized_sensor_instance = sensor_instance.init(&mut mock_delay);
+        
+        assert!(initialized_sensor_instance.is_ok());
+
+        initialized_sensor_instance.unwrap().sensor.i2c.done();
+    }
+
+    //One init attempt that needs calibrating, ending with `status`.
+    fn init_attempt(status: u8) -> Vec<I2cTransaction> {
+        vec![
+            I2cTransaction::write(SENSOR_ADDR, vec![Command::InitSensor as u8]),
+            I2cTransaction::write(SENSOR_ADDR, vec![Command::ReadStatus as u8]),
+            I2cTransaction::read(SENSOR_ADDR, vec![0]),
+            I2cTransaction::write(SENSOR_ADDR, vec![Command::Calibrate as u8, CAL_PARAM0, CAL_PARAM1]),
+            I2cTransaction::write(SENSOR_ADDR, vec![Command::ReadStatus as u8]),
+            I2cTransaction::read(SENSOR_ADDR, vec![status]),
+        ]
+    }
+
+    ///Records the delays asked for.
+    #[derive(Default)]
+    pub(crate) struct DelayLog(pub Vec<u16>);
+
+    impl Delay for DelayLog {
+        fn delay_ms(&mut self, ms: u16) {
+            self.0.push(ms);
+        }
+    }
+
+    #[test]
+    fn init_resumable()
+    {
+        //Fails at every transaction of an init that needs calibrating, then
+        //the second call finds the sensor calibrated. A failed init command
+        //is retried instead, see `early_nack`.
+        for fail_at in 1..6 {
+            let mut expected = init_attempt(sensor_status::CALENABLED_BM);
+            expected.truncate(fail_at + 1);
+            let last = expected.pop().unwrap();
+            expected.push(last.with_error(embedded_hal_mock::MockError::Io(std::io::ErrorKind::Other)));
+            expected.extend([
+                I2cTransaction::write(SENSOR_ADDR, vec![Command::InitSensor as u8]),
+                I2cTransaction::write(SENSOR_ADDR, vec![Command::ReadStatus as u8]),
+                I2cTransaction::read(SENSOR_ADDR, vec![0x18]),
+            ]);
+
+            let sensor_instance = Sensor::new(I2cMock::new(&expected), SENSOR_ADDR);
+            let mut delay = DelayLog::default();
+            let sensor_instance = match sensor_instance.init(&mut delay) {
+                Err(e) => e.sensor,
+                Ok(_) => panic!("fail at {}", fail_at),
+            };
+            assert_eq!(delay.0[0], 40);
+
+            let mut delay = DelayLog::default();
+            let mut inited_sensor = sensor_instance.init(&mut delay).unwrap();
+            assert!(delay.0.is_empty());
+            inited_sensor.sensor.i2c.done();
+        }
+
+        //Calling it again once initialized is fine too.
+        let expected = [
+            I2cTransaction::write(SENSOR_ADDR, vec![Command::InitSensor as u8]),
+            I2cTransaction::write(SENSOR_ADDR, vec![Command::ReadStatus as u8]),
+            I2cTransaction::read(SENSOR_ADDR, vec![0x18]),
+            I2cTransaction::write(SENSOR_ADDR, vec![Command::InitSensor as u8]),
+            I2cTransaction::write(SENSOR_ADDR, vec![Command::ReadStatus as u8]),
+            I2cTransaction::read(SENSOR_ADDR, vec![0x18]),
+        ];
+        let sensor_instance = Sensor::new(I2cMock::new(&expected), SENSOR_ADDR);
+        let mut mock_delay = embedded_hal_mock::delay::MockNoop;
+        let inited_sensor = sensor_instance.init(&mut mock_delay).unwrap();
+        let mut inited_sensor = inited_sensor.downgrade().init(&mut mock_delay).unwrap();
+        inited_sensor.sensor.i2c.done();
+    }
+
+    #[test]
+    fn early_nack()
+    {
+        let nack = I2cTransaction::write(SENSOR_ADDR, vec![Command::InitSensor as u8])
+            .with_error(embedded_hal_mock::MockError::Io(std::io::ErrorKind::Other));
+        let mut expected = vec![nack.clone(), nack.clone()];
+        expected.extend(init_attempt(sensor_status::CALENABLED_BM));
+
+        let mut delay = DelayLog::default();
+        let mut inited_sensor = Sensor::new(I2cMock::new(&expected), SENSOR_ADDR)
+            .init(&mut delay)
+            .unwrap();
+        assert_eq!(delay.0, [40, EARLY_NACK_DELAY_MS, EARLY_NACK_DELAY_MS, 10]);
+        inited_sensor.sensor.i2c.done();
+
+        //Bounded, and only on the first init.
+        let expected = vec![nack; usize::from(EARLY_NACK_RETRIES) + 2];
+        let mut delay = DelayLog::default();
+        let e = Sensor::new(I2cMock::new(&expected), SENSOR_ADDR).init(&mut delay).err().unwrap();
+        assert_eq!(delay.0.len(), 1 + usize::from(EARLY_NACK_RETRIES));
+        let mut delay = DelayLog::default();
+        let e = e.sensor.init(&mut delay).err().unwrap();
+        assert!(delay.0.is_empty());
+        let mut sensor_instance = e.sensor;
+        sensor_instance.i2c.done();
+    }
+
+    #[test]
+    fn init_calibration_check()
+    {
+        let mut expected = vec![
+            I2cTransaction::write(SENSOR_ADDR, vec![Command::ReadStatus as u8]),
+            I2cTransaction::read(SENSOR_ADDR, vec![0x08]),
+        ];
+        for reg in CAL_REGISTERS {
+            expected.push(I2cTransaction::write(SENSOR_ADDR, vec![reg, 0x00, 0x00]));
+            expected.push(I2cTransaction::read(SENSOR_ADDR, vec![0x1C, reg, 0x5A]));
+            expected.push(I2cTransaction::write(SENSOR_ADDR, vec![0xB0 | reg, reg, 0x5A]));
+        }
+        expected.extend([
+            I2cTransaction::write(SENSOR_ADDR, vec![Command::InitSensor as u8]),
+            I2cTransaction::write(SENSOR_ADDR, vec![Command::ReadStatus as u8]),
+            I2cTransaction::read(SENSOR_ADDR, vec![0x18]),
+        ]);
+
+        let mut sensor_instance = Sensor::new(I2cMock::new(&expected), SENSOR_ADDR);
+        sensor_instance.set_timings(Timings { register_ms: 7, calibrate_ms: 12, ..Timings::AHT20 });
+        sensor_instance.set_calibration_check(true);
+        assert_eq!(sensor_instance.init_worst_case_ms(), 40 + 4 * 10 + 3 * (12 + 3 * (7 + 12) + 12));
+        let mut delay = DelayLog::default();
+        let mut inited_sensor = sensor_instance.init(&mut delay).unwrap();
+        assert_eq!(delay.0, [40, 7, 12, 7, 12, 7, 12, 12]);
+        inited_sensor.sensor.i2c.done();
+    }
+
+    #[test]
+    fn init_bounded()
+    {
+        let mut expected = init_attempt(0);
+        expected.extend(init_attempt(sensor_status::CALENABLED_BM));
+
+        let sensor_instance = Sensor::new(I2cMock::new(&expected), SENSOR_ADDR);
+        assert_eq!(sensor_instance.init_worst_case_ms(), 110);
+
+        let mut clock = clock::clock_tests::FakeClock { now_ms: 0, step_ms: 50 };
+        let mut mock_delay = embedded_hal_mock::delay::MockNoop;
+        let mut inited_sensor = sensor_instance.init_bounded(&mut mock_delay, &mut clock, 200).unwrap();
+        inited_sensor.sensor.i2c.done();
+
+        //Out of budget after the first attempt.
+        let expected = init_attempt(0);
+        let sensor_instance = Sensor::new(I2cMock::new(&expected), SENSOR_ADDR);
+        let mut clock = clock::clock_tests::FakeClock { now_ms: 0, step_ms: 100 };
+        let r = sensor_instance.init_bounded(&mut mock_delay, &mut clock, 100).err().unwrap();
+        assert_eq!(r.error, Error::BudgetExceeded);
+        let mut sensor_instance = r.sensor;
+        assert_eq!(
+            sensor_instance.journal().latest().unwrap().event,
+            JournalEvent::Error(ErrorKind::BudgetExceeded),
+            );
+        sensor_instance.i2c.done();
+
+        //A first attempt that might not fit isn't started, a retry that
+        //fits what's left is.
+        let sensor_instance = Sensor::new(I2cMock::new(&[]), SENSOR_ADDR);
+        let mut clock = clock::clock_tests::FakeClock { now_ms: 0, step_ms: 0 };
+        let r = sensor_instance.init_bounded(&mut mock_delay, &mut clock, 89).err().unwrap();
+        assert_eq!(r.error, Error::BudgetExceeded);
+        let mut sensor_instance = r.sensor;
+        sensor_instance.i2c.done();
+
+        let mut expected = init_attempt(0);
+        expected.extend(init_attempt(sensor_status::CALENABLED_BM));
+        let sensor_instance = Sensor::new(I2cMock::new(&expected), SENSOR_ADDR);
+        let mut clock = clock::clock_tests::FakeClock { now_ms: 0, step_ms: 80 };
+        let mut inited_sensor = sensor_instance.init_bounded(&mut mock_delay, &mut clock, 90).unwrap();
+        inited_sensor.sensor.i2c.done();
+
+        //Out of attempts.
+        let expected: Vec<_> = (0..MAX_ATTEMPTS).flat_map(|_| init_attempt(0)).collect();
+        let sensor_instance = Sensor::new(I2cMock::new(&expected), SENSOR_ADDR);
+        let r = sensor_instance.init_bounded(&mut mock_delay, &mut clock, u32::MAX).err().unwrap();
+        assert_eq!(r.error, Error::Internal);
+        let mut sensor_instance = r.sensor;
+        sensor_instance.i2c.done();
+    }
+
+
+    #[test]
+    fn status_cache()
+    {
+        let read_status = [
+            I2cTransaction::write(SENSOR_ADDR, vec![Command::ReadStatus as u8]),
+            I2cTransaction::read(SENSOR_ADDR, vec![0x18]),
+        ];
+        let trigger = I2cTransaction::write(
+            SENSOR_ADDR,
+            vec![Command::TrigMessure as u8, TRIG_MEASURE_PARAM0, TRIG_MEASURE_PARAM1],
+            );
+
+        let mut expected = vec![];
+        expected.extend_from_slice(&read_status);
+        //Too old, read again.
+        expected.extend_from_slice(&read_status);
+        //The trigger command drops the cache.
+        expected.push(trigger);
+        expected.extend_from_slice(&read_status);
+
+        let i2c = I2cMock::new(&expected);
+        let mut sensor_instance = Sensor::new(i2c, SENSOR_ADDR);
+        sensor_instance.set_status_cache(Some(100));
+
+        let mut clock = clock::clock_tests::FakeClock { now_ms: 0, step_ms: 50 };
+        let mut inited_sensor = InitializedSensor {
+            sensor: sensor_instance
+        }; 
+
+        //t = 0, 50, 100 all come from one read.
+        for _ in 0..3 {
+            assert_eq!(inited_sensor.get_status_cached(&mut clock).unwrap().status, 0x18);
+        }
+        //t = 150
+        assert!(inited_sensor.get_status_cached(&mut clock).is_ok());
+
+        inited_sensor.trigger_measurement().unwrap();
+        assert!(inited_sensor.get_status_cached(&mut clock).is_ok());
+
+        inited_sensor.sensor.i2c.done();
+    }
+
+    #[test]
+    fn status_cache_disabled()
+    {
+        let expected = [
+            I2cTransaction::write(SENSOR_ADDR, vec![Command::ReadStatus as u8]),
+            I2cTransaction::read(SENSOR_ADDR, vec![0x18]),
+            I2cTransaction::write(SENSOR_ADDR, vec![Command::ReadStatus as u8]),
+            I2cTransaction::read(SENSOR_ADDR, vec![0x18]),
+        ];
 
+        let i2c = I2cMock::new(&expected);
         let mut sensor_instance = Sensor::new(i2c, SENSOR_ADDR);
+        let mut clock = clock::clock_tests::FakeClock { now_ms: 0, step_ms: 0 };
 
-        let mut mock_delay = embedded_hal_mock::delay::MockNoop;
-        let initialized_sensor_instance = sensor_instance.init(&mut mock_delay);
-        
-        assert!(initialized_sensor_instance.is_ok());
-
-        initialized_sensor_instance.unwrap().sensor.i2c.done();
+        assert!(sensor_instance.read_status_cached(&mut clock).is_ok());
+        assert!(sensor_instance.read_status_cached(&mut clock).is_ok());
+        sensor_instance.i2c.done();
     }
 
-
     #[test]
     fn get_initialized_status()
     {
         let wbuf = vec![Command::ReadStatus as u8];
         let sensor_status= vec![
-            sensor_status::CMDMODE_BM as u8 | 
-            sensor_status::CALENABLED_BM as u8
+            sensor_status::CMDMODE_BM | 
+            sensor_status::CALENABLED_BM
             ];
         
         let expected = [
@@ -466,9 +2007,9 @@ mod sensor_test {
 
         //Skip doing the INIT of the sensor.
         let i2c = I2cMock::new(&expected);
-        let mut sensor_instance = Sensor::new(i2c, SENSOR_ADDR);
+        let sensor_instance = Sensor::new(i2c, SENSOR_ADDR);
         let mut inited_sensor = InitializedSensor {
-            sensor: &mut sensor_instance
+            sensor: sensor_instance
         }; 
        
         let r = inited_sensor.get_status();
@@ -483,14 +2024,13 @@ mod sensor_test {
 
 #[cfg(test)]
 mod initialized_sensor_tests {
-    use embedded_hal_mock;
-
     use embedded_hal_mock::i2c::{
         Mock as I2cMock, 
         Transaction as I2cTransaction
     };
     
     use super::*;
+    use crate::core::sensor_status;
     
     #[test]
     fn trigger_messurement() 
@@ -505,9 +2045,9 @@ mod initialized_sensor_tests {
         
         //Skip doing the INIT of the sensor.
         let i2c = I2cMock::new(&expected);
-        let mut sensor_instance = Sensor::new(i2c, SENSOR_ADDR);
+        let sensor_instance = Sensor::new(i2c, SENSOR_ADDR);
         let mut inited_sensor = InitializedSensor {
-            sensor: &mut sensor_instance
+            sensor: sensor_instance
         }; 
         
         let res = inited_sensor.trigger_measurement();
@@ -521,11 +2061,11 @@ mod initialized_sensor_tests {
     fn read_sensor()
     {
 
-        let busy_status = sensor_status::CALENABLED_BM as u8 | 
-            sensor_status::BUSY_BM as u8 |
+        let busy_status = sensor_status::CALENABLED_BM | 
+            sensor_status::BUSY_BM |
             0x10;
 
-        let not_busy_status = sensor_status::CALENABLED_BM as u8 | 0x10;
+        let not_busy_status = sensor_status::CALENABLED_BM | 0x10;
 
         let fake_sensor_data = vec![
             busy_status,
@@ -553,9 +2093,9 @@ mod initialized_sensor_tests {
 
         //Skip doing the INIT of the sensor.
         let i2c = I2cMock::new(&expected);
-        let mut sensor_instance = Sensor::new(i2c, SENSOR_ADDR);
+        let sensor_instance = Sensor::new(i2c, SENSOR_ADDR);
         let mut inited_sensor = InitializedSensor {
-            sensor: &mut sensor_instance
+            sensor: sensor_instance
         }; 
         
         let mut mock_delay = embedded_hal_mock::delay::MockNoop;
@@ -575,6 +2115,486 @@ mod initialized_sensor_tests {
         inited_sensor.sensor.i2c.done();
     }
 
+    #[test]
+    fn measure()
+    {
+        let good = vec![0x18, 0x7E, 0x51, 0x65, 0xD4, 0xA0, 0xDA];
+        let mut bad_crc = good.clone();
+        bad_crc[6] = 0xD7;
+
+        let trigger = vec![commands::TRIG_MESSURE, TRIG_MEASURE_PARAM0, TRIG_MEASURE_PARAM1];
+        let expected = [
+            I2cTransaction::write(SENSOR_ADDR, trigger.clone()),
+            I2cTransaction::read(SENSOR_ADDR, good),
+            I2cTransaction::write(SENSOR_ADDR, trigger),
+            I2cTransaction::read(SENSOR_ADDR, bad_crc),
+        ];
+
+        let i2c = I2cMock::new(&expected);
+        let mut sensor_instance = Sensor::new(i2c, SENSOR_ADDR);
+        sensor_instance.set_id(SensorId(7));
+        sensor_instance.set_pressure(Some(Hectopascal(950.0)));
+        let mut inited_sensor = InitializedSensor {
+            sensor: sensor_instance
+        }; 
+
+        let mut mock_delay = embedded_hal_mock::delay::MockNoop;
+        let m = inited_sensor.measure(&mut mock_delay).unwrap();
+        assert_eq!(m.id, SensorId(7));
+        assert_eq!(m.pressure, Some(Hectopascal(950.0)));
+        assert!(m.temperature > Celsius(22.87) && m.temperature < Celsius(22.89));
+
+        let r = inited_sensor.measure(&mut mock_delay);
+        assert_eq!(r, Err(Error::InvalidChecksum));
+
+        inited_sensor.sensor.i2c.done();
+    }
+
+    #[test]
+    fn owned_and_moved()
+    {
+        //Kept in an application struct, as an RTOS task would.
+        struct Node {
+            sensor: InitializedSensor<crate::SimulatedSensor>,
+        }
+
+        let sim = crate::SimulatedSensor::new(Celsius(21.0), RelativeHumidity(50.0));
+        let mut delay = embedded_hal_mock::delay::MockNoop;
+        let mut node = Node { sensor: Sensor::new(sim, SENSOR_ADDR).init(&mut delay).unwrap() };
+        assert!(node.sensor.measure(&mut delay).is_ok());
+
+        //Back to a `Sensor` and initialized again, still the same bus.
+        let mut sensor = node.sensor.downgrade();
+        sensor.address = 0x39;
+        let e = sensor.init(&mut delay).err().unwrap();
+        assert!(matches!(e.error, Error::I2C(_)));
+        let mut sensor = e.sensor;
+        sensor.address = SENSOR_ADDR;
+        node.sensor = sensor.init(&mut delay).unwrap();
+        assert_eq!(node.sensor.sensor().state(), DriverState::Idle);
+        assert!(node.sensor.measure(&mut delay).is_ok());
+    }
+
+    #[test]
+    fn trigger_and_fetch()
+    {
+        let good = vec![0x18, 0x7E, 0x51, 0x65, 0xD4, 0xA0, 0xDA];
+        let mut busy = good.clone();
+        busy[0] = 0x98;
+        let mut bad_crc = good.clone();
+        bad_crc[6] = 0xD7;
+
+        let trigger = vec![commands::TRIG_MESSURE, TRIG_MEASURE_PARAM0, TRIG_MEASURE_PARAM1];
+        let expected = [
+            I2cTransaction::write(SENSOR_ADDR, trigger.clone()),
+            I2cTransaction::read(SENSOR_ADDR, busy),
+            I2cTransaction::read(SENSOR_ADDR, good),
+            I2cTransaction::write(SENSOR_ADDR, trigger),
+            I2cTransaction::read(SENSOR_ADDR, bad_crc),
+        ];
+
+        let mut sensor_instance = Sensor::new(I2cMock::new(&expected), SENSOR_ADDR);
+        sensor_instance.enable_sequence_numbers(3);
+        let mut inited_sensor = InitializedSensor {
+            sensor: sensor_instance
+        };
+
+        inited_sensor.trigger_measurement().unwrap();
+        assert_eq!(inited_sensor.sensor.state, DriverState::Measuring);
+        assert_eq!(inited_sensor.fetch(), Err(Error::UnexpectedBusy));
+        assert_eq!(inited_sensor.sensor.state, DriverState::Measuring);
+
+        let m = inited_sensor.fetch().unwrap();
+        assert!(m.temperature > Celsius(22.87) && m.temperature < Celsius(22.89));
+        assert_eq!(m.seq, Some(3));
+        assert_eq!(inited_sensor.sensor.state, DriverState::Idle);
+
+        inited_sensor.trigger_measurement().unwrap();
+        assert_eq!(inited_sensor.fetch(), Err(Error::InvalidChecksum));
+        assert_eq!(
+            inited_sensor.sensor.journal().latest().unwrap().event,
+            JournalEvent::Error(ErrorKind::InvalidChecksum),
+            );
+        //The busy fetch kept the number, the failed one used it up.
+        assert_eq!(inited_sensor.sensor.next_sequence_number(), Some(5));
+
+        inited_sensor.sensor.i2c.done();
+    }
+
+    #[test]
+    fn read_sensor_timeout()
+    {
+        let busy_frame = vec![
+            sensor_status::BUSY_BM | sensor_status::CALENABLED_BM,
+            0x7E, 0x51, 0x65, 0xD4, 0xA0, 0xDA,
+        ];
+
+        //80ms typical, 140ms max at 20ms polls: 4 reads before giving up.
+        let mut expected = vec![I2cTransaction::write(
+                SENSOR_ADDR,
+                vec![commands::TRIG_MESSURE, TRIG_MEASURE_PARAM0, TRIG_MEASURE_PARAM1],
+                )];
+        for _ in 0..4 {
+            expected.push(I2cTransaction::read(SENSOR_ADDR, busy_frame.clone()));
+        }
+
+        let i2c = I2cMock::new(&expected);
+        let sensor_instance = Sensor::new(i2c, SENSOR_ADDR);
+        let mut inited_sensor = InitializedSensor {
+            sensor: sensor_instance
+        }; 
+
+        let mut mock_delay = embedded_hal_mock::delay::MockNoop;
+        let r = inited_sensor.read_sensor(&mut mock_delay);
+        assert_eq!(r.err(), Some(Error::DeviceTimeOut));
+
+        inited_sensor.sensor.i2c.done();
+    }
+
+    #[test]
+    fn read_sensor_with_retry()
+    {
+        let busy_frame = vec![
+            sensor_status::BUSY_BM | sensor_status::CALENABLED_BM,
+            0x7E, 0x51, 0x65, 0xD4, 0xA0, 0xDA,
+        ];
+        let good_frame = vec![0x18, 0x7E, 0x51, 0x65, 0xD4, 0xA0, 0xDA];
+        let trigger = I2cTransaction::write(
+            SENSOR_ADDR,
+            vec![commands::TRIG_MESSURE, TRIG_MEASURE_PARAM0, TRIG_MEASURE_PARAM1],
+            );
+
+        let expected = [
+            //No retry gives up after the first busy read.
+            trigger.clone(),
+            I2cTransaction::read(SENSOR_ADDR, busy_frame.clone()),
+            //Two retries are enough the second time.
+            trigger,
+            I2cTransaction::read(SENSOR_ADDR, busy_frame.clone()),
+            I2cTransaction::read(SENSOR_ADDR, busy_frame),
+            I2cTransaction::read(SENSOR_ADDR, good_frame),
+        ];
+
+        let i2c = I2cMock::new(&expected);
+        let sensor_instance = Sensor::new(i2c, SENSOR_ADDR);
+        let mut inited_sensor = InitializedSensor {
+            sensor: sensor_instance
+        }; 
+
+        let mut mock_delay = embedded_hal_mock::delay::MockNoop;
+        let r = inited_sensor.read_sensor_with_retry(&mut mock_delay, &mut NoRetry);
+        assert_eq!(r.err(), Some(Error::DeviceTimeOut));
+
+        let mut backoff = ExponentialBackoff { initial_ms: 5, max_ms: 40, max_retries: 2 };
+        let mut sd = inited_sensor.read_sensor_with_retry(&mut mock_delay, &mut backoff).unwrap();
+        assert!(sd.is_crc_good());
+
+        inited_sensor.sensor.i2c.done();
+    }
+
+    #[test]
+    fn wait_until_idle()
+    {
+        let read_status = I2cTransaction::write(SENSOR_ADDR, vec![commands::READ_STATUS]);
+        let busy = vec![sensor_status::BUSY_BM];
+
+        let expected = [
+            read_status.clone(),
+            I2cTransaction::read(SENSOR_ADDR, busy.clone()),
+            read_status.clone(),
+            I2cTransaction::read(SENSOR_ADDR, vec![0x18]),
+            //Deadline of 0, one poll only.
+            read_status.clone(),
+            I2cTransaction::read(SENSOR_ADDR, busy.clone()),
+            //Deadline of 30, polls at 0, 20 and 30ms.
+            read_status.clone(),
+            I2cTransaction::read(SENSOR_ADDR, busy.clone()),
+            read_status.clone(),
+            I2cTransaction::read(SENSOR_ADDR, busy.clone()),
+            read_status,
+            I2cTransaction::read(SENSOR_ADDR, busy),
+        ];
+
+        let i2c = I2cMock::new(&expected);
+        let sensor_instance = Sensor::new(i2c, SENSOR_ADDR);
+        let mut inited_sensor = InitializedSensor {
+            sensor: sensor_instance
+        }; 
+
+        let mut mock_delay = embedded_hal_mock::delay::MockNoop;
+        let s = inited_sensor.wait_until_idle(&mut mock_delay, 100);
+        assert_eq!(s.unwrap().status, 0x18);
+
+        let s = inited_sensor.wait_until_idle(&mut mock_delay, 0);
+        assert_eq!(s.err(), Some(Error::DeviceTimeOut));
+
+        let s = inited_sensor.wait_until_idle(&mut mock_delay, 30);
+        assert_eq!(s.err(), Some(Error::DeviceTimeOut));
+
+        inited_sensor.sensor.i2c.done();
+    }
+
+    #[test]
+    fn measure_degraded()
+    {
+        let good = vec![0x18, 0x7E, 0x51, 0x65, 0xD4, 0xA0, 0xDA];
+        let mut bad_crc = good.clone();
+        bad_crc[6] = 0xD7;
+
+        let trigger = I2cTransaction::write(
+            SENSOR_ADDR,
+            vec![commands::TRIG_MESSURE, TRIG_MEASURE_PARAM0, TRIG_MEASURE_PARAM1],
+            );
+        let mut expected = vec![];
+        for frame in [&bad_crc, &good, &bad_crc, &bad_crc, &bad_crc] {
+            expected.push(trigger.clone());
+            expected.push(I2cTransaction::read(SENSOR_ADDR, frame.clone()));
+        }
+
+        let i2c = I2cMock::new(&expected);
+        let mut sensor_instance = Sensor::new(i2c, SENSOR_ADDR);
+        sensor_instance.set_degradation(Some(2));
+        let mut inited_sensor = InitializedSensor {
+            sensor: sensor_instance
+        }; 
+
+        let mut mock_delay = embedded_hal_mock::delay::MockNoop;
+        let m = inited_sensor.measure(&mut mock_delay).unwrap();
+        assert!(m.quality.contains(Quality::CRC_MISS));
+
+        //A good one resets the count.
+        let m = inited_sensor.measure(&mut mock_delay).unwrap();
+        assert!(m.quality.is_good());
+
+        assert!(inited_sensor.measure(&mut mock_delay).is_ok());
+        assert!(inited_sensor.measure(&mut mock_delay).is_ok());
+        let r = inited_sensor.measure(&mut mock_delay);
+        assert_eq!(r, Err(Error::InvalidChecksum));
+
+        inited_sensor.sensor.i2c.done();
+    }
+
+    #[test]
+    fn degradation_limit_max()
+    {
+        let mut sim = SimulatedSensor::new(Celsius(22.0), RelativeHumidity(45.0));
+        sim.corrupt_crc = true;
+        let mut sensor_instance = Sensor::new(sim, SENSOR_ADDR);
+        sensor_instance.set_degradation(Some(u8::MAX));
+        let mut inited_sensor = InitializedSensor {
+            sensor: sensor_instance
+        };
+
+        let mut mock_delay = embedded_hal_mock::delay::MockNoop;
+        for _ in 0..u8::MAX {
+            assert!(inited_sensor.measure(&mut mock_delay).is_ok());
+        }
+        assert_eq!(inited_sensor.measure(&mut mock_delay), Err(Error::InvalidChecksum));
+        assert_eq!(inited_sensor.sensor.diagnostic_dump().consecutive_failures, 256);
+    }
+
+    #[test]
+    fn measure_busy_overrun()
+    {
+        let busy_frame = vec![
+            sensor_status::BUSY_BM | sensor_status::CALENABLED_BM,
+            0x7E, 0x51, 0x65, 0xD4, 0xA0, 0xDA,
+        ];
+
+        let mut expected = vec![I2cTransaction::write(
+                SENSOR_ADDR,
+                vec![commands::TRIG_MESSURE, TRIG_MEASURE_PARAM0, TRIG_MEASURE_PARAM1],
+                )];
+        for _ in 0..4 {
+            expected.push(I2cTransaction::read(SENSOR_ADDR, busy_frame.clone()));
+        }
+
+        let i2c = I2cMock::new(&expected);
+        let mut sensor_instance = Sensor::new(i2c, SENSOR_ADDR);
+        sensor_instance.set_degradation(Some(1));
+        let mut inited_sensor = InitializedSensor {
+            sensor: sensor_instance
+        }; 
+
+        let mut mock_delay = embedded_hal_mock::delay::MockNoop;
+        let m = inited_sensor.measure(&mut mock_delay).unwrap();
+        assert!(m.quality.contains(Quality::BUSY_OVERRUN));
+        //The CRC still covers the busy status byte.
+        assert!(m.quality.contains(Quality::CRC_MISS));
+
+        inited_sensor.sensor.i2c.done();
+    }
+
+    fn spaced_reads(
+        policy: SpacingPolicy,
+        reads: usize,
+        ) -> (I2cMock, Vec<Result<SensorData, Error<embedded_hal_mock::MockError>>>, u16)
+    {
+        let frame = vec![0x18, 0x7E, 0x51, 0x65, 0xD4, 0xA0, 0xDA];
+        let mut expected = vec![];
+        for _ in 0..reads {
+            expected.push(I2cTransaction::write(
+                    SENSOR_ADDR,
+                    vec![commands::TRIG_MESSURE, TRIG_MEASURE_PARAM0, TRIG_MEASURE_PARAM1],
+                    ));
+            expected.push(I2cTransaction::read(SENSOR_ADDR, frame.clone()));
+        }
+
+        let i2c = I2cMock::new(&expected);
+        let mut sensor_instance = Sensor::new(i2c, SENSOR_ADDR);
+        sensor_instance.set_min_spacing(Some((800, policy)));
+        let mut inited_sensor = InitializedSensor {
+            sensor: sensor_instance
+        }; 
+
+        //Every clock read is 250ms after the previous one, the second
+        //trigger is read 500ms after the first.
+        let mut clock = clock::clock_tests::FakeClock { now_ms: 0, step_ms: 250 };
+        let mut mock_delay = embedded_hal_mock::delay::MockNoop;
+        let results = (0..2)
+            .map(|_| inited_sensor.read_sensor_timed(&mut mock_delay, &mut clock))
+            .collect();
+
+        let violations = inited_sensor.sensor.spacing_violations();
+        (inited_sensor.sensor.i2c, results, violations)
+    }
+
+    #[test]
+    fn spacing_reject()
+    {
+        let (mut i2c, results, _) = spaced_reads(SpacingPolicy::Reject, 1);
+        assert!(results[0].is_ok());
+        assert_eq!(results[1].as_ref().err(), Some(&Error::TooSoon));
+        i2c.done();
+    }
+
+    #[test]
+    fn spacing_flag()
+    {
+        let (mut i2c, results, violations) = spaced_reads(SpacingPolicy::Flag, 2);
+        assert!(results.iter().all(|r| r.is_ok()));
+        assert_eq!(violations, 1);
+        i2c.done();
+    }
+
+    #[test]
+    fn spacing_wait()
+    {
+        let (mut i2c, results, violations) = spaced_reads(SpacingPolicy::Wait, 2);
+        assert!(results.iter().all(|r| r.is_ok()));
+        assert_eq!(violations, 0);
+        i2c.done();
+    }
+
+    #[test]
+    fn strict_status()
+    {
+        let read_status = I2cTransaction::write(SENSOR_ADDR, vec![commands::READ_STATUS]);
+        let expected = [
+            read_status.clone(),
+            I2cTransaction::read(SENSOR_ADDR, vec![0x18]),
+            //CMD mode while measuring.
+            I2cTransaction::write(
+                SENSOR_ADDR,
+                vec![commands::TRIG_MESSURE, TRIG_MEASURE_PARAM0, TRIG_MEASURE_PARAM1],
+                ),
+            I2cTransaction::read(SENSOR_ADDR, vec![0x58, 0x7E, 0x51, 0x65, 0xD4, 0xA0, 0xDA]),
+            //Lost the CAL bit.
+            read_status,
+            I2cTransaction::read(SENSOR_ADDR, vec![0x10]),
+        ];
+
+        let i2c = I2cMock::new(&expected);
+        let mut sensor_instance = Sensor::new(i2c, SENSOR_ADDR);
+        sensor_instance.set_strict(true);
+        let mut inited_sensor = InitializedSensor {
+            sensor: sensor_instance
+        }; 
+
+        let mut mock_delay = embedded_hal_mock::delay::MockNoop;
+        assert!(inited_sensor.get_status().is_ok());
+        let r = inited_sensor.read_sensor(&mut mock_delay);
+        assert_eq!(r.err(), Some(Error::UnexpectedStatus(0x58)));
+        assert_eq!(inited_sensor.get_status(), Err(Error::UnexpectedStatus(0x10)));
+
+        assert_eq!(inited_sensor.sensor.status_anomalies(), 2);
+        inited_sensor.sensor.i2c.done();
+    }
+
+    #[test]
+    fn journal()
+    {
+        let read_status = I2cTransaction::write(SENSOR_ADDR, vec![commands::READ_STATUS]);
+        let expected = [
+            I2cTransaction::write(SENSOR_ADDR, vec![commands::INIT_SENSOR]),
+            read_status.clone(),
+            I2cTransaction::read(SENSOR_ADDR, vec![0x18]),
+            I2cTransaction::write(
+                SENSOR_ADDR,
+                vec![commands::TRIG_MESSURE, TRIG_MEASURE_PARAM0, TRIG_MEASURE_PARAM1],
+                ),
+            I2cTransaction::read(SENSOR_ADDR, vec![0x18, 0x7E, 0x51, 0x65, 0xD4, 0xA0, 0x00]),
+            read_status.clone(),
+            I2cTransaction::read(SENSOR_ADDR, vec![0x18]),
+            I2cTransaction::write(SENSOR_ADDR, vec![commands::SOFT_RESET]),
+            read_status,
+            I2cTransaction::read(SENSOR_ADDR, vec![0x18]),
+        ];
+
+        let i2c = I2cMock::new(&expected);
+        let sensor_instance = Sensor::new(i2c, SENSOR_ADDR);
+        let mut mock_delay = embedded_hal_mock::delay::MockNoop;
+
+        let mut inited_sensor = sensor_instance.init(&mut mock_delay).unwrap();
+        inited_sensor.sensor.tick(1234);
+        assert!(inited_sensor.measure(&mut mock_delay).is_err());
+        assert!(inited_sensor.soft_reset(&mut mock_delay).is_ok());
+
+        let mut sensor_instance = inited_sensor.downgrade();
+        let events: Vec<(Option<u32>, JournalEvent)> = sensor_instance.journal()
+            .iter()
+            .map(|e| (e.at_ms, e.event))
+            .collect();
+        assert_eq!(events, [
+            (None, JournalEvent::Init),
+            (Some(1234), JournalEvent::Error(ErrorKind::InvalidChecksum)),
+            (Some(1234), JournalEvent::Reset),
+        ]);
+
+        sensor_instance.i2c.done();
+    }
+
+    #[test]
+    fn conversion_durations()
+    {
+        let frame = vec![0x18, 0x7E, 0x51, 0x65, 0xD4, 0xA0, 0xDA];
+        let expected = [
+            I2cTransaction::write(
+                SENSOR_ADDR,
+                vec![commands::TRIG_MESSURE, TRIG_MEASURE_PARAM0, TRIG_MEASURE_PARAM1],
+                ),
+            I2cTransaction::read(SENSOR_ADDR, frame),
+        ];
+
+        let i2c = I2cMock::new(&expected);
+        let sensor_instance = Sensor::new(i2c, SENSOR_ADDR);
+        let mut inited_sensor = InitializedSensor {
+            sensor: sensor_instance
+        }; 
+
+        let mut clock = clock::clock_tests::FakeClock { now_ms: 1000, step_ms: 85 };
+        let mut mock_delay = embedded_hal_mock::delay::MockNoop;
+        assert!(inited_sensor.read_sensor_timed(&mut mock_delay, &mut clock).is_ok());
+
+        let d = inited_sensor.sensor.conversion_durations();
+        assert_eq!(d.count(), 1);
+        assert_eq!(d.typ_ms(), Some(85));
+        inited_sensor.sensor.reset_conversion_durations();
+        assert_eq!(inited_sensor.sensor.conversion_durations().count(), 0);
+
+        inited_sensor.sensor.i2c.done();
+    }
+
     #[test]
     fn soft_reset()
     {
@@ -592,9 +2612,9 @@ mod initialized_sensor_tests {
 
         //Skip doing the INIT of the sensor.
         let i2c = I2cMock::new(&expected);
-        let mut sensor_instance = Sensor::new(i2c, SENSOR_ADDR);
+        let sensor_instance = Sensor::new(i2c, SENSOR_ADDR);
         let mut inited_sensor = InitializedSensor {
-            sensor: &mut sensor_instance
+            sensor: sensor_instance
         }; 
         
         let mut mock_delay = embedded_hal_mock::delay::MockNoop;
@@ -602,6 +2622,80 @@ mod initialized_sensor_tests {
         let sr = inited_sensor.soft_reset(&mut mock_delay);
         assert!(sr.is_ok());
 
-        sensor_instance.i2c.done();
+        inited_sensor.sensor.i2c.done();
+    }
+
+    #[test]
+    fn bus_handed_back()
+    {
+        let sim = SimulatedSensor::new(Celsius(22.88), RelativeHumidity(49.34));
+        let mut mock_delay = embedded_hal_mock::delay::MockNoop;
+        let inited = Sensor::new(sim, SENSOR_ADDR).init(&mut mock_delay).unwrap();
+
+        //The bus goes to something else, then back to a new driver.
+        let mut sim = inited.release();
+        sim.corrupt_crc = true;
+        let mut inited = Sensor::new(sim, SENSOR_ADDR).init(&mut mock_delay).unwrap();
+        assert_eq!(inited.measure(&mut mock_delay).err(), Some(Error::InvalidChecksum));
+
+        let sim = inited.downgrade().destroy();
+        assert!(sim.corrupt_crc);
+    }
+
+    #[test]
+    fn force_reset()
+    {
+        let busy_status = vec![0x80];
+        let not_busy_status = vec![0x18];
+        let expected = [
+            I2cTransaction::write(SENSOR_ADDR, vec![commands::READ_STATUS]),
+            I2cTransaction::read(SENSOR_ADDR, busy_status.clone()),
+            //Forced, no status read first.
+            I2cTransaction::write(SENSOR_ADDR, vec![commands::SOFT_RESET]),
+            I2cTransaction::write(SENSOR_ADDR, vec![commands::READ_STATUS]),
+            I2cTransaction::read(SENSOR_ADDR, busy_status),
+            I2cTransaction::write(SENSOR_ADDR, vec![commands::SOFT_RESET]),
+            I2cTransaction::write(SENSOR_ADDR, vec![commands::READ_STATUS]),
+            I2cTransaction::read(SENSOR_ADDR, not_busy_status),
+        ];
+
+        let sensor_instance = Sensor::new(I2cMock::new(&expected), SENSOR_ADDR);
+        let mut inited_sensor = InitializedSensor {
+            sensor: sensor_instance
+        };
+        let mut delay = crate::sensor_test::DelayLog::default();
+
+        assert_eq!(inited_sensor.soft_reset(&mut delay).err(), Some(Error::UnexpectedBusy));
+        assert_eq!(inited_sensor.force_reset(&mut delay).err(), Some(Error::UnexpectedBusy));
+        assert_eq!(inited_sensor.sensor.state, DriverState::Uninitialized);
+        assert_eq!(inited_sensor.force_reset(&mut delay).unwrap(), SensorStatus::new(0x18));
+        assert_eq!(inited_sensor.sensor.state, DriverState::Idle);
+        assert_eq!(delay.0, [20, 20]);
+
+        inited_sensor.sensor.i2c.done();
+    }
+
+    #[test]
+    fn sequence_numbers()
+    {
+        let sim = SimulatedSensor::new(Celsius(22.88), RelativeHumidity(49.34));
+        let sensor_instance = Sensor::new(sim, SENSOR_ADDR);
+        let mut mock_delay = embedded_hal_mock::delay::MockNoop;
+        let mut inited = sensor_instance.init(&mut mock_delay).unwrap();
+        assert_eq!(inited.measure(&mut mock_delay).unwrap().seq, None);
+
+        inited.sensor.enable_sequence_numbers(u32::MAX - 1);
+        assert_eq!(inited.measure(&mut mock_delay).unwrap().seq, Some(u32::MAX - 1));
+
+        //The failed read and the reset leave gaps.
+        inited.sensor.i2c.corrupt_crc = true;
+        assert!(inited.measure(&mut mock_delay).is_err());
+        inited.sensor.i2c.corrupt_crc = false;
+        inited.soft_reset(&mut mock_delay).unwrap();
+        assert_eq!(inited.measure(&mut mock_delay).unwrap().seq, Some(1));
+        assert_eq!(inited.sensor.next_sequence_number(), Some(2));
+
+        inited.sensor.disable_sequence_numbers();
+        assert_eq!(inited.measure(&mut mock_delay).unwrap().seq, None);
     }
 }
@@ -0,0 +1,177 @@
+/*
+ * Filename: multi.rs
+ * Description: Synchronized reads of sensor arrays, e.g. eight AHT20s
+ * behind a TCA9548A with a transport per mux channel. Every conversion is
+ * started first and the frames read afterwards, so the sensors convert at
+ * the same time and a scan takes one conversion time instead of one per
+ * sensor.
+ */
+
+use alloc::vec::Vec;
+
+use crate::{
+    Aht2xTransport, Delay, DriverState, Error, Indicator, InitializedSensor,
+    Measurement, MeasurementPoll, Poll, PollState,
+};
+
+///Starts a conversion on every sensor, the results are in the sensors'
+///order. A failed trigger doesn't stop the others.
+pub fn trigger_all<E, I2C, L>(
+    sensors: &mut [InitializedSensor<I2C, L>],
+    ) -> Vec<Result<(), Error<E>>>
+where I2C: Aht2xTransport<Error = E>,
+      L: Indicator,
+{
+    sensors.iter_mut().map(|s| s.trigger_measurement()).collect()
+}
+
+///Reads back the conversions `trigger_all()` started. Each sensor gets a
+///`MeasurementPoll` and the waits in between are shared, so the longest
+///typical conversion time is waited once and the sensors still busy are
+///polled until their maximum conversion time.
+///
+///A sensor that isn't converting (its trigger failed) gives
+///`Error::Internal`.
+pub fn fetch_all<E, I2C, L>(
+    sensors: &mut [InitializedSensor<I2C, L>],
+    delay: &mut impl Delay,
+    ) -> Vec<Result<Measurement, Error<E>>>
+where I2C: Aht2xTransport<Error = E>,
+      L: Indicator,
+{
+    //Time since the triggers, the delays are the only clock.
+    let mut now: u32 = 0;
+    let mut polls: Vec<MeasurementPoll> = sensors.iter()
+        .map(|s| match s.sensor.state {
+            DriverState::Measuring => MeasurementPoll::triggered(s, now),
+            _ => MeasurementPoll::new(),
+        })
+        .collect();
+    let mut results: Vec<Option<Result<Measurement, Error<E>>>> = polls.iter()
+        .map(|p| match p.state() {
+            PollState::Idle => Some(Err(Error::Internal)),
+            _ => None,
+        })
+        .collect();
+
+    loop {
+        for ((sensor, poll), slot) in sensors.iter_mut().zip(polls.iter_mut()).zip(results.iter_mut()) {
+            if slot.is_some() {
+                continue;
+            }
+            *slot = match poll.poll(sensor, now) {
+                Ok(Poll::Pending) => None,
+                Ok(Poll::Ready(m)) => Some(Ok(m)),
+                Err(e) => Some(Err(e)),
+            };
+        }
+        let wait = polls.iter().filter_map(|p| p.due_in_ms(now)).min();
+        match wait {
+            Some(ms) => {
+                let ms = ms.min(u32::from(u16::MAX)) as u16;
+                delay.delay_ms(ms);
+                now = now.wrapping_add(u32::from(ms));
+            },
+            None => break,
+        }
+    }
+    results.into_iter().flatten().collect()
+}
+
+///`trigger_all()` followed by `fetch_all()`, a failed trigger's error is
+///passed through as that sensor's result.
+pub fn scan_all<E, I2C, L>(
+    sensors: &mut [InitializedSensor<I2C, L>],
+    delay: &mut impl Delay,
+    ) -> Vec<Result<Measurement, Error<E>>>
+where I2C: Aht2xTransport<Error = E>,
+      L: Indicator,
+{
+    let triggered = trigger_all(sensors);
+    fetch_all(sensors, delay).into_iter().zip(triggered)
+        .map(|(fetched, triggered)| triggered.and(fetched))
+        .collect()
+}
+
+#[cfg(test)]
+mod multi_tests {
+    use crate::Delay;
+    use embedded_hal_mock::i2c::{Mock as I2cMock, Transaction as I2cTransaction};
+    use embedded_hal_mock::MockError;
+
+    use super::*;
+    use crate::core::commands;
+    use crate::{Sensor, SENSOR_ADDR, TRIG_MEASURE_PARAM0, TRIG_MEASURE_PARAM1};
+
+    const FRAME: [u8; 7] = [0x18, 0x7E, 0x51, 0x65, 0xD4, 0xA0, 0xDA];
+    const BUSY: [u8; 7] = [0x98, 0x7E, 0x51, 0x65, 0xD4, 0xA0, 0xDA];
+
+    fn trigger() -> I2cTransaction {
+        I2cTransaction::write(
+            SENSOR_ADDR,
+            vec![commands::TRIG_MESSURE, TRIG_MEASURE_PARAM0, TRIG_MEASURE_PARAM1],
+            )
+    }
+
+    fn read(frame: [u8; 7]) -> I2cTransaction {
+        I2cTransaction::read(SENSOR_ADDR, frame.to_vec())
+    }
+
+    ///Adds up the waits.
+    struct TotalDelay(u32);
+
+    impl Delay for TotalDelay {
+        fn delay_ms(&mut self, ms: u16) {
+            self.0 += u32::from(ms);
+        }
+    }
+
+    #[test]
+    fn one_conversion_time()
+    {
+        let mut inited: Vec<InitializedSensor<I2cMock>> = (0..8)
+            .map(|_| Sensor::new(I2cMock::new(&[trigger(), read(FRAME)]), SENSOR_ADDR))
+            .map(|sensor| InitializedSensor { sensor })
+            .collect();
+
+        let mut delay = TotalDelay(0);
+        let readings = scan_all(&mut inited, &mut delay);
+        assert_eq!(readings.len(), 8);
+        assert!(readings.iter().all(|r| r.as_ref().is_ok_and(|m| m.quality.is_good())));
+        //One 80ms conversion for all eight.
+        assert_eq!(delay.0, 80);
+
+        for s in inited.iter_mut() {
+            s.sensor.i2c.done();
+        }
+    }
+
+    #[test]
+    fn busy_and_failed_sensors()
+    {
+        let failed = trigger().with_error(MockError::Io(std::io::ErrorKind::Other));
+        let slow = Sensor::new(I2cMock::new(&[trigger(), read(BUSY), read(FRAME)]), SENSOR_ADDR);
+        let broken = Sensor::new(I2cMock::new(&[failed]), SENSOR_ADDR);
+        let stuck = Sensor::new(
+            I2cMock::new(&[trigger(), read(BUSY), read(BUSY), read(BUSY), read(BUSY)]),
+            SENSOR_ADDR,
+            );
+        let mut inited = [
+            InitializedSensor { sensor: slow },
+            InitializedSensor { sensor: broken },
+            InitializedSensor { sensor: stuck },
+        ];
+
+        let mut delay = TotalDelay(0);
+        let readings = scan_all(&mut inited, &mut delay);
+        assert!(readings[0].is_ok());
+        assert!(matches!(readings[1], Err(Error::I2C(_))));
+        assert_eq!(readings[2], Err(Error::DeviceTimeOut));
+        //80ms typical, then 20ms polls up to the 140ms maximum.
+        assert_eq!(delay.0, 140);
+
+        for s in inited.iter_mut() {
+            s.sensor.i2c.done();
+        }
+    }
+}
@@ -0,0 +1,460 @@
+/*
+ * Filename: pipeline.rs
+ * Description: The processing stages for readings (median, EMA, calibration
+ * offset and alarms) built up as one object, so adding a stage doesn't mean
+ * another wrapper type around the last one. The stages always run in that
+ * order, whatever order the builder calls came in.
+ *
+ * Offsets are in the unit types and the alarms are set per channel, a
+ * humidity limit can't end up on the temperature. The whole setup is a
+ * `PipelineConfig`, with the `serde` feature it can be pushed to a device
+ * (e.g. as postcard) and swapped in without reflashing.
+ */
+
+use crate::alarm::{Alarm, AlarmConfig, AlarmEvent};
+use crate::core::measurement::Measurement;
+use crate::core::units::{Celsius, RelativeHumidity};
+
+///Longest median window, longer ones are cut to this.
+pub const MEDIAN_MAX: usize = 9;
+
+///Calibration offsets added to the filtered readings.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Offset {
+    pub temperature: Celsius,
+    pub humidity: RelativeHumidity,
+}
+
+impl Offset {
+    pub const ZERO: Offset = Offset::new(Celsius(0.0), RelativeHumidity(0.0));
+
+    pub const fn new(temperature: Celsius, humidity: RelativeHumidity) -> Self {
+        Offset { temperature, humidity }
+    }
+}
+
+///The alarms at the end of a pipeline, `None` for no alarm on a channel.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PipelineAlarms {
+    pub temperature: Option<AlarmConfig>,
+    pub humidity: Option<AlarmConfig>,
+}
+
+impl PipelineAlarms {
+    pub const NONE: PipelineAlarms = PipelineAlarms { temperature: None, humidity: None };
+}
+
+///Everything a `Pipeline` is built from, the default passes readings
+///through untouched.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PipelineConfig {
+    ///Median window, 0 or 1 for none.
+    pub median: u8,
+    ///EMA weight, `None` for no EMA.
+    pub ema_alpha: Option<f32>,
+    pub offset: Offset,
+    pub alarms: PipelineAlarms,
+}
+
+impl PipelineConfig {
+    ///The default, usable in a `const` or `static` table with
+    ///`..PipelineConfig::PASS_THROUGH` for the fields left out.
+    pub const PASS_THROUGH: PipelineConfig = PipelineConfig {
+        median: 0,
+        ema_alpha: None,
+        offset: Offset::ZERO,
+        alarms: PipelineAlarms::NONE,
+    };
+}
+
+///What `Pipeline::process()` hands back.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Processed {
+    pub measurement: Measurement,
+    pub temperature_alarm: Option<AlarmEvent>,
+    pub humidity_alarm: Option<AlarmEvent>,
+}
+
+//Median over the last `size` values, the warm up uses what's there.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Median {
+    window: [f32; MEDIAN_MAX],
+    size: u8,
+    len: u8,
+    next: u8,
+}
+
+impl Median {
+    const fn new(size: u8) -> Self {
+        Median { window: [0.0; MEDIAN_MAX], size, len: 0, next: 0 }
+    }
+
+    fn push(&mut self, value: f32) -> f32 {
+        if value.is_nan() {
+            return value;
+        }
+        self.window[usize::from(self.next)] = value;
+        self.next = (self.next + 1) % self.size;
+        self.len = (self.len + 1).min(self.size);
+
+        let mut sorted = self.window;
+        let sorted = &mut sorted[..usize::from(self.len)];
+        sorted.sort_unstable_by(f32::total_cmp);
+        let mid = sorted.len() / 2;
+        match sorted.len() % 2 {
+            1 => sorted[mid],
+            _ => (sorted[mid - 1] + sorted[mid]) / 2.0,
+        }
+    }
+}
+
+//Exponential moving average, the first value seeds it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Ema {
+    alpha: f32,
+    value: Option<f32>,
+}
+
+impl Ema {
+    fn push(&mut self, value: f32) -> f32 {
+        if value.is_nan() {
+            return value;
+        }
+        let next = match self.value {
+            Some(prev) => prev + self.alpha * (value - prev),
+            None => value,
+        };
+        self.value = Some(next);
+        next
+    }
+}
+
+///A chain of processing stages applied to every reading, built with
+///`Pipeline::new().median(5).ema(0.2).offset(cal).alarms(cfg)`. A stage
+///that wasn't added passes the reading through. NaN readings skip the
+///filters without touching their state.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Pipeline {
+    median: Option<[Median; 2]>,
+    ema: Option<[Ema; 2]>,
+    offset: Offset,
+    temperature_alarm: Option<Alarm>,
+    humidity_alarm: Option<Alarm>,
+}
+
+impl Pipeline {
+    ///No stages, readings come out as they went in.
+    pub const fn new() -> Self {
+        Pipeline {
+            median: None,
+            ema: None,
+            offset: Offset::ZERO,
+            temperature_alarm: None,
+            humidity_alarm: None,
+        }
+    }
+
+    ///Median of the last `window` readings, up to `MEDIAN_MAX`. Takes out
+    ///single spikes, 0 and 1 turn it off.
+    pub const fn median(mut self, window: u8) -> Self {
+        self.median = match window {
+            0 | 1 => None,
+            n if n as usize > MEDIAN_MAX => Some([Median::new(MEDIAN_MAX as u8); 2]),
+            n => Some([Median::new(n); 2]),
+        };
+        self
+    }
+
+    ///Exponential moving average, each reading moves the output by `alpha`
+    ///of the difference. Clamped to 0..=1, 1 (or NaN) turns it off.
+    pub const fn ema(mut self, alpha: f32) -> Self {
+        //NaN fails both comparisons and ends up off.
+        self.ema = match alpha {
+            a if a < 0.0 => Some([Ema { alpha: 0.0, value: None }; 2]),
+            a if a < 1.0 => Some([Ema { alpha: a, value: None }; 2]),
+            _ => None,
+        };
+        self
+    }
+
+    ///Calibration offsets, added after the filters. The humidity is kept
+    ///inside 0..=100 %RH.
+    pub const fn offset(mut self, offset: Offset) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    ///Alarms on the processed readings.
+    pub const fn alarms(mut self, alarms: PipelineAlarms) -> Self {
+        self.temperature_alarm = match alarms.temperature {
+            Some(config) => Some(Alarm::new(config)),
+            None => None,
+        };
+        self.humidity_alarm = match alarms.humidity {
+            Some(config) => Some(Alarm::new(config)),
+            None => None,
+        };
+        self
+    }
+
+    ///The pipeline for `config`, in a `const` or `static` too, e.g. from a
+    ///table of product configurations picked at boot.
+    pub const fn from_config(config: PipelineConfig) -> Self {
+        let p = Pipeline::new()
+            .median(config.median)
+            .offset(config.offset)
+            .alarms(config.alarms);
+        match config.ema_alpha {
+            Some(alpha) => p.ema(alpha),
+            None => p,
+        }
+    }
+
+    ///The current setup, for storing or sending it on.
+    pub fn config(&self) -> PipelineConfig {
+        PipelineConfig {
+            median: self.median.map_or(0, |m| m[0].size),
+            ema_alpha: self.ema.map(|e| e[0].alpha),
+            offset: self.offset,
+            alarms: PipelineAlarms {
+                temperature: self.temperature_alarm.map(|a| a.config()),
+                humidity: self.humidity_alarm.map(|a| a.config()),
+            },
+        }
+    }
+
+    ///Swaps in a new setup, e.g. one pushed by a gateway. Stages whose
+    ///setting didn't change keep their history and alarm state, the others
+    ///start over.
+    pub fn set_config(&mut self, config: PipelineConfig) {
+        let old = self.config();
+        let new = Pipeline::from_config(config);
+        let config = new.config();
+        if config.median != old.median {
+            self.median = new.median;
+        }
+        if config.ema_alpha != old.ema_alpha {
+            self.ema = new.ema;
+        }
+        self.offset = new.offset;
+        if config.alarms.temperature != old.alarms.temperature {
+            self.temperature_alarm = new.temperature_alarm;
+        }
+        if config.alarms.humidity != old.alarms.humidity {
+            self.humidity_alarm = new.humidity_alarm;
+        }
+    }
+
+    ///Runs one reading through the stages, `now_ms` is for alarms
+    ///debounced by time.
+    pub fn process(&mut self, m: Measurement, now_ms: u32) -> Processed {
+        let mut t = m.temperature.0;
+        let mut h = m.humidity.0;
+        if let Some([mt, mh]) = &mut self.median {
+            t = mt.push(t);
+            h = mh.push(h);
+        }
+        if let Some([et, eh]) = &mut self.ema {
+            t = et.push(t);
+            h = eh.push(h);
+        }
+        let temperature = Celsius(t) + self.offset.temperature;
+        let humidity = RelativeHumidity((h + self.offset.humidity.0).clamp(0.0, 100.0));
+
+        let temperature_alarm = self.temperature_alarm
+            .as_mut()
+            .and_then(|a| a.update(temperature.0, now_ms));
+        let humidity_alarm = self.humidity_alarm
+            .as_mut()
+            .and_then(|a| a.update(humidity.0, now_ms));
+        Processed {
+            measurement: Measurement { temperature, humidity, ..m },
+            temperature_alarm,
+            humidity_alarm,
+        }
+    }
+
+    ///Forgets the filter history, e.g. after the sensor was moved. The
+    ///alarms keep their state.
+    pub fn reset(&mut self) {
+        if let Some(medians) = &mut self.median {
+            *medians = [Median::new(medians[0].size); 2];
+        }
+        if let Some(emas) = &mut self.ema {
+            for ema in emas {
+                ema.value = None;
+            }
+        }
+    }
+
+    pub fn is_temperature_alarm_active(&self) -> bool {
+        self.temperature_alarm.is_some_and(|a| a.is_active())
+    }
+
+    pub fn is_humidity_alarm_active(&self) -> bool {
+        self.humidity_alarm.is_some_and(|a| a.is_active())
+    }
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Pipeline::new()
+    }
+}
+
+#[cfg(test)]
+mod pipeline_tests {
+    use super::*;
+    use crate::{Debounce, Quality, SensorId, Threshold};
+
+    fn reading(t: f32, h: f32) -> Measurement {
+        Measurement {
+            id: SensorId(0),
+            temperature: Celsius(t),
+            humidity: RelativeHumidity(h),
+            quality: Quality::GOOD,
+            pressure: None,
+            seq: None,
+        }
+    }
+
+    fn run(p: &mut Pipeline, temperatures: &[f32]) -> Vec<f32> {
+        temperatures
+            .iter()
+            .map(|&t| p.process(reading(t, 50.0), 0).measurement.temperature.0)
+            .collect()
+    }
+
+    #[test]
+    fn pass_through()
+    {
+        let mut p = Pipeline::new().median(1).ema(1.0);
+        let out = p.process(reading(21.5, 40.0), 0);
+        assert_eq!(out.measurement, reading(21.5, 40.0));
+        assert_eq!((out.temperature_alarm, out.humidity_alarm), (None, None));
+    }
+
+    #[test]
+    fn median()
+    {
+        let mut p = Pipeline::new().median(3);
+        assert_eq!(run(&mut p, &[20.0, 22.0, 90.0, 21.0]), [20.0, 21.0, 22.0, 22.0]);
+        assert!(run(&mut p, &[f32::NAN])[0].is_nan());
+        assert_eq!(run(&mut p, &[23.0]), [23.0]);
+
+        p.reset();
+        assert_eq!(run(&mut p, &[30.0]), [30.0]);
+    }
+
+    #[test]
+    fn ema_and_offset()
+    {
+        let cal = Offset { temperature: Celsius(-0.5), humidity: RelativeHumidity(3.0) };
+        let mut p = Pipeline::new().ema(0.5).offset(cal);
+        assert_eq!(run(&mut p, &[20.0, 22.0, 22.0]), [19.5, 20.5, 21.0]);
+
+        let out = Pipeline::new().offset(cal).process(reading(22.0, 99.0), 0).measurement;
+        assert_eq!(out.humidity, RelativeHumidity(100.0));
+    }
+
+    #[test]
+    fn alarms()
+    {
+        let alarms = PipelineAlarms {
+            temperature: Some(AlarmConfig {
+                threshold: Threshold::Above(30.0),
+                debounce: Debounce::Samples(2),
+            }),
+            humidity: None,
+        };
+        //The spike never reaches the alarm through the median.
+        let mut p = Pipeline::new().median(3).alarms(alarms);
+        let events: Vec<_> = [25.0, 25.0, 40.0, 25.0, 31.0, 32.0, 33.0]
+            .iter()
+            .map(|&t| p.process(reading(t, 50.0), 0).temperature_alarm)
+            .collect();
+        assert_eq!(events, [None, None, None, None, None, Some(AlarmEvent::Raised), None]);
+        assert!(p.is_temperature_alarm_active());
+        assert!(!p.is_humidity_alarm_active());
+    }
+
+    #[test]
+    fn config()
+    {
+        let cfg = PipelineConfig {
+            median: 5,
+            ema_alpha: Some(0.25),
+            offset: Offset { temperature: Celsius(1.0), humidity: RelativeHumidity(0.0) },
+            alarms: PipelineAlarms::default(),
+        };
+        assert_eq!(Pipeline::from_config(cfg).config(), cfg);
+        assert_eq!(Pipeline::new().config(), PipelineConfig::default());
+        //Normalized the way the builder does it.
+        let cfg = PipelineConfig { median: 20, ema_alpha: Some(1.0), ..cfg };
+        let out = Pipeline::from_config(cfg).config();
+        assert_eq!((out.median, out.ema_alpha), (MEDIAN_MAX as u8, None));
+    }
+
+    #[test]
+    fn reconfigured()
+    {
+        let mut p = Pipeline::new().median(3).ema(0.5);
+        run(&mut p, &[20.0, 20.0, 20.0]);
+
+        //Only the EMA changed, the median keeps its history.
+        let cfg = PipelineConfig { ema_alpha: Some(0.1), ..p.config() };
+        p.set_config(cfg);
+        assert_eq!(p.config(), cfg);
+        assert_eq!(run(&mut p, &[30.0]), [20.0]);
+    }
+
+    #[test]
+    fn const_tables()
+    {
+        const DAMP_ROOM: PipelineConfig = PipelineConfig {
+            median: 5,
+            alarms: PipelineAlarms {
+                humidity: Some(AlarmConfig::new(Threshold::Above(70.0), Debounce::Samples(3))),
+                ..PipelineAlarms::NONE
+            },
+            ..PipelineConfig::PASS_THROUGH
+        };
+        static PROFILES: [PipelineConfig; 2] = [PipelineConfig::PASS_THROUGH, DAMP_ROOM];
+        static DAMP: Pipeline = Pipeline::from_config(DAMP_ROOM);
+
+        assert_eq!(DAMP.config(), PROFILES[1]);
+        assert_eq!(Pipeline::from_config(PROFILES[0]).config(), PipelineConfig::default());
+        assert_eq!(Offset::ZERO, Offset::default());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn postcard_round_trip()
+    {
+        let cfg = PipelineConfig {
+            median: 5,
+            ema_alpha: Some(0.2),
+            offset: Offset { temperature: Celsius(-0.4), humidity: RelativeHumidity(1.5) },
+            alarms: PipelineAlarms {
+                temperature: None,
+                humidity: Some(AlarmConfig {
+                    threshold: Threshold::Above(80.0),
+                    debounce: Debounce::Millis(60_000),
+                }),
+            },
+        };
+        let mut buf = [0u8; 64];
+        let used = postcard::to_slice(&cfg, &mut buf).unwrap();
+        assert_eq!(postcard::from_bytes::<PipelineConfig>(used).unwrap(), cfg);
+    }
+}
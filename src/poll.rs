@@ -0,0 +1,364 @@
+/*
+ * Filename: poll.rs
+ * Description: A measurement as a state machine the application drives,
+ * for superloops that can't give the driver an 80ms `delay_ms()`. `start()`
+ * triggers the conversion and every `poll()` after it either reads the
+ * frame or says it isn't due yet, nothing in here ever waits.
+ */
+
+use crate::clock::elapsed_ms;
+use crate::core::retry::Deadline;
+use crate::core::sansio::{Action, Input, Machine, MeasureCycle, FRAME_LEN};
+use crate::{
+    Aht2xTransport, DriverState, Error, Indicator, InitializedSensor, Measurement, SensorData,
+    SensorStatus,
+};
+
+///Result of `MeasurementPoll::poll()`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Poll<T> {
+    Ready(T),
+    ///Not there yet, poll again later.
+    Pending,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PollState {
+    ///Nothing started, or the last measurement was handed out.
+    Idle,
+    ///Triggered at `started_ms`, waiting out the typical conversion time
+    ///until `ready_ms`.
+    Converting { started_ms: u32, ready_ms: u32 },
+    ///The sensor read busy, read again from `next_ms` on.
+    Busy { started_ms: u32, next_ms: u32 },
+}
+
+///Drives one measurement at a time on a sensor, see the module
+///description. The times passed in are a monotonic clock's milliseconds,
+///they may wrap.
+///
+///This is `MeasureCycle` with its waits turned into due times, so the bus
+///traffic, bus quirks and busy polling are the blocking `measure()`'s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MeasurementPoll {
+    state: PollState,
+    machine: Option<MeasureCycle<Deadline>>,
+}
+
+impl MeasurementPoll {
+    pub fn new() -> Self {
+        MeasurementPoll { state: PollState::Idle, machine: None }
+    }
+
+    ///Takes over a conversion already started with
+    ///`trigger_measurement()` at `now_ms`, e.g. one of `trigger_all()`'s.
+    pub fn triggered<I2C, L>(sensor: &InitializedSensor<I2C, L>, now_ms: u32) -> Self
+    where I2C: Aht2xTransport,
+          L: Indicator,
+    {
+        let mut poll = MeasurementPoll::new();
+        let mut machine = Self::machine(sensor);
+        //Its first action is the trigger that already went out.
+        machine.poll(Input::Ready);
+        if let Action::DelayMs(ms) = machine.poll(Input::Ready) {
+            poll.wait(machine, now_ms, now_ms, ms);
+        }
+        poll
+    }
+
+    pub fn state(&self) -> PollState {
+        self.state
+    }
+
+    fn machine<I2C, L>(sensor: &InitializedSensor<I2C, L>) -> MeasureCycle<Deadline>
+    where I2C: Aht2xTransport,
+          L: Indicator,
+    {
+        let s = &sensor.sensor;
+        MeasureCycle::new(&s.timings, &s.params, sensor.measure_deadline()).with_quirks(s.quirks)
+    }
+
+    //Parks `machine` until `ms` after `now_ms`.
+    fn wait(&mut self, machine: MeasureCycle<Deadline>, started_ms: u32, now_ms: u32, ms: u16) {
+        let due_ms = now_ms.wrapping_add(u32::from(ms));
+        self.state = match machine.busy_reads() {
+            0 => PollState::Converting { started_ms, ready_ms: due_ms },
+            _ => PollState::Busy { started_ms, next_ms: due_ms },
+        };
+        self.machine = Some(machine);
+    }
+
+    ///Triggers a conversion. One still running is `Error::UnexpectedBusy`,
+    ///a failed trigger leaves the machine idle.
+    pub fn start<E, I2C, L>(
+        &mut self,
+        sensor: &mut InitializedSensor<I2C, L>,
+        now_ms: u32,
+        ) -> Result<(), Error<E>>
+    where I2C: Aht2xTransport<Error = E>,
+          L: Indicator,
+    {
+        if self.state != PollState::Idle {
+            return Err(Error::UnexpectedBusy);
+        }
+        sensor.sensor.check_faulted()?;
+        let machine = Self::machine(sensor);
+        self.state = PollState::Converting { started_ms: now_ms, ready_ms: now_ms };
+        let r = self.advance(sensor, machine, now_ms).map(|_| ());
+        sensor.sensor.journal_result(None, r)
+    }
+
+    ///Milliseconds until a `poll()` would read the sensor, 0 if it would
+    ///now. `None` when idle.
+    pub fn due_in_ms(&self, now_ms: u32) -> Option<u32> {
+        let due_ms = match self.state {
+            PollState::Idle => return None,
+            PollState::Converting { ready_ms, .. } => ready_ms,
+            PollState::Busy { next_ms, .. } => next_ms,
+        };
+        //Past due when the wait is more than half the clock's range.
+        let wait = elapsed_ms(now_ms, due_ms);
+        Some(if wait < u32::MAX / 2 { wait } else { 0 })
+    }
+
+    //Runs the machine until it waits or is done, with the frame once it is.
+    //Any failure ends the conversion and leaves the poll idle.
+    fn advance<E, I2C, L>(
+        &mut self,
+        sensor: &mut InitializedSensor<I2C, L>,
+        mut machine: MeasureCycle<Deadline>,
+        now_ms: u32,
+        ) -> Result<Option<SensorData>, Error<E>>
+    where I2C: Aht2xTransport<Error = E>,
+          L: Indicator,
+    {
+        let started_ms = match self.state {
+            PollState::Idle => return Err(Error::Internal),
+            PollState::Converting { started_ms, .. } => started_ms,
+            PollState::Busy { started_ms, .. } => started_ms,
+        };
+        self.state = PollState::Idle;
+        let s = &mut sensor.sensor;
+        let mut buf = [0u8; FRAME_LEN];
+        let mut read = None;
+        let r = loop {
+            let input = match read.take() {
+                Some(n) => Input::Data(&buf[..n]),
+                None => Input::Ready,
+            };
+            match machine.poll(input) {
+                Action::Write(wbuf) => {
+                    if let Err(e) = s.write_command(wbuf.as_slice()) {
+                        break Err(e);
+                    }
+                },
+                Action::Read(n) => {
+                    let n = n.min(FRAME_LEN);
+                    if let Err(e) = s.i2c.read_frame(s.address, &mut buf[..n]) {
+                        break Err(Error::I2C(e));
+                    }
+                    if let Ok(frame) = buf[..n].try_into() {
+                        s.last_frame = Some(frame);
+                    }
+                    if let Err(e) = s.check_status(SensorStatus::new(buf[0])) {
+                        break Err(e);
+                    }
+                    read = Some(n);
+                },
+                Action::DelayMs(ms) => {
+                    self.wait(machine, started_ms, now_ms, ms);
+                    return Ok(None);
+                },
+                Action::Done => break Ok(machine.frame().map(|frame| {
+                    let mut sd = SensorData::new();
+                    sd.bytes = frame;
+                    sd
+                })),
+                Action::Fail(e) => break Err(e.into()),
+            }
+        };
+        s.last_retries = machine.busy_reads();
+        match r {
+            Ok(Some(mut sd)) => {
+                s.state = DriverState::Idle;
+                if let (Some(frames), true) = (s.frames.as_mut(), sd.is_crc_good()) {
+                    frames.push(sd.frame());
+                }
+                Ok(Some(sd))
+            },
+            Ok(None) => Err(Error::Internal),
+            Err(e) => {
+                s.end_conversion();
+                Err(e)
+            },
+        }
+    }
+
+    //`poll()` without the CRC check and journal entry, the frame as read.
+    pub(crate) fn poll_frame<E, I2C, L>(
+        &mut self,
+        sensor: &mut InitializedSensor<I2C, L>,
+        now_ms: u32,
+        ) -> Result<Poll<SensorData>, Error<E>>
+    where I2C: Aht2xTransport<Error = E>,
+          L: Indicator,
+    {
+        if self.state == PollState::Idle {
+            return Err(Error::Internal);
+        }
+        if self.due_in_ms(now_ms) != Some(0) {
+            return Ok(Poll::Pending);
+        }
+        if let Err(e) = sensor.sensor.check_faulted() {
+            *self = MeasurementPoll::new();
+            return Err(e);
+        }
+        let machine = self.machine.take().ok_or(Error::Internal)?;
+        Ok(match self.advance(sensor, machine, now_ms)? {
+            Some(sd) => Poll::Ready(sd),
+            None => Poll::Pending,
+        })
+    }
+
+    ///Reads the frame once it's due, `Poll::Pending` before then or while
+    ///the sensor is busy. Busy past the maximum conversion time is
+    ///`Error::DeviceTimeOut`. Any result other than `Pending` takes the
+    ///machine back to idle. Polling an idle machine is `Error::Internal`.
+    pub fn poll<E, I2C, L>(
+        &mut self,
+        sensor: &mut InitializedSensor<I2C, L>,
+        now_ms: u32,
+        ) -> Result<Poll<Measurement>, Error<E>>
+    where I2C: Aht2xTransport<Error = E>,
+          L: Indicator,
+    {
+        if self.state == PollState::Idle {
+            return Err(Error::Internal);
+        }
+        let r = match self.poll_frame(sensor, now_ms) {
+            Ok(Poll::Pending) => return Ok(Poll::Pending),
+            Err(Error::Faulted) => Err(Error::Faulted),
+            //Like `measure()`, a read that failed uses up its number too.
+            r => {
+                let seq = sensor.sensor.take_sequence_number();
+                match r {
+                    Ok(Poll::Ready(mut sd)) => match sd.is_crc_good() {
+                        true => Ok(sensor.measurement_of(&sd, seq)),
+                        false => Err(Error::InvalidChecksum),
+                    },
+                    Ok(Poll::Pending) => Err(Error::Internal),
+                    Err(e) => Err(e),
+                }
+            },
+        };
+        sensor.sensor.journal_result(None, r).map(Poll::Ready)
+    }
+}
+
+impl Default for MeasurementPoll {
+    fn default() -> Self {
+        MeasurementPoll::new()
+    }
+}
+
+#[cfg(test)]
+mod poll_tests {
+    use embedded_hal_mock::i2c::{Mock as I2cMock, Transaction as I2cTransaction};
+
+    use super::*;
+    use crate::core::commands;
+    use crate::{BusQuirks, Celsius, RelativeHumidity, Sensor, SimulatedSensor, SENSOR_ADDR};
+
+    fn sensor(busy_reads: u8) -> InitializedSensor<SimulatedSensor> {
+        let mut sim = SimulatedSensor::new(Celsius(22.0), RelativeHumidity(45.0));
+        sim.busy_reads = busy_reads;
+        InitializedSensor { sensor: Sensor::new(sim, SENSOR_ADDR) }
+    }
+
+    #[test]
+    fn transitions()
+    {
+        let mut sensor = sensor(2);
+        let mut m = MeasurementPoll::new();
+        assert_eq!(m.due_in_ms(0), None);
+        assert_eq!(m.poll(&mut sensor, 0), Err(Error::Internal));
+
+        //Across the clock wrapping.
+        let t0 = u32::MAX - 50;
+        m.start(&mut sensor, t0).unwrap();
+        assert_eq!(m.state(), PollState::Converting { started_ms: t0, ready_ms: 29 });
+        assert_eq!(m.due_in_ms(t0), Some(80));
+        assert_eq!(m.start(&mut sensor, t0), Err(Error::UnexpectedBusy));
+        assert_eq!(m.poll(&mut sensor, t0.wrapping_add(79)), Ok(Poll::Pending));
+        assert_eq!(sensor.sensor.state(), DriverState::Measuring);
+
+        //Read busy twice, every 20ms.
+        let t = t0.wrapping_add(80);
+        assert_eq!(m.poll(&mut sensor, t), Ok(Poll::Pending));
+        assert_eq!(m.state(), PollState::Busy { started_ms: t0, next_ms: t.wrapping_add(20) });
+        assert_eq!(m.due_in_ms(t.wrapping_add(5)), Some(15));
+        assert_eq!(m.poll(&mut sensor, t.wrapping_add(5)), Ok(Poll::Pending));
+        assert_eq!(m.poll(&mut sensor, t.wrapping_add(20)), Ok(Poll::Pending));
+        let r = m.poll(&mut sensor, t.wrapping_add(45)).unwrap();
+        assert!(matches!(r, Poll::Ready(m) if m.quality.is_good()));
+        assert_eq!(m.state(), PollState::Idle);
+        assert_eq!(sensor.sensor.state(), DriverState::Idle);
+    }
+
+    #[test]
+    fn times_out()
+    {
+        let mut sensor = sensor(10);
+        sensor.sensor.enable_sequence_numbers(7);
+        let mut m = MeasurementPoll::new();
+        m.start(&mut sensor, 0).unwrap();
+        let mut now = 0;
+        let r = loop {
+            now += 10;
+            match m.poll(&mut sensor, now) {
+                Ok(Poll::Pending) => continue,
+                r => break r,
+            }
+        };
+        assert_eq!(r, Err(Error::DeviceTimeOut));
+        assert_eq!(now, 140);
+        //Only the timeout used up a number, not the busy polls.
+        assert_eq!(sensor.sensor.next_sequence_number(), Some(8));
+        assert_eq!(m.state(), PollState::Idle);
+        assert_eq!(sensor.sensor.state(), DriverState::Idle);
+    }
+
+    #[test]
+    fn failed_trigger()
+    {
+        let mut sensor = sensor(0);
+        sensor.sensor.address = 0x39;
+        let mut m = MeasurementPoll::new();
+        assert!(matches!(m.start(&mut sensor, 0), Err(Error::I2C(_))));
+        assert_eq!(m.state(), PollState::Idle);
+    }
+
+    #[test]
+    fn status_reads_while_busy()
+    {
+        let expected = [
+            I2cTransaction::write(SENSOR_ADDR, vec![commands::TRIG_MESSURE, 0x33, 0x00]),
+            I2cTransaction::read(SENSOR_ADDR, vec![0x98]),
+            I2cTransaction::read(SENSOR_ADDR, vec![0x18]),
+            I2cTransaction::read(SENSOR_ADDR, vec![0x18, 0x7E, 0x51, 0x65, 0xD4, 0xA0, 0xDA]),
+        ];
+        let mut sensor = Sensor::new(I2cMock::new(&expected), SENSOR_ADDR);
+        sensor.set_bus_quirks(BusQuirks { split_reads: true, ..BusQuirks::NONE });
+        let mut sensor = InitializedSensor { sensor };
+
+        let mut m = MeasurementPoll::new();
+        m.start(&mut sensor, 0).unwrap();
+        assert_eq!(m.poll(&mut sensor, 80), Ok(Poll::Pending));
+        assert_eq!(m.state(), PollState::Busy { started_ms: 0, next_ms: 100 });
+        assert!(matches!(m.poll(&mut sensor, 100), Ok(Poll::Ready(_))));
+        assert_eq!(sensor.last_retries(), 1);
+        sensor.sensor.i2c.done();
+    }
+}
@@ -0,0 +1,106 @@
+/*
+ * Filename: probe.rs
+ * Description: Finds the sensor's address when the board doesn't say.
+ * AHT10 modules can be strapped to 0x39, so a driver that doesn't know the
+ * variant tries each AOSONG address with a status read, which every AHT
+ * part answers and which changes nothing on the sensor.
+ */
+
+use crate::core::commands::READ_STATUS;
+use crate::core::variant::{ChipVariant, AHT10_ALT_ADDR, AOSONG_ADDRESSES};
+use crate::{Aht2xTransport, NoIndicator, Sensor};
+
+///Returns the first of `addresses` answering a status read.
+pub fn probe_address<T: Aht2xTransport>(i2c: &mut T, addresses: &[u8]) -> Option<u8> {
+    addresses.iter().copied().find(|&address| {
+        let mut status = [0u8; 1];
+        i2c.write_frame(address, &[READ_STATUS]).is_ok()
+            && i2c.read_frame(address, &mut status).is_ok()
+    })
+}
+
+impl<E, I2C> Sensor<I2C, NoIndicator>
+where I2C: Aht2xTransport<Error = E>,
+{
+    ///`new()` on the address the sensor answers on. With a known variant
+    ///only its addresses are tried, with `None` every AOSONG address is.
+    ///Only the AHT10 can be on 0x39, so a sensor found there is set to it.
+    ///
+    ///Hands the bus back if nothing answered.
+    pub fn probe(mut i2c: I2C, variant: Option<ChipVariant>) -> Result<Self, I2C> {
+        let addresses = variant.map_or(&AOSONG_ADDRESSES[..], ChipVariant::addresses);
+        let address = match probe_address(&mut i2c, addresses) {
+            Some(address) => address,
+            None => return Err(i2c),
+        };
+
+        let mut sensor = Sensor::new(i2c, address);
+        match (variant, address) {
+            (Some(v), _) => sensor.set_variant(v),
+            (None, AHT10_ALT_ADDR) => sensor.set_variant(ChipVariant::Aht10),
+            (None, _) => {},
+        }
+        Ok(sensor)
+    }
+}
+
+#[cfg(test)]
+mod probe_tests {
+    use embedded_hal_mock::i2c::{Mock as I2cMock, Transaction as I2cTransaction};
+    use embedded_hal_mock::MockError;
+
+    use super::*;
+    use crate::{Celsius, RelativeHumidity, SimulatedSensor, SENSOR_ADDR};
+
+    fn nack(address: u8) -> I2cTransaction {
+        I2cTransaction::write(address, vec![READ_STATUS])
+            .with_error(MockError::Io(std::io::ErrorKind::Other))
+    }
+
+    fn answer(address: u8) -> [I2cTransaction; 2] {
+        [
+            I2cTransaction::write(address, vec![READ_STATUS]),
+            I2cTransaction::read(address, vec![0x18]),
+        ]
+    }
+
+    #[test]
+    fn default_address()
+    {
+        let sim = SimulatedSensor::new(Celsius(22.0), RelativeHumidity(45.0));
+        let sensor = Sensor::probe(sim, None).unwrap();
+        assert_eq!(sensor.address, SENSOR_ADDR);
+        assert_eq!(sensor.variant(), ChipVariant::Aht20);
+        assert!(sensor.address_is_valid());
+    }
+
+    #[test]
+    fn aht10_strapped_high()
+    {
+        let mut expected = vec![nack(SENSOR_ADDR)];
+        expected.extend(answer(AHT10_ALT_ADDR));
+        let sensor = Sensor::probe(I2cMock::new(&expected), None).unwrap();
+        assert_eq!(sensor.address, AHT10_ALT_ADDR);
+        assert_eq!(sensor.variant(), ChipVariant::Aht10);
+        assert_eq!(sensor.timings, ChipVariant::Aht10.timings());
+        let mut i2c = sensor.i2c;
+        i2c.done();
+    }
+
+    #[test]
+    fn known_variant_only_tries_its_addresses()
+    {
+        //An AHT20 can't be at 0x39, so that isn't tried.
+        let mut i2c = match Sensor::probe(I2cMock::new(&[nack(SENSOR_ADDR)]), Some(ChipVariant::Aht20)) {
+            Err(i2c) => i2c,
+            Ok(_) => panic!("nothing should have answered"),
+        };
+        i2c.done();
+
+        let expected = [nack(SENSOR_ADDR), nack(AHT10_ALT_ADDR)];
+        assert!(probe_address(&mut I2cMock::new(&expected), &AOSONG_ADDRESSES).is_none());
+
+        let sensor = Sensor::probe(I2cMock::new(&answer(SENSOR_ADDR)), Some(ChipVariant::Aht10)).unwrap();
+        assert_eq!(sensor.variant(), ChipVariant::Aht10);
+    }
+}
@@ -0,0 +1,160 @@
+/*
+ * Filename: progress.rs
+ * Description: How far along a conversion is, for a display showing
+ * "measuring..." with a countdown. The blocking `measure()` reports it
+ * through a delay wrapper that sees every wait, `MeasurementPoll` on
+ * request.
+ */
+
+use crate::clock::elapsed_ms;
+use crate::{
+    Aht2xTransport, Delay, Error, Indicator, InitializedSensor, Measurement, MeasurementPoll, PollState,
+    Timings,
+};
+
+///A conversion in progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MeasurementProgress {
+    ///Since the conversion was triggered.
+    pub elapsed_ms: u32,
+    ///The variant's typical conversion time.
+    pub typ_ms: u32,
+    ///The longest the driver waits for the sensor.
+    pub max_ms: u32,
+}
+
+impl MeasurementProgress {
+    fn new(timings: &Timings, elapsed_ms: u32) -> Self {
+        MeasurementProgress {
+            elapsed_ms,
+            typ_ms: u32::from(timings.measure_typ_ms),
+            max_ms: u32::from(timings.measure_max_ms),
+        }
+    }
+
+    ///Estimated time left: up to the typical conversion time, past it up
+    ///to the maximum as the sensor is running late.
+    pub fn remaining_ms(&self) -> u32 {
+        match self.typ_ms.checked_sub(self.elapsed_ms) {
+            Some(left) if left > 0 => left,
+            _ => self.max_ms.saturating_sub(self.elapsed_ms),
+        }
+    }
+
+    ///Past the typical conversion time, the sensor is still busy.
+    pub fn is_late(&self) -> bool {
+        self.elapsed_ms >= self.typ_ms
+    }
+
+    ///Elapsed share of the typical time in percent, capped at 99 until the
+    ///reading is in.
+    pub fn percent(&self) -> u8 {
+        let typ = self.typ_ms.max(1);
+        (self.elapsed_ms.saturating_mul(100) / typ).min(99) as u8
+    }
+}
+
+//Passes the waits on and reports the progress after each one.
+struct ProgressDelay<'a, D, F> {
+    delay: &'a mut D,
+    timings: Timings,
+    elapsed_ms: u32,
+    on_progress: F,
+}
+
+impl<D, F> Delay for ProgressDelay<'_, D, F>
+where D: Delay,
+      F: FnMut(MeasurementProgress),
+{
+    fn delay_ms(&mut self, ms: u16) {
+        self.delay.delay_ms(ms);
+        self.elapsed_ms = self.elapsed_ms.saturating_add(u32::from(ms));
+        (self.on_progress)(MeasurementProgress::new(&self.timings, self.elapsed_ms));
+    }
+}
+
+impl<E, I2C, L> InitializedSensor<I2C, L>
+where I2C: Aht2xTransport<Error = E>,
+      L: Indicator,
+{
+    ///`measure()`, calling `on_progress` when the conversion starts and
+    ///after every wait. The elapsed time counts the waits only, the bus
+    ///time isn't in it.
+    pub fn measure_with_progress(
+        &mut self,
+        delay: &mut impl Delay,
+        mut on_progress: impl FnMut(MeasurementProgress),
+        ) -> Result<Measurement, Error<E>>
+    {
+        let timings = self.sensor.timings;
+        on_progress(MeasurementProgress::new(&timings, 0));
+        let mut delay = ProgressDelay { delay, timings, elapsed_ms: 0, on_progress };
+        self.measure(&mut delay)
+    }
+}
+
+impl MeasurementPoll {
+    ///The progress of the running conversion, `None` when idle.
+    pub fn progress<I2C, L>(
+        &self,
+        sensor: &InitializedSensor<I2C, L>,
+        now_ms: u32,
+        ) -> Option<MeasurementProgress>
+    where I2C: Aht2xTransport,
+    {
+        let started_ms = match self.state() {
+            PollState::Idle => return None,
+            PollState::Converting { started_ms, .. } => started_ms,
+            PollState::Busy { started_ms, .. } => started_ms,
+        };
+        Some(MeasurementProgress::new(&sensor.sensor.timings, elapsed_ms(started_ms, now_ms)))
+    }
+}
+
+#[cfg(test)]
+mod progress_tests {
+    use super::*;
+    use crate::{Celsius, RelativeHumidity, Sensor, SimulatedSensor, SENSOR_ADDR};
+
+    fn sensor(busy_reads: u8) -> InitializedSensor<SimulatedSensor> {
+        let mut sim = SimulatedSensor::new(Celsius(22.0), RelativeHumidity(45.0));
+        sim.busy_reads = busy_reads;
+        InitializedSensor { sensor: Sensor::new(sim, SENSOR_ADDR) }
+    }
+
+    #[test]
+    fn estimates()
+    {
+        let p = MeasurementProgress::new(&Timings::AHT20, 30);
+        assert_eq!((p.remaining_ms(), p.percent(), p.is_late()), (50, 37, false));
+        let p = MeasurementProgress::new(&Timings::AHT20, 100);
+        assert_eq!((p.remaining_ms(), p.percent(), p.is_late()), (40, 99, true));
+        let p = MeasurementProgress::new(&Timings::AHT20, 200);
+        assert_eq!(p.remaining_ms(), 0);
+    }
+
+    #[test]
+    fn blocking()
+    {
+        let mut sensor = sensor(1);
+        let mut seen = Vec::new();
+        let m = sensor.measure_with_progress(&mut embedded_hal_mock::delay::MockNoop, |p| {
+            seen.push((p.elapsed_ms, p.remaining_ms()));
+        });
+        assert!(m.is_ok());
+        //Start, the typical wait and one busy poll.
+        assert_eq!(seen, [(0, 80), (80, 60), (100, 40)]);
+    }
+
+    #[test]
+    fn polled()
+    {
+        let mut sensor = sensor(0);
+        let mut m = MeasurementPoll::new();
+        assert_eq!(m.progress(&sensor, 0), None);
+        m.start(&mut sensor, u32::MAX - 9).unwrap();
+        let p = m.progress(&sensor, 10).unwrap();
+        assert_eq!((p.elapsed_ms, p.remaining_ms()), (20, 60));
+    }
+}
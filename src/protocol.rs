@@ -0,0 +1,228 @@
+/*
+ * Filename: protocol.rs
+ * Description: The sensor's protocol without a bus, for async HALs,
+ * bit-banged I2C and host side simulators. Command encoding, status and
+ * frame decoding, the CRC, the conversions and the sans-IO command
+ * sequences, nothing in here uses embedded-hal.
+ *
+ * The driver runs its init, calibration, measurements and resets through
+ * these machines as well, blocking or with `MeasurementPoll`. A single
+ * `fetch()` and the status reads are one transfer each, decoded with the
+ * same `Frame` and `SensorStatus`.
+ */
+
+//!The protocol without any I/O, see `Machine` for driving a whole command
+//!sequence over a bus of your own.
+//!
+//!```
+//!use sensor_lib_aht20::protocol::{
+//!    Action, CalibrateCycle, CommandParams, FixedRetry, InitCycle, Input, Machine, MeasureCycle,
+//!    ResetCycle, Timings,
+//!};
+//!
+//!//A sensor that is idle, calibrated and always has this frame ready.
+//!fn drive(machine: &mut impl Machine) {
+//!    let frame = [0x18, 0x7E, 0x51, 0x65, 0xD4, 0xA0, 0xDA];
+//!    let mut read = None;
+//!    loop {
+//!        let input = match read.take() {
+//!            Some(n) => Input::Data(&frame[..n]),
+//!            None => Input::Ready,
+//!        };
+//!        match machine.poll(input) {
+//!            Action::Write(_) | Action::DelayMs(_) => {},
+//!            Action::Read(n) => read = Some(n),
+//!            Action::Done => return,
+//!            Action::Fail(e) => panic!("{:?}", e),
+//!        }
+//!    }
+//!}
+//!
+//!let t = Timings::AHT20;
+//!let params = CommandParams::DATASHEET;
+//!drive(&mut InitCycle::new(&t, &params));
+//!drive(&mut CalibrateCycle::new(&t, &params));
+//!drive(&mut ResetCycle::new(&t));
+//!let mut measure = MeasureCycle::new(&t, &params, FixedRetry { interval_ms: 20, max_retries: 3 });
+//!drive(&mut measure);
+//!assert!(measure.frame().is_some());
+//!```
+
+pub use crate::core::commands::{
+    calibrate, init_sensor, read_status, soft_reset, trigger, Command, CALIBRATE, INIT_SENSOR,
+    READ_STATUS, SOFT_RESET, TRIG_MESSURE,
+};
+pub use crate::core::convert::{raw_to_celsius, raw_to_celsius_scaled, raw_to_rh, raw_to_rh_scaled};
+pub use crate::core::frame::Frame;
+pub use crate::core::params::CommandParams;
+pub use crate::core::retry::{ExponentialBackoff, FixedRetry, NoRetry, RetryStrategy};
+pub use crate::core::sansio::{
+    Action, CalibrateCycle, InitCycle, Input, Machine, MeasureCycle, ProtocolError, ResetCycle,
+    WriteBuf, FRAME_LEN,
+};
+pub use crate::core::sensor_status::SensorStatus;
+pub use crate::core::timings::Timings;
+
+use crate::core::data::crc8_maxim;
+
+///CRC-8/MAXIM of `bytes`, the frame's last byte is this over the six
+///before it.
+pub const fn crc8(bytes: &[u8]) -> u8 {
+    crc8_maxim(bytes, bytes.len())
+}
+
+///The status byte of a one byte status read.
+pub const fn decode_status(byte: u8) -> SensorStatus {
+    SensorStatus::new(byte)
+}
+
+///A measurement frame from the bytes read, `None` if it isn't `FRAME_LEN`
+///long. The CRC is left to `Frame::is_crc_good()`.
+pub fn decode_frame(bytes: &[u8]) -> Option<Frame> {
+    bytes.try_into().ok().map(Frame::new)
+}
+
+#[cfg(test)]
+mod protocol_tests {
+    use super::*;
+    use crate::core::retry::Deadline;
+    use crate::sensor_test::DelayLog;
+    use crate::{Aht2xTransport, Celsius, RelativeHumidity, Sensor, SimulatedSensor, SENSOR_ADDR};
+
+    const FRAME: [u8; FRAME_LEN] = [0x18, 0x7E, 0x51, 0x65, 0xD4, 0xA0, 0xDA];
+
+    #[test]
+    fn encoding()
+    {
+        assert_eq!(read_status(), [0x71]);
+        assert_eq!(init_sensor(), [0xBE]);
+        assert_eq!(soft_reset(), [0xBA]);
+        assert_eq!(trigger(CommandParams::DATASHEET.trigger), [0xAC, 0x33, 0x00]);
+        assert_eq!(calibrate(CommandParams::DATASHEET.calibrate), [0xE1, 0x08, 0x00]);
+    }
+
+    #[test]
+    fn decoding()
+    {
+        assert!(decode_status(0x98).is_busy());
+        assert_eq!(crc8(&FRAME[..6]), FRAME[6]);
+        let frame = decode_frame(&FRAME).unwrap();
+        assert!(frame.is_crc_good());
+        assert_eq!(raw_to_celsius_scaled(frame.temperature_raw(), 2), 2288);
+        assert_eq!(raw_to_rh_scaled(frame.humidity_raw(), 2), 4934);
+        assert_eq!(decode_frame(&FRAME[..6]), None);
+    }
+
+    //A host side simulator: the machine drives a sensor that is nothing
+    //but a byte script, no bus or delay in sight.
+    #[test]
+    fn measurement_without_a_bus()
+    {
+        let retry = FixedRetry { interval_ms: 20, max_retries: 3 };
+        let mut machine = MeasureCycle::new(&Timings::AHT20, &CommandParams::DATASHEET, retry);
+        let mut busy = FRAME;
+        busy[0] = 0x98;
+        let mut replies = [busy, FRAME].into_iter();
+
+        let mut written = vec![];
+        let mut waited = 0;
+        let mut reply: Option<Vec<u8>> = None;
+        loop {
+            let input = match &reply {
+                Some(bytes) => Input::Data(bytes.as_slice()),
+                None => Input::Ready,
+            };
+            match machine.poll(input) {
+                Action::Write(w) => {
+                    written.push(w.as_slice().to_vec());
+                    reply = None;
+                },
+                Action::Read(n) => reply = replies.next().map(|f| f[..n].to_vec()),
+                Action::DelayMs(ms) => {
+                    waited += u32::from(ms);
+                    reply = None;
+                },
+                Action::Done => break,
+                Action::Fail(e) => panic!("{:?}", e),
+            }
+        }
+        assert_eq!(written, [trigger(CommandParams::DATASHEET.trigger).to_vec()]);
+        assert_eq!(waited, 100);
+        assert_eq!(machine.frame(), Some(FRAME));
+    }
+
+    ///Logs the bus traffic, `true` for a write.
+    struct Log<T>(T, Vec<(bool, Vec<u8>)>);
+
+    impl<T: Aht2xTransport> Aht2xTransport for Log<T> {
+        type Error = T::Error;
+
+        fn write_frame(&mut self, address: u8, bytes: &[u8]) -> Result<(), T::Error> {
+            self.1.push((true, bytes.to_vec()));
+            self.0.write_frame(address, bytes)
+        }
+
+        fn read_frame(&mut self, address: u8, buf: &mut [u8]) -> Result<(), T::Error> {
+            let r = self.0.read_frame(address, buf);
+            self.1.push((false, buf.to_vec()));
+            r
+        }
+    }
+
+    //Runs the machine over the bus, logging the waits.
+    fn drive(machine: &mut impl Machine, bus: &mut impl Aht2xTransport, waits: &mut Vec<u16>) {
+        let mut buf = [0u8; FRAME_LEN];
+        let mut read = None;
+        loop {
+            let input = match read.take() {
+                Some(n) => Input::Data(&buf[..n]),
+                None => Input::Ready,
+            };
+            match machine.poll(input) {
+                Action::Write(w) => assert!(bus.write_frame(SENSOR_ADDR, w.as_slice()).is_ok()),
+                Action::Read(n) => {
+                    assert!(bus.read_frame(SENSOR_ADDR, &mut buf[..n]).is_ok());
+                    read = Some(n);
+                },
+                Action::DelayMs(ms) => waits.push(ms),
+                Action::Done => return,
+                Action::Fail(e) => panic!("{:?}", e),
+            }
+        }
+    }
+
+    //The blocking driver and the machines driven by hand: the same bus
+    //traffic, waits and reading.
+    #[test]
+    fn blocking_driver_agrees()
+    {
+        let sim = || {
+            let mut sim = SimulatedSensor::new(Celsius(22.88), RelativeHumidity(49.34));
+            sim.busy_reads = 1;
+            Log(sim, vec![])
+        };
+
+        let mut delay = DelayLog::default();
+        let mut inited = Sensor::new(sim(), SENSOR_ADDR).init(&mut delay).unwrap();
+        let m = inited.measure(&mut delay).unwrap();
+        let blocking = inited.release();
+
+        let t = Timings::AHT20;
+        let params = CommandParams::DATASHEET;
+        let mut bus = sim();
+        let mut waits = vec![];
+        drive(&mut InitCycle::new(&t, &params), &mut bus, &mut waits);
+        let retry = Deadline {
+            interval_ms: t.busy_poll_ms,
+            deadline_ms: t.measure_max_ms - t.measure_typ_ms,
+        };
+        let mut measure = MeasureCycle::new(&t, &params, retry);
+        drive(&mut measure, &mut bus, &mut waits);
+
+        assert_eq!(bus.1, blocking.1);
+        assert_eq!(waits, delay.0);
+        let frame = decode_frame(&measure.frame().unwrap()).unwrap();
+        assert_eq!(raw_to_celsius(frame.temperature_raw()), m.temperature.0);
+        assert_eq!(raw_to_rh(frame.humidity_raw()), m.humidity.0);
+    }
+}
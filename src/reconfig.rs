@@ -0,0 +1,331 @@
+/*
+ * Filename: reconfig.rs
+ * Description: Changing the driver's settings while it runs, e.g. from a
+ * gateway. `update_config()` checks every field first and only applies the
+ * update if all of them pass, and never while a conversion is running, so
+ * the sensor is never left half reconfigured.
+ */
+
+use crate::alarm::{AlarmConfig, Threshold};
+use crate::core::timings::Timings;
+use crate::pipeline::{Pipeline, PipelineConfig, MEDIAN_MAX};
+use crate::state::DriverState;
+use crate::{Aht2xTransport, Indicator, InitializedSensor, SpacingPolicy};
+
+///The settings to change, a `None` field is left as it is. For the ones
+///that can be turned off `Some(None)` turns them off.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ConfigUpdate {
+    ///Timing table, the busy poll interval and the maximum conversion time
+    ///are the retry settings.
+    pub timings: Option<Timings>,
+    pub min_spacing: Option<Option<(u32, SpacingPolicy)>>,
+    pub fault_limit: Option<Option<u8>>,
+    pub degradation: Option<Option<u8>>,
+    ///Filters, offsets and alarms of the pipeline passed along.
+    pub pipeline: Option<PipelineConfig>,
+}
+
+impl ConfigUpdate {
+    ///Changes nothing, the base for `const` and `static` updates:
+    ///`ConfigUpdate { fault_limit: Some(Some(3)), ..ConfigUpdate::NONE }`.
+    pub const NONE: ConfigUpdate = ConfigUpdate {
+        timings: None,
+        min_spacing: None,
+        fault_limit: None,
+        degradation: None,
+        pipeline: None,
+    };
+}
+
+///A field of `ConfigUpdate`, the pipeline's split up by stage (the last
+///four).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConfigField {
+    Timings,
+    MinSpacing,
+    FaultLimit,
+    Degradation,
+    Median,
+    EmaAlpha,
+    Offset,
+    Alarms,
+}
+
+impl ConfigField {
+    pub const ALL: [ConfigField; 8] = [
+        ConfigField::Timings,
+        ConfigField::MinSpacing,
+        ConfigField::FaultLimit,
+        ConfigField::Degradation,
+        ConfigField::Median,
+        ConfigField::EmaAlpha,
+        ConfigField::Offset,
+        ConfigField::Alarms,
+    ];
+}
+
+///Why a field was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RejectReason {
+    ///Zero, NaN or past a limit, e.g. a median window over `MEDIAN_MAX`.
+    OutOfRange,
+    ///The values don't fit together, e.g. a maximum conversion time below
+    ///the typical one.
+    Inconsistent,
+    ///A pipeline setting without a pipeline to apply it to.
+    NoPipeline,
+}
+
+///The fields an update was rejected for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Rejections {
+    reasons: [Option<RejectReason>; ConfigField::ALL.len()],
+}
+
+impl Rejections {
+    fn reject(&mut self, field: ConfigField, reason: RejectReason) {
+        self.reasons[field as usize] = Some(reason);
+    }
+
+    pub fn reason(&self, field: ConfigField) -> Option<RejectReason> {
+        self.reasons[field as usize]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.reasons.iter().all(Option::is_none)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ConfigField, RejectReason)> + '_ {
+        ConfigField::ALL
+            .iter()
+            .filter_map(|&field| self.reason(field).map(|reason| (field, reason)))
+    }
+}
+
+///A failed `update_config()`, nothing was applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigError {
+    ///A conversion, reset or init is running, try again once it's done.
+    Busy(DriverState),
+    Rejected(Rejections),
+}
+
+fn check_timings(t: &Timings) -> Option<RejectReason> {
+    if t.measure_typ_ms == 0 || t.busy_poll_ms == 0 {
+        return Some(RejectReason::OutOfRange);
+    }
+    if t.measure_max_ms < t.measure_typ_ms || t.busy_poll_ms > t.measure_max_ms {
+        return Some(RejectReason::Inconsistent);
+    }
+    None
+}
+
+fn check_alarm(alarm: &Option<AlarmConfig>) -> Option<RejectReason> {
+    match alarm.map(|a| a.threshold) {
+        Some(Threshold::Above(limit) | Threshold::Below(limit)) if limit.is_nan() => {
+            Some(RejectReason::OutOfRange)
+        },
+        _ => None,
+    }
+}
+
+fn check_pipeline(cfg: &PipelineConfig, rejections: &mut Rejections) {
+    if usize::from(cfg.median) > MEDIAN_MAX {
+        rejections.reject(ConfigField::Median, RejectReason::OutOfRange);
+    }
+    if cfg.ema_alpha.is_some_and(|a| !(a > 0.0 && a <= 1.0)) {
+        rejections.reject(ConfigField::EmaAlpha, RejectReason::OutOfRange);
+    }
+    let t = cfg.offset.temperature.0;
+    let h = cfg.offset.humidity.0;
+    if !t.is_finite() || !h.is_finite() || h.abs() > 100.0 {
+        rejections.reject(ConfigField::Offset, RejectReason::OutOfRange);
+    }
+    let alarm = check_alarm(&cfg.alarms.temperature).or(check_alarm(&cfg.alarms.humidity));
+    if let Some(reason) = alarm {
+        rejections.reject(ConfigField::Alarms, reason);
+    }
+}
+
+impl ConfigUpdate {
+    ///Checks every field without applying anything.
+    pub fn validate(&self, has_pipeline: bool) -> Rejections {
+        let mut rejections = Rejections::default();
+        if let Some(reason) = self.timings.as_ref().and_then(check_timings) {
+            rejections.reject(ConfigField::Timings, reason);
+        }
+        //`None` turns these off, a zero would be a check that always trips.
+        if let Some(Some((0, _))) = self.min_spacing {
+            rejections.reject(ConfigField::MinSpacing, RejectReason::OutOfRange);
+        }
+        if let Some(Some(0)) = self.fault_limit {
+            rejections.reject(ConfigField::FaultLimit, RejectReason::OutOfRange);
+        }
+        match (&self.pipeline, has_pipeline) {
+            (Some(cfg), true) => check_pipeline(cfg, &mut rejections),
+            (Some(_), false) => {
+                for field in &ConfigField::ALL[4..] {
+                    rejections.reject(*field, RejectReason::NoPipeline);
+                }
+            },
+            (None, _) => (),
+        }
+        rejections
+    }
+}
+
+impl<E, I2C, L> InitializedSensor<I2C, L>
+where I2C: Aht2xTransport<Error = E>,
+      L: Indicator,
+{
+    ///Validates `update` and applies all of it, or none of it if any field
+    ///is rejected. Refused with `ConfigError::Busy` while a conversion is
+    ///waiting to be read, so a running measurement keeps its settings.
+    ///`pipeline` is the one the readings go through, if any.
+    pub fn update_config(
+        &mut self,
+        update: &ConfigUpdate,
+        pipeline: Option<&mut Pipeline>,
+        ) -> Result<(), ConfigError>
+    {
+        let state = self.sensor.state;
+        if matches!(state, DriverState::Measuring | DriverState::Initializing | DriverState::Resetting) {
+            return Err(ConfigError::Busy(state));
+        }
+        let rejections = update.validate(pipeline.is_some());
+        if !rejections.is_empty() {
+            return Err(ConfigError::Rejected(rejections));
+        }
+
+        let sensor = &mut self.sensor;
+        if let Some(timings) = update.timings {
+            sensor.set_timings(timings);
+        }
+        if let Some(spacing) = update.min_spacing {
+            sensor.set_min_spacing(spacing);
+        }
+        if let Some(limit) = update.fault_limit {
+            sensor.set_fault_limit(limit);
+        }
+        if let Some(limit) = update.degradation {
+            sensor.set_degradation(limit);
+        }
+        if let (Some(cfg), Some(pipeline)) = (update.pipeline, pipeline) {
+            pipeline.set_config(cfg);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod reconfig_tests {
+    use embedded_hal_mock::delay::MockNoop;
+
+    use super::*;
+    use crate::{Celsius, ChipVariant, RelativeHumidity, Sensor, SimulatedSensor, SENSOR_ADDR};
+
+    fn sensor() -> InitializedSensor<SimulatedSensor> {
+        let sim = SimulatedSensor::new(Celsius(22.0), RelativeHumidity(45.0));
+        Sensor::new(sim, SENSOR_ADDR).init(&mut MockNoop).unwrap()
+    }
+
+    #[test]
+    fn applied()
+    {
+        let mut inited = sensor();
+        let mut pipeline = Pipeline::new();
+        let timings = Timings { measure_max_ms: 200, ..Timings::AHT20 };
+        let cfg = PipelineConfig { median: 3, ..PipelineConfig::default() };
+        let update = ConfigUpdate {
+            timings: Some(timings),
+            fault_limit: Some(Some(3)),
+            pipeline: Some(cfg),
+            ..ConfigUpdate::default()
+        };
+        assert_eq!(inited.update_config(&update, Some(&mut pipeline)), Ok(()));
+        assert_eq!(inited.sensor.timings(), timings);
+        assert_eq!(inited.sensor.fault_limit, Some(3));
+        assert_eq!(pipeline.config(), cfg);
+    }
+
+    #[test]
+    fn all_or_nothing()
+    {
+        let mut inited = sensor();
+        let mut pipeline = Pipeline::new();
+        let update = ConfigUpdate {
+            timings: Some(Timings { measure_max_ms: 50, ..Timings::AHT20 }),
+            fault_limit: Some(Some(3)),
+            pipeline: Some(PipelineConfig {
+                median: 20,
+                ema_alpha: Some(f32::NAN),
+                ..PipelineConfig::default()
+            }),
+            ..ConfigUpdate::default()
+        };
+        let rejections = match inited.update_config(&update, Some(&mut pipeline)) {
+            Err(ConfigError::Rejected(r)) => r,
+            r => panic!("{:?}", r),
+        };
+        assert_eq!(rejections.iter().collect::<Vec<_>>(), [
+            (ConfigField::Timings, RejectReason::Inconsistent),
+            (ConfigField::Median, RejectReason::OutOfRange),
+            (ConfigField::EmaAlpha, RejectReason::OutOfRange),
+        ]);
+        //The good field wasn't applied either.
+        assert_eq!(inited.sensor.fault_limit, None);
+        assert_eq!(inited.sensor.timings(), Timings::AHT20);
+        assert_eq!(pipeline.config(), PipelineConfig::default());
+
+        let update = ConfigUpdate {
+            pipeline: Some(PipelineConfig::default()),
+            ..ConfigUpdate::default()
+        };
+        let rejections = update.validate(false);
+        assert_eq!(rejections.reason(ConfigField::Alarms), Some(RejectReason::NoPipeline));
+    }
+
+    #[test]
+    fn from_flash()
+    {
+        const SLOW_BUS: ConfigUpdate = ConfigUpdate {
+            timings: Some(Timings { measure_max_ms: 200, ..ChipVariant::Aht20.timings() }),
+            ..ConfigUpdate::NONE
+        };
+        static PROFILES: [ConfigUpdate; 2] = [ConfigUpdate::NONE, SLOW_BUS];
+
+        let mut inited = sensor();
+        assert_eq!(inited.update_config(&PROFILES[1], None), Ok(()));
+        assert_eq!(inited.sensor.timings().measure_max_ms, 200);
+        assert_eq!(ConfigUpdate::NONE, ConfigUpdate::default());
+    }
+
+    #[test]
+    fn not_mid_measurement()
+    {
+        let mut inited = sensor();
+        inited.trigger_measurement().unwrap();
+        let update = ConfigUpdate { fault_limit: Some(Some(3)), ..ConfigUpdate::default() };
+        let r = inited.update_config(&update, None);
+        assert_eq!(r, Err(ConfigError::Busy(DriverState::Measuring)));
+
+        inited.fetch().unwrap();
+        assert_eq!(inited.update_config(&update, None), Ok(()));
+    }
+
+    #[test]
+    fn after_timeout()
+    {
+        let mut inited = sensor();
+        inited.sensor.i2c.busy_reads = 20;
+        assert_eq!(inited.measure(&mut MockNoop), Err(crate::Error::DeviceTimeOut));
+        let update = ConfigUpdate {
+            timings: Some(Timings { measure_max_ms: 300, ..Timings::AHT20 }),
+            ..ConfigUpdate::default()
+        };
+        assert_eq!(inited.update_config(&update, None), Ok(()));
+    }
+}
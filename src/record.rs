@@ -0,0 +1,186 @@
+/*
+ * Filename: record.rs
+ * Description: The canonical on-wire record of the acquisition system, a
+ * measurement with a timestamp in scaled integers. `to_bytes()` gives a
+ * fixed 16 byte little endian layout that won't change within a version:
+ *
+ *   0..8   timestamp, i64
+ *   8..10  sensor id, u16
+ *   10..12 temperature in 0.01C, i16
+ *   12..14 humidity in 0.01%, u16
+ *   14     quality flags
+ *   15     layout version, `TIMED_MEASUREMENT_VERSION`
+ *
+ * With the `serde` feature it also goes through postcard and the like.
+ */
+
+use crate::core::measurement::{Measurement, Quality, SensorId};
+use crate::core::units::{Celsius, RelativeHumidity};
+
+pub const TIMED_MEASUREMENT_LEN: usize = 16;
+pub const TIMED_MEASUREMENT_VERSION: u8 = 1;
+
+///Stored for a NaN temperature.
+pub const TEMPERATURE_INVALID: i16 = i16::MIN;
+///Stored for a NaN humidity.
+pub const HUMIDITY_INVALID: u16 = u16::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TimedMeasurement {
+    ///Epoch milliseconds or clock ticks, whatever the application uses.
+    pub timestamp: i64,
+    pub id: u16,
+    ///Hundredths of a degree C.
+    pub temperature_centi: i16,
+    ///Hundredths of a % RH.
+    pub humidity_centi: u16,
+    ///`Quality` bits.
+    pub quality: u8,
+}
+
+//Rounds to the nearest hundredth, saturating at the type limits.
+fn centi(v: f32) -> i32 {
+    let scaled = v * 100.0;
+    if scaled < 0.0 {
+        (scaled - 0.5) as i32
+    } else {
+        (scaled + 0.5) as i32
+    }
+}
+
+impl TimedMeasurement {
+    pub fn new(timestamp: i64, m: &Measurement) -> Self {
+        let temperature_centi = if m.temperature.is_valid() {
+            centi(m.temperature.0).clamp(i16::MIN as i32 + 1, i16::MAX as i32) as i16
+        } else {
+            TEMPERATURE_INVALID
+        };
+        let humidity_centi = if m.humidity.is_valid() {
+            centi(m.humidity.0).clamp(0, u16::MAX as i32 - 1) as u16
+        } else {
+            HUMIDITY_INVALID
+        };
+        TimedMeasurement {
+            timestamp,
+            id: m.id.0,
+            temperature_centi,
+            humidity_centi,
+            quality: m.quality.0,
+        }
+    }
+
+    pub fn id(&self) -> SensorId {
+        SensorId(self.id)
+    }
+
+    ///NaN if the stored value is `TEMPERATURE_INVALID`.
+    pub fn temperature(&self) -> Celsius {
+        if self.temperature_centi == TEMPERATURE_INVALID {
+            return Celsius(f32::NAN);
+        }
+        Celsius(self.temperature_centi as f32 / 100.0)
+    }
+
+    ///NaN if the stored value is `HUMIDITY_INVALID`.
+    pub fn humidity(&self) -> RelativeHumidity {
+        if self.humidity_centi == HUMIDITY_INVALID {
+            return RelativeHumidity(f32::NAN);
+        }
+        RelativeHumidity(self.humidity_centi as f32 / 100.0)
+    }
+
+    pub fn quality(&self) -> Quality {
+        Quality(self.quality)
+    }
+
+    pub fn to_bytes(&self) -> [u8; TIMED_MEASUREMENT_LEN] {
+        let mut b = [0u8; TIMED_MEASUREMENT_LEN];
+        b[0..8].copy_from_slice(&self.timestamp.to_le_bytes());
+        b[8..10].copy_from_slice(&self.id.to_le_bytes());
+        b[10..12].copy_from_slice(&self.temperature_centi.to_le_bytes());
+        b[12..14].copy_from_slice(&self.humidity_centi.to_le_bytes());
+        b[14] = self.quality;
+        b[15] = TIMED_MEASUREMENT_VERSION;
+        b
+    }
+
+    ///None if the layout version isn't one this crate knows.
+    pub fn from_bytes(b: &[u8; TIMED_MEASUREMENT_LEN]) -> Option<Self> {
+        if b[15] != TIMED_MEASUREMENT_VERSION {
+            return None;
+        }
+        let mut ts = [0u8; 8];
+        ts.copy_from_slice(&b[0..8]);
+        Some(TimedMeasurement {
+            timestamp: i64::from_le_bytes(ts),
+            id: u16::from_le_bytes([b[8], b[9]]),
+            temperature_centi: i16::from_le_bytes([b[10], b[11]]),
+            humidity_centi: u16::from_le_bytes([b[12], b[13]]),
+            quality: b[14],
+        })
+    }
+}
+
+#[cfg(test)]
+mod record_tests {
+    use super::*;
+
+    fn m(t: f32, h: f32) -> Measurement {
+        Measurement {
+            id: SensorId(3),
+            temperature: Celsius(t),
+            humidity: RelativeHumidity(h),
+            quality: Quality::CRC_MISS,
+            pressure: None,
+            seq: None,
+        }
+    }
+
+    #[test]
+    fn scaled_values()
+    {
+        let r = TimedMeasurement::new(1_700_000_000_000, &m(22.876, 49.344));
+        assert_eq!(r.temperature_centi, 2288);
+        assert_eq!(r.humidity_centi, 4934);
+        assert_eq!(r.id(), SensorId(3));
+        assert_eq!(r.quality(), Quality::CRC_MISS);
+        assert_eq!(r.temperature(), Celsius(22.88));
+
+        let r = TimedMeasurement::new(-5, &m(-12.345, 0.0));
+        assert_eq!(r.temperature_centi, -1235);
+
+        let r = TimedMeasurement::new(0, &m(f32::NAN, f32::NAN));
+        assert_eq!(r.temperature_centi, TEMPERATURE_INVALID);
+        assert!(!r.temperature().is_valid());
+        assert!(!r.humidity().is_valid());
+    }
+
+    #[test]
+    fn binary_layout()
+    {
+        let r = TimedMeasurement::new(0x0102_0304_0506_0708, &m(22.88, 49.34));
+        let b = r.to_bytes();
+        assert_eq!(b, [
+            0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01,
+            0x03, 0x00, 0xF0, 0x08, 0x46, 0x13, 0x01, TIMED_MEASUREMENT_VERSION,
+        ]);
+        assert_eq!(TimedMeasurement::from_bytes(&b), Some(r));
+
+        let mut future = b;
+        future[15] = 2;
+        assert_eq!(TimedMeasurement::from_bytes(&future), None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn postcard_round_trip()
+    {
+        let r = TimedMeasurement::new(1_700_000_000_000, &m(22.88, 49.34));
+        let mut buf = [0u8; 32];
+        let used = postcard::to_slice(&r, &mut buf).unwrap();
+        assert!(used.len() <= TIMED_MEASUREMENT_LEN);
+        assert_eq!(postcard::from_bytes::<TimedMeasurement>(used).unwrap(), r);
+    }
+}
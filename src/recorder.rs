@@ -0,0 +1,163 @@
+/*
+ * Filename: recorder.rs
+ * Description: A transport wrapper capturing every transaction with a
+ * timestamp, in a fixed size ring so it can run on the target. The log is
+ * dumped in the text form `ReplayTransport` reads back, or serialized with
+ * serde (e.g. postcard) when the `serde` feature is on.
+ */
+
+use core::fmt;
+
+use crate::clock::Clock;
+use crate::transaction::{Direction, Transaction};
+use crate::Aht2xTransport;
+
+///Wraps a transport and keeps its last `N` transactions.
+pub struct RecordingTransport<T, C, const N: usize> {
+    inner: T,
+    clock: C,
+    log: [Option<Transaction>; N],
+    next: usize,
+    dropped: u32,
+}
+
+impl<T, C, const N: usize> RecordingTransport<T, C, N>
+where
+    T: Aht2xTransport,
+    C: Clock,
+{
+    pub fn new(inner: T, clock: C) -> Self {
+        RecordingTransport { inner, clock, log: [None; N], next: 0, dropped: 0 }
+    }
+
+    pub fn release(self) -> (T, C) {
+        (self.inner, self.clock)
+    }
+
+    ///The transactions from the oldest to the newest.
+    pub fn iter(&self) -> impl Iterator<Item = &Transaction> {
+        let (newer, older) = self.log.split_at(self.next);
+        older.iter().chain(newer.iter()).flatten()
+    }
+
+    ///Transactions overwritten since the last `clear()`.
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+
+    pub fn clear(&mut self) {
+        self.log = [None; N];
+        self.next = 0;
+        self.dropped = 0;
+    }
+
+    ///Writes the log in the text form, one transaction per line.
+    pub fn write_text(&self, w: &mut impl fmt::Write) -> fmt::Result {
+        for t in self.iter() {
+            writeln!(w, "{}", t)?;
+        }
+        Ok(())
+    }
+
+    fn record(&mut self, at_ms: u32, address: u8, direction: Direction, bytes: &[u8], nack: bool) {
+        if N == 0 {
+            return;
+        }
+        if self.log[self.next].is_some() {
+            self.dropped = self.dropped.saturating_add(1);
+        }
+        self.log[self.next] = Some(Transaction::new(at_ms, address, direction, bytes, nack));
+        self.next = (self.next + 1) % N;
+    }
+}
+
+impl<T, C, const N: usize> Aht2xTransport for RecordingTransport<T, C, N>
+where
+    T: Aht2xTransport,
+    C: Clock,
+{
+    type Error = T::Error;
+
+    fn write_frame(&mut self, address: u8, bytes: &[u8]) -> Result<(), T::Error> {
+        let at_ms = self.clock.now_ms();
+        let r = self.inner.write_frame(address, bytes);
+        self.record(at_ms, address, Direction::Write, bytes, r.is_err());
+        r
+    }
+
+    fn read_frame(&mut self, address: u8, buf: &mut [u8]) -> Result<(), T::Error> {
+        let at_ms = self.clock.now_ms();
+        let r = self.inner.read_frame(address, buf);
+        match r {
+            Ok(()) => self.record(at_ms, address, Direction::Read, buf, false),
+            Err(_) => self.record(at_ms, address, Direction::Read, &[], true),
+        }
+        r
+    }
+}
+
+#[cfg(test)]
+mod recorder_tests {
+    use super::*;
+    use crate::clock::clock_tests::FakeClock;
+    use crate::core::units::{Celsius, RelativeHumidity};
+    use crate::{SimulatedSensor, SENSOR_ADDR};
+
+    fn sim() -> SimulatedSensor {
+        SimulatedSensor::new(Celsius(25.0), RelativeHumidity(40.0))
+    }
+
+    #[test]
+    fn records_with_timestamps()
+    {
+        let clock = FakeClock { now_ms: 100, step_ms: 10 };
+        let mut rec: RecordingTransport<_, _, 8> = RecordingTransport::new(sim(), clock);
+        rec.write_frame(SENSOR_ADDR, &[0x71]).unwrap();
+        rec.read_frame(SENSOR_ADDR, &mut [0]).unwrap();
+        assert!(rec.write_frame(0x39, &[0x71]).is_err());
+
+        let mut text = String::new();
+        rec.write_text(&mut text).unwrap();
+        assert_eq!(text, "100,38,W,0,71\n110,38,R,0,10\n120,39,W,1,71\n");
+        assert_eq!(rec.dropped(), 0);
+    }
+
+    #[test]
+    fn keeps_the_newest()
+    {
+        let clock = FakeClock { now_ms: 0, step_ms: 1 };
+        let mut rec: RecordingTransport<_, _, 2> = RecordingTransport::new(sim(), clock);
+        for _ in 0..5 {
+            rec.write_frame(SENSOR_ADDR, &[0x71]).unwrap();
+        }
+        let times: Vec<u32> = rec.iter().map(|t| t.at_ms).collect();
+        assert_eq!(times, [3, 4]);
+        assert_eq!(rec.dropped(), 3);
+
+        rec.clear();
+        assert_eq!(rec.iter().count(), 0);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn record_then_replay()
+    {
+        use crate::{InitializedSensor, ReplayTransport, Sensor};
+
+        let mut s = sim();
+        s.busy_reads = 1;
+        let clock = FakeClock { now_ms: 0, step_ms: 1 };
+        let rec: RecordingTransport<_, _, 16> = RecordingTransport::new(s, clock);
+        let mut inited = InitializedSensor { sensor: Sensor::new(rec, SENSOR_ADDR) };
+        let mut delay = embedded_hal_mock::delay::MockNoop;
+        let recorded = inited.measure(&mut delay).unwrap();
+
+        let mut text = String::new();
+        inited.sensor.i2c.write_text(&mut text).unwrap();
+        let replay = ReplayTransport::from_text(&text).unwrap();
+        let mut inited = InitializedSensor { sensor: Sensor::new(replay, SENSOR_ADDR) };
+        let replayed = inited.measure(&mut delay).unwrap();
+        assert_eq!(replayed.temperature, recorded.temperature);
+        assert!(inited.sensor.i2c.is_done());
+    }
+}
@@ -0,0 +1,285 @@
+/*
+ * Filename: recovery.rs
+ * Description: The recovery AOSONG recommends after the sensor has been
+ * exposed to condensation(see `CondensationCounter`) or other extreme
+ * conditions: a dry-out in an oven, a rehydration in humid air and then a
+ * recalibration. The driver can't heat anything, it checks with its own
+ * readings that each stage's conditions hold, counts the time spent in
+ * them and reports the progress. Readings are flagged
+ * `Quality::RECOVERING` until the recovery is done.
+ */
+
+use crate::core::measurement::Measurement;
+use crate::core::units::{Celsius, RelativeHumidity};
+use crate::{Aht2xTransport, Delay, Error, Indicator, InitializedSensor, Sensor};
+
+///Conditions to hold for a stage and for how long.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecoveryStage {
+    pub min_temperature: Celsius,
+    pub max_temperature: Celsius,
+    pub min_humidity: RelativeHumidity,
+    pub max_humidity: RelativeHumidity,
+    pub duration_ms: u32,
+}
+
+impl RecoveryStage {
+    ///True if the reading is within the stage's conditions, NaN never is.
+    pub fn holds(&self, m: &Measurement) -> bool {
+        m.temperature >= self.min_temperature
+            && m.temperature <= self.max_temperature
+            && m.humidity >= self.min_humidity
+            && m.humidity <= self.max_humidity
+    }
+}
+
+const HOUR_MS: u32 = 60 * 60 * 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecoveryProcedure {
+    pub dry_out: RecoveryStage,
+    pub rehydrate: RecoveryStage,
+}
+
+impl RecoveryProcedure {
+    ///The data sheet's recovery: 10 hours at 80-85C below 5% RH, then 5
+    ///hours at 20-30C above 70% RH.
+    pub const DATASHEET: RecoveryProcedure = RecoveryProcedure {
+        dry_out: RecoveryStage {
+            min_temperature: Celsius(80.0),
+            max_temperature: Celsius(85.0),
+            min_humidity: RelativeHumidity(0.0),
+            max_humidity: RelativeHumidity(5.0),
+            duration_ms: 10 * HOUR_MS,
+        },
+        rehydrate: RecoveryStage {
+            min_temperature: Celsius(20.0),
+            max_temperature: Celsius(30.0),
+            min_humidity: RelativeHumidity(70.0),
+            max_humidity: RelativeHumidity(100.0),
+            duration_ms: 5 * HOUR_MS,
+        },
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecoveryPhase {
+    DryOut,
+    Rehydrate,
+    ///Both stages are done, the next `recovery_step()` recalibrates.
+    Recalibrate,
+    Done,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecoveryProgress {
+    pub phase: RecoveryPhase,
+    ///Time spent within the conditions of the current stage.
+    pub elapsed_ms: u32,
+    ///What the current stage needs, 0 outside the stages.
+    pub duration_ms: u32,
+    ///The last reading was within the current stage's conditions.
+    pub in_conditions: bool,
+    ///Of the whole procedure, 0 to 100.
+    pub percent: u8,
+}
+
+impl RecoveryProgress {
+    pub const DONE: RecoveryProgress = RecoveryProgress {
+        phase: RecoveryPhase::Done,
+        elapsed_ms: 0,
+        duration_ms: 0,
+        in_conditions: false,
+        percent: 100,
+    };
+}
+
+//Where the recovery is, kept by the sensor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Recovery {
+    procedure: RecoveryProcedure,
+    phase: RecoveryPhase,
+    elapsed_ms: u32,
+    //Time of the last reading, if it was within the conditions.
+    last_ms: Option<u32>,
+}
+
+impl Recovery {
+    fn new(procedure: RecoveryProcedure) -> Self {
+        Recovery { procedure, phase: RecoveryPhase::DryOut, elapsed_ms: 0, last_ms: None }
+    }
+
+    fn stage(&self) -> Option<RecoveryStage> {
+        match self.phase {
+            RecoveryPhase::DryOut => Some(self.procedure.dry_out),
+            RecoveryPhase::Rehydrate => Some(self.procedure.rehydrate),
+            _ => None,
+        }
+    }
+
+    //Counts the time since the last reading if both were within the
+    //conditions, and moves on once the stage is done.
+    fn add(&mut self, m: &Measurement, now_ms: u32) {
+        let stage = match self.stage() {
+            Some(s) => s,
+            None => return,
+        };
+        if !stage.holds(m) {
+            self.last_ms = None;
+            return;
+        }
+        if let Some(last) = self.last_ms {
+            self.elapsed_ms = self.elapsed_ms.saturating_add(now_ms.wrapping_sub(last));
+        }
+        self.last_ms = Some(now_ms);
+
+        if self.elapsed_ms >= stage.duration_ms {
+            self.phase = match self.phase {
+                RecoveryPhase::DryOut => RecoveryPhase::Rehydrate,
+                _ => RecoveryPhase::Recalibrate,
+            };
+            self.elapsed_ms = 0;
+            self.last_ms = None;
+        }
+    }
+
+    fn progress(&self) -> RecoveryProgress {
+        let dry = self.procedure.dry_out.duration_ms as u64;
+        let total = dry + self.procedure.rehydrate.duration_ms as u64;
+        let done = match self.phase {
+            RecoveryPhase::DryOut => self.elapsed_ms as u64,
+            RecoveryPhase::Rehydrate => dry + self.elapsed_ms as u64,
+            RecoveryPhase::Recalibrate | RecoveryPhase::Done => total,
+        };
+        //The recalibration is the last percent.
+        let percent = (done * 99).checked_div(total).unwrap_or(99).min(99) as u8;
+        RecoveryProgress {
+            phase: self.phase,
+            elapsed_ms: self.elapsed_ms,
+            duration_ms: self.stage().map_or(0, |s| s.duration_ms),
+            in_conditions: self.last_ms.is_some(),
+            percent,
+        }
+    }
+}
+
+impl<E, I2C, L> Sensor<I2C, L>
+where I2C: Aht2xTransport<Error = E>,
+      L: Indicator,
+{
+    ///Starts the recovery over, drive it with `recovery_step()`. Until it's
+    ///done every `Measurement` has `Quality::RECOVERING` set.
+    pub fn begin_recovery(&mut self, procedure: RecoveryProcedure) {
+        self.recovery = Some(Recovery::new(procedure));
+    }
+
+    ///Stops the recovery, the readings are no longer flagged.
+    pub fn abort_recovery(&mut self) {
+        self.recovery = None;
+    }
+
+    pub fn is_recovering(&self) -> bool {
+        self.recovery.is_some()
+    }
+
+    ///None unless a recovery is running.
+    pub fn recovery_progress(&self) -> Option<RecoveryProgress> {
+        self.recovery.as_ref().map(Recovery::progress)
+    }
+}
+
+impl<E, I2C, L> InitializedSensor<I2C, L>
+where I2C: Aht2xTransport<Error = E>,
+      L: Indicator,
+{
+    ///Takes a reading and counts the time since the last one towards the
+    ///current stage if both were within its conditions, or recalibrates
+    ///once the stages are done. Call it every few minutes, `now_ms` from a
+    ///monotonic clock. Returns `RecoveryPhase::Done` without a recovery
+    ///running.
+    pub fn recovery_step(
+        &mut self,
+        delay: &mut impl Delay,
+        now_ms: u32,
+        ) -> Result<RecoveryProgress, Error<E>>
+    {
+        let phase = match self.sensor.recovery {
+            Some(r) => r.phase,
+            None => RecoveryPhase::Done,
+        };
+
+        match phase {
+            RecoveryPhase::DryOut | RecoveryPhase::Rehydrate => {
+                let m = self.measure(delay);
+                let recovery = self.sensor.recovery.as_mut().ok_or(Error::Internal)?;
+                match m {
+                    Ok(m) => recovery.add(&m, now_ms),
+                    Err(e) => {
+                        recovery.last_ms = None;
+                        return Err(e);
+                    },
+                }
+                Ok(recovery.progress())
+            },
+            RecoveryPhase::Recalibrate | RecoveryPhase::Done => {
+                if phase == RecoveryPhase::Recalibrate {
+                    self.soft_reset(delay)?;
+                    self.sensor.calibrate(delay)?;
+                }
+                self.sensor.recovery = None;
+                Ok(RecoveryProgress::DONE)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod recovery_tests {
+    use super::*;
+    use crate::{Quality, SimulatedSensor, SENSOR_ADDR};
+
+    #[test]
+    fn guided_recovery()
+    {
+        let sim = SimulatedSensor::new(Celsius(22.0), RelativeHumidity(45.0));
+        let sensor = Sensor::new(sim, SENSOR_ADDR);
+        let mut delay = embedded_hal_mock::delay::MockNoop;
+        let mut inited = sensor.init(&mut delay).unwrap();
+        assert_eq!(inited.sensor.recovery_progress(), None);
+
+        inited.sensor.begin_recovery(RecoveryProcedure::DATASHEET);
+        let m = inited.measure(&mut delay).unwrap();
+        assert_eq!(m.quality, Quality::RECOVERING);
+
+        //Not in the oven yet.
+        let p = inited.recovery_step(&mut delay, 0).unwrap();
+        assert_eq!((p.phase, p.elapsed_ms, p.in_conditions), (RecoveryPhase::DryOut, 0, false));
+
+        inited.sensor.i2c.temperature = Celsius(82.0);
+        inited.sensor.i2c.humidity = RelativeHumidity(2.0);
+        inited.recovery_step(&mut delay, HOUR_MS).unwrap();
+        let p = inited.recovery_step(&mut delay, 6 * HOUR_MS).unwrap();
+        assert_eq!(p.elapsed_ms, 5 * HOUR_MS);
+        assert!(p.in_conditions);
+        assert_eq!(p.percent, 33);
+
+        //Taken out for a while, that time doesn't count.
+        inited.sensor.i2c.temperature = Celsius(40.0);
+        inited.recovery_step(&mut delay, 7 * HOUR_MS).unwrap();
+        inited.sensor.i2c.temperature = Celsius(82.0);
+        inited.recovery_step(&mut delay, 8 * HOUR_MS).unwrap();
+        let p = inited.recovery_step(&mut delay, 13 * HOUR_MS).unwrap();
+        assert_eq!(p.phase, RecoveryPhase::Rehydrate);
+
+        inited.sensor.i2c.temperature = Celsius(25.0);
+        inited.sensor.i2c.humidity = RelativeHumidity(75.0);
+        inited.recovery_step(&mut delay, 14 * HOUR_MS).unwrap();
+        let p = inited.recovery_step(&mut delay, 19 * HOUR_MS).unwrap();
+        assert_eq!((p.phase, p.percent), (RecoveryPhase::Recalibrate, 99));
+
+        let p = inited.recovery_step(&mut delay, 19 * HOUR_MS).unwrap();
+        assert_eq!((p.phase, p.percent), (RecoveryPhase::Done, 100));
+        assert!(!inited.sensor.is_recovering());
+        assert!(inited.measure(&mut delay).unwrap().quality.is_good());
+    }
+}
@@ -0,0 +1,223 @@
+/*
+ * Filename: redundant.rs
+ * Description: Wraps two initialized sensors measuring the same air and
+ * cross checks them, for applications that can't trust a single element.
+ */
+
+use crate::{Aht2xTransport, Delay, Error, InitializedSensor};
+use crate::core::data::SensorData;
+use crate::core::units::{Celsius, RelativeHumidity};
+
+///How the two readings are combined into one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FusePolicy {
+    Min,
+    Max,
+    Mean,
+    ///The primary's reading if it's good, otherwise the secondary's.
+    FirstGood,
+}
+
+///The max difference between the two sensors before they are flagged as
+///diverged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tolerance {
+    pub temperature: Celsius,
+    pub humidity: RelativeHumidity,
+}
+
+impl Default for Tolerance {
+    ///Twice the typical accuracy of the AHT20, the most two good sensors
+    ///should disagree by.
+    fn default() -> Self {
+        Tolerance {
+            temperature: Celsius(0.6),
+            humidity: RelativeHumidity(4.0),
+        }
+    }
+}
+
+///The fused reading of a `RedundantPair`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PairReading {
+    pub temperature: Celsius,
+    pub humidity: RelativeHumidity,
+    ///Both sensors gave a reading but they differ by more than the tolerance.
+    pub diverged: bool,
+    ///Only one of the sensors gave a good reading.
+    pub degraded: bool,
+}
+
+///Two sensors read back to back and cross checked.
+pub struct RedundantPair<I1, I2>
+where I1: Aht2xTransport,
+      I2: Aht2xTransport,
+{
+    primary: InitializedSensor<I1>,
+    secondary: InitializedSensor<I2>,
+    policy: FusePolicy,
+    tolerance: Tolerance,
+}
+
+impl<E, I1, I2> RedundantPair<I1, I2>
+where I1: Aht2xTransport<Error = E>,
+      I2: Aht2xTransport<Error = E>,
+{
+    pub fn new(
+        primary: InitializedSensor<I1>,
+        secondary: InitializedSensor<I2>,
+        policy: FusePolicy,
+        tolerance: Tolerance,
+        ) -> Self
+    {
+        RedundantPair { primary, secondary, policy, tolerance }
+    }
+
+    ///Gives back the two sensors.
+    pub fn into_inner(self) -> (InitializedSensor<I1>, InitializedSensor<I2>) {
+        (self.primary, self.secondary)
+    }
+
+    ///Reads both sensors and fuses the readings. A reading with a bad CRC
+    ///counts as failed, if only one sensor fails the other one is used and
+    ///the result is flagged `degraded`. If both fail the primary's error is
+    ///returned.
+    pub fn read(&mut self, delay: &mut impl Delay) -> Result<PairReading, Error<E>> {
+        let a = checked(self.primary.read_sensor(delay));
+        let b = checked(self.secondary.read_sensor(delay));
+
+        let (a, b) = match (a, b) {
+            (Ok(a), Ok(b)) => (a, b),
+            (Ok(good), Err(_)) | (Err(_), Ok(good)) => {
+                return Ok(PairReading {
+                    temperature: good.temperature(),
+                    humidity: good.humidity(),
+                    diverged: false,
+                    degraded: true,
+                });
+            },
+            (Err(e), Err(_)) => return Err(e),
+        };
+
+        let (ta, tb) = (a.temperature(), b.temperature());
+        let (ha, hb) = (a.humidity(), b.humidity());
+        let diverged = ta.abs_delta(tb) > self.tolerance.temperature
+            || ha.abs_delta(hb) > self.tolerance.humidity;
+
+        let (temperature, humidity) = match self.policy {
+            FusePolicy::Min => (Celsius(ta.0.min(tb.0)), RelativeHumidity(ha.0.min(hb.0))),
+            FusePolicy::Max => (Celsius(ta.0.max(tb.0)), RelativeHumidity(ha.0.max(hb.0))),
+            FusePolicy::Mean => (
+                Celsius((ta.0 + tb.0) / 2.0),
+                RelativeHumidity((ha.0 + hb.0) / 2.0),
+                ),
+            FusePolicy::FirstGood => (ta, ha),
+        };
+
+        Ok(PairReading { temperature, humidity, diverged, degraded: false })
+    }
+}
+
+fn checked<E>(r: Result<SensorData, Error<E>>) -> Result<SensorData, Error<E>> {
+    let mut sd = r?;
+    if !sd.is_crc_good() {
+        return Err(Error::InvalidChecksum);
+    }
+    Ok(sd)
+}
+
+#[cfg(test)]
+mod redundant_tests {
+    use embedded_hal_mock::i2c::{
+        Mock as I2cMock,
+        Transaction as I2cTransaction,
+    };
+
+    use super::*;
+    use crate::core::commands;
+    use crate::{Sensor, SENSOR_ADDR, TRIG_MEASURE_PARAM0, TRIG_MEASURE_PARAM1};
+
+    fn measurement(frame: [u8; 7]) -> [I2cTransaction; 2] {
+        [
+            I2cTransaction::write(SENSOR_ADDR, vec![
+                                  commands::TRIG_MESSURE,
+                                  TRIG_MEASURE_PARAM0,
+                                  TRIG_MEASURE_PARAM1,
+            ]),
+            I2cTransaction::read(SENSOR_ADDR, frame.to_vec()),
+        ]
+    }
+
+    const FRAME_A: [u8; 7] = [0x18, 0x7E, 0x51, 0x65, 0xD4, 0xA0, 0xDA];
+    //50.07 %RH, 30.0 C
+    const FRAME_B: [u8; 7] = [0x18, 0x80, 0x2F, 0x26, 0x66, 0x66, 0xB6];
+
+    fn pair_read(
+        a: [u8; 7],
+        b: [u8; 7],
+        policy: FusePolicy,
+        ) -> Result<PairReading, Error<embedded_hal_mock::MockError>>
+    {
+        let s1 = Sensor::new(I2cMock::new(&measurement(a)), SENSOR_ADDR);
+        let s2 = Sensor::new(I2cMock::new(&measurement(b)), SENSOR_ADDR);
+
+        let mut pair = RedundantPair::new(
+            InitializedSensor { sensor: s1 },
+            InitializedSensor { sensor: s2 },
+            policy,
+            Tolerance::default(),
+            );
+
+        let mut mock_delay = embedded_hal_mock::delay::MockNoop;
+        let r = pair.read(&mut mock_delay);
+
+        let (mut p, mut s) = pair.into_inner();
+        p.sensor.i2c.done();
+        s.sensor.i2c.done();
+        r
+    }
+
+    #[test]
+    fn agreeing_pair()
+    {
+        let r = pair_read(FRAME_A, FRAME_A, FusePolicy::Mean).unwrap();
+        assert!(!r.diverged);
+        assert!(!r.degraded);
+        assert!(r.temperature > Celsius(22.87) && r.temperature < Celsius(22.89));
+    }
+
+    #[test]
+    fn diverged_pair()
+    {
+        let min = pair_read(FRAME_A, FRAME_B, FusePolicy::Min).unwrap();
+        let max = pair_read(FRAME_A, FRAME_B, FusePolicy::Max).unwrap();
+        assert!(min.diverged);
+        assert!(max.diverged);
+        assert!(min.temperature < max.temperature);
+        assert!(min.humidity < max.humidity);
+    }
+
+    #[test]
+    fn one_bad_crc()
+    {
+        let mut bad = FRAME_A;
+        bad[6] = 0x00;
+
+        let r = pair_read(bad, FRAME_B, FusePolicy::FirstGood).unwrap();
+        assert!(r.degraded);
+        //Fell back to the secondary.
+        let mut sd = SensorData::new();
+        sd.bytes = FRAME_B;
+        assert_eq!(r.temperature, sd.temperature());
+    }
+
+    #[test]
+    fn both_bad()
+    {
+        let mut bad = FRAME_A;
+        bad[6] = 0x00;
+
+        let r = pair_read(bad, bad, FusePolicy::Mean);
+        assert_eq!(r, Err(Error::InvalidChecksum));
+    }
+}
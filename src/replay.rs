@@ -0,0 +1,139 @@
+/*
+ * Filename: replay.rs
+ * Description: Feeds a recorded transaction log back through the driver,
+ * to reproduce field failures deterministically on a developer machine.
+ */
+
+extern crate std;
+
+use std::vec::Vec;
+
+use crate::transaction::{Direction, Transaction};
+use crate::Aht2xTransport;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayError {
+    ///The recorded transfer failed, replayed as a failure too.
+    Nack,
+    ///The driver did something else than what was recorded, `index` is
+    ///the log entry it should have matched.
+    Mismatch { index: usize },
+    ///The driver went on after the end of the log.
+    Exhausted,
+}
+
+///Replays a log, checking the driver's writes against the recorded ones
+///and answering its reads with the recorded bytes.
+pub struct ReplayTransport {
+    log: Vec<Transaction>,
+    next: usize,
+}
+
+impl ReplayTransport {
+    pub fn new(log: Vec<Transaction>) -> Self {
+        ReplayTransport { log, next: 0 }
+    }
+
+    ///Builds the log from the text form, one transaction per line. Empty
+    ///lines and lines starting with '#' are skipped. Gives the number of
+    ///the first malformed line on error.
+    pub fn from_text(text: &str) -> Result<Self, usize> {
+        let mut log = Vec::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            log.push(Transaction::parse(line).ok_or(n + 1)?);
+        }
+        Ok(ReplayTransport::new(log))
+    }
+
+    ///True once every recorded transaction was replayed.
+    pub fn is_done(&self) -> bool {
+        self.next >= self.log.len()
+    }
+
+    ///The next recorded transaction, i.e. where the replay is at.
+    pub fn peek(&self) -> Option<&Transaction> {
+        self.log.get(self.next)
+    }
+
+    //Moves on to the next entry if it matches, a mismatch leaves the
+    //replay where it is.
+    fn take(
+        &mut self,
+        address: u8,
+        direction: Direction,
+        matches: impl FnOnce(&Transaction) -> bool,
+        ) -> Result<Transaction, ReplayError>
+    {
+        let index = self.next;
+        let t = *self.log.get(index).ok_or(ReplayError::Exhausted)?;
+        if t.address != address || t.direction != direction || !matches(&t) {
+            return Err(ReplayError::Mismatch { index });
+        }
+        self.next += 1;
+        if t.nack {
+            return Err(ReplayError::Nack);
+        }
+        Ok(t)
+    }
+}
+
+impl Aht2xTransport for ReplayTransport {
+    type Error = ReplayError;
+
+    fn write_frame(&mut self, address: u8, bytes: &[u8]) -> Result<(), ReplayError> {
+        self.take(address, Direction::Write, |t| t.bytes() == bytes)?;
+        Ok(())
+    }
+
+    fn read_frame(&mut self, address: u8, buf: &mut [u8]) -> Result<(), ReplayError> {
+        let t = self.take(address, Direction::Read, |t| t.nack || t.bytes().len() == buf.len())?;
+        buf.copy_from_slice(t.bytes());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod replay_tests {
+    use super::*;
+    use crate::{Error, InitializedSensor, Sensor, SENSOR_ADDR};
+
+    const FIELD_LOG: &str = "
+        # A sensor stuck busy after a trigger.
+        0,38,W,0,AC3300
+        80,38,R,0,98000000000000
+        100,38,R,0,98000000000000
+        120,38,R,0,98000000000000
+        140,38,R,0,98000000000000
+        ";
+
+    #[test]
+    fn replays_field_failure()
+    {
+        let replay = ReplayTransport::from_text(FIELD_LOG).unwrap();
+        let mut inited = InitializedSensor { sensor: Sensor::new(replay, SENSOR_ADDR) };
+        let mut delay = embedded_hal_mock::delay::MockNoop;
+
+        assert_eq!(inited.read_sensor(&mut delay).err(), Some(Error::DeviceTimeOut));
+        assert!(inited.sensor.i2c.is_done());
+    }
+
+    #[test]
+    fn mismatch_and_nack()
+    {
+        let mut replay = ReplayTransport::from_text("0,38,W,0,71\n1,38,W,1,BA").unwrap();
+        assert_eq!(replay.write_frame(SENSOR_ADDR, &[0xBE]), Err(ReplayError::Mismatch { index: 0 }));
+        assert!(replay.write_frame(SENSOR_ADDR, &[0x71]).is_ok());
+        assert_eq!(replay.write_frame(SENSOR_ADDR, &[0xBA]), Err(ReplayError::Nack));
+        assert_eq!(replay.read_frame(SENSOR_ADDR, &mut [0]), Err(ReplayError::Exhausted));
+    }
+
+    #[test]
+    fn malformed_line()
+    {
+        assert_eq!(ReplayTransport::from_text("0,38,W,0,71\n\nnonsense").err(), Some(3));
+    }
+}
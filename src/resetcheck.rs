@@ -0,0 +1,135 @@
+/*
+ * Filename: resetcheck.rs
+ * Description: How much `soft_reset()` and `force_reset()` check before
+ * calling the sensor back. A status byte is enough for most products, a
+ * calibration check or a test conversion costs boot time but catches a
+ * sensor that answers without measuring.
+ */
+
+use crate::{
+    Aht2xTransport, Delay, Error, Indicator, InitializedSensor, Sensor, SensorData, SensorStatus,
+};
+
+///What a reset checks before it succeeds, see
+///`Sensor::set_reset_verification()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ResetVerification {
+    ///The status byte reads back and isn't busy.
+    #[default]
+    Quick,
+    ///Quick, and the calibration bit is set.
+    Standard,
+    ///Standard, and a test conversion has a good CRC and readings inside
+    ///the variant's rated range. Adds a conversion time to the reset.
+    Deep,
+}
+
+impl<E, I2C, L> Sensor<I2C, L>
+where I2C: Aht2xTransport<Error = E>,
+      L: Indicator,
+{
+    ///Sets how much a reset verifies, `ResetVerification::Quick` by
+    ///default. A failed check is `Error::VerificationFailed` and leaves the
+    ///sensor uninitialized.
+    pub fn set_reset_verification(&mut self, verification: ResetVerification) {
+        self.reset_verification = verification;
+    }
+
+    pub fn reset_verification(&self) -> ResetVerification {
+        self.reset_verification
+    }
+}
+
+impl<E, I2C, L> InitializedSensor<I2C, L>
+where I2C: Aht2xTransport<Error = E>,
+      L: Indicator,
+{
+    //The checks past the not busy status the reset already read back.
+    pub(crate) fn verify_reset(
+        &mut self,
+        delay: &mut impl Delay,
+        status: SensorStatus,
+        ) -> Result<(), Error<E>>
+    {
+        if self.sensor.reset_verification == ResetVerification::Quick {
+            return Ok(());
+        }
+        if !status.is_calibration_enabled() {
+            return Err(Error::VerificationFailed);
+        }
+        if self.sensor.reset_verification == ResetVerification::Standard {
+            return Ok(());
+        }
+
+        let mut sd = SensorData::new();
+        let mut retry = self.measure_deadline();
+        self.read_frame(delay, &mut retry, &mut sd)?;
+        let specs = self.sensor.variant.specs();
+        let plausible = sd.is_crc_good()
+            && specs.is_temperature_in_range(sd.calculate_temperature())
+            && specs.is_humidity_in_range(sd.calculate_humidity());
+        match plausible {
+            true => Ok(()),
+            false => Err(Error::VerificationFailed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod resetcheck_tests {
+    use embedded_hal_mock::delay::MockNoop;
+
+    use super::*;
+    use crate::{Celsius, DriverState, RelativeHumidity, SimError, SimulatedSensor, SENSOR_ADDR};
+
+    fn sensor(verification: ResetVerification) -> Sensor<SimulatedSensor> {
+        let sim = SimulatedSensor::new(Celsius(22.0), RelativeHumidity(45.0));
+        let mut sensor = Sensor::new(sim, SENSOR_ADDR);
+        sensor.set_reset_verification(verification);
+        sensor
+    }
+
+    fn reset(verification: ResetVerification, corrupt_crc: bool) -> Result<SensorStatus, Error<SimError>> {
+        let mut inited = sensor(verification).init(&mut MockNoop).unwrap();
+        inited.sensor.i2c.corrupt_crc = corrupt_crc;
+        inited.soft_reset(&mut MockNoop)
+    }
+
+    #[test]
+    fn depths()
+    {
+        assert_eq!(sensor(ResetVerification::Quick).reset_verification(), ResetVerification::Quick);
+        assert!(reset(ResetVerification::Quick, false).is_ok());
+        assert!(reset(ResetVerification::Standard, false).is_ok());
+        assert!(reset(ResetVerification::Deep, false).is_ok());
+
+        //Only the test conversion sees the bad frame.
+        assert!(reset(ResetVerification::Standard, true).is_ok());
+        assert_eq!(reset(ResetVerification::Deep, true), Err(Error::VerificationFailed));
+    }
+
+    #[test]
+    fn waits_out_the_reset()
+    {
+        let mut inited = sensor(ResetVerification::Deep).init(&mut MockNoop).unwrap();
+        let mut delay = crate::sensor_test::DelayLog(vec![]);
+        assert!(inited.soft_reset(&mut delay).is_ok());
+        //The reset time before the status read, then the test conversion.
+        assert_eq!(delay.0, [20, 80]);
+    }
+
+    #[test]
+    fn uncalibrated()
+    {
+        //Never initialized, so the calibration bit is clear.
+        let mut inited = InitializedSensor { sensor: sensor(ResetVerification::Quick) };
+        assert!(inited.force_reset(&mut MockNoop).is_ok());
+
+        inited.sensor.set_reset_verification(ResetVerification::Standard);
+        assert_eq!(inited.force_reset(&mut MockNoop), Err(Error::VerificationFailed));
+        assert_eq!(inited.sensor.state(), DriverState::Uninitialized);
+        assert_eq!(inited.soft_reset(&mut MockNoop), Err(Error::VerificationFailed));
+    }
+}
@@ -0,0 +1,223 @@
+/*
+ * Filename: scheduler.rs
+ * Description: Fills the AHT20's conversion time with other work. A
+ * measurement leaves the bus idle for 80ms, on a dense acquisition node
+ * that's time other sensors' transactions or housekeeping can use. Work
+ * is registered as callbacks with a period and a time budget, and only
+ * run when it fits before the sensor's frame is due.
+ */
+
+use alloc::vec::Vec;
+
+use crate::clock::{elapsed_ms, Clock};
+use crate::{
+    Aht2xTransport, Delay, Error, Indicator, InitializedSensor, Measurement, MeasurementPoll, Poll,
+};
+
+///Handle of registered work, see `Scheduler::remove()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkId(usize);
+
+struct Work<'w> {
+    period_ms: u32,
+    budget_ms: u32,
+    ///None until it first ran.
+    next_ms: Option<u32>,
+    f: &'w mut dyn FnMut(u32),
+}
+
+///Runs registered work while a sensor converts.
+pub struct Scheduler<'w> {
+    work: Vec<Option<Work<'w>>>,
+    runs: u32,
+    idle_ms: u32,
+}
+
+impl<'w> Scheduler<'w> {
+    pub fn new() -> Self {
+        Scheduler { work: Vec::new(), runs: 0, idle_ms: 0 }
+    }
+
+    ///Registers `f` to run every `period_ms`, it's first due straight away.
+    ///`budget_ms` is how long it can take, it's only started when that
+    ///fits before the sensor's frame is due. `f` gets the clock's time.
+    pub fn add(&mut self, period_ms: u32, budget_ms: u32, f: &'w mut dyn FnMut(u32)) -> WorkId {
+        let work = Work { period_ms, budget_ms, next_ms: None, f };
+        let free = self.work.iter().position(Option::is_none);
+        match free {
+            Some(i) => {
+                self.work[i] = Some(work);
+                WorkId(i)
+            },
+            None => {
+                self.work.push(Some(work));
+                WorkId(self.work.len() - 1)
+            },
+        }
+    }
+
+    pub fn remove(&mut self, id: WorkId) {
+        if let Some(slot) = self.work.get_mut(id.0) {
+            *slot = None;
+        }
+    }
+
+    ///Work runs so far.
+    pub fn runs(&self) -> u32 {
+        self.runs
+    }
+
+    ///Time spent waiting with nothing to run.
+    pub fn idle_ms(&self) -> u32 {
+        self.idle_ms
+    }
+
+    ///Runs the due work that takes no more than `free_ms`, returns whether
+    ///anything ran.
+    pub fn run_due(&mut self, now_ms: u32, free_ms: u32) -> bool {
+        //The most overdue first.
+        let overdue = |w: &Work| w.next_ms.map_or(Some(u32::MAX), |next| {
+            Some(elapsed_ms(next, now_ms)).filter(|&late| late < u32::MAX / 2)
+        });
+        let due = self.work.iter_mut().flatten()
+            .filter(|w| w.budget_ms <= free_ms && overdue(w).is_some())
+            .max_by_key(|w| overdue(w));
+        match due {
+            Some(w) => {
+                (w.f)(now_ms);
+                w.next_ms = Some(now_ms.wrapping_add(w.period_ms));
+                self.runs = self.runs.saturating_add(1);
+                true
+            },
+            None => false,
+        }
+    }
+
+    //Milliseconds until the next work is due, if any is registered.
+    fn next_due_ms(&self, now_ms: u32) -> Option<u32> {
+        self.work.iter().flatten()
+            .map(|w| match w.next_ms.map(|next| elapsed_ms(now_ms, next)) {
+                Some(ahead) if ahead < u32::MAX / 2 => ahead,
+                _ => 0,
+            })
+            .min()
+    }
+
+    ///Measures with `sensor`, running due work during the conversion
+    ///instead of waiting. The sensor is driven by a `MeasurementPoll`, so
+    ///busy polling and the timeout are the same as there.
+    pub fn measure<E, I2C, L>(
+        &mut self,
+        sensor: &mut InitializedSensor<I2C, L>,
+        clock: &mut impl Clock,
+        delay: &mut impl Delay,
+        ) -> Result<Measurement, Error<E>>
+    where I2C: Aht2xTransport<Error = E>,
+          L: Indicator,
+    {
+        let mut poll = MeasurementPoll::new();
+        poll.start(sensor, clock.now_ms())?;
+
+        loop {
+            let now = clock.now_ms();
+            let free = poll.due_in_ms(now).unwrap_or(0);
+            if free == 0 {
+                match poll.poll(sensor, now)? {
+                    Poll::Ready(m) => return Ok(m),
+                    Poll::Pending => continue,
+                }
+            }
+
+            if self.run_due(now, free) {
+                continue;
+            }
+            let wait = self.next_due_ms(now).map_or(free, |due| due.clamp(1, free));
+            let wait = wait.min(u32::from(u16::MAX)) as u16;
+            delay.delay_ms(wait);
+            self.idle_ms = self.idle_ms.saturating_add(u32::from(wait));
+        }
+    }
+}
+
+impl Default for Scheduler<'_> {
+    fn default() -> Self {
+        Scheduler::new()
+    }
+}
+
+#[cfg(test)]
+mod scheduler_tests {
+    use core::cell::Cell;
+
+    use super::*;
+    use crate::{Celsius, RelativeHumidity, Sensor, SimulatedSensor, SENSOR_ADDR};
+
+    //A clock and delay sharing one time, the work advances it too.
+    struct Time<'a>(&'a Cell<u32>);
+
+    impl Clock for Time<'_> {
+        fn now_ms(&mut self) -> u32 {
+            self.0.get()
+        }
+    }
+
+    impl Delay for Time<'_> {
+        fn delay_ms(&mut self, ms: u16) {
+            self.0.set(self.0.get().wrapping_add(u32::from(ms)));
+        }
+    }
+
+    #[test]
+    fn work_fills_the_conversion()
+    {
+        let now = Cell::new(u32::MAX - 40);
+        let mut other_sensor = 0;
+        let mut housekeeping = 0;
+        let mut too_long = 0;
+        let mut read_other = |_| {
+            other_sensor += 1;
+            now.set(now.get().wrapping_add(5));
+        };
+        let mut tidy = |_| {
+            housekeeping += 1;
+            now.set(now.get().wrapping_add(30));
+        };
+        let mut never = |_| too_long += 1;
+
+        let mut sim = SimulatedSensor::new(Celsius(22.0), RelativeHumidity(45.0));
+        sim.busy_reads = 1;
+        let mut inited = InitializedSensor { sensor: Sensor::new(sim, SENSOR_ADDR) };
+
+        let mut sched = Scheduler::new();
+        sched.add(20, 5, &mut read_other);
+        sched.add(1000, 30, &mut tidy);
+        let id = sched.add(10, 200, &mut never);
+        let m = sched.measure(&mut inited, &mut Time(&now), &mut Time(&now)).unwrap();
+        assert!(m.quality.is_good());
+        sched.remove(id);
+
+        //80ms typical plus one 20ms busy poll, across the clock wrapping.
+        assert_eq!(now.get(), 59);
+        assert!(sched.runs() >= 5);
+        assert!(sched.idle_ms() < 70);
+        drop(sched);
+        assert_eq!(housekeeping, 1);
+        assert!(other_sensor >= 4);
+        assert_eq!(too_long, 0);
+    }
+
+    #[test]
+    fn times_out()
+    {
+        let now = Cell::new(0);
+        let mut sim = SimulatedSensor::new(Celsius(22.0), RelativeHumidity(45.0));
+        sim.busy_reads = 10;
+        let mut inited = InitializedSensor { sensor: Sensor::new(sim, SENSOR_ADDR) };
+
+        let mut sched = Scheduler::new();
+        let r = sched.measure(&mut inited, &mut Time(&now), &mut Time(&now));
+        assert_eq!(r, Err(Error::DeviceTimeOut));
+        assert_eq!(now.get(), 140);
+        assert_eq!(sched.idle_ms(), 140);
+    }
+}
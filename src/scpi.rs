@@ -0,0 +1,244 @@
+/*
+ * Filename: scpi.rs
+ * Description: SCPI style text commands for bench instruments and UART
+ * consoles. Responses go to any `core::fmt::Write`, a `String` with std or
+ * a `heapless::String` without. Headers take the short or the long form in
+ * any case, e.g. `meas:temp?` or `MEASure:TEMPerature?`.
+ *
+ *  *IDN?           identification
+ *  *RST            soft reset
+ *  MEAS?           temperature and humidity, "22.88,49.34"
+ *  MEAS:TEMP?      temperature in C
+ *  MEAS:HUM?       relative humidity in %
+ *  SYST:ERR?       oldest queued error, `0,"No error"` once empty
+ */
+
+use core::fmt::{self, Write};
+
+use crate::journal::ErrorKind;
+use crate::{Aht2xTransport, Delay, Indicator, InitializedSensor};
+
+///Errors kept for `SYST:ERR?`, older ones past this are dropped.
+pub const SCPI_QUEUE_LEN: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScpiError {
+    ///-113, the command isn't known.
+    UndefinedHeader,
+    ///-350, errors were dropped because the queue was full.
+    QueueOverflow,
+    ///The driver failed, reported with `ErrorKind::code()`.
+    Driver(ErrorKind),
+}
+
+impl ScpiError {
+    pub fn code(self) -> i16 {
+        match self {
+            ScpiError::UndefinedHeader => -113,
+            ScpiError::QueueOverflow => -350,
+            ScpiError::Driver(kind) => i16::from(kind.code()),
+        }
+    }
+}
+
+impl fmt::Display for ScpiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScpiError::UndefinedHeader => write!(f, "{},\"Undefined header\"", self.code()),
+            ScpiError::QueueOverflow => write!(f, "{},\"Queue overflow\"", self.code()),
+            ScpiError::Driver(kind) => write!(f, "{},\"{:?}\"", self.code(), kind),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Identify,
+    Reset,
+    Measure,
+    Temperature,
+    Humidity,
+    NextError,
+}
+
+//True if `word` is the short or long form of a header, e.g. MEAS/MEASURE.
+fn keyword(word: &str, short: &str, long: &str) -> bool {
+    word.eq_ignore_ascii_case(short) || word.eq_ignore_ascii_case(long)
+}
+
+fn parse(line: &str) -> Option<Command> {
+    let line = line.trim();
+    if line.eq_ignore_ascii_case("*IDN?") {
+        return Some(Command::Identify);
+    }
+    if line.eq_ignore_ascii_case("*RST") {
+        return Some(Command::Reset);
+    }
+
+    let query = line.strip_suffix('?')?;
+    let mut parts = query.split(':');
+    let (first, second) = (parts.next()?, parts.next());
+    if parts.next().is_some() {
+        return None;
+    }
+    match second {
+        None if keyword(first, "MEAS", "MEASURE") => Some(Command::Measure),
+        Some(s) if keyword(first, "MEAS", "MEASURE") && keyword(s, "TEMP", "TEMPERATURE") => {
+            Some(Command::Temperature)
+        },
+        Some(s) if keyword(first, "MEAS", "MEASURE") && keyword(s, "HUM", "HUMIDITY") => {
+            Some(Command::Humidity)
+        },
+        Some(s) if keyword(first, "SYST", "SYSTEM") && keyword(s, "ERR", "ERROR") => {
+            Some(Command::NextError)
+        },
+        _ => None,
+    }
+}
+
+///Runs text commands against a sensor and keeps the SCPI error queue.
+#[derive(Debug, Clone, Default)]
+pub struct ScpiAdapter {
+    errors: [Option<ScpiError>; SCPI_QUEUE_LEN],
+}
+
+impl ScpiAdapter {
+    pub fn new() -> Self {
+        ScpiAdapter::default()
+    }
+
+    fn push_error(&mut self, e: ScpiError) {
+        match self.errors.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => *slot = Some(e),
+            None => self.errors[SCPI_QUEUE_LEN - 1] = Some(ScpiError::QueueOverflow),
+        }
+    }
+
+    ///Takes the oldest queued error.
+    pub fn pop_error(&mut self) -> Option<ScpiError> {
+        let first = self.errors[0].take();
+        self.errors.rotate_left(1);
+        first
+    }
+
+    ///Runs one command line. Queries write their response and a newline
+    ///to `out`, a failed one writes nothing and queues the error.
+    pub fn execute<E, I2C, L>(
+        &mut self,
+        sensor: &mut InitializedSensor<I2C, L>,
+        delay: &mut impl Delay,
+        line: &str,
+        out: &mut impl Write,
+        ) -> fmt::Result
+    where I2C: Aht2xTransport<Error = E>,
+          L: Indicator,
+    {
+        let command = match parse(line) {
+            Some(c) => c,
+            None => {
+                self.push_error(ScpiError::UndefinedHeader);
+                return Ok(());
+            },
+        };
+
+        match command {
+            Command::Identify => {
+                writeln!(out, "AOSONG,AHT20,0,{}", env!("CARGO_PKG_VERSION"))
+            },
+            Command::NextError => match self.pop_error() {
+                Some(e) => writeln!(out, "{}", e),
+                None => writeln!(out, "0,\"No error\""),
+            },
+            Command::Reset => {
+                if let Err(e) = sensor.soft_reset(delay) {
+                    self.push_error(ScpiError::Driver(e.kind()));
+                }
+                Ok(())
+            },
+            Command::Measure | Command::Temperature | Command::Humidity => {
+                let m = match sensor.measure(delay) {
+                    Ok(m) => m,
+                    Err(e) => {
+                        self.push_error(ScpiError::Driver(e.kind()));
+                        return Ok(());
+                    },
+                };
+                match command {
+                    Command::Temperature => writeln!(out, "{:.2}", m.temperature.0),
+                    Command::Humidity => writeln!(out, "{:.2}", m.humidity.0),
+                    _ => writeln!(out, "{:.2},{:.2}", m.temperature.0, m.humidity.0),
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod scpi_tests {
+    use super::*;
+    use crate::{Celsius, RelativeHumidity, Sensor, SimulatedSensor, SENSOR_ADDR};
+
+    #[test]
+    fn parsing()
+    {
+        assert_eq!(parse("MEAS:TEMP?"), Some(Command::Temperature));
+        assert_eq!(parse(" measure:temperature?\r\n"), Some(Command::Temperature));
+        assert_eq!(parse("MEAS:HUM?"), Some(Command::Humidity));
+        assert_eq!(parse("meas?"), Some(Command::Measure));
+        assert_eq!(parse("SYSTem:ERRor?"), Some(Command::NextError));
+        assert_eq!(parse("SYSTEM:ERROR?"), Some(Command::NextError));
+        assert_eq!(parse("*idn?"), Some(Command::Identify));
+        assert_eq!(parse("MEAS:TEMP"), None);
+        assert_eq!(parse("MEAS:TEMP:X?"), None);
+    }
+
+    #[test]
+    fn session()
+    {
+        let sim = SimulatedSensor::new(Celsius(22.88), RelativeHumidity(49.34));
+        let sensor = Sensor::new(sim, SENSOR_ADDR);
+        let mut delay = embedded_hal_mock::delay::MockNoop;
+        let mut inited = sensor.init(&mut delay).unwrap();
+        let mut scpi = ScpiAdapter::new();
+
+        let mut out = String::new();
+        for line in ["MEAS:TEMP?", "MEAS:HUM?", "MEAS?", "BOGUS?", "SYST:ERR?", "SYST:ERR?"] {
+            scpi.execute(&mut inited, &mut delay, line, &mut out).unwrap();
+        }
+        assert_eq!(out, "22.88\n49.34\n22.88,49.34\n-113,\"Undefined header\"\n0,\"No error\"\n");
+
+        inited.sensor.i2c.corrupt_crc = true;
+        out.clear();
+        scpi.execute(&mut inited, &mut delay, "MEAS:TEMP?", &mut out).unwrap();
+        scpi.execute(&mut inited, &mut delay, "SYST:ERR?", &mut out).unwrap();
+        assert_eq!(out, "2,\"InvalidChecksum\"\n");
+    }
+
+    #[test]
+    fn queue_overflow()
+    {
+        let mut scpi = ScpiAdapter::new();
+        for _ in 0..SCPI_QUEUE_LEN + 2 {
+            scpi.push_error(ScpiError::UndefinedHeader);
+        }
+        for _ in 0..SCPI_QUEUE_LEN - 1 {
+            assert_eq!(scpi.pop_error(), Some(ScpiError::UndefinedHeader));
+        }
+        assert_eq!(scpi.pop_error(), Some(ScpiError::QueueOverflow));
+        assert_eq!(scpi.pop_error(), None);
+    }
+
+    #[cfg(feature = "heapless")]
+    #[test]
+    fn heapless_output()
+    {
+        let sim = SimulatedSensor::new(Celsius(22.88), RelativeHumidity(49.34));
+        let sensor = Sensor::new(sim, SENSOR_ADDR);
+        let mut delay = embedded_hal_mock::delay::MockNoop;
+        let mut inited = sensor.init(&mut delay).unwrap();
+
+        let mut out: heapless::String<32> = heapless::String::new();
+        ScpiAdapter::new().execute(&mut inited, &mut delay, "MEAS?", &mut out).unwrap();
+        assert_eq!(out.as_str(), "22.88,49.34\n");
+    }
+}
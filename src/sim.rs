@@ -0,0 +1,186 @@
+/*
+ * Filename: sim.rs
+ * Description: A simulated sensor and frame encoder, for demos, teaching
+ * tools and tests without hardware. Everything here is no_std and builds
+ * for wasm32-unknown-unknown, so a browser front end can show frames, CRC
+ * and conversions using the real crate code.
+ */
+
+use crate::core::commands::{CALIBRATE, INIT_SENSOR, READ_STATUS, SOFT_RESET, TRIG_MESSURE};
+use crate::core::data::SensorData;
+use crate::core::sensor_status::{BUSY_BM, CALENABLED_BM};
+use crate::core::units::{Celsius, RelativeHumidity};
+use crate::{Aht2xTransport, AHT20_DIVISOR, SENSOR_ADDR};
+
+///Status byte of an idle sensor that hasn't been calibrated yet.
+pub const SIM_IDLE_STATUS: u8 = 0x10;
+
+//The inverse of the data sheet formulas, clamped to the 20 bit range.
+fn to_raw(fraction: f32) -> u32 {
+    let raw = fraction * AHT20_DIVISOR + 0.5;
+    raw.clamp(0.0, AHT20_DIVISOR - 1.0) as u32
+}
+
+///Builds the 7 byte frame the sensor would send for these values, with a
+///valid CRC.
+pub fn encode_frame(status: u8, temperature: Celsius, humidity: RelativeHumidity) -> SensorData {
+    let rh = to_raw(humidity.0 / 100.0);
+    let t = to_raw((temperature.0 + 50.0) / 200.0);
+
+    let mut sd = SensorData::new();
+    sd.bytes = [
+        status,
+        (rh >> 12) as u8,
+        (rh >> 4) as u8,
+        (((rh & 0x0F) << 4) | (t >> 16)) as u8,
+        (t >> 8) as u8,
+        t as u8,
+        0,
+    ];
+    sd.crc_8_maxim();
+    sd.bytes[6] = sd.crc;
+    sd
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimError {
+    ///Nobody at that address.
+    Nack,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pending {
+    Nothing,
+    Status,
+    Measurement,
+}
+
+///A sensor on a simulated bus, answers like an AHT20 at `SENSOR_ADDR`.
+#[derive(Debug, Clone)]
+pub struct SimulatedSensor {
+    pub temperature: Celsius,
+    pub humidity: RelativeHumidity,
+    ///Reads that come back busy after each trigger.
+    pub busy_reads: u8,
+    ///Send a wrong CRC, for showing what a bad frame looks like.
+    pub corrupt_crc: bool,
+    ///The calibration bit, the init and calibrate commands set it. Clear
+    ///it to show a sensor that lost its calibration.
+    pub calibrated: bool,
+    busy_left: u8,
+    pending: Pending,
+}
+
+impl SimulatedSensor {
+    pub fn new(temperature: Celsius, humidity: RelativeHumidity) -> Self {
+        SimulatedSensor {
+            temperature,
+            humidity,
+            busy_reads: 0,
+            corrupt_crc: false,
+            calibrated: false,
+            busy_left: 0,
+            pending: Pending::Nothing,
+        }
+    }
+
+    pub fn status(&self) -> u8 {
+        let mut status = SIM_IDLE_STATUS;
+        if self.calibrated {
+            status |= CALENABLED_BM;
+        }
+        if self.busy_left > 0 {
+            status |= BUSY_BM;
+        }
+        status
+    }
+}
+
+impl Aht2xTransport for SimulatedSensor {
+    type Error = SimError;
+
+    fn write_frame(&mut self, address: u8, bytes: &[u8]) -> Result<(), SimError> {
+        if address != SENSOR_ADDR {
+            return Err(SimError::Nack);
+        }
+        match bytes.first() {
+            Some(&READ_STATUS) => self.pending = Pending::Status,
+            Some(&INIT_SENSOR) | Some(&CALIBRATE) => self.calibrated = true,
+            Some(&TRIG_MESSURE) => {
+                self.busy_left = self.busy_reads;
+                self.pending = Pending::Measurement;
+            },
+            Some(&SOFT_RESET) => {
+                self.busy_left = 0;
+                self.pending = Pending::Nothing;
+            },
+            _ => {},
+        }
+        Ok(())
+    }
+
+    fn read_frame(&mut self, address: u8, buf: &mut [u8]) -> Result<(), SimError> {
+        if address != SENSOR_ADDR {
+            return Err(SimError::Nack);
+        }
+        buf.fill(0);
+
+        if self.pending == Pending::Measurement {
+            let sd = encode_frame(self.status(), self.temperature, self.humidity);
+            let mut bytes = sd.bytes;
+            if self.corrupt_crc {
+                bytes[6] ^= 0xFF;
+            }
+            let n = buf.len().min(bytes.len());
+            buf[..n].copy_from_slice(&bytes[..n]);
+            self.busy_left = self.busy_left.saturating_sub(1);
+        } else if let Some(first) = buf.first_mut() {
+            *first = self.status();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod sim_tests {
+    use super::*;
+    use crate::Sensor;
+
+    #[test]
+    fn encode_round_trip()
+    {
+        let mut sd = encode_frame(0x18, Celsius(22.88), RelativeHumidity(49.34));
+        assert!(sd.is_crc_good());
+        assert!(sd.temperature().abs_delta(Celsius(22.88)) < Celsius(0.001));
+        assert!(sd.humidity().abs_delta(RelativeHumidity(49.34)) < RelativeHumidity(0.001));
+
+        //Out of range values are clamped.
+        let sd = encode_frame(0x18, Celsius(200.0), RelativeHumidity(-5.0));
+        assert!(sd.temperature() > Celsius(149.9));
+        assert_eq!(sd.humidity(), RelativeHumidity(0.0));
+    }
+
+    #[test]
+    fn driver_against_sim()
+    {
+        let mut sim = SimulatedSensor::new(Celsius(25.0), RelativeHumidity(40.0));
+        sim.busy_reads = 2;
+
+        let sensor = Sensor::new(sim, SENSOR_ADDR);
+        let mut delay = embedded_hal_mock::delay::MockNoop;
+        let mut inited = sensor.init(&mut delay).unwrap();
+        let m = inited.measure(&mut delay).unwrap();
+        assert!(m.temperature.abs_delta(Celsius(25.0)) < Celsius(0.01));
+        assert!(m.humidity.abs_delta(RelativeHumidity(40.0)) < RelativeHumidity(0.01));
+
+        inited.sensor.i2c.corrupt_crc = true;
+        assert_eq!(inited.measure(&mut delay).err(), Some(crate::Error::InvalidChecksum));
+    }
+
+    #[test]
+    fn wrong_address()
+    {
+        let mut sim = SimulatedSensor::new(Celsius(25.0), RelativeHumidity(40.0));
+        assert_eq!(sim.write_frame(0x39, &[READ_STATUS]), Err(SimError::Nack));
+    }
+}
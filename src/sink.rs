@@ -0,0 +1,177 @@
+/*
+ * Filename: sink.rs
+ * Description: Where logged records go. The application implements
+ * `RecordSink` for its storage (SD card, SPI flash through
+ * `encode_record()`, a UART...) and the sampling helpers append to it, so
+ * the storage can change without touching the driver code.
+ */
+
+use crate::aggregate::{Decimator, IntervalRecord};
+use crate::core::measurement::{Measurement, Quality};
+use crate::record::TimedMeasurement;
+use crate::core::units::{Celsius, RelativeHumidity};
+use crate::{Aht2xTransport, Delay, Error, Indicator, InitializedSensor};
+
+pub trait RecordSink {
+    type Error;
+
+    ///Appends one record, it may be buffered until `flush()`.
+    fn append(&mut self, record: &TimedMeasurement) -> Result<(), Self::Error>;
+
+    ///Writes out anything buffered, e.g. before going to sleep.
+    fn flush(&mut self) -> Result<(), Self::Error>;
+}
+
+impl<S: RecordSink + ?Sized> RecordSink for &mut S {
+    type Error = S::Error;
+
+    fn append(&mut self, record: &TimedMeasurement) -> Result<(), S::Error> {
+        (**self).append(record)
+    }
+
+    fn flush(&mut self) -> Result<(), S::Error> {
+        (**self).flush()
+    }
+}
+
+///Either the sensor or the sink failed.
+#[derive(Debug, PartialEq)]
+pub enum LogError<E, S> {
+    Sensor(Error<E>),
+    Sink(S),
+}
+
+impl<E, I2C, L> InitializedSensor<I2C, L>
+where I2C: Aht2xTransport<Error = E>,
+      L: Indicator,
+{
+    ///Measures and appends the result to `sink` with `timestamp`.
+    pub fn measure_into<S: RecordSink>(
+        &mut self,
+        delay: &mut impl Delay,
+        timestamp: i64,
+        sink: &mut S,
+        ) -> Result<TimedMeasurement, LogError<E, S::Error>>
+    {
+        let m = self.measure(delay).map_err(LogError::Sensor)?;
+        let record = TimedMeasurement::new(timestamp, &m);
+        sink.append(&record).map_err(LogError::Sink)?;
+        Ok(record)
+    }
+}
+
+impl IntervalRecord {
+    ///The interval as one record of its means, timestamped with `start_ms`.
+    pub fn to_timed(&self) -> TimedMeasurement {
+        let m = Measurement {
+            id: self.id,
+            temperature: Celsius(self.temperature.mean),
+            humidity: RelativeHumidity(self.humidity.mean),
+            quality: Quality::GOOD,
+            pressure: None,
+            seq: None,
+        };
+        TimedMeasurement::new(self.start_ms as i64, &m)
+    }
+}
+
+impl Decimator {
+    ///`push()`, with a finished interval appended to `sink`.
+    pub fn push_into<S: RecordSink>(
+        &mut self,
+        m: &Measurement,
+        now_ms: u32,
+        sink: &mut S,
+        ) -> Result<Option<IntervalRecord>, S::Error>
+    {
+        let finished = self.push(m, now_ms);
+        if let Some(r) = &finished {
+            sink.append(&r.to_timed())?;
+        }
+        Ok(finished)
+    }
+
+    ///`flush()`, with the running interval appended to `sink` and the sink
+    ///flushed.
+    pub fn flush_into<S: RecordSink>(&mut self, sink: &mut S) -> Result<Option<IntervalRecord>, S::Error> {
+        let finished = self.flush();
+        if let Some(r) = &finished {
+            sink.append(&r.to_timed())?;
+        }
+        sink.flush()?;
+        Ok(finished)
+    }
+}
+
+#[cfg(test)]
+mod sink_tests {
+    use super::*;
+    use crate::core::measurement::SensorId;
+    use crate::{Sensor, SimulatedSensor, SENSOR_ADDR};
+
+    #[derive(Default)]
+    struct VecSink {
+        records: Vec<TimedMeasurement>,
+        flushes: u8,
+        full: bool,
+    }
+
+    impl RecordSink for VecSink {
+        type Error = ();
+
+        fn append(&mut self, record: &TimedMeasurement) -> Result<(), ()> {
+            if self.full {
+                return Err(());
+            }
+            self.records.push(*record);
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), ()> {
+            self.flushes += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn measure_into_sink()
+    {
+        let sim = SimulatedSensor::new(Celsius(21.5), RelativeHumidity(40.0));
+        let sensor = Sensor::new(sim, SENSOR_ADDR);
+        let mut delay = embedded_hal_mock::delay::MockNoop;
+        let mut inited = sensor.init(&mut delay).unwrap();
+
+        let mut sink = VecSink::default();
+        let r = inited.measure_into(&mut delay, 1_000, &mut sink).unwrap();
+        assert_eq!(sink.records, [r]);
+        assert_eq!(r.temperature_centi, 2150);
+
+        sink.full = true;
+        assert_eq!(inited.measure_into(&mut delay, 2_000, &mut sink), Err(LogError::Sink(())));
+    }
+
+    #[test]
+    fn decimator_into_sink()
+    {
+        let m = |t: f32| Measurement {
+            id: SensorId(2),
+            temperature: Celsius(t),
+            humidity: RelativeHumidity(50.0),
+            quality: Quality::GOOD,
+            pressure: None,
+            seq: None,
+        };
+
+        let mut sink = VecSink::default();
+        let mut d = Decimator::new(1000);
+        assert_eq!(d.push_into(&m(20.0), 0, &mut sink), Ok(None));
+        d.push_into(&m(22.0), 500, &mut sink).unwrap();
+        assert!(d.push_into(&m(30.0), 1000, &mut sink).unwrap().is_some());
+        assert!(d.flush_into(&mut sink).unwrap().is_some());
+
+        let temps: Vec<i16> = sink.records.iter().map(|r| r.temperature_centi).collect();
+        assert_eq!(temps, [2100, 3000]);
+        assert_eq!(sink.records[1].timestamp, 1000);
+        assert_eq!(sink.flushes, 1);
+    }
+}
@@ -0,0 +1,251 @@
+/*
+ * Filename: snapshot.rs
+ * Description: Keeping the driver's state through a deep sleep that loses
+ * RAM. `suspend()` copies what took time to build up (settings, statistics,
+ * sequence numbers, the pipeline's filters and offsets) into a plain value
+ * for RTC memory or flash, `resume()` builds the sensor back from it
+ * without going through `init()` and its warm up again. A sensor that was
+ * powered down too comes back with `Sensor::resume_cold()` and `init()`.
+ */
+
+use crate::core::frame::Frame;
+use crate::core::measurement::SensorId;
+use crate::core::params::CommandParams;
+use crate::core::quirks::BusQuirks;
+use crate::core::timings::{ConversionDurations, Timings};
+use crate::core::units::Hectopascal;
+use crate::core::variant::ChipVariant;
+use crate::history::DuplicatePolicy;
+use crate::pipeline::Pipeline;
+use crate::resetcheck::ResetVerification;
+use crate::state::DriverState;
+use crate::{Aht2xTransport, Indicator, InitializedSensor, NoIndicator, Sensor, SpacingPolicy};
+
+///The driver state `InitializedSensor::suspend()` saves. The journal,
+///the frame history and anything timed against the clock (status cache,
+///trigger spacing, a recovery in progress) start over on resume.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StateSnapshot {
+    address: u8,
+    timings: Timings,
+    id: SensorId,
+    status_max_age_ms: Option<u32>,
+    min_spacing: Option<(u32, SpacingPolicy)>,
+    spacing_violations: u16,
+    degrade_limit: Option<u8>,
+    consecutive_failures: u16,
+    params: CommandParams,
+    durations: ConversionDurations,
+    strict: bool,
+    status_anomalies: u16,
+    variant: ChipVariant,
+    pressure: Option<Hectopascal>,
+    fault_limit: Option<u8>,
+    bus_failures: u8,
+    quirks: BusQuirks,
+    seq: Option<u32>,
+    duplicate_policy: Option<DuplicatePolicy>,
+    last_good_frame: Option<Frame>,
+    duplicates: u16,
+    reset_verification: ResetVerification,
+    calibration_check: bool,
+    general_call: bool,
+    ///The pipeline the readings go through, set it before storing the
+    ///snapshot to keep its filter history, offsets and alarm states.
+    pub pipeline: Option<Pipeline>,
+}
+
+impl<E, I2C, L> InitializedSensor<I2C, L>
+where I2C: Aht2xTransport<Error = E>,
+      L: Indicator,
+{
+    ///Saves the driver state before a deep sleep, see `StateSnapshot` for
+    ///what's in it.
+    pub fn suspend(&self) -> StateSnapshot {
+        let s = &self.sensor;
+        StateSnapshot {
+            address: s.address,
+            timings: s.timings,
+            id: s.id,
+            status_max_age_ms: s.status_max_age_ms,
+            min_spacing: s.min_spacing,
+            spacing_violations: s.spacing_violations,
+            degrade_limit: s.degrade_limit,
+            consecutive_failures: s.consecutive_failures,
+            params: s.params,
+            durations: s.durations,
+            strict: s.strict,
+            status_anomalies: s.status_anomalies,
+            variant: s.variant,
+            pressure: s.pressure,
+            fault_limit: s.fault_limit,
+            bus_failures: s.bus_failures,
+            quirks: s.quirks,
+            seq: s.seq,
+            duplicate_policy: s.duplicate_policy,
+            last_good_frame: s.last_good_frame,
+            duplicates: s.duplicates,
+            reset_verification: s.reset_verification,
+            calibration_check: s.calibration_check,
+            general_call: s.general_call,
+            pipeline: None,
+        }
+    }
+}
+
+impl<E, I2C, L> Sensor<I2C, L>
+where I2C: Aht2xTransport<Error = E>,
+      L: Indicator,
+{
+    ///Builds the sensor back from a snapshot after the sensor lost power
+    ///in the sleep. It's uninitialized, so `init()` goes through the
+    ///startup delay and the early retries of a sensor powering up.
+    pub fn resume_cold_with_indicator(snapshot: &StateSnapshot, i2c: I2C, indicator: L) -> Self {
+        let mut s = Sensor::with_indicator(i2c, snapshot.address, indicator);
+        s.timings = snapshot.timings;
+        s.id = snapshot.id;
+        s.status_max_age_ms = snapshot.status_max_age_ms;
+        s.min_spacing = snapshot.min_spacing;
+        s.spacing_violations = snapshot.spacing_violations;
+        s.degrade_limit = snapshot.degrade_limit;
+        s.consecutive_failures = snapshot.consecutive_failures;
+        s.params = snapshot.params;
+        s.durations = snapshot.durations;
+        s.strict = snapshot.strict;
+        s.status_anomalies = snapshot.status_anomalies;
+        s.variant = snapshot.variant;
+        s.pressure = snapshot.pressure;
+        s.fault_limit = snapshot.fault_limit;
+        s.bus_failures = snapshot.bus_failures;
+        s.quirks = snapshot.quirks;
+        s.seq = snapshot.seq;
+        s.duplicate_policy = snapshot.duplicate_policy;
+        s.last_good_frame = snapshot.last_good_frame;
+        s.duplicates = snapshot.duplicates;
+        s.reset_verification = snapshot.reset_verification;
+        s.calibration_check = snapshot.calibration_check;
+        s.general_call = snapshot.general_call;
+        s
+    }
+}
+
+impl<E, I2C> Sensor<I2C>
+where I2C: Aht2xTransport<Error = E>,
+{
+    ///`resume_cold_with_indicator()` without an indicator.
+    pub fn resume_cold(snapshot: &StateSnapshot, i2c: I2C) -> Self {
+        Sensor::resume_cold_with_indicator(snapshot, i2c, NoIndicator)
+    }
+}
+
+impl<E, I2C, L> InitializedSensor<I2C, L>
+where I2C: Aht2xTransport<Error = E>,
+      L: Indicator,
+{
+    ///Builds the sensor back from a snapshot on a fresh bus, idle and
+    ///ready to measure. Only for a sensor that kept its power and
+    ///calibration through the sleep, one that was powered down needs
+    ///`Sensor::resume_cold_with_indicator()` and `init()` instead.
+    pub fn resume_with_indicator(snapshot: &StateSnapshot, i2c: I2C, indicator: L) -> Self {
+        let mut s = Sensor::resume_cold_with_indicator(snapshot, i2c, indicator);
+        s.started_up = true;
+        s.state = DriverState::Idle;
+        InitializedSensor { sensor: s }
+    }
+}
+
+impl<E, I2C> InitializedSensor<I2C>
+where I2C: Aht2xTransport<Error = E>,
+{
+    ///`resume_with_indicator()` without an indicator, see there.
+    pub fn resume(snapshot: &StateSnapshot, i2c: I2C) -> Self {
+        InitializedSensor::resume_with_indicator(snapshot, i2c, NoIndicator)
+    }
+}
+
+#[cfg(test)]
+mod snapshot_tests {
+    use embedded_hal_mock::delay::MockNoop;
+
+    use super::*;
+    use crate::{Celsius, RelativeHumidity, SimulatedSensor, SENSOR_ADDR};
+
+    fn sim() -> SimulatedSensor {
+        SimulatedSensor::new(Celsius(22.0), RelativeHumidity(45.0))
+    }
+
+    #[test]
+    fn survives_sleep()
+    {
+        let mut inited = Sensor::new(sim(), SENSOR_ADDR).init(&mut MockNoop).unwrap();
+        inited.sensor.set_id(SensorId(4));
+        inited.sensor.enable_sequence_numbers(10);
+        inited.sensor.set_fault_limit(Some(3));
+        let mut pipeline = Pipeline::new().ema(0.5);
+        for _ in 0..2 {
+            let m = inited.measure(&mut MockNoop).unwrap();
+            pipeline.process(m, 0);
+        }
+
+        let mut snapshot = inited.suspend();
+        snapshot.pipeline = Some(pipeline);
+        //Only the MCU sleeps, the sensor keeps its calibration.
+        let sim = inited.release();
+
+        let mut resumed = InitializedSensor::resume(&snapshot, sim);
+        assert_eq!(resumed.sensor.state(), DriverState::Idle);
+        assert_eq!(resumed.sensor.fault_limit, Some(3));
+        let m = resumed.measure(&mut MockNoop).unwrap();
+        assert_eq!((m.id, m.seq), (SensorId(4), Some(12)));
+        assert_eq!(resumed.suspend().pipeline, None);
+
+        let mut pipeline = snapshot.pipeline.unwrap();
+        let out = pipeline.process(m, 0).measurement;
+        assert_eq!(out.temperature, m.temperature);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn postcard_round_trip()
+    {
+        let inited = Sensor::new(sim(), SENSOR_ADDR).init(&mut MockNoop).unwrap();
+        let mut snapshot = inited.suspend();
+        snapshot.pipeline = Some(Pipeline::new().median(5));
+
+        let mut buf = [0u8; 256];
+        let used = postcard::to_slice(&snapshot, &mut buf).unwrap();
+        assert_eq!(postcard::from_bytes::<StateSnapshot>(used).unwrap(), snapshot);
+    }
+
+    #[test]
+    fn powered_down()
+    {
+        let inited = Sensor::new(sim(), SENSOR_ADDR).init(&mut MockNoop).unwrap();
+        let mut snapshot = inited.suspend();
+        snapshot.fault_limit = Some(3);
+
+        let sensor = Sensor::resume_cold(&snapshot, sim());
+        assert_eq!(sensor.state(), DriverState::Uninitialized);
+        let mut delay = crate::sensor_test::DelayLog(vec![]);
+        let inited = sensor.init(&mut delay).unwrap();
+        //The startup delay again, a warm resume skips it.
+        assert_eq!(delay.0, [40]);
+        assert_eq!(inited.sensor.fault_limit, Some(3));
+    }
+
+    #[test]
+    fn with_indicator()
+    {
+        let inited = Sensor::new(sim(), SENSOR_ADDR).init(&mut MockNoop).unwrap();
+        let snapshot = inited.suspend();
+        let mut signals = vec![];
+        let mut resumed = InitializedSensor::resume_with_indicator(
+            &snapshot,
+            inited.release(),
+            |s| signals.push(s),
+            );
+        resumed.measure(&mut MockNoop).unwrap();
+        assert_eq!(signals.last(), Some(&crate::Signal::Ok));
+    }
+}
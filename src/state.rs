@@ -0,0 +1,316 @@
+/*
+ * Filename: state.rs
+ * Description: The driver's view of what the sensor is doing, for
+ * supervisory firmware that has to decide on a reset or a power cycle
+ * without guessing from the error history.
+ */
+
+use crate::{
+    Aht2xTransport, Delay, Error, Indicator, InitError, InitializedSensor, Sensor, SensorStatus,
+    Signal,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DriverState {
+    ///`init()` hasn't succeeded yet, the last one or a soft reset failed.
+    Uninitialized,
+    ///In the middle of `init()`, only seen from inside callbacks such as
+    ///a delay.
+    Initializing,
+    ///Initialized and not measuring.
+    Idle,
+    ///A measurement was triggered and its frame not read yet. A read that
+    ///fails or times out goes back to `Idle`.
+    Measuring,
+    ///A soft reset was sent and the sensor hasn't answered since.
+    Resetting,
+    ///Too many bus failures in a row, see `Sensor::set_fault_limit()`.
+    ///Measurements and `init()` give `Error::Faulted` until `recover()`.
+    Faulted,
+}
+
+impl DriverState {
+    ///A stable number for the state, never reused or renumbered.
+    pub const fn code(self) -> u8 {
+        match self {
+            DriverState::Uninitialized => 0,
+            DriverState::Initializing => 1,
+            DriverState::Idle => 2,
+            DriverState::Measuring => 3,
+            DriverState::Resetting => 4,
+            DriverState::Faulted => 5,
+        }
+    }
+
+    ///Whether a status byte read in this state fits it, what strict mode
+    ///checks. NOR mode always, the CMD/CYC mode bits never show in normal
+    ///operation. Past that:
+    /// - before and during init the CAL bit may still be clear, init
+    ///   calibrates then;
+    /// - idle the CAL bit is set and the sensor isn't busy;
+    /// - measuring the CAL bit is set, busy is expected;
+    /// - resetting the CAL bit is left to the reset verification, busy to
+    ///   the reset itself.
+    ///
+    ///Faulted accepts anything, nothing reaches the bus then.
+    pub fn expects(self, status: SensorStatus) -> bool {
+        if self == DriverState::Faulted {
+            return true;
+        }
+        if !status.is_normal_mode() {
+            return false;
+        }
+        match self {
+            DriverState::Idle => status.is_calibration_enabled() && !status.is_busy(),
+            DriverState::Measuring => status.is_calibration_enabled(),
+            _ => true,
+        }
+    }
+}
+
+impl<E, I2C, L> Sensor<I2C, L>
+where I2C: Aht2xTransport<Error = E>,
+      L: Indicator,
+{
+    pub fn state(&self) -> DriverState {
+        self.state
+    }
+
+    ///After `limit` bus errors or timeouts in a row the sensor goes into
+    ///`DriverState::Faulted`, so a loop doesn't keep hammering a dead bus.
+    ///None never faults, it is the default unless `AHT20_FAULT_LIMIT` was
+    ///set at build time. `Some(0)` is taken as None, a limit of no
+    ///failures at all would never be reached. Any success resets the count.
+    pub fn set_fault_limit(&mut self, limit: Option<u8>) {
+        self.fault_limit = limit.filter(|&limit| limit > 0);
+    }
+
+    ///Leaves the faulted state and runs `init()` again. If that fails the
+    ///sensor is uninitialized and the failures count up from there.
+    #[allow(clippy::result_large_err)]
+    pub fn recover(
+        mut self,
+        delay: &mut impl Delay,
+        ) -> Result<InitializedSensor<I2C, L>, InitError<E, I2C, L>>
+    {
+        self.bus_failures = 0;
+        self.state = DriverState::Uninitialized;
+        self.init(delay)
+    }
+
+    //Rejects calls while faulted, without touching the bus or journal.
+    pub(crate) fn check_faulted(&mut self) -> Result<(), Error<E>> {
+        if self.state == DriverState::Faulted {
+            self.indicator.show(Signal::Error);
+            return Err(Error::Faulted);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn count_failure(&mut self, error: Option<&Error<E>>) {
+        match error {
+            None => self.bus_failures = 0,
+            Some(Error::I2C(_)) | Some(Error::DeviceTimeOut) => {
+                self.bus_failures = self.bus_failures.saturating_add(1);
+                if self.fault_limit.is_some_and(|limit| self.bus_failures >= limit) {
+                    self.state = DriverState::Faulted;
+                }
+            },
+            Some(_) => {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod state_tests {
+    use embedded_hal_mock::i2c::{
+        Mock as I2cMock,
+        Transaction as I2cTransaction,
+    };
+
+    use super::*;
+    use crate::core::commands;
+    use crate::{SENSOR_ADDR, TRIG_MEASURE_PARAM0, TRIG_MEASURE_PARAM1};
+
+    #[test]
+    fn transitions()
+    {
+        let io_error = embedded_hal_mock::MockError::Io(std::io::ErrorKind::Other);
+        let read_status = I2cTransaction::write(SENSOR_ADDR, vec![commands::READ_STATUS]);
+        let expected = [
+            //Failed init, then a good one.
+            I2cTransaction::write(SENSOR_ADDR, vec![commands::INIT_SENSOR]),
+            read_status.clone().with_error(io_error.clone()),
+            I2cTransaction::write(SENSOR_ADDR, vec![commands::INIT_SENSOR]),
+            read_status.clone(),
+            I2cTransaction::read(SENSOR_ADDR, vec![0x18]),
+            //Trigger, busy, then done.
+            I2cTransaction::write(
+                SENSOR_ADDR,
+                vec![commands::TRIG_MESSURE, TRIG_MEASURE_PARAM0, TRIG_MEASURE_PARAM1],
+                ),
+            read_status.clone(),
+            I2cTransaction::read(SENSOR_ADDR, vec![0x98]),
+            read_status.clone(),
+            I2cTransaction::read(SENSOR_ADDR, vec![0x18]),
+            //A reset the sensor doesn't answer after.
+            read_status.clone(),
+            I2cTransaction::read(SENSOR_ADDR, vec![0x18]),
+            I2cTransaction::write(SENSOR_ADDR, vec![commands::SOFT_RESET]),
+            read_status.with_error(io_error),
+        ];
+
+        let sensor = Sensor::new(I2cMock::new(&expected), SENSOR_ADDR);
+        let mut delay = embedded_hal_mock::delay::MockNoop;
+        assert_eq!(sensor.state(), DriverState::Uninitialized);
+        let sensor = sensor.init(&mut delay).err().unwrap().sensor;
+        assert_eq!(sensor.state(), DriverState::Uninitialized);
+
+        let mut inited = sensor.init(&mut delay).unwrap();
+        assert_eq!(inited.sensor.state(), DriverState::Idle);
+
+        inited.trigger_measurement().unwrap();
+        assert_eq!(inited.sensor.state(), DriverState::Measuring);
+        inited.get_status().unwrap();
+        assert_eq!(inited.sensor.state(), DriverState::Measuring);
+        inited.get_status().unwrap();
+        assert_eq!(inited.sensor.state(), DriverState::Idle);
+
+        assert!(inited.soft_reset(&mut delay).is_err());
+        assert_eq!(inited.sensor.state(), DriverState::Uninitialized);
+        inited.sensor.i2c.done();
+    }
+
+    #[test]
+    fn expected_status()
+    {
+        let idle = SensorStatus::new(0x18);
+        let busy = SensorStatus::new(0x98);
+        let uncalibrated = SensorStatus::new(0x10);
+        let cmd_mode = SensorStatus::new(0x58);
+        let cyc_mode = SensorStatus::new(0x38);
+
+        for state in [DriverState::Uninitialized, DriverState::Initializing] {
+            assert!(state.expects(idle) && state.expects(uncalibrated));
+            assert!(!state.expects(cmd_mode));
+        }
+        assert!(DriverState::Idle.expects(idle));
+        assert!(!DriverState::Idle.expects(busy));
+        assert!(!DriverState::Idle.expects(uncalibrated));
+        assert!(!DriverState::Idle.expects(cyc_mode));
+        assert!(DriverState::Measuring.expects(busy));
+        assert!(!DriverState::Measuring.expects(SensorStatus::new(0x90)));
+        assert!(!DriverState::Measuring.expects(SensorStatus::new(0xD8)));
+        assert!(DriverState::Resetting.expects(uncalibrated));
+        assert!(!DriverState::Resetting.expects(cmd_mode));
+        assert!(DriverState::Faulted.expects(cmd_mode));
+    }
+
+    #[test]
+    fn strict_init()
+    {
+        let expected = [
+            I2cTransaction::write(SENSOR_ADDR, vec![commands::INIT_SENSOR]),
+            I2cTransaction::write(SENSOR_ADDR, vec![commands::READ_STATUS]),
+            I2cTransaction::read(SENSOR_ADDR, vec![0x58]),
+        ];
+        let mut sensor = Sensor::new(I2cMock::new(&expected), SENSOR_ADDR);
+        sensor.set_strict(true);
+        let mut delay = embedded_hal_mock::delay::MockNoop;
+        let mut sensor = sensor.init(&mut delay).err().unwrap().sensor;
+        assert_eq!(sensor.status_anomalies(), 1);
+        sensor.i2c.done();
+    }
+
+    #[test]
+    fn codes()
+    {
+        assert_eq!(DriverState::Uninitialized.code(), 0);
+        assert_eq!(DriverState::Faulted.code(), 5);
+    }
+
+    #[test]
+    fn faults_and_recovers()
+    {
+        let mut sim = crate::SimulatedSensor::new(crate::Celsius(20.0), crate::RelativeHumidity(40.0));
+        sim.busy_reads = 10;
+        let mut sensor = Sensor::new(sim, SENSOR_ADDR);
+        sensor.set_fault_limit(Some(2));
+        let mut delay = embedded_hal_mock::delay::MockNoop;
+
+        let mut inited = sensor.init(&mut delay).unwrap();
+        assert_eq!(inited.measure(&mut delay).err(), Some(Error::DeviceTimeOut));
+        assert_eq!(inited.sensor.state(), DriverState::Idle);
+        assert_eq!(inited.measure(&mut delay).err(), Some(Error::DeviceTimeOut));
+        assert_eq!(inited.sensor.state(), DriverState::Faulted);
+
+        //Rejected without bus traffic or journal entries.
+        inited.sensor.i2c.busy_reads = 0;
+        let journaled = inited.sensor.journal().len();
+        assert_eq!(inited.measure(&mut delay).err(), Some(Error::Faulted));
+        assert_eq!(inited.trigger_measurement(), Err(Error::Faulted));
+        assert_eq!(inited.sensor.journal().len(), journaled);
+        let sensor = inited.downgrade();
+        let sensor = sensor.init(&mut delay).err().unwrap();
+        assert_eq!(sensor.error, Error::Faulted);
+
+        let mut inited = sensor.sensor.recover(&mut delay).unwrap();
+        assert_eq!(inited.sensor.state(), DriverState::Idle);
+        assert!(inited.measure(&mut delay).is_ok());
+    }
+
+    #[test]
+    fn zero_limit_never_faults()
+    {
+        let mut sim = crate::SimulatedSensor::new(crate::Celsius(20.0), crate::RelativeHumidity(40.0));
+        sim.busy_reads = 10;
+        let mut sensor = Sensor::new(sim, SENSOR_ADDR);
+        sensor.set_fault_limit(Some(0));
+        assert_eq!(sensor.fault_limit, None);
+        let mut delay = embedded_hal_mock::delay::MockNoop;
+
+        let mut inited = sensor.init(&mut delay).unwrap();
+        for _ in 0..3 {
+            assert_eq!(inited.measure(&mut delay).err(), Some(Error::DeviceTimeOut));
+        }
+        assert_eq!(inited.sensor.state(), DriverState::Idle);
+    }
+
+    #[test]
+    fn success_resets_the_count()
+    {
+        let mut sensor = Sensor::new(
+            crate::SimulatedSensor::new(crate::Celsius(20.0), crate::RelativeHumidity(40.0)),
+            SENSOR_ADDR,
+            );
+        sensor.set_fault_limit(Some(2));
+        let mut delay = embedded_hal_mock::delay::MockNoop;
+        let mut inited = sensor.init(&mut delay).unwrap();
+
+        for _ in 0..3 {
+            inited.sensor.i2c.busy_reads = 10;
+            assert!(inited.measure(&mut delay).is_err());
+            inited.sensor.i2c.busy_reads = 0;
+            assert!(inited.measure(&mut delay).is_ok());
+        }
+        assert_eq!(inited.sensor.state(), DriverState::Idle);
+    }
+
+    #[test]
+    fn timed_out_read_goes_idle()
+    {
+        let mut sim = crate::SimulatedSensor::new(crate::Celsius(20.0), crate::RelativeHumidity(40.0));
+        sim.busy_reads = 10;
+        let sensor = Sensor::new(sim, SENSOR_ADDR);
+        let mut delay = embedded_hal_mock::delay::MockNoop;
+        let mut inited = sensor.init(&mut delay).unwrap();
+        assert!(inited.read_sensor(&mut delay).is_err());
+        assert_eq!(inited.sensor.state(), DriverState::Idle);
+
+        inited.sensor.i2c.busy_reads = 0;
+        assert!(inited.read_sensor(&mut delay).is_ok());
+        assert_eq!(inited.sensor.state(), DriverState::Idle);
+    }
+}
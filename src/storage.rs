@@ -0,0 +1,30 @@
+/*
+ * Filename: storage.rs
+ * Description: Non volatile storage for the small bits of state that have
+ * to survive a power cycle, e.g. the condensation counters. The
+ * application implements `Storage` on an EEPROM page, a flash sector or a
+ * file, one implementation per blob.
+ */
+
+pub trait Storage {
+    type Error;
+
+    ///Fills `buf` with the stored bytes, storage that was never written
+    ///may read as anything(0xFF on erased flash), the users check it.
+    fn read(&mut self, buf: &mut [u8]) -> Result<(), Self::Error>;
+
+    ///Replaces the stored bytes.
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error>;
+}
+
+impl<S: Storage + ?Sized> Storage for &mut S {
+    type Error = S::Error;
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<(), S::Error> {
+        (**self).read(buf)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), S::Error> {
+        (**self).write(bytes)
+    }
+}
@@ -0,0 +1,100 @@
+/*
+ * Filename: superloop.rs
+ * Description: `nb` style reads for bare metal superloops, where the main
+ * loop calls every driver in turn and none of them may block. The first
+ * call starts a conversion, the following ones give `WouldBlock` until the
+ * frame is read. Underneath it's a `MeasurementPoll` kept in the sensor.
+ */
+
+use crate::{Aht2xTransport, Error, Indicator, InitializedSensor, Poll, PollState, SensorData};
+
+impl<E, I2C, L> InitializedSensor<I2C, L>
+where I2C: Aht2xTransport<Error = E>,
+      L: Indicator,
+{
+    ///Triggers a measurement unless one is running and gives
+    ///`nb::Error::WouldBlock`, then gives `WouldBlock` until the frame is
+    ///read. `now_ms` is a monotonic clock's time, it may wrap. As with
+    ///`read_sensor()` the CRC is left to the caller.
+    ///
+    ///Calls before the typical conversion time is up don't touch the bus,
+    ///after it the sensor is read every `busy_poll_ms`. Still busy past the
+    ///maximum conversion time is `Error::DeviceTimeOut`, any error ends the
+    ///conversion and the next call triggers a new one.
+    pub fn read_sensor_nb(&mut self, now_ms: u32) -> nb::Result<SensorData, Error<E>> {
+        let mut poll = self.sensor.nb_poll;
+        if poll.state() == PollState::Idle {
+            //Journals a failed trigger itself.
+            poll.start(self, now_ms)?;
+            self.sensor.nb_poll = poll;
+            return Err(nb::Error::WouldBlock);
+        }
+        let r = poll.poll_frame(self, now_ms);
+        self.sensor.nb_poll = poll;
+        match r {
+            Ok(Poll::Pending) => Err(nb::Error::WouldBlock),
+            Ok(Poll::Ready(sd)) => Ok(self.sensor.journal_result(None, Ok(sd))?),
+            Err(e) => Err(nb::Error::Other(self.sensor.journal_result(None, Err(e))?)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod superloop_tests {
+    use super::*;
+    use crate::{Celsius, DriverState, RelativeHumidity, Sensor, SimulatedSensor, SENSOR_ADDR};
+
+    fn sensor(busy_reads: u8) -> InitializedSensor<SimulatedSensor> {
+        let mut sim = SimulatedSensor::new(Celsius(22.0), RelativeHumidity(45.0));
+        sim.busy_reads = busy_reads;
+        InitializedSensor { sensor: Sensor::new(sim, SENSOR_ADDR) }
+    }
+
+    #[test]
+    fn would_block_until_ready()
+    {
+        let mut inited = sensor(2);
+
+        //Trigger, a call before the frame is due, then two busy reads.
+        for now in [0, 40, 80, 100] {
+            assert_eq!(inited.read_sensor_nb(now).err(), Some(nb::Error::WouldBlock));
+        }
+        let mut sd = inited.read_sensor_nb(120).unwrap();
+        assert!(sd.is_crc_good());
+        assert!((sd.calculate_temperature() - 22.0).abs() < 0.01);
+
+        //The next call starts over.
+        inited.sensor.i2c.busy_reads = 0;
+        assert_eq!(inited.read_sensor_nb(200).err(), Some(nb::Error::WouldBlock));
+        assert!(inited.read_sensor_nb(280).is_ok());
+    }
+
+    #[test]
+    fn times_out()
+    {
+        let mut inited = sensor(20);
+        let mut now = 0;
+        let r = loop {
+            match inited.read_sensor_nb(now) {
+                Err(nb::Error::WouldBlock) => now += 10,
+                r => break r,
+            }
+        };
+        assert_eq!(r.err(), Some(nb::Error::Other(Error::DeviceTimeOut)));
+        assert_eq!(now, 140);
+        assert_eq!(inited.sensor.state(), DriverState::Idle);
+
+        //Triggers again instead of reading on.
+        inited.sensor.i2c.busy_reads = 0;
+        assert_eq!(inited.read_sensor_nb(now).err(), Some(nb::Error::WouldBlock));
+        assert_eq!(inited.sensor.state(), DriverState::Measuring);
+    }
+
+    #[test]
+    fn bus_error()
+    {
+        let mut inited = sensor(0);
+        inited.sensor.address = 0x39;
+        assert!(matches!(inited.read_sensor_nb(0), Err(nb::Error::Other(Error::I2C(_)))));
+    }
+}
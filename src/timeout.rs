@@ -0,0 +1,261 @@
+/*
+ * Filename: timeout.rs
+ * Description: A per transaction timeout for async code, so a wedged bus
+ * can't hang a task forever, the async side of the crate's "no infinite
+ * loops" rule.
+ *
+ * It works around any async I2C call. With the `async` feature
+ * `TimeoutI2c` puts it around every transaction of an embedded-hal-async
+ * `I2c`, `AsyncSensor`'s included. It doesn't depend on an executor or
+ * timer crate, the timer is any future that completes when the time is up,
+ * e.g. `embassy_time::Timer::after_millis(10)`.
+ */
+
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+///The timer finished before the transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimedOut;
+
+///See `with_timeout()`.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless awaited"]
+pub struct WithTimeout<F, T> {
+    fut: F,
+    timer: T,
+}
+
+///Runs `fut` until it completes or `timer` does, whichever is first. Both
+///need to be `Unpin`, use `core::pin::pin!` for the ones that aren't.
+pub fn with_timeout<F, T>(fut: F, timer: T) -> WithTimeout<F, T>
+where
+    F: Future + Unpin,
+    T: Future + Unpin,
+{
+    WithTimeout { fut, timer }
+}
+
+impl<F, T> Future for WithTimeout<F, T>
+where
+    F: Future + Unpin,
+    T: Future + Unpin,
+{
+    type Output = Result<F::Output, TimedOut>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        //The transaction goes first, a result that is ready counts even if
+        //the timer ran out at the same time.
+        if let Poll::Ready(v) = Pin::new(&mut self.fut).poll(cx) {
+            return Poll::Ready(Ok(v));
+        }
+        if Pin::new(&mut self.timer).poll(cx).is_ready() {
+            return Poll::Ready(Err(TimedOut));
+        }
+        Poll::Pending
+    }
+}
+
+///Starts the timers `TimeoutI2c` races the transactions against. Any
+///`FnMut(u32) -> impl Future` is one, e.g.
+///`|ms| embassy_time::Timer::after_millis(ms.into())`.
+#[cfg(feature = "async")]
+pub trait AsyncTimer {
+    type Timer: Future;
+
+    ///A future that completes `ms` milliseconds from now.
+    fn after_ms(&mut self, ms: u32) -> Self::Timer;
+}
+
+#[cfg(feature = "async")]
+impl<F, T> AsyncTimer for F
+where
+    F: FnMut(u32) -> T,
+    T: Future,
+{
+    type Timer = T;
+
+    fn after_ms(&mut self, ms: u32) -> T {
+        self(ms)
+    }
+}
+
+///A `TimeoutI2c` error: the bus failed, or the transaction didn't finish
+///in time.
+#[cfg(feature = "async")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutError<E> {
+    Bus(E),
+    TimedOut,
+}
+
+#[cfg(feature = "async")]
+impl<E: embedded_hal_async::i2c::Error> embedded_hal_async::i2c::Error for TimeoutError<E> {
+    fn kind(&self) -> embedded_hal_async::i2c::ErrorKind {
+        match self {
+            TimeoutError::Bus(e) => e.kind(),
+            TimeoutError::TimedOut => embedded_hal_async::i2c::ErrorKind::Other,
+        }
+    }
+}
+
+///An embedded-hal-async `I2c` whose every transaction gives up after
+///`timeout_ms`, with `TimeoutError::TimedOut`. The driver sees that as
+///`Error::I2C`, so a wedged bus fails a measurement instead of hanging the
+///task.
+#[cfg(feature = "async")]
+#[derive(Debug)]
+pub struct TimeoutI2c<I, T> {
+    i2c: I,
+    timer: T,
+    timeout_ms: u32,
+}
+
+#[cfg(feature = "async")]
+impl<I, T> TimeoutI2c<I, T> {
+    pub fn new(i2c: I, timeout_ms: u32, timer: T) -> Self {
+        TimeoutI2c { i2c, timer, timeout_ms }
+    }
+
+    pub fn into_inner(self) -> I {
+        self.i2c
+    }
+}
+
+#[cfg(feature = "async")]
+impl<I, T> embedded_hal_async::i2c::ErrorType for TimeoutI2c<I, T>
+where I: embedded_hal_async::i2c::ErrorType,
+{
+    type Error = TimeoutError<I::Error>;
+}
+
+#[cfg(feature = "async")]
+impl<I, T> embedded_hal_async::i2c::I2c for TimeoutI2c<I, T>
+where I: embedded_hal_async::i2c::I2c,
+      T: AsyncTimer,
+{
+    async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [embedded_hal_async::i2c::Operation<'_>],
+        ) -> Result<(), Self::Error>
+    {
+        let timer = core::pin::pin!(self.timer.after_ms(self.timeout_ms));
+        let transaction = core::pin::pin!(self.i2c.transaction(address, operations));
+        match with_timeout(transaction, timer).await {
+            Ok(r) => r.map_err(TimeoutError::Bus),
+            Err(TimedOut) => Err(TimeoutError::TimedOut),
+        }
+    }
+}
+
+#[cfg(test)]
+mod timeout_tests {
+    use core::future::{pending, ready};
+    use core::pin::pin;
+    use core::task::Waker;
+
+    use super::*;
+
+    fn poll_once<F: Future + Unpin>(f: &mut F) -> Poll<F::Output> {
+        let mut cx = Context::from_waker(Waker::noop());
+        Pin::new(f).poll(&mut cx)
+    }
+
+    ///Ready after being polled `left` more times.
+    struct Countdown {
+        left: u8,
+    }
+
+    impl Future for Countdown {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.left == 0 {
+                return Poll::Ready(());
+            }
+            self.left -= 1;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    #[test]
+    fn completes_first()
+    {
+        let mut f = with_timeout(ready(7u8), pending::<()>());
+        assert_eq!(poll_once(&mut f), Poll::Ready(Ok(7)));
+
+        //Both ready, the result wins.
+        let mut f = with_timeout(ready(1u8), ready(()));
+        assert_eq!(poll_once(&mut f), Poll::Ready(Ok(1)));
+    }
+
+    #[test]
+    fn wedged_bus()
+    {
+        let wedged = pin!(pending::<u8>());
+        let mut f = with_timeout(wedged, Countdown { left: 2 });
+        assert_eq!(poll_once(&mut f), Poll::Pending);
+        assert_eq!(poll_once(&mut f), Poll::Pending);
+        assert_eq!(poll_once(&mut f), Poll::Ready(Err(TimedOut)));
+    }
+
+    #[cfg(feature = "async")]
+    mod i2c {
+        use embedded_hal_async::i2c::{ErrorKind, ErrorType, I2c, Operation};
+
+        use super::*;
+        use crate::adapters::asynch::asynch_tests::{block_on, DelayLog};
+        use crate::{AsyncSensor, Error, SENSOR_ADDR};
+
+        ///Answers reads with the status byte, or never if `wedged`.
+        struct Bus {
+            wedged: bool,
+        }
+
+        impl ErrorType for Bus {
+            type Error = ErrorKind;
+        }
+
+        impl I2c for Bus {
+            async fn transaction(&mut self, _address: u8, operations: &mut [Operation<'_>]) -> Result<(), ErrorKind> {
+                if self.wedged {
+                    pending::<()>().await;
+                }
+                for op in operations {
+                    if let Operation::Read(buf) = op {
+                        buf.fill(0x18);
+                    }
+                }
+                Ok(())
+            }
+        }
+
+        #[test]
+        fn passes_through()
+        {
+            let mut asked = vec![];
+            let timer = |ms| {
+                asked.push(ms);
+                pending::<()>()
+            };
+            let i2c = TimeoutI2c::new(Bus { wedged: false }, 50, timer);
+            let mut sensor = AsyncSensor::new(i2c, SENSOR_ADDR);
+            assert_eq!(block_on(sensor.read_status()).unwrap().status, 0x18);
+            //One timer per transaction, the write and the read.
+            assert_eq!(asked, [50, 50]);
+        }
+
+        #[test]
+        fn wedged_bus()
+        {
+            let timer = |_| Countdown { left: 3 };
+            let mut sensor = AsyncSensor::new(TimeoutI2c::new(Bus { wedged: true }, 50, timer), SENSOR_ADDR);
+            let r = block_on(sensor.init(&mut DelayLog::default()));
+            assert_eq!(r, Err(Error::I2C(TimeoutError::TimedOut)));
+            assert_eq!(embedded_hal_async::i2c::Error::kind(&TimeoutError::<ErrorKind>::TimedOut), ErrorKind::Other);
+        }
+    }
+}
@@ -0,0 +1,125 @@
+/*
+ * Filename: transaction.rs
+ * Description: One bus transaction as it's logged and replayed, plus the
+ * one line text form used for log files:
+ *
+ *   <at_ms>,<address>,<W|R>,<nack 0|1>,<hex bytes>
+ *   120,38,W,0,AC3300
+ *
+ * Address and bytes are hex without the 0x prefix.
+ */
+
+use core::fmt;
+
+///Most bytes kept per transaction, the length of a measurement frame.
+pub const MAX_TRANSACTION_LEN: usize = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Direction {
+    Write,
+    Read,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Transaction {
+    pub at_ms: u32,
+    pub address: u8,
+    pub direction: Direction,
+    ///The transfer failed, e.g. the address wasn't acknowledged.
+    pub nack: bool,
+    len: u8,
+    data: [u8; MAX_TRANSACTION_LEN],
+}
+
+impl Transaction {
+    ///Longer transfers are cut to `MAX_TRANSACTION_LEN` bytes.
+    pub fn new(at_ms: u32, address: u8, direction: Direction, bytes: &[u8], nack: bool) -> Self {
+        let len = bytes.len().min(MAX_TRANSACTION_LEN);
+        let mut data = [0u8; MAX_TRANSACTION_LEN];
+        data[..len].copy_from_slice(&bytes[..len]);
+        Transaction { at_ms, address, direction, nack, len: len as u8, data }
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.data[..self.len as usize]
+    }
+
+    ///Parses a line of the text form, None if it's malformed.
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.trim().split(',');
+        let at_ms = fields.next()?.parse().ok()?;
+        let address = u8::from_str_radix(fields.next()?, 16).ok()?;
+        let direction = match fields.next()? {
+            "W" => Direction::Write,
+            "R" => Direction::Read,
+            _ => return None,
+        };
+        let nack = match fields.next()? {
+            "0" => false,
+            "1" => true,
+            _ => return None,
+        };
+        let hex = fields.next().unwrap_or("");
+        if fields.next().is_some() || !hex.len().is_multiple_of(2) || hex.len() > 2 * MAX_TRANSACTION_LEN {
+            return None;
+        }
+
+        let mut data = [0u8; MAX_TRANSACTION_LEN];
+        for (i, byte) in data.iter_mut().take(hex.len() / 2).enumerate() {
+            *byte = u8::from_str_radix(hex.get(2 * i..2 * i + 2)?, 16).ok()?;
+        }
+        Some(Transaction { at_ms, address, direction, nack, len: (hex.len() / 2) as u8, data })
+    }
+}
+
+impl fmt::Display for Transaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let dir = match self.direction {
+            Direction::Write => "W",
+            Direction::Read => "R",
+        };
+        write!(f, "{},{:02X},{},{},", self.at_ms, self.address, dir, self.nack as u8)?;
+        for b in self.bytes() {
+            write!(f, "{:02X}", b)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod transaction_tests {
+    use super::*;
+
+    #[test]
+    fn text_round_trip()
+    {
+        let t = Transaction::new(120, 0x38, Direction::Write, &[0xAC, 0x33, 0x00], false);
+        assert_eq!(t.to_string(), "120,38,W,0,AC3300");
+        assert_eq!(Transaction::parse("120,38,W,0,AC3300"), Some(t));
+
+        let t = Transaction::parse("5,38,R,1,\n").unwrap();
+        assert!(t.nack);
+        assert!(t.bytes().is_empty());
+    }
+
+    #[test]
+    fn malformed()
+    {
+        assert_eq!(Transaction::parse(""), None);
+        assert_eq!(Transaction::parse("1,38,X,0,71"), None);
+        assert_eq!(Transaction::parse("1,38,W,0,7"), None);
+        assert_eq!(Transaction::parse("1,38,W,0,0102030405060708"), None);
+        assert_eq!(Transaction::parse("1,38,W,0,71,extra"), None);
+    }
+
+    #[test]
+    fn truncated()
+    {
+        let t = Transaction::new(0, 0x38, Direction::Read, &[1, 2, 3, 4, 5, 6, 7, 8, 9], false);
+        assert_eq!(t.bytes(), [1, 2, 3, 4, 5, 6, 7]);
+    }
+}
@@ -0,0 +1,59 @@
+/*
+ * Filename: transport.rs
+ * Description: The two bus operations and the wait the driver needs.
+ * With the `legacy` feature every embedded-hal 0.2 blocking I2C peripheral
+ * and `DelayMs<u16>` gets them for free(see `adapters::eh0`), 1.0 ones
+ * through the `Eh1` wrapper. Exotic setups (USB to I2C bridges like the
+ * FT232H/CP2112, Linux SMBus, PIO bit-banging) implement them themselves.
+ */
+
+///Writes and reads whole frames to and from the sensor at `address`.
+pub trait Aht2xTransport {
+    type Error;
+
+    fn write_frame(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error>;
+
+    fn read_frame(&mut self, address: u8, buf: &mut [u8]) -> Result<(), Self::Error>;
+}
+
+///Blocks for `ms` milliseconds, every wait of the blocking driver goes
+///through this.
+pub trait Delay {
+    fn delay_ms(&mut self, ms: u16);
+}
+
+#[cfg(test)]
+mod transport_tests {
+    use super::*;
+    use crate::{Sensor, SENSOR_ADDR};
+
+    ///A transport that isn't an embedded-hal I2C peripheral, answers every
+    ///read with a calibrated idle status.
+    struct Bridge {
+        writes: Vec<Vec<u8>>,
+    }
+
+    impl Aht2xTransport for Bridge {
+        type Error = ();
+
+        fn write_frame(&mut self, _address: u8, bytes: &[u8]) -> Result<(), ()> {
+            self.writes.push(bytes.to_vec());
+            Ok(())
+        }
+
+        fn read_frame(&mut self, _address: u8, buf: &mut [u8]) -> Result<(), ()> {
+            buf.fill(0);
+            buf[0] = 0x18;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn custom_transport()
+    {
+        let mut sensor = Sensor::new(Bridge { writes: vec![] }, SENSOR_ADDR);
+        let status = sensor.read_status().unwrap();
+        assert!(status.is_calibration_enabled());
+        assert_eq!(sensor.i2c.writes, [vec![0x71]]);
+    }
+}
@@ -0,0 +1,115 @@
+/*
+ * Filename: watchdog.rs
+ * Description: Keeps an independent watchdog fed while the driver waits,
+ * the startup delay, the 80ms conversion and the busy retries all go
+ * through the delay so wrapping it covers every long operation.
+ */
+
+use crate::Delay;
+
+///Something that keeps the watchdog from firing, e.g. an IWDG reload.
+pub trait WatchdogFeeder {
+    fn feed(&mut self);
+}
+
+impl<W: WatchdogFeeder + ?Sized> WatchdogFeeder for &mut W {
+    fn feed(&mut self) {
+        (**self).feed();
+    }
+}
+
+///Delay wrapper that splits the waits into chunks of at most `max_chunk_ms`
+///and feeds the watchdog before each one. Pass it anywhere the driver takes
+///a delay.
+pub struct WatchdogDelay<D, W> {
+    delay: D,
+    feeder: W,
+    max_chunk_ms: u16,
+}
+
+impl<D, W> WatchdogDelay<D, W>
+where D: Delay,
+      W: WatchdogFeeder,
+{
+    ///`max_chunk_ms` should be comfortably below the watchdog timeout, a
+    ///zero is treated as 1ms.
+    pub fn new(delay: D, feeder: W, max_chunk_ms: u16) -> Self {
+        WatchdogDelay { delay, feeder, max_chunk_ms: max_chunk_ms.max(1) }
+    }
+
+    ///Gives back the delay and the feeder.
+    pub fn release(self) -> (D, W) {
+        (self.delay, self.feeder)
+    }
+}
+
+impl<D, W> Delay for WatchdogDelay<D, W>
+where D: Delay,
+      W: WatchdogFeeder,
+{
+    fn delay_ms(&mut self, ms: u16) {
+        let mut left = ms;
+        loop {
+            self.feeder.feed();
+            if left == 0 {
+                return;
+            }
+            let step = left.min(self.max_chunk_ms);
+            self.delay.delay_ms(step);
+            left -= step;
+        }
+    }
+}
+
+#[cfg(test)]
+mod watchdog_tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorder {
+        feeds: u16,
+        waits: Vec<u16>,
+    }
+
+    struct Feeder<'a>(&'a core::cell::RefCell<Recorder>);
+
+    impl WatchdogFeeder for Feeder<'_> {
+        fn feed(&mut self) {
+            self.0.borrow_mut().feeds += 1;
+        }
+    }
+
+    struct RecDelay<'a>(&'a core::cell::RefCell<Recorder>);
+
+    impl Delay for RecDelay<'_> {
+        fn delay_ms(&mut self, ms: u16) {
+            self.0.borrow_mut().waits.push(ms);
+        }
+    }
+
+    #[test]
+    fn chunked_waits()
+    {
+        let rec = core::cell::RefCell::new(Recorder::default());
+        let mut delay = WatchdogDelay::new(RecDelay(&rec), Feeder(&rec), 30);
+
+        delay.delay_ms(80);
+        assert_eq!(rec.borrow().waits, [30, 30, 20]);
+        //Fed before each chunk and once the wait is over.
+        assert_eq!(rec.borrow().feeds, 4);
+
+        delay.delay_ms(0);
+        assert_eq!(rec.borrow().waits.len(), 3);
+        assert_eq!(rec.borrow().feeds, 5);
+    }
+
+    #[test]
+    fn zero_chunk()
+    {
+        let rec = core::cell::RefCell::new(Recorder::default());
+        let mut delay = WatchdogDelay::new(RecDelay(&rec), Feeder(&rec), 0);
+
+        delay.delay_ms(2);
+        assert_eq!(rec.borrow().waits, [1, 1]);
+    }
+}